@@ -0,0 +1,135 @@
+//! Benchmarks of the core operations.
+//!
+//! Save a baseline before a change and compare with it after the change:
+//!
+//! ```sh
+//! cargo bench --bench core -- --save-baseline before
+//! cargo bench --bench core -- --baseline before
+//! ```
+
+use std::fs;
+use std::path::PathBuf;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use josekit::jwe::{self, JweDecrypter, JweEncrypter, JweHeader, ECDH_ES, RSA_OAEP};
+use josekit::jwk::Jwk;
+use josekit::jws::{self, JwsHeader, JwsSigner, JwsVerifier, ES256, HS256, RS256};
+use josekit::util::SHA_256;
+
+fn jws_sign_and_verify(c: &mut Criterion) {
+    let secret = b"0123456789abcdef0123456789abcdef";
+    let rsa_private_key = load_file("pem/RSA_2048bit_private.pem");
+    let rsa_public_key = load_file("pem/RSA_2048bit_public.pem");
+    let ec_private_key = load_file("pem/EC_P-256_private.pem");
+    let ec_public_key = load_file("pem/EC_P-256_public.pem");
+
+    let pairs: [(Box<dyn JwsSigner>, Box<dyn JwsVerifier>); 3] = [
+        (
+            Box::new(HS256.signer_from_bytes(secret).unwrap()),
+            Box::new(HS256.verifier_from_bytes(secret).unwrap()),
+        ),
+        (
+            Box::new(RS256.signer_from_pem(&rsa_private_key).unwrap()),
+            Box::new(RS256.verifier_from_pem(&rsa_public_key).unwrap()),
+        ),
+        (
+            Box::new(ES256.signer_from_pem(&ec_private_key).unwrap()),
+            Box::new(ES256.verifier_from_pem(&ec_public_key).unwrap()),
+        ),
+    ];
+
+    let header = JwsHeader::new();
+    let payload = b"{\"sub\":\"subject\"}";
+
+    let mut group = c.benchmark_group("jws_sign");
+    for (signer, _) in &pairs {
+        group.bench_function(signer.algorithm().name(), |b| {
+            b.iter(|| jws::serialize_compact(black_box(payload), &header, &**signer).unwrap())
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("jws_verify");
+    for (signer, verifier) in &pairs {
+        let input = jws::serialize_compact(payload, &header, &**signer).unwrap();
+        group.bench_function(verifier.algorithm().name(), |b| {
+            b.iter(|| jws::deserialize_compact(black_box(&input), &**verifier).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn jwe_encrypt_and_decrypt(c: &mut Criterion) {
+    let rsa_private_key = load_jwk("jwk/RSA_private.jwk");
+    let rsa_public_key = load_jwk("jwk/RSA_public.jwk");
+    let ec_private_key = load_jwk("jwk/EC_P-256_private.jwk");
+    let ec_public_key = load_jwk("jwk/EC_P-256_public.jwk");
+
+    let pairs: [(Box<dyn JweEncrypter>, Box<dyn JweDecrypter>); 2] = [
+        (
+            Box::new(RSA_OAEP.encrypter_from_jwk(&rsa_public_key).unwrap()),
+            Box::new(RSA_OAEP.decrypter_from_jwk(&rsa_private_key).unwrap()),
+        ),
+        (
+            Box::new(ECDH_ES.encrypter_from_jwk(&ec_public_key).unwrap()),
+            Box::new(ECDH_ES.decrypter_from_jwk(&ec_private_key).unwrap()),
+        ),
+    ];
+
+    let mut header = JweHeader::new();
+    header.set_content_encryption("A128GCM");
+    let payload = b"{\"sub\":\"subject\"}";
+
+    let mut group = c.benchmark_group("jwe_encrypt");
+    for (encrypter, _) in &pairs {
+        group.bench_function(encrypter.algorithm().name(), |b| {
+            b.iter(|| jwe::serialize_compact(black_box(payload), &header, &**encrypter).unwrap())
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("jwe_decrypt");
+    for (encrypter, decrypter) in &pairs {
+        let input = jwe::serialize_compact(payload, &header, &**encrypter).unwrap();
+        group.bench_function(decrypter.algorithm().name(), |b| {
+            b.iter(|| jwe::deserialize_compact(black_box(&input), &**decrypter).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn jwk_thumbprint(c: &mut Criterion) {
+    let mut group = c.benchmark_group("jwk_thumbprint");
+    for (name, path) in [
+        ("RSA", "jwk/RSA_public.jwk"),
+        ("EC", "jwk/EC_P-256_public.jwk"),
+        ("OKP", "jwk/OKP_Ed25519_public.jwk"),
+    ] {
+        let jwk = load_jwk(path);
+        group.bench_function(name, |b| {
+            b.iter(|| black_box(&jwk).thumbprint(SHA_256).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn load_jwk(path: &str) -> Jwk {
+    Jwk::from_bytes(load_file(path)).unwrap()
+}
+
+fn load_file(path: &str) -> Vec<u8> {
+    let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    pb.push("data");
+    pb.push(path);
+
+    fs::read(&pb).unwrap()
+}
+
+criterion_group!(
+    benches,
+    jws_sign_and_verify,
+    jwe_encrypt_and_decrypt,
+    jwk_thumbprint
+);
+criterion_main!(benches);
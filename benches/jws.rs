@@ -0,0 +1,124 @@
+//! Benchmarks of verification-heavy workloads.
+//!
+//! Save a baseline before a change and compare with it after the change:
+//!
+//! ```sh
+//! cargo bench --bench jws -- --save-baseline before
+//! cargo bench --bench jws -- --baseline before
+//! ```
+
+use std::fs;
+use std::path::PathBuf;
+
+use criterion::measurement::WallTime;
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkGroup, Criterion, Throughput,
+};
+
+use josekit::jws::{self, JwsHeader, JwsHeaderSet, JwsSigner, JwsVerifier, ES256, HS256, RS256};
+use josekit::jwt::{self, JwtPayload};
+
+fn verify_many(c: &mut Criterion) {
+    let secret = b"0123456789abcdef0123456789abcdef";
+    let rsa_private_key = load_file("pem/RSA_2048bit_private.pem");
+    let rsa_public_key = load_file("pem/RSA_2048bit_public.pem");
+    let ec_private_key = load_file("pem/EC_P-256_private.pem");
+    let ec_public_key = load_file("pem/EC_P-256_public.pem");
+
+    let mut group = c.benchmark_group("jwt_verify_many");
+    bench_verify(
+        &mut group,
+        &HS256.signer_from_bytes(secret).unwrap(),
+        &HS256.verifier_from_bytes(secret).unwrap(),
+    );
+    bench_verify(
+        &mut group,
+        &RS256.signer_from_pem(&rsa_private_key).unwrap(),
+        &RS256.verifier_from_pem(&rsa_public_key).unwrap(),
+    );
+    bench_verify(
+        &mut group,
+        &ES256.signer_from_pem(&ec_private_key).unwrap(),
+        &ES256.verifier_from_pem(&ec_public_key).unwrap(),
+    );
+    group.finish();
+}
+
+fn bench_verify(
+    group: &mut BenchmarkGroup<WallTime>,
+    signer: &dyn JwsSigner,
+    verifier: &dyn JwsVerifier,
+) {
+    let mut header = JwsHeader::new();
+    header.set_token_type("JWT");
+    let mut payload = JwtPayload::new();
+    payload.set_subject("subject");
+
+    let tokens: Vec<String> = (0..100)
+        .map(|i| {
+            payload.set_jwt_id(i.to_string());
+            jwt::encode_with_signer(&payload, &header, signer).unwrap()
+        })
+        .collect();
+
+    group.throughput(Throughput::Elements(tokens.len() as u64));
+    group.bench_function(verifier.algorithm().name(), |b| {
+        b.iter(|| {
+            for token in &tokens {
+                jwt::decode_with_verifier(token, verifier).unwrap();
+            }
+        })
+    });
+}
+
+fn deserialize_and_verify(c: &mut Criterion) {
+    let secret = b"0123456789abcdef0123456789abcdef";
+    let ec_private_key = load_file("pem/EC_P-256_private.pem");
+    let ec_public_key = load_file("pem/EC_P-256_public.pem");
+
+    let pairs: [(Box<dyn JwsSigner>, Box<dyn JwsVerifier>); 2] = [
+        (
+            Box::new(HS256.signer_from_bytes(secret).unwrap()),
+            Box::new(HS256.verifier_from_bytes(secret).unwrap()),
+        ),
+        (
+            Box::new(ES256.signer_from_pem(&ec_private_key).unwrap()),
+            Box::new(ES256.verifier_from_pem(&ec_public_key).unwrap()),
+        ),
+    ];
+
+    let payload = b"{\"iss\":\"https://example.com\",\"sub\":\"subject\"}";
+    let mut header = JwsHeader::new();
+    header.set_token_type("JWT");
+    header.set_key_id("key-1");
+    let mut header_set = JwsHeaderSet::new();
+    header_set.set_token_type("JWT", true);
+    header_set.set_key_id("key-1", false);
+
+    let mut group = c.benchmark_group("jws_deserialize_and_verify");
+    for (signer, verifier) in &pairs {
+        let name = verifier.algorithm().name();
+
+        let input = jws::serialize_compact(payload, &header, &**signer).unwrap();
+        group.bench_function(format!("compact/{}", name), |b| {
+            b.iter(|| jws::deserialize_compact(black_box(&input), &**verifier).unwrap())
+        });
+
+        let input = jws::serialize_flattened_json(payload, &header_set, &**signer).unwrap();
+        group.bench_function(format!("json/{}", name), |b| {
+            b.iter(|| jws::deserialize_json(black_box(&input), &**verifier).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn load_file(path: &str) -> Vec<u8> {
+    let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    pb.push("data");
+    pb.push(path);
+
+    fs::read(&pb).unwrap()
+}
+
+criterion_group!(benches, verify_many, deserialize_and_verify);
+criterion_main!(benches);
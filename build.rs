@@ -0,0 +1,26 @@
+use std::env;
+
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(ossl320)");
+
+    // The version of the linked OpenSSL is exported by openssl-sys.
+    if env::var("DEP_OPENSSL_LIBRESSL_VERSION_NUMBER").is_ok() {
+        return;
+    }
+    if let Ok(version) = env::var("DEP_OPENSSL_VERSION_NUMBER") {
+        // A malformed version is treated as an old one.
+        let version = match u64::from_str_radix(&version, 16) {
+            Ok(val) => val,
+            Err(_) => {
+                println!(
+                    "cargo:warning=DEP_OPENSSL_VERSION_NUMBER is malformed: {}",
+                    version
+                );
+                0
+            }
+        };
+        if version >= 0x3020_0000 {
+            println!("cargo:rustc-cfg=ossl320");
+        }
+    }
+}
@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use josekit::jwk::alg::ec::EcKeyPair;
+use josekit::jwk::alg::ecx::EcxKeyPair;
+use josekit::jwk::alg::ed::EdKeyPair;
+use josekit::jwk::alg::rsa::RsaKeyPair;
+use josekit::jwk::alg::rsapss::RsaPssKeyPair;
+use josekit::jwk::{Jwk, KeyInfo};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = KeyInfo::detect(&data);
+    let _ = Jwk::from_public_der(data);
+    let _ = Jwk::from_public_pem(data);
+    let _ = EcKeyPair::from_der(data, None);
+    let _ = EcxKeyPair::from_der(data);
+    let _ = EdKeyPair::from_der(data);
+    let _ = RsaKeyPair::from_der(data);
+    let _ = RsaPssKeyPair::from_der(data, None, None, None);
+});
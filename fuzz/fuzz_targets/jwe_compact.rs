@@ -0,0 +1,45 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use josekit::jwe::{
+    self, Dir, JweDecrypter, A128GCMKW, A128KW, ECDH_ES, ECDH_ES_A128KW, PBES2_HS256_A128KW,
+};
+use josekit::jwk::Jwk;
+
+const EC_PRIVATE_KEY: &str = r#"{"kty":"EC","crv":"P-256",
+    "x":"f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU",
+    "y":"x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0",
+    "d":"jpsQnnGQmL-YBIffH1136cspYG6-0iY7X1fCE9-E9LI"}"#;
+const X25519_PRIVATE_KEY: &str = r#"{"kty":"OKP","crv":"X25519",
+    "x":"hSDwCYkwp1R0i33ctD73Wg2_Og0mOBr066SpjqqbTmo",
+    "d":"dwdtCnMYpX08FsFyUbJmRd9ML4frwJkqsXf7pR25LCo"}"#;
+
+fuzz_target!(|data: &[u8]| {
+    // The compact and json serializations are text.
+    let input = match std::str::from_utf8(data) {
+        Ok(val) => val,
+        Err(_) => return,
+    };
+
+    let ec_key = Jwk::from_bytes(EC_PRIVATE_KEY).unwrap();
+    let x25519_key = Jwk::from_bytes(X25519_PRIVATE_KEY).unwrap();
+    let decrypters: Vec<Box<dyn JweDecrypter>> = vec![
+        Box::new(Dir.decrypter_from_bytes(b"0123456789ABCDEF").unwrap()),
+        Box::new(A128KW.decrypter_from_bytes(b"0123456789ABCDEF").unwrap()),
+        Box::new(A128GCMKW.decrypter_from_bytes(b"0123456789ABCDEF").unwrap()),
+        Box::new(
+            PBES2_HS256_A128KW
+                .decrypter_from_bytes(b"password")
+                .unwrap(),
+        ),
+        Box::new(ECDH_ES.decrypter_from_jwk(&ec_key).unwrap()),
+        Box::new(ECDH_ES_A128KW.decrypter_from_jwk(&ec_key).unwrap()),
+        Box::new(ECDH_ES.decrypter_from_jwk(&x25519_key).unwrap()),
+    ];
+
+    for decrypter in &decrypters {
+        let _ = jwe::deserialize_compact(input, &**decrypter);
+        let _ = jwe::deserialize_json(input, &**decrypter);
+    }
+});
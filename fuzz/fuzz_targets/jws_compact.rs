@@ -0,0 +1,32 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use josekit::jws::{self, EdDSA, ES256, HS256};
+
+const SECRET: &[u8] = b"0123456789ABCDEF0123456789ABCDEF";
+
+// A verifier of the matching algorithm is used so that the input reaches
+// the signature check instead of stopping at the algorithm mismatch.
+const ES256_PUBLIC_KEY: &str = r#"{"kty":"EC","crv":"P-256",
+    "x":"f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU",
+    "y":"x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0"}"#;
+const ED25519_PUBLIC_KEY: &str =
+    r#"{"kty":"OKP","crv":"Ed25519","x":"11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"}"#;
+
+fuzz_target!(|data: &[u8]| {
+    let hs256 = HS256.verifier_from_bytes(SECRET).unwrap();
+    let es256 = ES256
+        .verifier_from_jwk(&josekit::jwk::Jwk::from_bytes(ES256_PUBLIC_KEY).unwrap())
+        .unwrap();
+    let eddsa = EdDSA
+        .verifier_from_jwk(&josekit::jwk::Jwk::from_bytes(ED25519_PUBLIC_KEY).unwrap())
+        .unwrap();
+
+    let _ = jws::deserialize_compact(data, &hs256);
+    let _ = jws::deserialize_compact(data, &es256);
+    let _ = jws::deserialize_compact(data, &eddsa);
+    let _ = jws::deserialize_compact_detached(data, b"payload", &hs256);
+    let _ = jws::deserialize_json(data, &hs256);
+    let _ = jws::verify_with_embedded_jwk(data, None);
+});
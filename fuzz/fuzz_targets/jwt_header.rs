@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use josekit::jwk::{Jwk, JwkSet};
+use josekit::jwt;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = jwt::decode_header(data);
+    let _ = jwt::decode_unsecured(data);
+    let _ = Jwk::from_bytes(data);
+    let _ = JwkSet::from_bytes(data);
+});
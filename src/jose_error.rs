@@ -28,4 +28,89 @@ pub enum JoseError {
 
     #[error("Invalid signature: {0}")]
     InvalidSignature(#[source] anyhow::Error),
+
+    #[error("I/O error: {0}")]
+    Io(#[source] std::io::Error),
+}
+
+impl JoseError {
+    /// Return true if the error is caused by an invalid key or JWK.
+    pub fn is_key_error(&self) -> bool {
+        matches!(self, Self::InvalidKeyFormat(_) | Self::InvalidJwkFormat(_))
+    }
+
+    /// Return true if the error is caused by a malformed JWT, JWS, JWE or JSON input.
+    pub fn is_format_error(&self) -> bool {
+        matches!(
+            self,
+            Self::InvalidJwtFormat(_)
+                | Self::InvalidJwsFormat(_)
+                | Self::InvalidJweFormat(_)
+                | Self::InvalidJson(_)
+        )
+    }
+
+    /// Return true if the input is well-formed but fails a signature or claim validation.
+    pub fn is_validation_error(&self) -> bool {
+        matches!(self, Self::InvalidSignature(_) | Self::InvalidClaim(_))
+    }
+
+    /// Return true if the error is caused by an unsupported algorithm.
+    pub fn is_unsupported(&self) -> bool {
+        matches!(self, Self::UnsupportedSignatureAlgorithm(_))
+    }
+
+    /// Return true if the error is caused by a reader or writer supplied by the caller.
+    pub fn is_io_error(&self) -> bool {
+        matches!(self, Self::Io(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::jwk::Jwk;
+    use crate::jws::{JwsHeader, HS256};
+    use crate::jwt::{self, JwtPayload, JwtPayloadValidator};
+    use crate::JoseError;
+
+    #[test]
+    fn test_error_categories() -> Result<()> {
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let verifier = HS256.verifier_from_bytes(key)?;
+
+        let err = jwt::decode_with_verifier("not a token", &verifier).unwrap_err();
+        assert!(err.is_format_error());
+        assert!(!err.is_key_error() && !err.is_validation_error() && !err.is_unsupported());
+
+        let err = Jwk::from_bytes(b"{\"kty\":1}").unwrap_err();
+        assert!(err.is_key_error());
+        assert!(!err.is_format_error());
+
+        let err = HS256.signer_from_bytes(b"short").unwrap_err();
+        assert!(err.is_key_error());
+
+        let mut payload = JwtPayload::new();
+        payload.set_issuer("a");
+        let signer = HS256.signer_from_bytes(key)?;
+        let jwt_string = jwt::encode_with_signer(&payload, &JwsHeader::new(), &signer)?;
+
+        let other = HS256.verifier_from_bytes(b"FEDCBA9876543210FEDCBA9876543210")?;
+        let err = jwt::decode_with_verifier(&jwt_string, &other).unwrap_err();
+        assert!(err.is_validation_error());
+        assert!(!err.is_format_error());
+
+        let mut validator = JwtPayloadValidator::new();
+        validator.set_issuer("b");
+        let err = validator.validate(&payload).unwrap_err();
+        assert!(err.is_validation_error());
+
+        let err = jwt::sign_auto(&payload, &Jwk::new("oct")).unwrap_err();
+        assert!(matches!(err, JoseError::UnsupportedSignatureAlgorithm(_)));
+        assert!(err.is_unsupported());
+        assert!(!err.is_key_error());
+
+        Ok(())
+    }
 }
@@ -1,7 +1,9 @@
-use crate::Value;
-
 use std::fmt::Debug;
 
+use anyhow::bail;
+
+use crate::{Map, Value};
+
 pub trait JoseHeader: Send + Sync + Debug {
     /// Return claim count.
     fn len(&self) -> usize;
@@ -13,6 +15,44 @@ pub trait JoseHeader: Send + Sync + Debug {
     /// * `key` - a key name of header claim
     fn claim(&self, key: &str) -> Option<&Value>;
 
+    /// Return true if the header has a claim of a specified key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - a key name of header claim
+    fn contains_claim(&self, key: &str) -> bool {
+        self.claim(key).is_some()
+    }
+
+    /// Return the string value for header claim of a specified key.
+    ///
+    /// This returns None if the claim is not present or is not a string.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - a key name of header claim
+    fn claim_str(&self, key: &str) -> Option<&str> {
+        match self.claim(key) {
+            Some(Value::String(val)) => Some(val),
+            _ => None,
+        }
+    }
+
+    /// Return an iterator over header claims sorted by their names.
+    ///
+    /// The default implementation returns no claims, because the trait has no way
+    /// to enumerate them. The headers of this crate override it, and a header
+    /// defined outside of this crate should override it too, or it cannot be merged
+    /// with other headers.
+    fn claims_iter<'a>(&'a self) -> Box<dyn Iterator<Item = (&'a str, &'a Value)> + 'a> {
+        Box::new(std::iter::empty())
+    }
+
+    /// Return header claim names sorted in ascending order.
+    fn claim_names(&self) -> Vec<&str> {
+        self.claims_iter().map(|(key, _)| key).collect()
+    }
+
     fn box_clone(&self) -> Box<dyn JoseHeader>;
 }
 
@@ -21,3 +61,125 @@ impl Clone for Box<dyn JoseHeader> {
         self.box_clone()
     }
 }
+
+/// Return the union of the claims and the other header claims.
+///
+/// It is an error that a header claim name exists in both, or that the other
+/// header doesn't enumerate all of its claims.
+pub(crate) fn merge_claims(
+    claims: &Map<String, Value>,
+    other: &dyn JoseHeader,
+) -> anyhow::Result<Map<String, Value>> {
+    let mut claims = claims.clone();
+    let mut count = 0;
+    for (key, value) in other.claims_iter() {
+        if claims.contains_key(key) {
+            bail!("A duplicate key exists: {}", key);
+        }
+        claims.insert(key.to_string(), value.clone());
+        count += 1;
+    }
+    if count != other.len() {
+        bail!(
+            "The header enumerates {} of {} claims: claims_iter must be implemented.",
+            count,
+            other.len()
+        );
+    }
+    Ok(claims)
+}
+
+/// Return an iterator over claims sorted by their names.
+pub(crate) fn sorted_claims<'a>(
+    claims: impl Iterator<Item = (&'a String, &'a Value)>,
+) -> Box<dyn Iterator<Item = (&'a str, &'a Value)> + 'a> {
+    let mut vec: Vec<(&str, &Value)> = claims.map(|(key, value)| (key.as_str(), value)).collect();
+    vec.sort_by(|a, b| a.0.cmp(b.0));
+    Box::new(vec.into_iter())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_claims, sorted_claims, JoseHeader};
+    use crate::{Map, Value};
+
+    #[derive(Debug, Clone)]
+    struct MinimalHeader(Map<String, Value>);
+
+    impl JoseHeader for MinimalHeader {
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn claim(&self, key: &str) -> Option<&Value> {
+            self.0.get(key)
+        }
+
+        fn claims_iter<'a>(&'a self) -> Box<dyn Iterator<Item = (&'a str, &'a Value)> + 'a> {
+            sorted_claims(self.0.iter())
+        }
+
+        fn box_clone(&self) -> Box<dyn JoseHeader> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    struct LegacyHeader(Map<String, Value>);
+
+    impl JoseHeader for LegacyHeader {
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn claim(&self, key: &str) -> Option<&Value> {
+            self.0.get(key)
+        }
+
+        fn box_clone(&self) -> Box<dyn JoseHeader> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn test_claims_iter_default() {
+        let mut map = Map::new();
+        map.insert("kid".to_string(), Value::from("key-1"));
+        let header = LegacyHeader(map);
+        assert_eq!(header.claims_iter().count(), 0);
+        assert!(header.claim_names().is_empty());
+        assert_eq!(header.claim_str("kid"), Some("key-1"));
+
+        let claims = Map::new();
+        assert!(merge_claims(&claims, &header).is_err());
+        assert!(merge_claims(&claims, &LegacyHeader(Map::new())).is_ok());
+    }
+
+    #[test]
+    fn test_claims_iter_sorted() {
+        let mut map = Map::new();
+        map.insert("kid".to_string(), Value::from("key-1"));
+        map.insert("alg".to_string(), Value::from("none"));
+        let header: Box<dyn JoseHeader> = Box::new(MinimalHeader(map));
+        assert_eq!(header.claim_names(), vec!["alg", "kid"]);
+        assert_eq!(header.claims_iter().count(), header.len());
+    }
+
+    #[test]
+    fn test_merge_claims() {
+        let mut map = Map::new();
+        map.insert("kid".to_string(), Value::from("key-1"));
+        let other = MinimalHeader(map.clone());
+
+        let mut claims = Map::new();
+        claims.insert("alg".to_string(), Value::from("none"));
+        let merged = merge_claims(&claims, &other).unwrap();
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged.get("kid"), Some(&Value::from("key-1")));
+
+        match merge_claims(&map, &other) {
+            Err(err) => assert_eq!(err.to_string(), "A duplicate key exists: kid"),
+            res => panic!("{:?}", res),
+        }
+    }
+}
@@ -1,27 +1,36 @@
 //! JSON Web Encryption (JWE) support.
 
 pub mod alg;
+mod content_encryption_algorithm;
 pub mod enc;
 mod jwe_algorithm;
 mod jwe_compression;
 mod jwe_content_encryption;
 mod jwe_context;
+mod jwe_decryption_options;
 mod jwe_header;
 mod jwe_header_set;
+mod key_management_algorithm;
 pub mod zip;
 
+use anyhow::bail;
 use once_cell::sync::Lazy;
 
+use crate::jwk::JwkSet;
+use crate::util;
 use crate::JoseError;
 
+pub use crate::jwe::content_encryption_algorithm::ContentEncryptionAlgorithm;
 pub use crate::jwe::jwe_algorithm::JweAlgorithm;
 pub use crate::jwe::jwe_algorithm::JweDecrypter;
 pub use crate::jwe::jwe_algorithm::JweEncrypter;
 pub use crate::jwe::jwe_compression::JweCompression;
 pub use crate::jwe::jwe_content_encryption::JweContentEncryption;
 pub use crate::jwe::jwe_context::JweContext;
+pub use crate::jwe::jwe_decryption_options::JweDecryptionOptions;
 pub use crate::jwe::jwe_header::JweHeader;
 pub use crate::jwe::jwe_header_set::JweHeaderSet;
+pub use crate::jwe::key_management_algorithm::KeyManagementAlgorithm;
 
 pub use crate::jwe::alg::direct::DirectJweAlgorithm::Dir;
 
@@ -54,6 +63,9 @@ pub use RsaesJweAlgorithm::RsaOaep256 as RSA_OAEP_256;
 pub use RsaesJweAlgorithm::RsaOaep384 as RSA_OAEP_384;
 pub use RsaesJweAlgorithm::RsaOaep512 as RSA_OAEP_512;
 
+/// The IV, ciphertext and authentication tag.
+type EncryptedContent = (Vec<u8>, Vec<u8>, Vec<u8>);
+
 static DEFAULT_CONTEXT: Lazy<JweContext> = Lazy::new(|| JweContext::new());
 
 /// Return a representation of the data that is formatted by compact serialization.
@@ -224,6 +236,22 @@ pub fn deserialize_json<'a>(
     DEFAULT_CONTEXT.deserialize_json(input, decrypter)
 }
 
+/// Deserialize the input that is formatted by json serialization
+/// and has the expected additional authenticated data.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `aad` - The expected JWE additional authenticated data.
+/// * `decrypter` - The JWE decrypter.
+pub fn deserialize_json_with_aad(
+    input: &str,
+    aad: Option<&[u8]>,
+    decrypter: &dyn JweDecrypter,
+) -> Result<(Vec<u8>, JweHeader), JoseError> {
+    DEFAULT_CONTEXT.deserialize_json_with_aad(input, aad, decrypter)
+}
+
 /// Deserialize the input that is formatted by flattened json serialization.
 ///
 /// # Arguments
@@ -240,6 +268,160 @@ where
     DEFAULT_CONTEXT.deserialize_json_with_selector(input, selector)
 }
 
+/// Encrypt a plaintext with a content encryption key and return the IV, ciphertext and tag.
+///
+/// This is the content encryption of JWE without key management. A random IV is generated
+/// for the enc algorithm.
+///
+/// # Arguments
+///
+/// * `enc` - The content encryption algorithm.
+/// * `cek` - The content encryption key.
+/// * `plaintext` - The plaintext.
+/// * `aad` - The additional authenticated data.
+pub fn encrypt_content(
+    enc: &dyn JweContentEncryption,
+    cek: &[u8],
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<EncryptedContent, JoseError> {
+    check_content_encryption_key(enc, cek)?;
+
+    let iv = util::random_bytes(enc.iv_len())?;
+    let (ciphertext, tag) = enc.encrypt(cek, Some(&iv), plaintext, aad)?;
+    let tag = match tag {
+        Some(val) => val,
+        None => {
+            return Err(JoseError::InvalidJweFormat(anyhow::anyhow!(
+                "The content encryption algorithm doesn't produce a tag: {}",
+                enc.name()
+            )))
+        }
+    };
+    Ok((iv, ciphertext, tag))
+}
+
+/// Decrypt a ciphertext with a content encryption key and return the plaintext.
+///
+/// # Arguments
+///
+/// * `enc` - The content encryption algorithm.
+/// * `cek` - The content encryption key.
+/// * `iv` - The initialization vector.
+/// * `ciphertext` - The ciphertext.
+/// * `aad` - The additional authenticated data.
+/// * `tag` - The authentication tag.
+pub fn decrypt_content(
+    enc: &dyn JweContentEncryption,
+    cek: &[u8],
+    iv: &[u8],
+    ciphertext: &[u8],
+    aad: &[u8],
+    tag: &[u8],
+) -> Result<Vec<u8>, JoseError> {
+    check_content_encryption_key(enc, cek)?;
+    enc.decrypt(cek, Some(iv), ciphertext, aad, Some(tag))
+}
+
+/// Return the additional authenticated data of a JWE in compact serialization.
+///
+/// The AAD is exactly ASCII(BASE64URL(UTF8(JWE Protected Header))), that is, the
+/// first part of the input without the trailing period. An AAD changed by any byte
+/// fails the verification of the authentication tag. This is useful for debugging.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+pub fn compact_aad(input: &str) -> Result<&[u8], JoseError> {
+    (|| -> anyhow::Result<&[u8]> {
+        let parts = input.split('.').count();
+        if parts != 5 {
+            bail!(
+                "The compact serialization form of JWE must be five parts separated by period: {} parts",
+                parts
+            );
+        }
+
+        let header_b64 = input.split('.').next().unwrap_or_default();
+        let header = util::decode_base64_urlsafe_no_pad(header_b64)?;
+        util::parse_json_object_strict(&header)?;
+
+        Ok(header_b64.as_bytes())
+    })()
+    .map_err(JoseError::InvalidJweFormat)
+}
+
+fn check_content_encryption_key(
+    enc: &dyn JweContentEncryption,
+    cek: &[u8],
+) -> Result<(), JoseError> {
+    if cek.len() != enc.key_len() {
+        return Err(JoseError::InvalidKeyFormat(anyhow::anyhow!(
+            "The length of content encryption key must be {}: {}",
+            enc.key_len(),
+            cek.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Return a encrypter for the first key in a JWK set that matches a preferred algorithm.
+///
+/// The preferred algorithms are tried in order and, for each algorithm, the keys are
+/// tried in the order of the set. Keys that are designated for signatures by the use
+/// parameter, or whose key_ops parameter lacks the operation of the algorithm
+/// (encrypt, wrapKey or deriveKey), are never selected.
+///
+/// # Arguments
+///
+/// * `jwk_set` - The JWK set of the recipient.
+/// * `preferred_algs` - The alg header claim values in order of preference.
+pub fn encrypter_from_jwk_set(
+    jwk_set: &JwkSet,
+    preferred_algs: &[&str],
+) -> Result<Box<dyn JweEncrypter>, JoseError> {
+    let mut errors = Vec::new();
+    for alg in preferred_algs {
+        let alg = match KeyManagementAlgorithm::from_name(alg) {
+            Some(val) => val,
+            None => continue,
+        };
+        for jwk in jwk_set.keys() {
+            match jwk.key_use() {
+                Some(val) if val != "enc" => continue,
+                _ => {}
+            }
+            // The key_ops parameter is checked by each algorithm.
+            match alg.encrypter_from_jwk(jwk) {
+                Ok(val) => return Ok(val),
+                Err(err) => errors.push(format!("{}: {}", alg.name(), err)),
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(JoseError::InvalidKeyFormat(anyhow::anyhow!(
+            "An encrypter cannot be created from the keys for the preferred algorithms: {}: {}",
+            preferred_algs.join(", "),
+            errors.join("; ")
+        )));
+    }
+
+    Err(JoseError::InvalidKeyFormat(anyhow::anyhow!(
+        "A key for the preferred algorithms is not found: {}",
+        preferred_algs.join(", ")
+    )))
+}
+
+/// Return the key management algorithm for a alg header claim value.
+///
+/// # Arguments
+///
+/// * `name` - a alg header claim value
+pub fn key_management_algorithm_from_name(name: &str) -> Option<KeyManagementAlgorithm> {
+    KeyManagementAlgorithm::from_name(name)
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
@@ -248,12 +430,75 @@ mod tests {
     use anyhow::Result;
 
     use crate::jwe::{
-        self, Dir, JweAlgorithm, JweHeader, JweHeaderSet, ECDH_ES_A128KW, PBES2_HS256_A128KW,
-        RSA_OAEP,
+        self, ContentEncryptionAlgorithm, Dir, JweAlgorithm, JweEncrypter, JweHeader, JweHeaderSet,
+        ECDH_ES, ECDH_ES_A128KW, PBES2_HS256_A128KW, RSA_OAEP,
     };
-    use crate::jwk::Jwk;
-    use crate::util;
-    use crate::Value;
+    use crate::jwk::alg::ec::EcCurve;
+    use crate::jwk::alg::ecx::EcxCurve;
+    use crate::jwk::{Jwk, JwkSet};
+    use crate::util::{self, from_hex};
+    use crate::{JoseError, Value};
+
+    #[test]
+    fn test_encrypt_and_decrypt_content() -> Result<()> {
+        let plaintext = b"abcde12345";
+        let aad = b"aad";
+        for name in [
+            "A128CBC-HS256",
+            "A192CBC-HS384",
+            "A256CBC-HS512",
+            "A128GCM",
+            "A192GCM",
+            "A256GCM",
+        ] {
+            let enc = ContentEncryptionAlgorithm::from_name(name).unwrap();
+            let enc = enc.content_encryption();
+            let cek = util::random_bytes(enc.key_len())?;
+
+            let (iv, ciphertext, tag) = jwe::encrypt_content(enc, &cek, plaintext, aad)?;
+            assert_eq!(iv.len(), enc.iv_len());
+            let decrypted = jwe::decrypt_content(enc, &cek, &iv, &ciphertext, aad, &tag)?;
+            assert_eq!(decrypted, plaintext);
+
+            assert!(jwe::decrypt_content(enc, &cek, &iv, &ciphertext, b"other", &tag).is_err());
+            assert!(matches!(
+                jwe::encrypt_content(enc, &cek[1..], plaintext, aad),
+                Err(JoseError::InvalidKeyFormat(_))
+            ));
+            assert!(matches!(
+                jwe::decrypt_content(enc, &cek[1..], &iv, &ciphertext, aad, &tag),
+                Err(JoseError::InvalidKeyFormat(_))
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decrypt_content_rfc7518_vector() -> Result<()> {
+        // RFC 7518 Appendix B.1 AES_128_CBC_HMAC_SHA_256
+        let enc = ContentEncryptionAlgorithm::from_name("A128CBC-HS256").unwrap();
+        let cek: Vec<u8> = (0..32).collect();
+        let iv = from_hex("1af38c2dc2b96ffdd86694092341bc04");
+        let ciphertext = from_hex(concat!(
+            "c80edfa32ddf39d5ef00c0b468834279a2e46a1b8049f792f76bfe54b903a9c9",
+            "a94ac9b47ad2655c5f10f9aef71427e2fc6f9b3f399a221489f16362c7032336",
+            "09d45ac69864e3321cf82935ac4096c86e133314c54019e8ca7980dfa4b9cf1b",
+            "384c486f3a54c51078158ee5d79de59fbd34d848b3d69550a67646344427ade5",
+            "4b8851ffb598f7f80074b9473c82e2db"
+        ));
+        let tag = from_hex("652c3fa36b0a7c5b3219fab3a30bc1c4");
+        let aad = b"The second principle of Auguste Kerckhoffs";
+
+        let plaintext =
+            jwe::decrypt_content(enc.content_encryption(), &cek, &iv, &ciphertext, aad, &tag)?;
+        assert_eq!(
+            plaintext,
+            b"A cipher system must not be required to be secret, and it must be able to fall into the hands of the enemy without inconvenience".to_vec()
+        );
+
+        Ok(())
+    }
 
     #[test]
     fn test_jwe_compact_serialization() -> Result<()> {
@@ -273,15 +518,11 @@ mod tests {
             //println!("{}", enc);
 
             let alg = Dir;
-            let key = match enc {
-                "A128CBC-HS256" => util::random_bytes(32),
-                "A192CBC-HS384" => util::random_bytes(48),
-                "A256CBC-HS512" => util::random_bytes(64),
-                "A128GCM" => util::random_bytes(16),
-                "A192GCM" => util::random_bytes(24),
-                "A256GCM" => util::random_bytes(32),
-                _ => unreachable!(),
+            let key_len = match ContentEncryptionAlgorithm::from_name(enc) {
+                Some(val) => val.content_encryption().key_len(),
+                None => unreachable!(),
             };
+            let key = util::random_bytes(key_len)?;
             let encrypter = alg.encrypter_from_bytes(&key)?;
             let jwe = jwe::serialize_compact(src_payload, &src_header, &encrypter)?;
 
@@ -296,6 +537,59 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_jwe_compact_aad_binds_protected_header() -> Result<()> {
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128CBC-HS256");
+        header.set_token_type("JWT");
+
+        let key = util::random_bytes(32)?;
+        let encrypter = Dir.encrypter_from_bytes(&key)?;
+        let decrypter = Dir.decrypter_from_bytes(&key)?;
+        let jwe = jwe::serialize_compact(b"test payload!", &header, &encrypter)?;
+
+        let aad = jwe::compact_aad(&jwe)?;
+        let header_b64 = &jwe[..jwe.find('.').unwrap()];
+        assert_eq!(aad, header_b64.as_bytes());
+
+        let parts: Vec<&str> = jwe.split('.').collect();
+        let enc = ContentEncryptionAlgorithm::from_name("A128CBC-HS256").unwrap();
+        let enc = enc.content_encryption();
+        let iv = util::decode_base64_urlsafe_no_pad(parts[2])?;
+        let ciphertext = util::decode_base64_urlsafe_no_pad(parts[3])?;
+        let tag = util::decode_base64_urlsafe_no_pad(parts[4])?;
+        let decrypted = jwe::decrypt_content(enc, &key, &iv, &ciphertext, aad, &tag)?;
+        assert_eq!(decrypted, b"test payload!");
+
+        let mut aad_with_extra = aad.to_vec();
+        aad_with_extra.push(b'.');
+        assert!(matches!(
+            jwe::decrypt_content(enc, &key, &iv, &ciphertext, &aad_with_extra, &tag),
+            Err(JoseError::InvalidSignature(_))
+        ));
+
+        // Change "typ":"JWT" to "typ":"JWS" so that the header is still valid.
+        let protected = util::decode_base64_urlsafe_no_pad(header_b64)?;
+        let pos = protected.windows(5).position(|w| w == b"\"JWT\"").unwrap();
+        let mut tampered = protected.clone();
+        tampered[pos + 3] = b'S';
+        let tampered_b64 = util::encode_base64_urlsafe_nopad(&tampered);
+        let tampered_jwe = format!("{}{}", tampered_b64, &jwe[header_b64.len()..]);
+        assert_eq!(jwe::compact_aad(&tampered_jwe)?, tampered_b64.as_bytes());
+
+        assert!(matches!(
+            jwe::deserialize_compact(&tampered_jwe, &decrypter),
+            Err(JoseError::InvalidSignature(_))
+        ));
+
+        assert!(matches!(
+            jwe::compact_aad(&parts[..4].join(".")),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn test_jwe_json_serialization() -> Result<()> {
         let alg = RSA_OAEP;
@@ -383,6 +677,156 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_encrypter_from_jwk_set() -> Result<()> {
+        let mut jwk_set = JwkSet::new();
+        jwk_set.push_key(Jwk::from_bytes(load_file("jwk/RSA_public.jwk")?)?);
+        jwk_set.push_key(Jwk::from_bytes(load_file("jwk/EC_P-256_public.jwk")?)?);
+
+        let rsa_decrypter =
+            RSA_OAEP.decrypter_from_jwk(&Jwk::from_bytes(load_file("jwk/RSA_private.jwk")?)?)?;
+        let ec_decrypter = ECDH_ES
+            .decrypter_from_jwk(&Jwk::from_bytes(load_file("jwk/EC_P-256_private.jwk")?)?)?;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128GCM");
+        let src_payload = b"test payload!";
+
+        let encrypter = jwe::encrypter_from_jwk_set(&jwk_set, &["ECDH-ES", "RSA-OAEP"])?;
+        assert_eq!(encrypter.algorithm().name(), "ECDH-ES");
+        let jwe = jwe::serialize_compact(src_payload, &header, &*encrypter)?;
+        let (dst_payload, _) = jwe::deserialize_compact(&jwe, &ec_decrypter)?;
+        assert_eq!(src_payload.to_vec(), dst_payload);
+
+        let encrypter = jwe::encrypter_from_jwk_set(&jwk_set, &["RSA-OAEP", "ECDH-ES"])?;
+        assert_eq!(encrypter.algorithm().name(), "RSA-OAEP");
+        let jwe = jwe::serialize_compact(src_payload, &header, &*encrypter)?;
+        let (dst_payload, _) = jwe::deserialize_compact(&jwe, &rsa_decrypter)?;
+        assert_eq!(src_payload.to_vec(), dst_payload);
+
+        let encrypter = jwe::encrypter_from_jwk_set(&jwk_set, &["A128KW", "ECDH-ES+A128KW"])?;
+        assert_eq!(encrypter.algorithm().name(), "ECDH-ES+A128KW");
+
+        let err = jwe::encrypter_from_jwk_set(&jwk_set, &["A128KW", "dir"])
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("A128KW: "), "{}", err);
+        assert!(err.contains("dir: "), "{}", err);
+        assert!(err.contains("kty"), "{}", err);
+        let err = jwe::encrypter_from_jwk_set(&jwk_set, &["unknown"])
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("is not found"), "{}", err);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encrypter_from_jwk_set_with_key_use() -> Result<()> {
+        let enc_private_key = Jwk::generate_ec_key(EcCurve::P256)?;
+
+        let mut sig_key = Jwk::generate_ec_key(EcCurve::P256)?.to_public_key()?;
+        sig_key.set_key_id("sig-key");
+        sig_key.set_key_use("sig");
+        let mut verify_key = Jwk::generate_ec_key(EcCurve::P256)?.to_public_key()?;
+        verify_key.set_key_id("verify-key");
+        verify_key.set_key_operations(vec!["verify"]);
+        let mut enc_key = enc_private_key.to_public_key()?;
+        enc_key.set_key_id("enc-key");
+        enc_key.set_key_use("enc");
+
+        let mut jwk_set = JwkSet::new();
+        jwk_set.push_key(sig_key.clone());
+        jwk_set.push_key(verify_key.clone());
+        jwk_set.push_key(enc_key);
+
+        let encrypter = jwe::encrypter_from_jwk_set(&jwk_set, &["ECDH-ES"])?;
+        assert_eq!(encrypter.key_id(), Some("enc-key"));
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128GCM");
+        let jwe = jwe::serialize_compact(b"payload", &header, &*encrypter)?;
+        let (payload, _) =
+            jwe::deserialize_compact(&jwe, &ECDH_ES.decrypter_from_jwk(&enc_private_key)?)?;
+        assert_eq!(payload, b"payload");
+
+        // Only keys for signatures are left.
+        let mut jwk_set = JwkSet::new();
+        jwk_set.push_key(sig_key);
+        jwk_set.push_key(verify_key);
+        assert!(jwe::encrypter_from_jwk_set(&jwk_set, &["ECDH-ES", "ECDH-ES+A128KW"]).is_err());
+
+        // The only key is for encryption, but its key_ops excludes deriveKey.
+        let mut unusable_key = Jwk::generate_ec_key(EcCurve::P256)?.to_public_key()?;
+        unusable_key.set_key_use("enc");
+        unusable_key.set_key_operations(vec!["encrypt"]);
+        let mut jwk_set = JwkSet::new();
+        jwk_set.push_key(unusable_key);
+        let err = jwe::encrypter_from_jwk_set(&jwk_set, &["ECDH-ES+A128KW"])
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("ECDH-ES+A128KW: "), "{}", err);
+        assert!(err.contains("key_ops must contains deriveKey"), "{}", err);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encrypter_key_type_and_curve() -> Result<()> {
+        let encrypter = Dir.encrypter_from_bytes(b"0123456789ABCDEF")?;
+        assert_eq!(encrypter.key_type(), Some("oct"));
+        assert_eq!(encrypter.curve(), None);
+
+        let encrypter = PBES2_HS256_A128KW.encrypter_from_bytes(b"password")?;
+        assert_eq!(encrypter.key_type(), Some("oct"));
+
+        let private_key = Jwk::generate_ec_key(EcCurve::P384)?;
+        let encrypter = ECDH_ES.encrypter_from_jwk(&private_key.to_public_key()?)?;
+        assert_eq!(encrypter.key_type(), Some("EC"));
+        assert_eq!(encrypter.curve(), Some("P-384"));
+
+        let private_key = Jwk::generate_ecx_key(EcxCurve::X25519)?;
+        let encrypter = ECDH_ES_A128KW.encrypter_from_jwk(&private_key.to_public_key()?)?;
+        assert_eq!(encrypter.key_type(), Some("OKP"));
+        assert_eq!(encrypter.curve(), Some("X25519"));
+
+        let private_key = Jwk::generate_rsa_key(2048)?;
+        let encrypter = RSA_OAEP.encrypter_from_jwk(&private_key.to_public_key()?)?;
+        assert_eq!(encrypter.key_type(), Some("RSA"));
+        assert_eq!(encrypter.curve(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_with_malformed_input() -> Result<()> {
+        let decrypter = Dir.decrypter_from_bytes(b"0123456789ABCDEF")?;
+
+        // These inputs were found by the fuzz targets in the fuzz directory.
+        let header = util::encode_base64_urlsafe_nopad(r#"{"alg":"dir","enc":"A128GCM"}"#);
+        for input in [
+            format!("{}....AA", header),
+            format!("{}..AA..", header),
+            format!("{}....", header),
+            format!("{}.", header),
+            "....".to_string(),
+            "".to_string(),
+        ] {
+            assert!(jwe::deserialize_compact(&input, &decrypter).is_err());
+        }
+
+        let input = format!(
+            r#"{{"protected":"{}","ciphertext":"AA","tag":"AA"}}"#,
+            header
+        );
+        assert!(jwe::deserialize_json(&input, &decrypter).is_err());
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
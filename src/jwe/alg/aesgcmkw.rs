@@ -6,8 +6,8 @@ use anyhow::bail;
 use openssl::symm::{self, Cipher};
 
 use crate::jwe::{JweAlgorithm, JweContentEncryption, JweDecrypter, JweEncrypter, JweHeader};
-use crate::jwk::Jwk;
-use crate::util;
+use crate::jwk::{Jwk, KeyOps};
+use crate::util::{self, OpensslRand, RandomSource};
 use crate::{JoseError, JoseHeader, Value};
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
@@ -40,6 +40,7 @@ impl AesgcmkwJweAlgorithm {
                 algorithm: self.clone(),
                 private_key,
                 key_id: None,
+                random_source: Box::new(OpensslRand),
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -56,7 +57,7 @@ impl AesgcmkwJweAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be enc: {}", val),
             }
-            if !jwk.is_for_key_operation("encrypt") {
+            if !jwk.is_for_key_operations(KeyOps::ENCRYPT) {
                 bail!("A parameter key_ops must contains encrypt.");
             }
             match jwk.algorithm() {
@@ -80,6 +81,7 @@ impl AesgcmkwJweAlgorithm {
                 algorithm: self.clone(),
                 private_key: k,
                 key_id,
+                random_source: Box::new(OpensslRand),
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -120,7 +122,7 @@ impl AesgcmkwJweAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be enc: {}", val),
             }
-            if !jwk.is_for_key_operation("decrypt") {
+            if !jwk.is_for_key_operations(KeyOps::DECRYPT) {
                 bail!("A parameter key_ops must contains decrypt.");
             }
             match jwk.algorithm() {
@@ -200,6 +202,7 @@ pub struct AesgcmkwJweEncrypter {
     algorithm: AesgcmkwJweAlgorithm,
     private_key: Vec<u8>,
     key_id: Option<String>,
+    random_source: Box<dyn RandomSource>,
 }
 
 impl AesgcmkwJweEncrypter {
@@ -210,6 +213,15 @@ impl AesgcmkwJweEncrypter {
     pub fn remove_key_id(&mut self) {
         self.key_id = None;
     }
+
+    /// Set a random source used to generate IVs.
+    ///
+    /// # Arguments
+    ///
+    /// * `random_source` - a random source
+    pub fn set_random_source(&mut self, random_source: Box<dyn RandomSource>) {
+        self.random_source = random_source;
+    }
 }
 
 impl JweEncrypter for AesgcmkwJweEncrypter {
@@ -224,12 +236,16 @@ impl JweEncrypter for AesgcmkwJweEncrypter {
         }
     }
 
+    fn key_type(&self) -> Option<&str> {
+        Some("oct")
+    }
+
     fn compute_content_encryption_key(
         &self,
         _cencryption: &dyn JweContentEncryption,
         _in_header: &JweHeader,
         _out_header: &mut JweHeader,
-    ) -> Result<Option<Cow<[u8]>>, JoseError> {
+    ) -> Result<Option<Cow<'_, [u8]>>, JoseError> {
         Ok(None)
     }
 
@@ -240,7 +256,7 @@ impl JweEncrypter for AesgcmkwJweEncrypter {
         out_header: &mut JweHeader,
     ) -> Result<Option<Vec<u8>>, JoseError> {
         (|| -> anyhow::Result<Option<Vec<u8>>> {
-            let iv = util::random_bytes(12);
+            let iv = util::random_bytes_from(self.random_source.as_ref(), 12)?;
 
             let cipher = self.algorithm.cipher();
             let mut tag = [0; 16];
@@ -306,9 +322,9 @@ impl JweDecrypter for AesgcmkwJweDecrypter {
     fn decrypt(
         &self,
         encrypted_key: Option<&[u8]>,
-        _cencryption: &dyn JweContentEncryption,
+        cencryption: &dyn JweContentEncryption,
         header: &JweHeader,
-    ) -> Result<Cow<[u8]>, JoseError> {
+    ) -> Result<Cow<'_, [u8]>, JoseError> {
         (|| -> anyhow::Result<Cow<[u8]>> {
             let encrypted_key = match encrypted_key {
                 Some(val) => val,
@@ -327,6 +343,16 @@ impl JweDecrypter for AesgcmkwJweDecrypter {
                 None => bail!("The tag header claim is required."),
             };
 
+            if iv.len() != 12 {
+                bail!("The length of the iv header claim must be 12: {}", iv.len());
+            }
+            if tag.len() != 16 {
+                bail!(
+                    "The length of the tag header claim must be 16: {}",
+                    tag.len()
+                );
+            }
+
             let cipher = self.algorithm.cipher();
             let key = symm::decrypt_aead(
                 cipher,
@@ -337,6 +363,14 @@ impl JweDecrypter for AesgcmkwJweDecrypter {
                 &tag,
             )?;
 
+            if key.len() != cencryption.key_len() {
+                bail!(
+                    "The length of the unwrapped key must be {}: {}",
+                    cencryption.key_len(),
+                    key.len()
+                );
+            }
+
             Ok(Cow::Owned(key))
         })()
         .map_err(|err| JoseError::InvalidJweFormat(err))
@@ -365,6 +399,7 @@ mod tests {
     use crate::jwe::JweHeader;
     use crate::jwk::Jwk;
     use crate::util;
+    use crate::JoseError;
 
     #[test]
     fn encrypt_and_decrypt_aes_gcm() -> Result<()> {
@@ -379,7 +414,7 @@ mod tests {
             header.set_content_encryption(enc.name());
 
             let jwk = {
-                let key = util::random_bytes(alg.key_len());
+                let key = util::random_bytes(alg.key_len())?;
                 let key = util::encode_base64_urlsafe_nopad(&key);
 
                 let mut jwk = Jwk::new("oct");
@@ -389,7 +424,7 @@ mod tests {
             };
 
             let encrypter = alg.encrypter_from_jwk(&jwk)?;
-            let src_key = util::random_bytes(enc.key_len());
+            let src_key = util::random_bytes(enc.key_len())?;
             let mut out_header = header.clone();
             let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
 
@@ -401,4 +436,31 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn decrypt_aes_gcm_with_wrong_key_length() -> Result<()> {
+        let enc = AescbcHmacJweEncryption::A128cbcHs256;
+        let alg = AesgcmkwJweAlgorithm::A256gcmkw;
+        let key = util::random_bytes(alg.key_len())?;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption(enc.name());
+
+        // The tag of A256GCMKW covers a 16-byte key, so it decrypts, but A128CBC-HS256
+        // expects 32 bytes.
+        let encrypter = alg.encrypter_from_bytes(&key)?;
+        let mut out_header = header.clone();
+        let encrypted_key = encrypter.encrypt(&[0; 16], &header, &mut out_header)?;
+
+        let decrypter = alg.decrypter_from_bytes(&key)?;
+        match decrypter.decrypt(encrypted_key.as_deref(), &enc, &out_header) {
+            Err(JoseError::InvalidJweFormat(err)) => assert_eq!(
+                err.to_string(),
+                "The length of the unwrapped key must be 32: 16"
+            ),
+            res => panic!("{:?}", res),
+        }
+
+        Ok(())
+    }
 }
@@ -6,7 +6,7 @@ use anyhow::bail;
 use openssl::aes::{self, AesKey};
 
 use crate::jwe::{JweAlgorithm, JweContentEncryption, JweDecrypter, JweEncrypter, JweHeader};
-use crate::jwk::Jwk;
+use crate::jwk::{Jwk, KeyOps};
 use crate::{util, JoseError, Value};
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
@@ -55,7 +55,7 @@ impl AeskwJweAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be enc: {}", val),
             }
-            if !jwk.is_for_key_operation("wrapKey") {
+            if !jwk.is_for_key_operations(KeyOps::WRAP_KEY) {
                 bail!("A parameter key_ops must contains wrapKey.");
             }
             match jwk.algorithm() {
@@ -119,7 +119,7 @@ impl AeskwJweAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be enc: {}", val),
             }
-            if !jwk.is_for_key_operation("unwrapKey") {
+            if !jwk.is_for_key_operations(KeyOps::UNWRAP_KEY) {
                 bail!("A parameter key_ops must contains unwrapKey.");
             }
             match jwk.algorithm() {
@@ -215,12 +215,16 @@ impl JweEncrypter for AeskwJweEncrypter {
         }
     }
 
+    fn key_type(&self) -> Option<&str> {
+        Some("oct")
+    }
+
     fn compute_content_encryption_key(
         &self,
         _cencryption: &dyn JweContentEncryption,
         _in_header: &JweHeader,
         _out_header: &mut JweHeader,
-    ) -> Result<Option<Cow<[u8]>>, JoseError> {
+    ) -> Result<Option<Cow<'_, [u8]>>, JoseError> {
         Ok(None)
     }
 
@@ -296,14 +300,20 @@ impl JweDecrypter for AeskwJweDecrypter {
     fn decrypt(
         &self,
         encrypted_key: Option<&[u8]>,
-        _cencryption: &dyn JweContentEncryption,
+        cencryption: &dyn JweContentEncryption,
         _header: &JweHeader,
-    ) -> Result<Cow<[u8]>, JoseError> {
+    ) -> Result<Cow<'_, [u8]>, JoseError> {
         (|| -> anyhow::Result<Cow<[u8]>> {
             let encrypted_key = match encrypted_key {
                 Some(val) => val,
                 None => bail!("A encrypted_key is required."),
             };
+            if encrypted_key.len() < 16 {
+                bail!(
+                    "The length of encrypted_key is invalid: {}",
+                    encrypted_key.len()
+                );
+            }
 
             let aes = match AesKey::new_decrypt(&self.private_key) {
                 Ok(val) => val,
@@ -320,6 +330,14 @@ impl JweDecrypter for AeskwJweDecrypter {
                 Err(_) => bail!("Failed to unwrap key."),
             };
 
+            if key.len() != cencryption.key_len() {
+                bail!(
+                    "The length of the unwrapped key must be {}: {}",
+                    cencryption.key_len(),
+                    key.len()
+                );
+            }
+
             Ok(Cow::Owned(key))
         })()
         .map_err(|err| JoseError::InvalidJweFormat(err))
@@ -348,6 +366,7 @@ mod tests {
     use crate::jwe::JweHeader;
     use crate::jwk::Jwk;
     use crate::util;
+    use crate::JoseError;
 
     #[test]
     fn encrypt_and_decrypt_aes() -> Result<()> {
@@ -362,7 +381,7 @@ mod tests {
             header.set_content_encryption(enc.name());
 
             let jwk = {
-                let key = util::random_bytes(alg.key_len());
+                let key = util::random_bytes(alg.key_len())?;
                 let key = util::encode_base64_urlsafe_nopad(&key);
 
                 let mut jwk = Jwk::new("oct");
@@ -372,7 +391,7 @@ mod tests {
             };
 
             let encrypter = alg.encrypter_from_jwk(&jwk)?;
-            let src_key = util::random_bytes(enc.key_len());
+            let src_key = util::random_bytes(enc.key_len())?;
             let mut out_header = header.clone();
             let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
 
@@ -384,4 +403,35 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn decrypt_aes_with_wrong_key_length() -> Result<()> {
+        let enc = AescbcHmacJweEncryption::A128cbcHs256;
+        let alg = AeskwJweAlgorithm::A256kw;
+        let key = util::random_bytes(alg.key_len())?;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption(enc.name());
+
+        // A 16-byte key is wrapped, but A128CBC-HS256 needs a 32-byte CEK after unwrapping.
+        let encrypter = alg.encrypter_from_bytes(&key)?;
+        let mut out_header = header.clone();
+        let encrypted_key = encrypter.encrypt(&[0; 16], &header, &mut out_header)?;
+
+        let decrypter = alg.decrypter_from_bytes(&key)?;
+        match decrypter.decrypt(encrypted_key.as_deref(), &enc, &out_header) {
+            Err(JoseError::InvalidJweFormat(err)) => assert_eq!(
+                err.to_string(),
+                "The length of the unwrapped key must be 32: 16"
+            ),
+            res => panic!("{:?}", res),
+        }
+
+        assert!(matches!(
+            decrypter.decrypt(Some(&[0; 8]), &enc, &out_header),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        Ok(())
+    }
 }
@@ -5,7 +5,7 @@ use std::ops::Deref;
 use anyhow::bail;
 
 use crate::jwe::{JweAlgorithm, JweContentEncryption, JweDecrypter, JweEncrypter, JweHeader};
-use crate::jwk::Jwk;
+use crate::jwk::{Jwk, KeyOps};
 use crate::{util, JoseError, Value};
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
@@ -39,7 +39,7 @@ impl DirectJweAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be enc: {}", val),
             }
-            if !jwk.is_for_key_operation("encrypt") {
+            if !jwk.is_for_key_operations(KeyOps::ENCRYPT) {
                 bail!("A parameter key_ops must contains encrypt.");
             }
             match jwk.algorithm() {
@@ -88,7 +88,7 @@ impl DirectJweAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be enc: {}", val),
             }
-            if !jwk.is_for_key_operation("decrypt") {
+            if !jwk.is_for_key_operations(KeyOps::DECRYPT) {
                 bail!("A parameter key_ops must contains decrypt.");
             }
             match jwk.algorithm() {
@@ -170,12 +170,16 @@ impl JweEncrypter for DirectJweEncrypter {
         }
     }
 
+    fn key_type(&self) -> Option<&str> {
+        Some("oct")
+    }
+
     fn compute_content_encryption_key(
         &self,
         cencryption: &dyn JweContentEncryption,
         _merged: &JweHeader,
         _header: &mut JweHeader,
-    ) -> Result<Option<Cow<[u8]>>, JoseError> {
+    ) -> Result<Option<Cow<'_, [u8]>>, JoseError> {
         (|| -> anyhow::Result<Option<Cow<[u8]>>> {
             let actual_len = self.cencryption_key.len();
             if cencryption.key_len() != actual_len {
@@ -245,14 +249,23 @@ impl JweDecrypter for DirectJweDecrypter {
     fn decrypt(
         &self,
         encrypted_key: Option<&[u8]>,
-        _cencryption: &dyn JweContentEncryption,
+        cencryption: &dyn JweContentEncryption,
         _header: &JweHeader,
-    ) -> Result<Cow<[u8]>, JoseError> {
+    ) -> Result<Cow<'_, [u8]>, JoseError> {
         (|| -> anyhow::Result<Cow<[u8]>> {
             if let Some(_) = encrypted_key {
                 bail!("The encrypted_key must not exist.");
             }
 
+            let actual_len = self.cencryption_key.len();
+            if cencryption.key_len() != actual_len {
+                bail!(
+                    "The key size is expected to be {}: {}",
+                    cencryption.key_len(),
+                    actual_len
+                );
+            }
+
             Ok(Cow::Borrowed(&self.cencryption_key))
         })()
         .map_err(|err| JoseError::InvalidJweFormat(err))
@@ -278,8 +291,10 @@ mod tests {
 
     use super::DirectJweAlgorithm;
     use crate::jwe::enc::aescbc_hmac::AescbcHmacJweEncryption;
-    use crate::jwe::JweHeader;
+    use crate::jwe::enc::aesgcm::AesgcmJweEncryption;
+    use crate::jwe::{self, JweContentEncryption, JweHeader};
     use crate::jwk::Jwk;
+    use crate::JoseError;
 
     #[test]
     fn encrypt_and_decrypt_direct() -> Result<()> {
@@ -314,4 +329,53 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn encrypt_and_decrypt_direct_a256gcm() -> Result<()> {
+        let enc = AesgcmJweEncryption::A256gcm;
+        let alg = DirectJweAlgorithm::Dir;
+        let jwk = Jwk::generate_oct_key(32)?;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption(enc.name());
+        let encrypter = alg.encrypter_from_jwk(&jwk)?;
+        let decrypter = alg.decrypter_from_jwk(&jwk)?;
+        let jwe = jwe::serialize_compact(b"payload", &header, &encrypter)?;
+
+        // The encrypted key segment is empty.
+        assert_eq!(jwe.split('.').nth(1), Some(""));
+
+        let (payload, header) = jwe::deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(payload, b"payload");
+        assert_eq!(header.algorithm(), Some("dir"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_direct_with_mismatched_key_len() -> Result<()> {
+        let enc = AesgcmJweEncryption::A256gcm;
+        let alg = DirectJweAlgorithm::Dir;
+        let jwk = Jwk::generate_oct_key(16)?;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption(enc.name());
+
+        let encrypter = alg.encrypter_from_jwk(&jwk)?;
+        let mut out_header = header.clone();
+        assert!(matches!(
+            encrypter.compute_content_encryption_key(&enc, &header, &mut out_header),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+
+        let decrypter = alg.decrypter_from_jwk(&jwk)?;
+        match decrypter.decrypt(None, &enc, &out_header) {
+            Err(JoseError::InvalidJweFormat(err)) => {
+                assert_eq!(err.to_string(), "The key size is expected to be 32: 16")
+            }
+            res => panic!("{:?}", res),
+        }
+
+        Ok(())
+    }
 }
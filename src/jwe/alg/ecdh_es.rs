@@ -4,22 +4,23 @@ use std::ops::Deref;
 
 use anyhow::bail;
 use openssl::aes::{self, AesKey};
-use openssl::derive::Deriver;
 use openssl::hash::{Hasher, MessageDigest};
 use openssl::pkey::{PKey, Private, Public};
 
-use crate::jwe::{JweAlgorithm, JweContentEncryption, JweDecrypter, JweEncrypter, JweHeader};
+use crate::jwe::{
+    JweAlgorithm, JweContentEncryption, JweDecrypter, JweDecryptionOptions, JweEncrypter, JweHeader,
+};
 use crate::jwk::alg::{
     ec::{EcCurve, EcKeyPair},
     ecx::{EcxCurve, EcxKeyPair},
 };
-use crate::jwk::Jwk;
-use crate::util;
+use crate::jwk::{Jwk, KeyOps};
 use crate::util::der::{DerReader, DerType};
 use crate::util::oid::{
     OID_ID_EC_PUBLIC_KEY, OID_PRIME256V1, OID_SECP256K1, OID_SECP384R1, OID_SECP521R1, OID_X25519,
     OID_X448,
 };
+use crate::util::{self, RandomSource};
 use crate::{JoseError, JoseHeader, Map, Value};
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
@@ -42,6 +43,15 @@ impl EcdhEsKeyType {
             Self::Ecx(val) => val.name(),
         }
     }
+
+    /// Return the field size that is the length of a coordinate and a shared secret.
+    fn field_size(&self) -> usize {
+        match self {
+            Self::Ec(val) => val.coordinate_size(),
+            Self::Ecx(EcxCurve::X25519) => 32,
+            Self::Ecx(EcxCurve::X448) => 56,
+        }
+    }
 }
 
 impl Display for EcdhEsKeyType {
@@ -152,6 +162,8 @@ impl EcdhEsJweAlgorithm {
                 key_id: None,
                 agreement_partyuinfo: None,
                 agreement_partyvinfo: None,
+                agreement_info_from_kids: false,
+                random_source: None,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -181,12 +193,33 @@ impl EcdhEsJweAlgorithm {
                 key_id: None,
                 agreement_partyuinfo: None,
                 agreement_partyvinfo: None,
+                agreement_info_from_kids: false,
+                random_source: None,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
     pub fn encrypter_from_jwk(&self, jwk: &Jwk) -> Result<EcdhEsJweEncrypter, JoseError> {
+        self.encrypter_from_jwk_with(jwk, false)
+    }
+
+    /// Return a encrypter from a public key that is formatted by a JWK.
+    ///
+    /// Unlike `encrypter_from_jwk`, the key_ops parameter must contain the exact operation
+    /// of the algorithm: deriveBits for direct key agreement and deriveKey for key wrapping.
+    ///
+    /// # Arguments
+    /// * `jwk` - A public key that is formatted by a JWK.
+    pub fn encrypter_from_jwk_strict(&self, jwk: &Jwk) -> Result<EcdhEsJweEncrypter, JoseError> {
+        self.encrypter_from_jwk_with(jwk, true)
+    }
+
+    fn encrypter_from_jwk_with(
+        &self,
+        jwk: &Jwk,
+        strict: bool,
+    ) -> Result<EcdhEsJweEncrypter, JoseError> {
         (|| -> anyhow::Result<EcdhEsJweEncrypter> {
             let key_type = match jwk.key_type() {
                 val if val == "EC" || val == "OKP" => val,
@@ -197,9 +230,7 @@ impl EcdhEsJweAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be enc: {}", val),
             }
-            if !jwk.is_for_key_operation("deriveKey") {
-                bail!("A parameter key_ops must contains deriveKey.");
-            }
+            self.check_key_operations(jwk, strict)?;
             match jwk.algorithm() {
                 Some(val) if val == self.name() => {}
                 None => {}
@@ -208,12 +239,9 @@ impl EcdhEsJweAlgorithm {
             let (public_key, key_type) = match jwk.parameter("crv") {
                 Some(Value::String(val)) => match key_type {
                     "EC" => {
-                        let curve = match val.as_str() {
-                            "P-256" => EcCurve::P256,
-                            "P-384" => EcCurve::P384,
-                            "P-521" => EcCurve::P521,
-                            "secp256k1" => EcCurve::Secp256k1,
-                            val => bail!("EC key doesn't support the curve algorithm: {}", val),
+                        let curve = match EcCurve::from_crv_str(val) {
+                            Some(val) => val,
+                            None => bail!("EC key doesn't support the curve algorithm: {}", val),
                         };
                         let x = match jwk.parameter("x") {
                             Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
@@ -237,10 +265,9 @@ impl EcdhEsJweAlgorithm {
                         (public_key, EcdhEsKeyType::Ec(curve))
                     }
                     "OKP" => {
-                        let curve = match val.as_str() {
-                            "X25519" => EcxCurve::X25519,
-                            "X448" => EcxCurve::X448,
-                            val => bail!("OKP key doesn't support the curve algorithm: {}", val),
+                        let curve = match EcxCurve::from_crv_str(val) {
+                            Some(val) => val,
+                            None => bail!("OKP key doesn't support the curve algorithm: {}", val),
                         };
                         let x = match jwk.parameter("x") {
                             Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
@@ -253,7 +280,7 @@ impl EcdhEsJweAlgorithm {
 
                         (public_key, EcdhEsKeyType::Ecx(curve))
                     }
-                    _ => unreachable!(),
+                    val => bail!("A parameter kty must be EC or OKP: {}", val),
                 },
                 Some(_) => bail!("A parameter crv must be a string."),
                 None => bail!("A parameter crv is required."),
@@ -267,6 +294,8 @@ impl EcdhEsJweAlgorithm {
                 key_id,
                 agreement_partyuinfo: None,
                 agreement_partyvinfo: None,
+                agreement_info_from_kids: false,
+                random_source: None,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -346,7 +375,42 @@ impl EcdhEsJweAlgorithm {
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
+    /// Return a decrypter from a private key of encrypted PKCS#8 PEM format.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - A private key of encrypted PKCS#8 PEM format.
+    /// * `passphrase` - A passphrase for decrypting the private key.
+    pub fn decrypter_from_encrypted_pem(
+        &self,
+        input: impl AsRef<[u8]>,
+        passphrase: impl AsRef<[u8]>,
+    ) -> Result<EcdhEsJweDecrypter, JoseError> {
+        let pkcs8_der = util::decrypt_pkcs8_pem(input.as_ref(), passphrase.as_ref())
+            .map_err(JoseError::InvalidKeyFormat)?;
+        self.decrypter_from_der(pkcs8_der)
+    }
+
     pub fn decrypter_from_jwk(&self, jwk: &Jwk) -> Result<EcdhEsJweDecrypter, JoseError> {
+        self.decrypter_from_jwk_with(jwk, false)
+    }
+
+    /// Return a decrypter from a private key that is formatted by a JWK.
+    ///
+    /// Unlike `decrypter_from_jwk`, the key_ops parameter must contain the exact operation
+    /// of the algorithm: deriveBits for direct key agreement and deriveKey for key wrapping.
+    ///
+    /// # Arguments
+    /// * `jwk` - A private key that is formatted by a JWK.
+    pub fn decrypter_from_jwk_strict(&self, jwk: &Jwk) -> Result<EcdhEsJweDecrypter, JoseError> {
+        self.decrypter_from_jwk_with(jwk, true)
+    }
+
+    fn decrypter_from_jwk_with(
+        &self,
+        jwk: &Jwk,
+        strict: bool,
+    ) -> Result<EcdhEsJweDecrypter, JoseError> {
         (|| -> anyhow::Result<EcdhEsJweDecrypter> {
             let key_type = match jwk.key_type() {
                 val if val == "EC" || val == "OKP" => val,
@@ -357,9 +421,7 @@ impl EcdhEsJweAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be enc: {}", val),
             }
-            if !jwk.is_for_key_operation("deriveKey") {
-                bail!("A parameter key_ops must contains deriveKey.");
-            }
+            self.check_key_operations(jwk, strict)?;
             match jwk.algorithm() {
                 Some(val) if val == self.name() => {}
                 None => {}
@@ -368,12 +430,9 @@ impl EcdhEsJweAlgorithm {
             let (private_key, key_type) = match jwk.parameter("crv") {
                 Some(Value::String(val)) => match key_type {
                     "EC" => {
-                        let curve = match val.as_str() {
-                            "P-256" => EcCurve::P256,
-                            "P-384" => EcCurve::P384,
-                            "P-521" => EcCurve::P521,
-                            "secp256k1" => EcCurve::Secp256k1,
-                            val => bail!("EC key doesn't support the curve algorithm: {}", val),
+                        let curve = match EcCurve::from_crv_str(val) {
+                            Some(val) => val,
+                            None => bail!("EC key doesn't support the curve algorithm: {}", val),
                         };
                         match jwk.curve() {
                             Some(val) if val == curve.name() => {}
@@ -388,10 +447,9 @@ impl EcdhEsJweAlgorithm {
                         (private_key, EcdhEsKeyType::Ec(curve))
                     }
                     "OKP" => {
-                        let curve = match val.as_str() {
-                            "X25519" => EcxCurve::X25519,
-                            "X448" => EcxCurve::X448,
-                            val => bail!("OKP key doesn't support the curve algorithm: {}", val),
+                        let curve = match EcxCurve::from_crv_str(val) {
+                            Some(val) => val,
+                            None => bail!("OKP key doesn't support the curve algorithm: {}", val),
                         };
                         match jwk.curve() {
                             Some(val) if val == curve.name() => {}
@@ -405,7 +463,7 @@ impl EcdhEsJweAlgorithm {
 
                         (private_key, EcdhEsKeyType::Ecx(curve))
                     }
-                    _ => unreachable!(),
+                    val => bail!("A parameter kty must be EC or OKP: {}", val),
                 },
                 Some(_) => bail!("A parameter crv must be a string."),
                 None => bail!("A parameter crv is required."),
@@ -422,6 +480,25 @@ impl EcdhEsJweAlgorithm {
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
+    /// Check the key_ops parameter allows the key derivation of the algorithm.
+    ///
+    /// Direct key agreement derives bits, so deriveBits is accepted besides deriveKey.
+    /// In strict mode, only the exact operation is accepted.
+    fn check_key_operations(&self, jwk: &Jwk, strict: bool) -> anyhow::Result<()> {
+        let key_operations = match (self, strict) {
+            (Self::EcdhEs, false) => KeyOps::DERIVE_KEY | KeyOps::DERIVE_BITS,
+            (Self::EcdhEs, true) => KeyOps::DERIVE_BITS,
+            _ => KeyOps::DERIVE_KEY,
+        };
+        if !jwk.key_operations_typed().intersects(key_operations) {
+            bail!(
+                "A parameter key_ops must contains {}.",
+                key_operations.names().join(" or ")
+            );
+        }
+        Ok(())
+    }
+
     fn key_len(&self) -> usize {
         match self {
             Self::EcdhEsA128kw => 16,
@@ -538,10 +615,15 @@ impl EcdhEsJweAlgorithm {
             shared_key.extend(digest.to_vec());
         }
 
-        if shared_key.len() > shared_key_len {
-            shared_key.truncate(shared_key_len);
-        } else if shared_key.len() < shared_key_len {
-            unreachable!();
+        // The whole rounds are always hashed first, and then the output is
+        // cut down to the requested length regardless of the size of Z.
+        shared_key.truncate(shared_key_len);
+        if shared_key.len() != shared_key_len {
+            bail!(
+                "The derived key length is invalid: {} != {}",
+                shared_key.len(),
+                shared_key_len
+            );
         }
 
         Ok(shared_key)
@@ -584,7 +666,9 @@ pub struct EcdhEsJweEncrypter {
     public_key: PKey<Public>,
     agreement_partyuinfo: Option<Vec<u8>>,
     agreement_partyvinfo: Option<Vec<u8>>,
+    agreement_info_from_kids: bool,
     key_id: Option<String>,
+    random_source: Option<Box<dyn RandomSource>>,
 }
 
 impl EcdhEsJweEncrypter {
@@ -604,6 +688,23 @@ impl EcdhEsJweEncrypter {
         self.agreement_partyvinfo = None;
     }
 
+    /// Set whether the agreement PartyUInfo and PartyVInfo are populated from key IDs.
+    ///
+    /// When enabled, the apu header claim is the sender key ID (skid) and
+    /// the apv header claim is the recipient key ID (kid) unless they are set explicitly.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - true if the key IDs are used.
+    pub fn set_agreement_info_from_kids(&mut self, value: bool) {
+        self.agreement_info_from_kids = value;
+    }
+
+    /// Return whether the agreement PartyUInfo and PartyVInfo are populated from key IDs.
+    pub fn is_agreement_info_from_kids(&self) -> bool {
+        self.agreement_info_from_kids
+    }
+
     pub fn set_key_id(&mut self, value: impl Into<String>) {
         self.key_id = Some(value.into());
     }
@@ -612,13 +713,43 @@ impl EcdhEsJweEncrypter {
         self.key_id = None;
     }
 
+    /// Set a random source used to generate ephemeral private keys.
+    ///
+    /// By default, ephemeral key pairs are generated by OpenSSL.
+    ///
+    /// # Arguments
+    ///
+    /// * `random_source` - a random source
+    pub fn set_random_source(&mut self, random_source: Box<dyn RandomSource>) {
+        self.random_source = Some(random_source);
+    }
+
+    /// Set the JWK thumbprint (RFC 7638) of the public key using SHA-256 as a key ID.
+    pub fn set_key_id_to_thumbprint(&mut self) -> Result<(), JoseError> {
+        let jwk = Jwk::from_public_key(&self.public_key)?;
+        self.key_id = Some(jwk.thumbprint_sha256()?);
+        Ok(())
+    }
+
     fn compute_shared_key(
         &self,
+        merged: &JweHeader,
         header: &mut JweHeader,
         alg: &str,
         key_len: usize,
     ) -> Result<Vec<u8>, JoseError> {
         (|| -> anyhow::Result<Vec<u8>> {
+            let (skid, kid) = if self.agreement_info_from_kids {
+                let skid = merged.claim_str("skid").map(str::as_bytes);
+                let kid = merged
+                    .key_id()
+                    .or(self.key_id.as_deref())
+                    .map(str::as_bytes);
+                (skid, kid)
+            } else {
+                (None, None)
+            };
+
             let apu_vec;
             let apu = match header.claim("apu") {
                 Some(Value::String(val)) => {
@@ -626,11 +757,11 @@ impl EcdhEsJweEncrypter {
                     Some(apu_vec.as_slice())
                 }
                 Some(_) => bail!("The apu header claim must be string."),
-                None => match &self.agreement_partyuinfo {
+                None => match self.agreement_partyuinfo.as_deref().or(skid) {
                     Some(val) => {
                         let apu_b64 = util::encode_base64_urlsafe_nopad(val);
                         header.set_claim("apu", Some(Value::String(apu_b64)))?;
-                        Some(val.as_slice())
+                        Some(val)
                     }
                     None => None,
                 },
@@ -642,11 +773,11 @@ impl EcdhEsJweEncrypter {
                     Some(apv_vec.as_slice())
                 }
                 Some(_) => bail!("The apv header claim must be string."),
-                None => match &self.agreement_partyvinfo {
+                None => match self.agreement_partyvinfo.as_deref().or(kid) {
                     Some(val) => {
                         let apv_b64 = util::encode_base64_urlsafe_nopad(val);
                         header.set_claim("apv", Some(Value::String(apv_b64)))?;
-                        Some(val.as_slice())
+                        Some(val)
                     }
                     None => None,
                 },
@@ -663,33 +794,39 @@ impl EcdhEsJweEncrypter {
             );
             let private_key = match self.key_type {
                 EcdhEsKeyType::Ec(curve) => {
-                    let key_pair = EcKeyPair::generate(curve)?;
+                    let key_pair = match &self.random_source {
+                        Some(val) => EcKeyPair::generate_from(curve, val.as_ref())?,
+                        None => EcKeyPair::generate(curve)?,
+                    };
                     let mut jwk: Map<String, Value> = key_pair.to_jwk_public_key().into();
 
                     match jwk.remove("x") {
                         Some(val) => {
                             map.insert("x".to_string(), val);
                         }
-                        None => unreachable!(),
+                        None => bail!("The ephemeral key must have a x parameter."),
                     }
                     match jwk.remove("y") {
                         Some(val) => {
                             map.insert("y".to_string(), val);
                         }
-                        None => unreachable!(),
+                        None => bail!("The ephemeral key must have a y parameter."),
                     }
 
                     key_pair.into_private_key()
                 }
                 EcdhEsKeyType::Ecx(curve) => {
-                    let key_pair = EcxKeyPair::generate(curve)?;
+                    let key_pair = match &self.random_source {
+                        Some(val) => EcxKeyPair::generate_from(curve, val.as_ref())?,
+                        None => EcxKeyPair::generate(curve)?,
+                    };
                     let mut jwk: Map<String, Value> = key_pair.to_jwk_public_key().into();
 
                     match jwk.remove("x") {
                         Some(val) => {
                             map.insert("x".to_string(), val);
                         }
-                        None => unreachable!(),
+                        None => bail!("The ephemeral key must have a x parameter."),
                     }
 
                     key_pair.into_private_key()
@@ -698,9 +835,7 @@ impl EcdhEsJweEncrypter {
 
             header.set_claim("epk", Some(Value::Object(map)))?;
 
-            let mut deriver = Deriver::new(&private_key)?;
-            deriver.set_peer(&self.public_key)?;
-            let derived_key = deriver.derive_to_vec()?;
+            let derived_key = util::derive_shared_secret(&private_key, &self.public_key)?;
 
             let shared_key = self.algorithm.concat_kdf(
                 alg,
@@ -731,15 +866,23 @@ impl JweEncrypter for EcdhEsJweEncrypter {
         }
     }
 
+    fn key_type(&self) -> Option<&str> {
+        Some(self.key_type.key_type())
+    }
+
+    fn curve(&self) -> Option<&str> {
+        Some(self.key_type.curve_name())
+    }
+
     fn compute_content_encryption_key(
         &self,
         cencryption: &dyn JweContentEncryption,
-        _merged: &JweHeader,
+        merged: &JweHeader,
         header: &mut JweHeader,
-    ) -> Result<Option<Cow<[u8]>>, JoseError> {
+    ) -> Result<Option<Cow<'_, [u8]>>, JoseError> {
         if let EcdhEsJweAlgorithm::EcdhEs = self.algorithm {
             let shared_key =
-                self.compute_shared_key(header, cencryption.name(), cencryption.key_len())?;
+                self.compute_shared_key(merged, header, cencryption.name(), cencryption.key_len())?;
             Ok(Some(Cow::Owned(shared_key)))
         } else {
             Ok(None)
@@ -749,7 +892,7 @@ impl JweEncrypter for EcdhEsJweEncrypter {
     fn encrypt(
         &self,
         key: &[u8],
-        _merged: &JweHeader,
+        merged: &JweHeader,
         header: &mut JweHeader,
     ) -> Result<Option<Vec<u8>>, JoseError> {
         (|| -> anyhow::Result<Option<Vec<u8>>> {
@@ -757,6 +900,7 @@ impl JweEncrypter for EcdhEsJweEncrypter {
                 Ok(None)
             } else {
                 let shared_key = self.compute_shared_key(
+                    merged,
                     header,
                     self.algorithm().name(),
                     self.algorithm.key_len(),
@@ -833,19 +977,43 @@ impl JweDecrypter for EcdhEsJweDecrypter {
         encrypted_key: Option<&[u8]>,
         cencryption: &dyn JweContentEncryption,
         header: &JweHeader,
-    ) -> Result<Cow<[u8]>, JoseError> {
+    ) -> Result<Cow<'_, [u8]>, JoseError> {
+        self.decrypt_with_options(
+            encrypted_key,
+            cencryption,
+            header,
+            &JweDecryptionOptions::default(),
+        )
+    }
+
+    fn decrypt_with_options(
+        &self,
+        encrypted_key: Option<&[u8]>,
+        cencryption: &dyn JweContentEncryption,
+        header: &JweHeader,
+        options: &JweDecryptionOptions,
+    ) -> Result<Cow<'_, [u8]>, JoseError> {
         (|| -> anyhow::Result<Cow<[u8]>> {
             match &self.algorithm {
                 EcdhEsJweAlgorithm::EcdhEs => {
                     if encrypted_key.is_some() {
                         bail!("The encrypted_key must be empty.");
                     }
-                }
-                _ => {
-                    if encrypted_key.is_none() {
-                        bail!("A encrypted_key is required.");
+                    if cencryption.key_len() > options.max_key_len() {
+                        bail!(
+                            "The key length of the content encryption is too large: {}",
+                            cencryption.key_len()
+                        );
                     }
                 }
+                _ => match encrypted_key {
+                    Some(val) => {
+                        if val.len() < 16 || val.len() - 8 > options.max_key_len() {
+                            bail!("The length of encrypted_key is invalid: {}", val.len());
+                        }
+                    }
+                    None => bail!("A encrypted_key is required."),
+                },
             }
 
             let apu = match header.claim("apu") {
@@ -865,80 +1033,87 @@ impl JweDecrypter for EcdhEsJweDecrypter {
                 None => None,
             };
 
-            let public_key = match header.claim("epk") {
-                Some(Value::Object(map)) => {
-                    match map.get("kty") {
-                        Some(Value::String(val)) => {
-                            if val != self.key_type.key_type() {
-                                bail!("The kty parameter in epk header claim is invalid: {}", val);
-                            }
-                        }
-                        Some(_) => bail!("The kty parameter in epk header claim must be a string."),
-                        None => bail!("The kty parameter in epk header claim is required."),
+            let epk = match header.ephemeral_public_key() {
+                Some(val) => val?,
+                None => bail!("This algorithm must have epk header claim."),
+            };
+
+            if epk.key_type() != self.key_type.key_type() {
+                bail!(
+                    "The kty parameter in epk header claim is invalid: {}",
+                    epk.key_type()
+                );
+            }
+
+            match epk.parameter("crv") {
+                Some(Value::String(val)) => {
+                    if val != self.key_type.curve_name() {
+                        bail!("The crv parameter in epk header claim is invalid: {}", val);
                     }
+                }
+                Some(_) => bail!("The crv parameter in epk header claim must be a string."),
+                None => bail!("The crv parameter in epk header claim is required."),
+            }
 
-                    match map.get("crv") {
-                        Some(Value::String(val)) => {
-                            if val != self.key_type.curve_name() {
-                                bail!("The crv parameter in epk header claim is invalid: {}", val);
-                            }
+            let public_key = match &self.key_type {
+                EcdhEsKeyType::Ec(curve) => {
+                    let x = match epk.parameter("x") {
+                        Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
+                        Some(_) => {
+                            bail!("The x parameter in epk header claim must be a string.")
+                        }
+                        None => bail!("The x parameter in epk header claim is required."),
+                    };
+                    let y = match epk.parameter("y") {
+                        Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
+                        Some(_) => {
+                            bail!("The y parameter in epk header claim must be a string.")
                         }
-                        Some(_) => bail!("The crv parameter in epk header claim must be a string."),
-                        None => bail!("The crv parameter in epk header claim is required."),
+                        None => bail!("The y parameter in epk header claim is required."),
+                    };
+                    if x.len() != curve.coordinate_size() || y.len() != curve.coordinate_size() {
+                        bail!(
+                            "The epk header claim is not a point on the curve: {}",
+                            curve
+                        );
                     }
 
-                    match &self.key_type {
-                        EcdhEsKeyType::Ec(curve) => {
-                            let x = match map.get("x") {
-                                Some(Value::String(val)) => {
-                                    util::decode_base64_urlsafe_no_pad(val)?
-                                }
-                                Some(_) => {
-                                    bail!("The x parameter in epk header claim must be a string.")
-                                }
-                                None => bail!("The x parameter in epk header claim is required."),
-                            };
-                            let y = match map.get("y") {
-                                Some(Value::String(val)) => {
-                                    util::decode_base64_urlsafe_no_pad(val)?
-                                }
-                                Some(_) => {
-                                    bail!("The x parameter in epk header claim must be a string.")
-                                }
-                                None => bail!("The x parameter in epk header claim is required."),
-                            };
-
-                            let mut vec = Vec::with_capacity(1 + x.len() + y.len());
-                            vec.push(0x04);
-                            vec.extend_from_slice(&x);
-                            vec.extend_from_slice(&y);
-
-                            let pkcs8 = EcKeyPair::to_pkcs8(&vec, true, *curve);
-                            PKey::public_key_from_der(&pkcs8)?
-                        }
-                        EcdhEsKeyType::Ecx(curve) => {
-                            let x = match map.get("x") {
-                                Some(Value::String(val)) => {
-                                    util::decode_base64_urlsafe_no_pad(val)?
-                                }
-                                Some(_) => {
-                                    bail!("The x parameter in epk header claim must be a string.")
-                                }
-                                None => bail!("The x parameter in epk header claim is required."),
-                            };
-
-                            let pkcs8 = EcxKeyPair::to_pkcs8(&x, true, *curve);
-                            PKey::public_key_from_der(&pkcs8)?
+                    let mut vec = Vec::with_capacity(1 + x.len() + y.len());
+                    vec.push(0x04);
+                    vec.extend_from_slice(&x);
+                    vec.extend_from_slice(&y);
+
+                    let pkcs8 = EcKeyPair::to_pkcs8(&vec, true, *curve);
+                    PKey::public_key_from_der(&pkcs8)?
+                }
+                EcdhEsKeyType::Ecx(curve) => {
+                    let x = match epk.parameter("x") {
+                        Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
+                        Some(_) => {
+                            bail!("The x parameter in epk header claim must be a string.")
                         }
+                        None => bail!("The x parameter in epk header claim is required."),
+                    };
+                    if x.len() != self.key_type.field_size() {
+                        bail!(
+                            "The epk header claim is not a point on the curve: {}",
+                            curve
+                        );
                     }
+
+                    let pkcs8 = EcxKeyPair::to_pkcs8(&x, true, *curve);
+                    PKey::public_key_from_der(&pkcs8)?
                 }
-                Some(_) => bail!("The epk header claim must be object."),
-                None => bail!("This algorithm must have epk header claim."),
             };
 
-            let mut deriver = Deriver::new(&self.private_key)?;
-            deriver.set_peer(&public_key)?;
-            let derived_key = deriver.derive_to_vec()?;
+            let derived_key = util::derive_shared_secret(&self.private_key, &public_key)?;
+            if derived_key.len() != self.key_type.field_size() {
+                bail!(
+                    "The length of the shared secret must be {}: {}",
+                    self.key_type.field_size(),
+                    derived_key.len()
+                );
+            }
 
             // concat KDF
             if let EcdhEsJweAlgorithm::EcdhEs = self.algorithm {
@@ -966,7 +1141,7 @@ impl JweDecrypter for EcdhEsJweDecrypter {
 
                 let encrypted_key = match encrypted_key {
                     Some(val) => val,
-                    None => unreachable!(),
+                    None => bail!("A encrypted_key is required."),
                 };
 
                 let mut key = vec![0; encrypted_key.len() - 8];
@@ -979,6 +1154,14 @@ impl JweDecrypter for EcdhEsJweDecrypter {
                     Err(_) => bail!("Failed to unwrap key."),
                 };
 
+                if key.len() != cencryption.key_len() {
+                    bail!(
+                        "The length of the unwrapped key must be {}: {}",
+                        cencryption.key_len(),
+                        key.len()
+                    );
+                }
+
                 Ok(Cow::Owned(key))
             }
         })()
@@ -1005,13 +1188,23 @@ mod tests {
     use std::fs;
     use std::path::PathBuf;
 
+    use openssl::aes::{self, AesKey};
+    use openssl::derive::Deriver;
+    use openssl::pkey::PKey;
+    use openssl::sha::sha256;
+    use openssl::symm::{encrypt_aead, Cipher};
+
     use super::{EcdhEsJweAlgorithm, EcdhEsKeyType};
     use crate::jwe::enc::aescbc_hmac::AescbcHmacJweEncryption;
     use crate::jwe::enc::aesgcm::AesgcmJweEncryption;
-    use crate::jwe::JweHeader;
-    use crate::jwk::alg::{ec::EcCurve, ecx::EcxCurve};
-    use crate::jwk::Jwk;
-    use crate::util;
+    use crate::jwe::{self, JweContentEncryption, JweHeader};
+    use crate::jwk::alg::{
+        ec::{EcCurve, EcKeyPair},
+        ecx::{EcxCurve, EcxKeyPair},
+    };
+    use crate::jwk::{Jwk, KeyPair};
+    use crate::util::{self, RandomSource};
+    use crate::{JoseError, JoseHeader, Map, Value};
 
     #[test]
     fn encrypt_and_decrypt_ecdh_es_with_pkcs8_der() -> Result<()> {
@@ -1060,7 +1253,7 @@ mod tests {
                     &mut out_header,
                 )? {
                     Some(val) => val,
-                    None => Cow::Owned(util::random_bytes(enc.key_len())),
+                    None => Cow::Owned(util::random_bytes(enc.key_len())?),
                 };
                 let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
 
@@ -1122,7 +1315,7 @@ mod tests {
                     &mut out_header,
                 )? {
                     Some(val) => val,
-                    None => Cow::Owned(util::random_bytes(enc.key_len())),
+                    None => Cow::Owned(util::random_bytes(enc.key_len())?),
                 };
                 let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
 
@@ -1186,7 +1379,7 @@ mod tests {
                     &mut out_header,
                 )? {
                     Some(val) => val,
-                    None => Cow::Owned(util::random_bytes(enc.key_len())),
+                    None => Cow::Owned(util::random_bytes(enc.key_len())?),
                 };
                 let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
 
@@ -1249,7 +1442,7 @@ mod tests {
                     &mut out_header,
                 )? {
                     Some(val) => val,
-                    None => Cow::Owned(util::random_bytes(enc.key_len())),
+                    None => Cow::Owned(util::random_bytes(enc.key_len())?),
                 };
                 let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
 
@@ -1265,6 +1458,670 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn encrypt_and_decrypt_ecdh_es_p521_with_all_encs() -> Result<()> {
+        let private_key = Jwk::from_bytes(&load_file("jwk/EC_P-521_private.jwk")?)?;
+        let public_key = Jwk::from_bytes(&load_file("jwk/EC_P-521_public.jwk")?)?;
+
+        let encs: Vec<Box<dyn JweContentEncryption>> = vec![
+            Box::new(AescbcHmacJweEncryption::A128cbcHs256),
+            Box::new(AescbcHmacJweEncryption::A192cbcHs384),
+            Box::new(AescbcHmacJweEncryption::A256cbcHs512),
+            Box::new(AesgcmJweEncryption::A128gcm),
+            Box::new(AesgcmJweEncryption::A192gcm),
+            Box::new(AesgcmJweEncryption::A256gcm),
+        ];
+
+        let alg = EcdhEsJweAlgorithm::EcdhEs;
+        for enc in encs {
+            let enc = enc.as_ref();
+
+            let mut header = JweHeader::new();
+            header.set_content_encryption(enc.name());
+
+            let encrypter = alg.encrypter_from_jwk(&public_key)?;
+            let mut out_header = header.clone();
+            let src_key =
+                match encrypter.compute_content_encryption_key(enc, &header, &mut out_header)? {
+                    Some(val) => val,
+                    None => unreachable!(),
+                };
+            assert_eq!(src_key.len(), enc.key_len());
+
+            let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
+            assert!(encrypted_key.is_none());
+
+            out_header.set_algorithm(alg.name());
+            let decrypter = alg.decrypter_from_jwk(&private_key)?;
+            let dst_key = decrypter.decrypt(None, enc, &out_header)?;
+            assert_eq!(dst_key.len(), enc.key_len());
+            assert_eq!(&src_key, &dst_key);
+
+            let payload = b"abcde12345";
+            let jwe = crate::jwe::serialize_compact(payload, &header, &encrypter)?;
+            let (dst_payload, _) = crate::jwe::deserialize_compact(&jwe, &decrypter)?;
+            assert_eq!(&dst_payload, payload);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_ecdh_es_x25519() -> Result<()> {
+        let enc = AesgcmJweEncryption::A256gcm;
+        let private_key = Jwk::from_bytes(load_file("jwk/OKP_X25519_private.jwk")?)?;
+        let public_key = Jwk::from_bytes(load_file("jwk/OKP_X25519_public.jwk")?)?;
+
+        for alg in [
+            EcdhEsJweAlgorithm::EcdhEs,
+            EcdhEsJweAlgorithm::EcdhEsA128kw,
+            EcdhEsJweAlgorithm::EcdhEsA256kw,
+        ] {
+            let mut header = JweHeader::new();
+            header.set_content_encryption(enc.name());
+
+            let encrypter = alg.encrypter_from_jwk(&public_key)?;
+            let mut out_header = header.clone();
+            let src_key =
+                match encrypter.compute_content_encryption_key(&enc, &header, &mut out_header)? {
+                    Some(val) => val,
+                    None => Cow::Owned(util::random_bytes(enc.key_len())?),
+                };
+            let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
+
+            let epk = match out_header.claim("epk") {
+                Some(Value::Object(val)) => val,
+                _ => unreachable!(),
+            };
+            assert_eq!(epk.get("kty"), Some(&Value::from("OKP")));
+            assert_eq!(epk.get("crv"), Some(&Value::from("X25519")));
+            assert!(epk.get("y").is_none());
+
+            out_header.set_algorithm(alg.name());
+            let decrypter = alg.decrypter_from_jwk(&private_key)?;
+            let dst_key = decrypter.decrypt(encrypted_key.as_deref(), &enc, &out_header)?;
+            assert_eq!(&src_key, &dst_key);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_ecdh_es_with_deterministic_random_source() -> Result<()> {
+        #[derive(Debug, Clone)]
+        struct PatternRand;
+
+        impl RandomSource for PatternRand {
+            fn fill(&self, buf: &mut [u8]) -> Result<(), JoseError> {
+                for (i, b) in buf.iter_mut().enumerate() {
+                    *b = i as u8 + 1;
+                }
+                Ok(())
+            }
+
+            fn box_clone(&self) -> Box<dyn RandomSource> {
+                Box::new(self.clone())
+            }
+        }
+
+        let enc = AesgcmJweEncryption::A128gcm;
+        for private_key in [
+            Jwk::generate_ec_key(EcCurve::P256)?,
+            Jwk::generate_ecx_key(EcxCurve::X25519)?,
+            Jwk::generate_ecx_key(EcxCurve::X448)?,
+        ] {
+            let public_key = private_key.to_public_key()?;
+            let alg = EcdhEsJweAlgorithm::EcdhEs;
+
+            let mut header = JweHeader::new();
+            header.set_content_encryption(enc.name());
+
+            let mut encrypter = alg.encrypter_from_jwk(&public_key)?;
+            encrypter.set_random_source(Box::new(PatternRand));
+
+            let mut epks = Vec::new();
+            for _ in 0..2 {
+                let mut out_header = header.clone();
+                let key = encrypter
+                    .compute_content_encryption_key(&enc, &header, &mut out_header)?
+                    .unwrap();
+
+                out_header.set_algorithm(alg.name());
+                let decrypter = alg.decrypter_from_jwk(&private_key)?;
+                let dst_key = decrypter.decrypt(None, &enc, &out_header)?;
+                assert_eq!(key, dst_key);
+
+                epks.push(out_header.claim("epk").cloned());
+            }
+            assert_eq!(epks[0], epks[1]);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_ecdh_es_x448_with_all_content_encryptions() -> Result<()> {
+        let private_key = Jwk::generate_ecx_key(EcxCurve::X448)?;
+        let public_key = private_key.to_public_key()?;
+
+        let encs: [Box<dyn JweContentEncryption>; 6] = [
+            Box::new(AescbcHmacJweEncryption::A128cbcHs256),
+            Box::new(AescbcHmacJweEncryption::A192cbcHs384),
+            Box::new(AescbcHmacJweEncryption::A256cbcHs512),
+            Box::new(AesgcmJweEncryption::A128gcm),
+            Box::new(AesgcmJweEncryption::A192gcm),
+            Box::new(AesgcmJweEncryption::A256gcm),
+        ];
+        for alg in [
+            EcdhEsJweAlgorithm::EcdhEs,
+            EcdhEsJweAlgorithm::EcdhEsA128kw,
+            EcdhEsJweAlgorithm::EcdhEsA192kw,
+            EcdhEsJweAlgorithm::EcdhEsA256kw,
+        ] {
+            let encrypter = alg.encrypter_from_jwk(&public_key)?;
+            let decrypter = alg.decrypter_from_jwk(&private_key)?;
+
+            for enc in &encs {
+                let mut header = JweHeader::new();
+                header.set_content_encryption(enc.name());
+                let input = jwe::serialize_compact(b"payload", &header, &encrypter)?;
+                let (payload, dst_header) = jwe::deserialize_compact(&input, &decrypter)?;
+                assert_eq!(payload, b"payload", "{} {}", alg, enc.name());
+
+                // The ephemeral key and the shared secret of X448 are 56 bytes.
+                let epk = match dst_header.claim("epk") {
+                    Some(Value::Object(val)) => Jwk::from_map(val.clone())?,
+                    _ => unreachable!(),
+                };
+                assert_eq!(epk.curve(), Some("X448"));
+                let x = match epk.parameter("x") {
+                    Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
+                    _ => unreachable!(),
+                };
+                assert_eq!(x.len(), 56);
+            }
+        }
+
+        let key_pair = EcxKeyPair::generate(EcxCurve::X448)?;
+        let other = EcxKeyPair::generate(EcxCurve::X448)?;
+        let public_key = PKey::public_key_from_der(&other.to_der_public_key())?;
+        let derived_key = util::derive_shared_secret(&key_pair.into_private_key(), &public_key)?;
+        assert_eq!(
+            derived_key.len(),
+            EcdhEsKeyType::Ecx(EcxCurve::X448).field_size()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn decrypt_ecdh_es_with_mismatched_epk() -> Result<()> {
+        let enc = AesgcmJweEncryption::A128gcm;
+
+        for (recipient, other) in [
+            (
+                Jwk::generate_ec_key(EcCurve::P256)?,
+                Jwk::generate_ec_key(EcCurve::P384)?,
+            ),
+            (
+                Jwk::generate_ecx_key(EcxCurve::X25519)?,
+                Jwk::generate_ecx_key(EcxCurve::X448)?,
+            ),
+        ] {
+            let alg = EcdhEsJweAlgorithm::EcdhEs;
+            let mut header = JweHeader::new();
+            header.set_content_encryption(enc.name());
+            header.set_algorithm(alg.name());
+
+            // A well-formed epk that claims the curve of the recipient
+            // but has a point of the other curve.
+            let mut epk = Map::new();
+            for name in ["kty", "crv"] {
+                epk.insert(name.to_string(), recipient.parameter(name).unwrap().clone());
+            }
+            for name in ["x", "y"] {
+                if let Some(val) = other.parameter(name) {
+                    epk.insert(name.to_string(), val.clone());
+                }
+            }
+            header.set_claim("epk", Some(Value::Object(epk)))?;
+
+            let decrypter = alg.decrypter_from_jwk(&recipient)?;
+            match decrypter.decrypt(None, &enc, &header) {
+                Err(JoseError::InvalidJweFormat(err)) => {
+                    assert!(err
+                        .to_string()
+                        .starts_with("The epk header claim is not a point on the curve"));
+                }
+                res => panic!("{:?}", res),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_ecdh_es_with_encrypted_pem() -> Result<()> {
+        let enc = AesgcmJweEncryption::A128gcm;
+        let alg = EcdhEsJweAlgorithm::EcdhEs;
+
+        let private_key = load_file("pem/X25519_encrypted_private.pem")?;
+        let public_key = load_file("pem/X25519_public.pem")?;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption(enc.name());
+
+        let encrypter = alg.encrypter_from_pem(&public_key)?;
+        let mut out_header = header.clone();
+        let src_key = encrypter
+            .compute_content_encryption_key(&enc, &header, &mut out_header)?
+            .unwrap();
+
+        out_header.set_algorithm(alg.name());
+        let decrypter = alg.decrypter_from_encrypted_pem(&private_key, "passphrase")?;
+        let dst_key = decrypter.decrypt(None, &enc, &out_header)?;
+        assert_eq!(&src_key, &dst_key);
+
+        assert!(matches!(
+            alg.decrypter_from_encrypted_pem(&private_key, "wrong"),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_ecdh_es_with_agreement_info_from_kids() -> Result<()> {
+        let enc = AesgcmJweEncryption::A128gcm;
+        let private_key = load_file("pem/EC_P-256_private.pem")?;
+        let public_key = load_file("pem/EC_P-256_public.pem")?;
+
+        for alg in [EcdhEsJweAlgorithm::EcdhEs, EcdhEsJweAlgorithm::EcdhEsA128kw] {
+            let mut header = JweHeader::new();
+            header.set_content_encryption(enc.name());
+            header.set_claim("skid", Some(Value::from("sender-key")))?;
+
+            let mut encrypter = alg.encrypter_from_pem(&public_key)?;
+            encrypter.set_key_id("recipient-key");
+            encrypter.set_agreement_info_from_kids(true);
+            assert!(encrypter.is_agreement_info_from_kids());
+
+            let mut out_header = header.clone();
+            let src_key =
+                match encrypter.compute_content_encryption_key(&enc, &header, &mut out_header)? {
+                    Some(val) => val,
+                    None => Cow::Owned(util::random_bytes(enc.key_len())?),
+                };
+            let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
+            assert_eq!(
+                out_header.agreement_partyuinfo(),
+                Some(b"sender-key".to_vec())
+            );
+            assert_eq!(
+                out_header.agreement_partyvinfo(),
+                Some(b"recipient-key".to_vec())
+            );
+
+            out_header.set_algorithm(alg.name());
+            let decrypter = alg.decrypter_from_pem(&private_key)?;
+            let dst_key = decrypter.decrypt(encrypted_key.as_deref(), &enc, &out_header)?;
+            assert_eq!(&src_key, &dst_key);
+        }
+
+        // The key IDs are not used by default.
+        let alg = EcdhEsJweAlgorithm::EcdhEs;
+        let mut header = JweHeader::new();
+        header.set_content_encryption(enc.name());
+        header.set_claim("skid", Some(Value::from("sender-key")))?;
+        let mut encrypter = alg.encrypter_from_pem(&public_key)?;
+        encrypter.set_key_id("recipient-key");
+        let mut out_header = header.clone();
+        encrypter.compute_content_encryption_key(&enc, &header, &mut out_header)?;
+        assert_eq!(out_header.agreement_partyuinfo(), None);
+        assert_eq!(out_header.agreement_partyvinfo(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parameter_error_messages_reference_checked_parameter() -> Result<()> {
+        // Each "must be a string" or "is required" message must name the parameter
+        // that the enclosing match looks up.
+        let source = include_str!("ecdh_es.rs");
+        let lookup = regex::Regex::new(r#"(?:parameter|remove|get)\("(\w+)"\)"#)?;
+        let message = regex::Regex::new(concat!(
+            r#"bail!\("(?:A parameter (\w+) (?:must be a string|is required)"#,
+            r#"|The (\w+) parameter in epk header claim (?:must be a string|is required)"#,
+            r#"|The ephemeral key must have a (\w+) parameter)"#
+        ))?;
+
+        // The lookups of the enclosing matches by the indentation.
+        let mut lookups: Vec<(usize, &str)> = Vec::new();
+        let mut checked = 0;
+        for (i, line) in source.lines().enumerate() {
+            if line.contains("regex::Regex::new") || line.trim_start().starts_with("r#") {
+                continue;
+            }
+            let indent = line.len() - line.trim_start().len();
+            if let Some(caps) = message.captures(line) {
+                let name = (1..=3).find_map(|n| caps.get(n)).unwrap().as_str();
+                let current = lookups
+                    .iter()
+                    .rev()
+                    .find(|(val, _)| *val < indent)
+                    .map(|(_, name)| *name);
+                assert_eq!(Some(name), current, "line {}: {}", i + 1, line.trim());
+                checked += 1;
+            } else if let Some(caps) = lookup.captures(line) {
+                lookups.retain(|(val, _)| *val < indent);
+                lookups.push((indent, caps.get(1).unwrap().as_str()));
+            }
+        }
+        assert!(checked >= 20, "{}", checked);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ecdh_es_key_operations() -> Result<()> {
+        let private_key = Jwk::generate_ec_key(EcCurve::P256)?;
+        let public_key = private_key.to_public_key()?;
+
+        for (key_ops, alg, lenient, strict) in [
+            ("deriveBits", EcdhEsJweAlgorithm::EcdhEs, true, true),
+            ("deriveKey", EcdhEsJweAlgorithm::EcdhEs, true, false),
+            ("deriveBits", EcdhEsJweAlgorithm::EcdhEsA128kw, false, false),
+            ("deriveKey", EcdhEsJweAlgorithm::EcdhEsA128kw, true, true),
+            ("encrypt", EcdhEsJweAlgorithm::EcdhEs, false, false),
+        ] {
+            let mut private_key = private_key.clone();
+            private_key.set_key_operations(vec![key_ops]);
+            let mut public_key = public_key.clone();
+            public_key.set_key_operations(vec![key_ops]);
+
+            let msg = format!("{} {}", key_ops, alg);
+            assert_eq!(
+                alg.encrypter_from_jwk(&public_key).is_ok(),
+                lenient,
+                "{}",
+                msg
+            );
+            assert_eq!(
+                alg.decrypter_from_jwk(&private_key).is_ok(),
+                lenient,
+                "{}",
+                msg
+            );
+            assert_eq!(
+                alg.encrypter_from_jwk_strict(&public_key).is_ok(),
+                strict,
+                "{}",
+                msg
+            );
+            assert_eq!(
+                alg.decrypter_from_jwk_strict(&private_key).is_ok(),
+                strict,
+                "{}",
+                msg
+            );
+        }
+
+        // A key with only deriveBits works for direct key agreement.
+        let mut private_key = private_key.clone();
+        private_key.set_key_operations(vec!["deriveBits"]);
+        let mut public_key = public_key.clone();
+        public_key.set_key_operations(vec!["deriveBits"]);
+
+        let enc = AesgcmJweEncryption::A128gcm;
+        let alg = EcdhEsJweAlgorithm::EcdhEs;
+        let mut header = JweHeader::new();
+        header.set_content_encryption(enc.name());
+        let encrypter = alg.encrypter_from_jwk(&public_key)?;
+        let mut out_header = header.clone();
+        let src_key = encrypter
+            .compute_content_encryption_key(&enc, &header, &mut out_header)?
+            .unwrap();
+        out_header.set_algorithm(alg.name());
+        let decrypter = alg.decrypter_from_jwk(&private_key)?;
+        let dst_key = decrypter.decrypt(None, &enc, &out_header)?;
+        assert_eq!(&src_key, &dst_key);
+
+        Ok(())
+    }
+
+    #[test]
+    fn concat_kdf_rfc7518_vector() -> Result<()> {
+        // RFC 7518 Appendix C
+        let alice_key = Jwk::from_bytes(
+            br#"{"kty":"EC","crv":"P-256",
+            "x":"gI0GAILBdu7T53akrFmMyGcsF3n5dO7MmwNBHKW5SV0",
+            "y":"SLW_xSffzlPWrHEVI30DHM_4egVwt3NQqeUD7nMFpps",
+            "d":"0_NxaRPUMQoAJt50Gz8YiTr8gRTwyEaCumd-MToTmIo"}"#,
+        )?;
+        let bob_key = Jwk::from_bytes(
+            br#"{"kty":"EC","crv":"P-256",
+            "x":"weNJy2HscCSM6AEDTDg04biOvhFhyyWvOHQfeF_PxMQ",
+            "y":"e8lnCO-AlStT-NJVX-crhB7QRYhiix03illJOVAOyck",
+            "d":"VEmDZpDXXK8p8N0Cndsxs924q6nS1RXFASRl6BfUqdw"}"#,
+        )?;
+
+        let mut header = JweHeader::new();
+        header.set_algorithm("ECDH-ES");
+        header.set_content_encryption("A128GCM");
+        header.set_agreement_partyuinfo(b"Alice");
+        header.set_agreement_partyvinfo(b"Bob");
+        let epk: Map<String, Value> = alice_key.to_public_key()?.into();
+        header.set_claim("epk", Some(Value::Object(epk)))?;
+
+        // The direct key agreement uses the enc value as AlgorithmID.
+        let decrypter = EcdhEsJweAlgorithm::EcdhEs.decrypter_from_jwk(&bob_key)?;
+        let key = decrypter.decrypt(None, &AesgcmJweEncryption::A128gcm, &header)?;
+        assert_eq!(
+            util::encode_base64_urlsafe_nopad(key),
+            "VqqN6vgjbSBcIijNcacQGg"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn decrypt_ecdh_es_kw_rfc7518_inputs() -> Result<()> {
+        // The tokens are built in this test from the keys, apu, apv and plaintext of
+        // RFC 7518 Appendix C. The Concat KDF, the key wrap and the content encryption
+        // are done by OpenSSL directly, so the decrypter is checked against the alg value
+        // as AlgorithmID without using the key derivation of this crate.
+        let alice_key = Jwk::from_bytes(
+            br#"{"kty":"EC","crv":"P-256",
+            "x":"gI0GAILBdu7T53akrFmMyGcsF3n5dO7MmwNBHKW5SV0",
+            "y":"SLW_xSffzlPWrHEVI30DHM_4egVwt3NQqeUD7nMFpps",
+            "d":"0_NxaRPUMQoAJt50Gz8YiTr8gRTwyEaCumd-MToTmIo"}"#,
+        )?;
+        let bob_key = Jwk::from_bytes(
+            br#"{"kty":"EC","crv":"P-256",
+            "x":"weNJy2HscCSM6AEDTDg04biOvhFhyyWvOHQfeF_PxMQ",
+            "y":"e8lnCO-AlStT-NJVX-crhB7QRYhiix03illJOVAOyck",
+            "d":"VEmDZpDXXK8p8N0Cndsxs924q6nS1RXFASRl6BfUqdw"}"#,
+        )?;
+
+        let alice_private_key = EcKeyPair::from_jwk(&alice_key)?.into_private_key();
+        let bob_public_key =
+            PKey::public_key_from_der(&EcKeyPair::from_jwk(&bob_key)?.to_der_public_key())?;
+        let mut deriver = Deriver::new(&alice_private_key)?;
+        deriver.set_peer(&bob_public_key)?;
+        let z = deriver.derive_to_vec()?;
+
+        // Concat KDF of RFC 7518 Section 4.6.2 for an output of one SHA-256 round.
+        let concat_kdf = |algorithm_id: &str, key_len: usize| {
+            let mut input = vec![0, 0, 0, 1];
+            input.extend_from_slice(&z);
+            for val in [algorithm_id.as_bytes(), b"Alice", b"Bob"] {
+                input.extend_from_slice(&(val.len() as u32).to_be_bytes());
+                input.extend_from_slice(val);
+            }
+            input.extend_from_slice(&((key_len * 8) as u32).to_be_bytes());
+            sha256(&input)[..key_len].to_vec()
+        };
+        assert_eq!(
+            util::encode_base64_urlsafe_nopad(concat_kdf("A128GCM", 16)),
+            "VqqN6vgjbSBcIijNcacQGg"
+        );
+
+        let epk = alice_key.to_public_key()?;
+        let cek = [0x5au8; 16];
+        let iv = [0u8; 12];
+        let plaintext = b"The true sign of intelligence";
+        let build_token = |alg: &EcdhEsJweAlgorithm, algorithm_id: &str| -> Result<String> {
+            let header = format!(
+                r#"{{"alg":"{}","enc":"A128GCM","apu":"QWxpY2U","apv":"Qm9i","epk":{{"kty":"EC","crv":"P-256","x":"{}","y":"{}"}}}}"#,
+                alg.name(),
+                epk.parameter("x").and_then(|val| val.as_str()).unwrap(),
+                epk.parameter("y").and_then(|val| val.as_str()).unwrap(),
+            );
+            let header = util::encode_base64_urlsafe_nopad(header);
+
+            let kek = AesKey::new_encrypt(&concat_kdf(algorithm_id, alg.key_len())).unwrap();
+            let mut encrypted_key = vec![0; cek.len() + 8];
+            aes::wrap_key(&kek, None, &mut encrypted_key, &cek).unwrap();
+
+            let mut tag = [0; 16];
+            let ciphertext = encrypt_aead(
+                Cipher::aes_128_gcm(),
+                &cek,
+                Some(&iv),
+                header.as_bytes(),
+                plaintext,
+                &mut tag,
+            )?;
+
+            Ok([
+                header,
+                util::encode_base64_urlsafe_nopad(encrypted_key),
+                util::encode_base64_urlsafe_nopad(iv),
+                util::encode_base64_urlsafe_nopad(ciphertext),
+                util::encode_base64_urlsafe_nopad(tag),
+            ]
+            .join("."))
+        };
+
+        for alg in [
+            EcdhEsJweAlgorithm::EcdhEsA128kw,
+            EcdhEsJweAlgorithm::EcdhEsA192kw,
+            EcdhEsJweAlgorithm::EcdhEsA256kw,
+        ] {
+            let decrypter = alg.decrypter_from_jwk(&bob_key)?;
+
+            let input = build_token(&alg, alg.name())?;
+            let (payload, header) = jwe::deserialize_compact(&input, &decrypter)?;
+            assert_eq!(payload, plaintext);
+            assert_eq!(header.algorithm(), Some(alg.name()));
+            assert_eq!(header.agreement_partyuinfo(), Some(b"Alice".to_vec()));
+
+            // A token whose key is derived with the enc value as AlgorithmID is rejected.
+            let input = build_token(&alg, "A128GCM")?;
+            assert!(jwe::deserialize_compact(&input, &decrypter).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn concat_kdf_output_length() -> Result<()> {
+        // P-521 shared secrets are 66 bytes which is longer than a SHA-256 round.
+        let derived_key = vec![0xA5; 66];
+        for alg in [
+            EcdhEsJweAlgorithm::EcdhEs,
+            EcdhEsJweAlgorithm::EcdhEsA128kw,
+            EcdhEsJweAlgorithm::EcdhEsA192kw,
+            EcdhEsJweAlgorithm::EcdhEsA256kw,
+        ] {
+            for key_len in [16, 24, 32, 33, 48, 64, 65] {
+                let key = alg.concat_kdf("A256CBC-HS512", key_len, &derived_key, None, None)?;
+                assert_eq!(key.len(), key_len);
+            }
+        }
+
+        let short =
+            EcdhEsJweAlgorithm::EcdhEs.concat_kdf("A256GCM", 32, &derived_key, None, None)?;
+        let long =
+            EcdhEsJweAlgorithm::EcdhEs.concat_kdf("A256GCM", 64, &derived_key, None, None)?;
+        assert_ne!(&short[..], &long[..32]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_ecdh_es_kw_with_wrap_key_len() -> Result<()> {
+        let enc = AescbcHmacJweEncryption::A128cbcHs256;
+        let private_key = load_file("der/EC_P-256_pkcs8_private.der")?;
+        let public_key = load_file("der/EC_P-256_spki_public.der")?;
+
+        for (alg, wrap_key_len) in [
+            (EcdhEsJweAlgorithm::EcdhEsA128kw, 16),
+            (EcdhEsJweAlgorithm::EcdhEsA192kw, 24),
+            (EcdhEsJweAlgorithm::EcdhEsA256kw, 32),
+        ] {
+            assert_eq!(alg.key_len(), wrap_key_len);
+
+            let mut header = JweHeader::new();
+            header.set_content_encryption(enc.name());
+
+            let encrypter = alg.encrypter_from_der(&public_key)?;
+            let mut out_header = header.clone();
+            let src_key =
+                encrypter.compute_content_encryption_key(&enc, &header, &mut out_header)?;
+            assert!(src_key.is_none());
+
+            // The CEK is random and has the length of the content encryption.
+            let src_key = util::random_bytes(enc.key_len())?;
+            let encrypted_key = match encrypter.encrypt(&src_key, &header, &mut out_header)? {
+                Some(val) => val,
+                None => unreachable!(),
+            };
+            assert_eq!(encrypted_key.len(), enc.key_len() + 8);
+
+            // Derive the wrap key independently from the recipient private key and epk.
+            let epk = match out_header.claim("epk") {
+                Some(Value::Object(val)) => val.clone(),
+                _ => unreachable!(),
+            };
+            let mut point = vec![0x04];
+            for name in ["x", "y"] {
+                match epk.get(name) {
+                    Some(Value::String(val)) => {
+                        point.extend_from_slice(&util::decode_base64_urlsafe_no_pad(val)?)
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            let epk = PKey::public_key_from_der(&EcKeyPair::to_pkcs8(&point, true, EcCurve::P256))?;
+            let recipient_key = PKey::private_key_from_der(&private_key)?;
+            let mut deriver = Deriver::new(&recipient_key)?;
+            deriver.set_peer(&epk)?;
+            let derived_key = deriver.derive_to_vec()?;
+
+            let wrap_key = alg.concat_kdf(alg.name(), wrap_key_len, &derived_key, None, None)?;
+            assert_eq!(wrap_key.len(), wrap_key_len);
+
+            let aes = AesKey::new_decrypt(&wrap_key).unwrap();
+            let mut unwrapped = vec![0; encrypted_key.len() - 8];
+            aes::unwrap_key(&aes, None, &mut unwrapped, &encrypted_key).unwrap();
+            assert_eq!(unwrapped, src_key);
+
+            // A wrap key that is derived with another length must not work.
+            let wrong_len = if wrap_key_len == 16 { 32 } else { 16 };
+            let wrong_key = alg.concat_kdf(alg.name(), wrong_len, &derived_key, None, None)?;
+            let aes = AesKey::new_decrypt(&wrong_key).unwrap();
+            assert!(aes::unwrap_key(&aes, None, &mut unwrapped, &encrypted_key).is_err());
+
+            out_header.set_algorithm(alg.name());
+            let decrypter = alg.decrypter_from_der(&private_key)?;
+            let dst_key = decrypter.decrypt(Some(&encrypted_key), &enc, &out_header)?;
+            assert_eq!(&src_key, &dst_key.as_ref());
+        }
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
@@ -7,9 +7,11 @@ use anyhow::bail;
 use openssl::aes::{self, AesKey};
 use openssl::pkcs5;
 
-use crate::jwe::{JweAlgorithm, JweContentEncryption, JweDecrypter, JweEncrypter, JweHeader};
-use crate::jwk::Jwk;
-use crate::util::{self, HashAlgorithm};
+use crate::jwe::{
+    JweAlgorithm, JweContentEncryption, JweDecrypter, JweDecryptionOptions, JweEncrypter, JweHeader,
+};
+use crate::jwk::{Jwk, KeyOps};
+use crate::util::{self, HashAlgorithm, OpensslRand, RandomSource};
 use crate::{JoseError, JoseHeader, Number, Value};
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
@@ -40,6 +42,7 @@ impl Pbes2HmacAeskwJweAlgorithm {
                 salt_len: 8,
                 iter_count: 1000,
                 key_id: None,
+                random_source: Box::new(OpensslRand),
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -56,7 +59,7 @@ impl Pbes2HmacAeskwJweAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be enc: {}", val),
             }
-            if !jwk.is_for_key_operation("deriveKey") {
+            if !jwk.is_for_key_operations(KeyOps::DERIVE_KEY) {
                 bail!("A parameter key_ops must contains deriveKey.");
             }
             match jwk.algorithm() {
@@ -82,6 +85,7 @@ impl Pbes2HmacAeskwJweAlgorithm {
                 salt_len: 8,
                 iter_count: 1000,
                 key_id,
+                random_source: Box::new(OpensslRand),
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -118,7 +122,7 @@ impl Pbes2HmacAeskwJweAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be enc: {}", val),
             }
-            if !jwk.is_for_key_operation("deriveKey") {
+            if !jwk.is_for_key_operations(KeyOps::DERIVE_KEY) {
                 bail!("A parameter key_ops must contains deriveKey.");
             }
             match jwk.algorithm() {
@@ -200,6 +204,7 @@ pub struct Pbes2HmacAeskwJweEncrypter {
     salt_len: usize,
     iter_count: usize,
     key_id: Option<String>,
+    random_source: Box<dyn RandomSource>,
 }
 
 impl Pbes2HmacAeskwJweEncrypter {
@@ -224,6 +229,15 @@ impl Pbes2HmacAeskwJweEncrypter {
     pub fn remove_key_id(&mut self) {
         self.key_id = None;
     }
+
+    /// Set a random source used to generate salts.
+    ///
+    /// # Arguments
+    ///
+    /// * `random_source` - a random source
+    pub fn set_random_source(&mut self, random_source: Box<dyn RandomSource>) {
+        self.random_source = random_source;
+    }
 }
 
 impl JweEncrypter for Pbes2HmacAeskwJweEncrypter {
@@ -238,12 +252,16 @@ impl JweEncrypter for Pbes2HmacAeskwJweEncrypter {
         }
     }
 
+    fn key_type(&self) -> Option<&str> {
+        Some("oct")
+    }
+
     fn compute_content_encryption_key(
         &self,
         _cencryption: &dyn JweContentEncryption,
         _in_header: &JweHeader,
         _out_header: &mut JweHeader,
-    ) -> Result<Option<Cow<[u8]>>, JoseError> {
+    ) -> Result<Option<Cow<'_, [u8]>>, JoseError> {
         Ok(None)
     }
 
@@ -264,7 +282,7 @@ impl JweEncrypter for Pbes2HmacAeskwJweEncrypter {
                 }
                 Some(_) => bail!("The p2s header claim must be string."),
                 None => {
-                    let p2s = util::random_bytes(self.salt_len);
+                    let p2s = util::random_bytes_from(self.random_source.as_ref(), self.salt_len)?;
                     let p2s_b64 = util::encode_base64_urlsafe_nopad(&p2s);
                     out_header.set_claim("p2s", Some(Value::String(p2s_b64)))?;
                     p2s
@@ -357,15 +375,37 @@ impl JweDecrypter for Pbes2HmacAeskwJweDecrypter {
     fn decrypt(
         &self,
         encrypted_key: Option<&[u8]>,
-        _cencryption: &dyn JweContentEncryption,
+        cencryption: &dyn JweContentEncryption,
         header: &JweHeader,
-    ) -> Result<Cow<[u8]>, JoseError> {
+    ) -> Result<Cow<'_, [u8]>, JoseError> {
+        self.decrypt_with_options(
+            encrypted_key,
+            cencryption,
+            header,
+            &JweDecryptionOptions::default(),
+        )
+    }
+
+    fn decrypt_with_options(
+        &self,
+        encrypted_key: Option<&[u8]>,
+        cencryption: &dyn JweContentEncryption,
+        header: &JweHeader,
+        options: &JweDecryptionOptions,
+    ) -> Result<Cow<'_, [u8]>, JoseError> {
         (|| -> anyhow::Result<Cow<[u8]>> {
             let encrypted_key = match encrypted_key {
                 Some(val) => val,
                 None => bail!("A encrypted_key value is required."),
             };
 
+            if encrypted_key.len() < 16 || encrypted_key.len() - 8 > options.max_key_len() {
+                bail!(
+                    "The length of encrypted_key is invalid: {}",
+                    encrypted_key.len()
+                );
+            }
+
             let p2s = match header.claim("p2s") {
                 Some(Value::String(val)) => {
                     let p2s = util::decode_base64_urlsafe_no_pad(val)?;
@@ -386,8 +426,11 @@ impl JweDecrypter for Pbes2HmacAeskwJweDecrypter {
                 None => bail!("The p2c header claim is required."),
             };
 
-            if p2c > 1000000 {
-                bail!("The p2c value is too large. This is a possible DoS attack: {}", p2c);
+            if p2c > options.max_pbes2_iterations() {
+                bail!(
+                    "The p2c value is too large. This is a possible DoS attack: {}",
+                    p2c
+                );
             }
 
             let mut salt = Vec::with_capacity(self.algorithm().name().len() + 1 + p2s.len());
@@ -414,6 +457,14 @@ impl JweDecrypter for Pbes2HmacAeskwJweDecrypter {
                 Err(_) => bail!("Failed to unwrap a key."),
             }
 
+            if key.len() != cencryption.key_len() {
+                bail!(
+                    "The length of the unwrapped key must be {}: {}",
+                    cencryption.key_len(),
+                    key.len()
+                );
+            }
+
             Ok(Cow::Owned(key))
         })()
         .map_err(|err| JoseError::InvalidJweFormat(err))
@@ -456,7 +507,7 @@ mod tests {
             header.set_content_encryption(enc.name());
 
             let jwk = {
-                let key = util::random_bytes(8);
+                let key = util::random_bytes(8)?;
                 let key = util::encode_base64_urlsafe_nopad(&key);
 
                 let mut jwk = Jwk::new("oct");
@@ -467,7 +518,7 @@ mod tests {
 
             let encrypter = alg.encrypter_from_jwk(&jwk)?;
             let mut out_header = header.clone();
-            let src_key = util::random_bytes(enc.key_len());
+            let src_key = util::random_bytes(enc.key_len())?;
             let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
 
             let decrypter = alg.decrypter_from_jwk(&jwk)?;
@@ -493,7 +544,7 @@ mod tests {
             header.set_content_encryption(enc.name());
 
             let jwk = {
-                let key = util::random_bytes(8);
+                let key = util::random_bytes(8)?;
                 let key = util::encode_base64_urlsafe_nopad(&key);
 
                 let mut jwk = Jwk::new("oct");
@@ -505,12 +556,14 @@ mod tests {
             let mut encrypter = alg.encrypter_from_jwk(&jwk)?;
             encrypter.set_iter_count(1000001);
             let mut out_header = header.clone();
-            let src_key = util::random_bytes(enc.key_len());
+            let src_key = util::random_bytes(enc.key_len())?;
             let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
 
             let decrypter = alg.decrypter_from_jwk(&jwk)?;
 
-            let err = decrypter.decrypt(encrypted_key.as_deref(), &enc, &out_header).unwrap_err();
+            let err = decrypter
+                .decrypt(encrypted_key.as_deref(), &enc, &out_header)
+                .unwrap_err();
             assert_eq!(format!("{}", err), "Invalid JWE format: The p2c value is too large. This is a possible DoS attack: 1000001");
         }
 
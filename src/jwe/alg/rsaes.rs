@@ -8,7 +8,7 @@ use openssl::pkey::{PKey, Private, Public};
 use openssl::rsa::Padding;
 
 use crate::jwe::{JweAlgorithm, JweContentEncryption, JweDecrypter, JweEncrypter, JweHeader};
-use crate::jwk::{alg::rsa::RsaKeyPair, Jwk};
+use crate::jwk::{alg::rsa::RsaKeyPair, Jwk, KeyOps};
 use crate::util;
 use crate::util::der::{DerBuilder, DerType};
 use crate::{JoseError, Value};
@@ -122,6 +122,7 @@ impl RsaesJweAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id: None,
+                oaep_label: Vec::new(),
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -155,6 +156,7 @@ impl RsaesJweAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id: None,
+                oaep_label: Vec::new(),
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -171,7 +173,7 @@ impl RsaesJweAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be enc: {}", val),
             }
-            if !jwk.is_for_key_operation("encrypt") {
+            if !jwk.is_for_key_operations(KeyOps::ENCRYPT) {
                 bail!("A parameter key_ops must contains encrypt.");
             }
             match jwk.algorithm() {
@@ -213,6 +215,7 @@ impl RsaesJweAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id,
+                oaep_label: Vec::new(),
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -227,6 +230,7 @@ impl RsaesJweAlgorithm {
             algorithm: self.clone(),
             private_key: key_pair.into_private_key(),
             key_id: None,
+            oaep_label: Vec::new(),
         })
     }
 
@@ -239,9 +243,26 @@ impl RsaesJweAlgorithm {
             algorithm: self.clone(),
             private_key: key_pair.into_private_key(),
             key_id: None,
+            oaep_label: Vec::new(),
         })
     }
 
+    /// Return a decrypter from a private key of encrypted PKCS#8 PEM format.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - A private key of encrypted PKCS#8 PEM format.
+    /// * `passphrase` - A passphrase for decrypting the private key.
+    pub fn decrypter_from_encrypted_pem(
+        &self,
+        input: impl AsRef<[u8]>,
+        passphrase: impl AsRef<[u8]>,
+    ) -> Result<RsaesJweDecrypter, JoseError> {
+        let pkcs8_der = util::decrypt_pkcs8_pem(input.as_ref(), passphrase.as_ref())
+            .map_err(JoseError::InvalidKeyFormat)?;
+        self.decrypter_from_der(pkcs8_der)
+    }
+
     pub fn decrypter_from_jwk(&self, jwk: &Jwk) -> Result<RsaesJweDecrypter, JoseError> {
         (|| -> anyhow::Result<RsaesJweDecrypter> {
             match jwk.key_use() {
@@ -249,7 +270,7 @@ impl RsaesJweAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be enc: {}", val),
             }
-            if !jwk.is_for_key_operation("decrypt") {
+            if !jwk.is_for_key_operations(KeyOps::DECRYPT) {
                 bail!("A parameter key_ops must contains decrypt.");
             }
             match jwk.algorithm() {
@@ -270,6 +291,7 @@ impl RsaesJweAlgorithm {
                 algorithm: self.clone(),
                 private_key,
                 key_id,
+                oaep_label: Vec::new(),
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -312,6 +334,7 @@ pub struct RsaesJweEncrypter {
     algorithm: RsaesJweAlgorithm,
     public_key: PKey<Public>,
     key_id: Option<String>,
+    oaep_label: Vec<u8>,
 }
 
 impl RsaesJweEncrypter {
@@ -322,6 +345,30 @@ impl RsaesJweEncrypter {
     pub fn remove_key_id(&mut self) {
         self.key_id = None;
     }
+
+    /// Set a label (the P parameter of RFC 8017) of RSA-OAEP.
+    ///
+    /// JWA uses the empty label. A non-empty label must be agreed by the both sides
+    /// out-of-band, and RSA1_5 rejects it.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - A label of RSA-OAEP.
+    pub fn set_oaep_label(&mut self, value: &[u8]) {
+        self.oaep_label = value.to_vec();
+    }
+
+    /// Return the label of RSA-OAEP.
+    pub fn oaep_label(&self) -> &[u8] {
+        &self.oaep_label
+    }
+
+    /// Set the JWK thumbprint (RFC 7638) of the public key using SHA-256 as a key ID.
+    pub fn set_key_id_to_thumbprint(&mut self) -> Result<(), JoseError> {
+        let jwk = Jwk::from_public_key(&self.public_key)?;
+        self.key_id = Some(jwk.thumbprint_sha256()?);
+        Ok(())
+    }
 }
 
 impl JweEncrypter for RsaesJweEncrypter {
@@ -336,12 +383,16 @@ impl JweEncrypter for RsaesJweEncrypter {
         }
     }
 
+    fn key_type(&self) -> Option<&str> {
+        Some("RSA")
+    }
+
     fn compute_content_encryption_key(
         &self,
         _cencryption: &dyn JweContentEncryption,
         _in_header: &JweHeader,
         _out_header: &mut JweHeader,
-    ) -> Result<Option<Cow<[u8]>>, JoseError> {
+    ) -> Result<Option<Cow<'_, [u8]>>, JoseError> {
         Ok(None)
     }
 
@@ -353,34 +404,40 @@ impl JweEncrypter for RsaesJweEncrypter {
         _out_header: &mut JweHeader,
     ) -> Result<Option<Vec<u8>>, JoseError> {
         (|| -> anyhow::Result<Option<Vec<u8>>> {
-            let rsa = self.public_key.rsa()?;
             let encrypted_key = match self.algorithm {
                 RsaesJweAlgorithm::Rsa1_5 => {
+                    if !self.oaep_label.is_empty() {
+                        bail!("A OAEP label cannot be used by RSA1_5.");
+                    }
+                    let rsa = self.public_key.rsa()?;
                     let mut encrypted_key = vec![0; rsa.size() as usize];
-                    let len = rsa.public_encrypt(&key, &mut encrypted_key, Padding::PKCS1)?;
-                    encrypted_key.truncate(len);
-                    encrypted_key
-                }
-                RsaesJweAlgorithm::RsaOaep => {
-                    let mut encrypted_key = vec![0; rsa.size() as usize];
-                    let len = rsa.public_encrypt(&key, &mut encrypted_key, Padding::PKCS1_OAEP)?;
+                    let len = rsa.public_encrypt(key, &mut encrypted_key, Padding::PKCS1)?;
                     encrypted_key.truncate(len);
                     encrypted_key
                 }
+                RsaesJweAlgorithm::RsaOaep => openssl_rsa_oaep::pkey_public_encrypt(
+                    &self.public_key,
+                    key,
+                    MessageDigest::sha1(),
+                    &self.oaep_label,
+                )?,
                 RsaesJweAlgorithm::RsaOaep256 => openssl_rsa_oaep::pkey_public_encrypt(
                     &self.public_key,
-                    &key,
+                    key,
                     MessageDigest::sha256(),
+                    &self.oaep_label,
                 )?,
                 RsaesJweAlgorithm::RsaOaep384 => openssl_rsa_oaep::pkey_public_encrypt(
                     &self.public_key,
-                    &key,
+                    key,
                     MessageDigest::sha384(),
+                    &self.oaep_label,
                 )?,
                 RsaesJweAlgorithm::RsaOaep512 => openssl_rsa_oaep::pkey_public_encrypt(
                     &self.public_key,
-                    &key,
+                    key,
                     MessageDigest::sha512(),
+                    &self.oaep_label,
                 )?,
             };
 
@@ -407,6 +464,7 @@ pub struct RsaesJweDecrypter {
     algorithm: RsaesJweAlgorithm,
     private_key: PKey<Private>,
     key_id: Option<String>,
+    oaep_label: Vec<u8>,
 }
 
 impl RsaesJweDecrypter {
@@ -417,6 +475,23 @@ impl RsaesJweDecrypter {
     pub fn remove_key_id(&mut self) {
         self.key_id = None;
     }
+
+    /// Set a label (the P parameter of RFC 8017) of RSA-OAEP.
+    ///
+    /// JWA uses the empty label. A non-empty label must be agreed by the both sides
+    /// out-of-band, and RSA1_5 rejects it.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - A label of RSA-OAEP.
+    pub fn set_oaep_label(&mut self, value: &[u8]) {
+        self.oaep_label = value.to_vec();
+    }
+
+    /// Return the label of RSA-OAEP.
+    pub fn oaep_label(&self) -> &[u8] {
+        &self.oaep_label
+    }
 }
 
 impl JweDecrypter for RsaesJweDecrypter {
@@ -437,41 +512,47 @@ impl JweDecrypter for RsaesJweDecrypter {
         encrypted_key: Option<&[u8]>,
         _cencryption: &dyn JweContentEncryption,
         _header: &JweHeader,
-    ) -> Result<Cow<[u8]>, JoseError> {
+    ) -> Result<Cow<'_, [u8]>, JoseError> {
         (|| -> anyhow::Result<Cow<[u8]>> {
             let encrypted_key = match encrypted_key {
                 Some(val) => val,
                 None => bail!("A encrypted_key is required."),
             };
 
-            let rsa = self.private_key.rsa()?;
             let key = match self.algorithm {
                 RsaesJweAlgorithm::Rsa1_5 => {
+                    if !self.oaep_label.is_empty() {
+                        bail!("A OAEP label cannot be used by RSA1_5.");
+                    }
+                    let rsa = self.private_key.rsa()?;
                     let mut key = vec![0; rsa.size() as usize];
-                    let len = rsa.private_decrypt(&encrypted_key, &mut key, Padding::PKCS1)?;
-                    key.truncate(len);
-                    key
-                }
-                RsaesJweAlgorithm::RsaOaep => {
-                    let mut key = vec![0; rsa.size() as usize];
-                    let len = rsa.private_decrypt(&encrypted_key, &mut key, Padding::PKCS1_OAEP)?;
+                    let len = rsa.private_decrypt(encrypted_key, &mut key, Padding::PKCS1)?;
                     key.truncate(len);
                     key
                 }
+                RsaesJweAlgorithm::RsaOaep => openssl_rsa_oaep::pkey_private_decrypt(
+                    &self.private_key,
+                    encrypted_key,
+                    MessageDigest::sha1(),
+                    &self.oaep_label,
+                )?,
                 RsaesJweAlgorithm::RsaOaep256 => openssl_rsa_oaep::pkey_private_decrypt(
                     &self.private_key,
-                    &encrypted_key,
+                    encrypted_key,
                     MessageDigest::sha256(),
+                    &self.oaep_label,
                 )?,
                 RsaesJweAlgorithm::RsaOaep384 => openssl_rsa_oaep::pkey_private_decrypt(
                     &self.private_key,
-                    &encrypted_key,
+                    encrypted_key,
                     MessageDigest::sha384(),
+                    &self.oaep_label,
                 )?,
                 RsaesJweAlgorithm::RsaOaep512 => openssl_rsa_oaep::pkey_private_decrypt(
                     &self.private_key,
-                    &encrypted_key,
+                    encrypted_key,
                     MessageDigest::sha512(),
+                    &self.oaep_label,
                 )?,
             };
 
@@ -528,7 +609,7 @@ mod tests {
 
             let encrypter = alg.encrypter_from_jwk(&public_key)?;
             let mut out_header = header.clone();
-            let src_key = util::random_bytes(enc.key_len());
+            let src_key = util::random_bytes(enc.key_len())?;
             let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
 
             let decrypter = alg.decrypter_from_jwk(&private_key)?;
@@ -540,6 +621,75 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn encrypt_and_decrypt_rsaes_with_thumbprint_key_id() -> Result<()> {
+        let alg = RsaesJweAlgorithm::RsaOaep;
+        let public_key = Jwk::from_bytes(load_file("jwk/RSA_public.jwk")?)?;
+        let private_key = Jwk::from_bytes(load_file("jwk/RSA_private.jwk")?)?;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128GCM");
+
+        let mut encrypter = alg.encrypter_from_jwk(&public_key)?;
+        encrypter.set_key_id_to_thumbprint()?;
+        let jwe = crate::jwe::serialize_compact(b"abcde12345", &header, &encrypter)?;
+
+        let decrypter = alg.decrypter_from_jwk(&private_key)?;
+        let (_, header) = crate::jwe::deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(
+            header.key_id(),
+            Some(public_key.thumbprint_sha256()?.as_str())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_rsaes_with_oaep_label() -> Result<()> {
+        let enc = AescbcHmacJweEncryption::A128cbcHs256;
+        let public_key = Jwk::from_bytes(load_file("jwk/RSA_public.jwk")?)?;
+        let private_key = Jwk::from_bytes(load_file("jwk/RSA_private.jwk")?)?;
+
+        for alg in [
+            RsaesJweAlgorithm::RsaOaep,
+            RsaesJweAlgorithm::RsaOaep256,
+            RsaesJweAlgorithm::RsaOaep512,
+        ] {
+            let mut header = JweHeader::new();
+            header.set_content_encryption(enc.name());
+
+            let mut encrypter = alg.encrypter_from_jwk(&public_key)?;
+            encrypter.set_oaep_label(b"label");
+            let mut out_header = header.clone();
+            let src_key = util::random_bytes(enc.key_len())?;
+            let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
+
+            let mut decrypter = alg.decrypter_from_jwk(&private_key)?;
+            decrypter.set_oaep_label(b"label");
+            let dst_key = decrypter.decrypt(encrypted_key.as_deref(), &enc, &out_header)?;
+            assert_eq!(&src_key as &[u8], &dst_key as &[u8]);
+
+            // The labels of the both sides must be the same.
+            decrypter.set_oaep_label(b"other");
+            assert!(decrypter
+                .decrypt(encrypted_key.as_deref(), &enc, &out_header)
+                .is_err());
+            decrypter.set_oaep_label(b"");
+            assert!(decrypter
+                .decrypt(encrypted_key.as_deref(), &enc, &out_header)
+                .is_err());
+        }
+
+        #[allow(deprecated)]
+        let mut encrypter = RsaesJweAlgorithm::Rsa1_5.encrypter_from_jwk(&public_key)?;
+        encrypter.set_oaep_label(b"label");
+        let mut header = JweHeader::new();
+        header.set_content_encryption(enc.name());
+        assert!(crate::jwe::serialize_compact(b"payload", &header, &encrypter).is_err());
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
@@ -563,11 +713,15 @@ mod openssl_rsa_oaep {
         pkey: &PKey<Public>,
         input: &[u8],
         md: MessageDigest,
+        label: &[u8],
     ) -> Result<Vec<u8>, ErrorStack> {
         let mut encrypter = Encrypter::new(pkey)?;
         encrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
         encrypter.set_rsa_oaep_md(md)?;
         encrypter.set_rsa_mgf1_md(md)?;
+        if !label.is_empty() {
+            encrypter.set_rsa_oaep_label(label)?;
+        }
 
         let outlen = encrypter.encrypt_len(input)?;
         let mut output = vec![0; outlen];
@@ -580,11 +734,15 @@ mod openssl_rsa_oaep {
         pkey: &PKey<Private>,
         input: &[u8],
         md: MessageDigest,
+        label: &[u8],
     ) -> Result<Vec<u8>, ErrorStack> {
         let mut decrypter = Decrypter::new(pkey)?;
         decrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
         decrypter.set_rsa_oaep_md(md)?;
         decrypter.set_rsa_mgf1_md(md)?;
+        if !label.is_empty() {
+            decrypter.set_rsa_oaep_label(label)?;
+        }
 
         let outlen = decrypter.decrypt_len(input)?;
         let mut output = vec![0; outlen];
@@ -0,0 +1,94 @@
+use crate::jwe::enc::aescbc_hmac::AescbcHmacJweEncryption;
+use crate::jwe::enc::aesgcm::AesgcmJweEncryption;
+use crate::jwe::JweContentEncryption;
+
+/// Represent a content encryption algorithm that is identified by a JWE enc header claim value.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ContentEncryptionAlgorithm {
+    AescbcHmac(AescbcHmacJweEncryption),
+    Aesgcm(AesgcmJweEncryption),
+}
+
+impl ContentEncryptionAlgorithm {
+    /// Return the content encryption algorithm for a enc header claim value.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a enc header claim value
+    pub fn from_name(name: &str) -> Option<Self> {
+        let enc = match name {
+            "A128CBC-HS256" => Self::AescbcHmac(AescbcHmacJweEncryption::A128cbcHs256),
+            "A192CBC-HS384" => Self::AescbcHmac(AescbcHmacJweEncryption::A192cbcHs384),
+            "A256CBC-HS512" => Self::AescbcHmac(AescbcHmacJweEncryption::A256cbcHs512),
+            "A128GCM" => Self::Aesgcm(AesgcmJweEncryption::A128gcm),
+            "A192GCM" => Self::Aesgcm(AesgcmJweEncryption::A192gcm),
+            "A256GCM" => Self::Aesgcm(AesgcmJweEncryption::A256gcm),
+            _ => return None,
+        };
+        Some(enc)
+    }
+
+    /// Return the enc header claim value.
+    pub fn name(&self) -> &str {
+        self.content_encryption().name()
+    }
+
+    /// Return the length of the initialization vector.
+    pub fn iv_len(&self) -> usize {
+        self.content_encryption().iv_len()
+    }
+
+    /// Return the content encryption instance.
+    pub fn content_encryption(&self) -> &dyn JweContentEncryption {
+        match self {
+            Self::AescbcHmac(val) => val,
+            Self::Aesgcm(val) => val,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::ContentEncryptionAlgorithm;
+    use crate::jwe::enc::{A128CBC_HS256, A256GCM};
+    use crate::jwe::JweHeader;
+
+    #[test]
+    fn test_content_encryption_algorithm_from_name() -> Result<()> {
+        for (name, key_len, iv_len) in [
+            ("A128CBC-HS256", 32, 16),
+            ("A192CBC-HS384", 48, 16),
+            ("A256CBC-HS512", 64, 16),
+            ("A128GCM", 16, 12),
+            ("A192GCM", 24, 12),
+            ("A256GCM", 32, 12),
+        ] {
+            let enc = ContentEncryptionAlgorithm::from_name(name).unwrap();
+            assert_eq!(enc.name(), name);
+            assert_eq!(enc.content_encryption().key_len(), key_len);
+            assert_eq!(enc.iv_len(), iv_len);
+        }
+        assert_eq!(ContentEncryptionAlgorithm::from_name("none"), None);
+        assert_eq!(ContentEncryptionAlgorithm::from_name("a128gcm"), None);
+
+        let mut header = JweHeader::new();
+        assert_eq!(header.content_encryption_typed(), None);
+        header.set_content_encryption("A128CBC-HS256");
+        assert_eq!(
+            header.content_encryption_typed(),
+            Some(ContentEncryptionAlgorithm::AescbcHmac(A128CBC_HS256))
+        );
+        header.set_content_encryption("A256GCM");
+        assert_eq!(
+            header.content_encryption_typed(),
+            Some(ContentEncryptionAlgorithm::Aesgcm(A256GCM))
+        );
+        header.set_content_encryption("A512GCM");
+        assert_eq!(header.content_encryption(), Some("A512GCM"));
+        assert_eq!(header.content_encryption_typed(), None);
+
+        Ok(())
+    }
+}
@@ -1,15 +1,19 @@
+use std::io::Write;
 use std::{fmt::Display, ops::Deref};
 
 use anyhow::bail;
 use openssl::{
     hash::MessageDigest,
+    memcmp,
     pkey::{PKey, Private},
     sign::Signer,
-    symm::{self, Cipher},
+    symm::{self, Cipher, Crypter, Mode},
 };
 
 use crate::{jwe::JweContentEncryption, JoseError};
 
+const DECRYPT_CHUNK_LEN: usize = 4096;
+
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum AescbcHmacJweEncryption {
     /// AES_128_CBC_HMAC_SHA_256 authenticated encryption algorithm
@@ -66,6 +70,114 @@ impl AescbcHmacJweEncryption {
 
         Ok(signature)
     }
+
+    fn split_key<'a>(&self, key: &'a [u8]) -> anyhow::Result<(&'a [u8], &'a [u8])> {
+        let expected_len = self.key_len();
+        if key.len() != expected_len {
+            bail!(
+                "The length of content encryption key must be {}: {}",
+                expected_len,
+                key.len()
+            );
+        }
+
+        let mac_key_len = expected_len / 2;
+        Ok((&key[0..mac_key_len], &key[mac_key_len..]))
+    }
+
+    fn verify_tag(
+        &self,
+        aad: &[u8],
+        iv: Option<&[u8]>,
+        encrypted_message: &[u8],
+        mac_key: &[u8],
+        tag: Option<&[u8]>,
+    ) -> Result<(), JoseError> {
+        (|| -> anyhow::Result<()> {
+            let tag = match tag {
+                Some(val) => val,
+                None => bail!("A tag value is required."),
+            };
+
+            let calc_tag = self.calcurate_tag(aad, iv, encrypted_message, mac_key)?;
+            if calc_tag.len() != tag.len() || !memcmp::eq(&calc_tag, tag) {
+                bail!("The tag doesn't match.");
+            }
+
+            Ok(())
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidSignature(err),
+        })
+    }
+
+    /// Decrypt the message into the writer after the tag is verified.
+    ///
+    /// The HMAC tag is checked over the whole ciphertext before any decryption,
+    /// so nothing is written to `out` when the ciphertext, the IV, the AAD or the tag
+    /// is tampered. The plaintext is written in chunks, and the number of bytes
+    /// written is returned.
+    ///
+    /// A malformed ciphertext is reported as [`JoseError::InvalidJweFormat`], and a
+    /// failure of `out` is reported as [`JoseError::Io`].
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The content encryption key.
+    /// * `iv` - The initialization vector.
+    /// * `encrypted_message` - The ciphertext.
+    /// * `aad` - The additional authenticated data.
+    /// * `tag` - The authentication tag.
+    /// * `out` - The writer that receives the verified plaintext.
+    pub fn decrypt_to_writer(
+        &self,
+        key: &[u8],
+        iv: Option<&[u8]>,
+        encrypted_message: &[u8],
+        aad: &[u8],
+        tag: Option<&[u8]>,
+        out: &mut impl Write,
+    ) -> Result<usize, JoseError> {
+        let (mac_key, enc_key) = self.split_key(key).map_err(JoseError::InvalidKeyFormat)?;
+
+        let iv = match iv {
+            Some(val) if val.len() == self.iv_len() => val,
+            Some(val) => {
+                return Err(JoseError::InvalidJweFormat(anyhow::anyhow!(
+                    "The length of IV must be {}: {}",
+                    self.iv_len(),
+                    val.len()
+                )))
+            }
+            None => {
+                return Err(JoseError::InvalidJweFormat(anyhow::anyhow!(
+                    "A IV is required."
+                )))
+            }
+        };
+
+        self.verify_tag(aad, Some(iv), encrypted_message, mac_key, tag)?;
+
+        let cipher = self.cipher();
+        let mut crypter = Crypter::new(cipher, Mode::Decrypt, enc_key, Some(iv))
+            .map_err(|err| JoseError::InvalidKeyFormat(err.into()))?;
+        let mut buf = vec![0; DECRYPT_CHUNK_LEN + cipher.block_size()];
+        let mut written = 0;
+        for chunk in encrypted_message.chunks(DECRYPT_CHUNK_LEN) {
+            let len = crypter
+                .update(chunk, &mut buf)
+                .map_err(|err| JoseError::InvalidJweFormat(err.into()))?;
+            out.write_all(&buf[..len]).map_err(JoseError::Io)?;
+            written += len;
+        }
+        let len = crypter
+            .finalize(&mut buf)
+            .map_err(|err| JoseError::InvalidJweFormat(err.into()))?;
+        out.write_all(&buf[..len]).map_err(JoseError::Io)?;
+        written += len;
+        Ok(written)
+    }
 }
 
 impl JweContentEncryption for AescbcHmacJweEncryption {
@@ -97,18 +209,7 @@ impl JweContentEncryption for AescbcHmacJweEncryption {
         aad: &[u8],
     ) -> Result<(Vec<u8>, Option<Vec<u8>>), JoseError> {
         let (encrypted_message, mac_key) = (|| -> anyhow::Result<(Vec<u8>, &[u8])> {
-            let expected_len = self.key_len();
-            if key.len() != expected_len {
-                bail!(
-                    "The length of content encryption key must be {}: {}",
-                    expected_len,
-                    key.len()
-                );
-            }
-
-            let mac_key_len = expected_len / 2;
-            let mac_key = &key[0..mac_key_len];
-            let enc_key = &key[mac_key_len..];
+            let (mac_key, enc_key) = self.split_key(key)?;
 
             let cipher = self.cipher();
             let encrypted_message = symm::encrypt(cipher, enc_key, iv, message)?;
@@ -129,41 +230,8 @@ impl JweContentEncryption for AescbcHmacJweEncryption {
         aad: &[u8],
         tag: Option<&[u8]>,
     ) -> Result<Vec<u8>, JoseError> {
-        let (message, mac_key) = (|| -> anyhow::Result<(Vec<u8>, &[u8])> {
-            let expected_len = self.key_len();
-            if key.len() != expected_len {
-                bail!(
-                    "The length of content encryption key must be {}: {}",
-                    expected_len,
-                    key.len()
-                );
-            }
-
-            let mac_key_len = expected_len / 2;
-            let mac_key = &key[0..mac_key_len];
-            let enc_key = &key[mac_key_len..];
-
-            let cipher = self.cipher();
-            let message = symm::decrypt(cipher, enc_key, iv, encrypted_message)?;
-            Ok((message, mac_key))
-        })()
-        .map_err(|err| JoseError::InvalidKeyFormat(err))?;
-
-        (|| -> anyhow::Result<()> {
-            let tag = match tag {
-                Some(val) => val,
-                None => bail!("A tag value is required."),
-            };
-
-            let calc_tag = self.calcurate_tag(aad, iv, &encrypted_message, mac_key)?;
-            if calc_tag.as_slice() != tag {
-                bail!("The tag doesn't match.");
-            }
-
-            Ok(())
-        })()
-        .map_err(|err| JoseError::InvalidSignature(err))?;
-
+        let mut message = Vec::with_capacity(encrypted_message.len());
+        self.decrypt_to_writer(key, iv, encrypted_message, aad, tag, &mut message)?;
         Ok(message)
     }
 
@@ -188,10 +256,13 @@ impl Deref for AescbcHmacJweEncryption {
 
 #[cfg(test)]
 mod tests {
+    use std::io::{self, Write};
+
     use anyhow::Result;
 
     use super::AescbcHmacJweEncryption;
-    use crate::util;
+    use crate::util::{self, from_hex};
+    use crate::JoseError;
 
     #[test]
     fn encrypt_and_decrypt_aes_cbc_hmac() -> Result<()> {
@@ -203,8 +274,8 @@ mod tests {
             AescbcHmacJweEncryption::A192cbcHs384,
             AescbcHmacJweEncryption::A256cbcHs512,
         ] {
-            let key = util::random_bytes(enc.key_len());
-            let iv = util::random_bytes(enc.iv_len());
+            let key = util::random_bytes(enc.key_len())?;
+            let iv = util::random_bytes(enc.iv_len())?;
 
             let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), message, aad)?;
             let decrypted_message = enc.decrypt(
@@ -220,4 +291,203 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn encrypt_and_decrypt_aes_cbc_hmac_rfc7518_vectors() -> Result<()> {
+        // RFC 7518 Appendix B.1, B.2 and B.3
+        let message = b"A cipher system must not be required to be secret, \
+            and it must be able to fall into the hands of the enemy without inconvenience";
+        let iv = from_hex("1af38c2dc2b96ffdd86694092341bc04");
+        let aad = b"The second principle of Auguste Kerckhoffs";
+
+        for (enc, key_len, expected_message, expected_tag) in [
+            (
+                AescbcHmacJweEncryption::A128cbcHs256,
+                32,
+                "c80edfa32ddf39d5ef00c0b468834279a2e46a1b8049f792f76bfe54b903a9c9\
+                 a94ac9b47ad2655c5f10f9aef71427e2fc6f9b3f399a221489f16362c7032336\
+                 09d45ac69864e3321cf82935ac4096c86e133314c54019e8ca7980dfa4b9cf1b\
+                 384c486f3a54c51078158ee5d79de59fbd34d848b3d69550a67646344427ade5\
+                 4b8851ffb598f7f80074b9473c82e2db",
+                "652c3fa36b0a7c5b3219fab3a30bc1c4",
+            ),
+            (
+                AescbcHmacJweEncryption::A192cbcHs384,
+                48,
+                "ea65da6b59e61edb419be62d19712ae5d303eeb50052d0dfd6697f77224c8edb\
+                 000d279bdc14c1072654bd30944230c657bed4ca0c9f4a8466f22b226d174621\
+                 4bf8cfc2400add9f5126e479663fc90b3bed787a2f0ffcbf3904be2a641d5c21\
+                 05bfe591bae23b1d7449e532eef60a9ac8bb6c6b01d35d49787bcd57ef484927\
+                 f280adc91ac0c4e79c7b11efc60054e3",
+                "8490ac0e58949bfe51875d733f93ac2075168039ccc733d7",
+            ),
+            (
+                AescbcHmacJweEncryption::A256cbcHs512,
+                64,
+                "4affaaadb78c31c5da4b1b590d10ffbd3dd8d5d302423526912da037ecbcc7bd\
+                 822c301dd67c373bccb584ad3e9279c2e6d12a1374b77f077553df829410446b\
+                 36ebd97066296ae6427ea75c2e0846a11a09ccf5370dc80bfecbad28c73f09b3\
+                 a3b75e662a2594410ae496b2e2e6609e31e6e02cc837f053d21f37ff4f51950b\
+                 be2638d09dd7a4930930806d0703b1f6",
+                "4dd3b4c088a7f45c216839645b2012bf2e6269a8c56a816dbc1b267761955bc5",
+            ),
+        ] {
+            let key: Vec<u8> = (0..key_len).collect();
+            assert_eq!(enc.key_len(), key.len());
+
+            let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), message, aad)?;
+            assert_eq!(encrypted_message, from_hex(expected_message));
+            assert_eq!(tag, Some(from_hex(expected_tag)));
+
+            let decrypted_message = enc.decrypt(
+                &key,
+                Some(&iv),
+                &from_hex(expected_message),
+                aad,
+                Some(&from_hex(expected_tag)),
+            )?;
+            assert_eq!(&message[..], &decrypted_message[..]);
+
+            // The AAD and its length are authenticated.
+            assert!(enc
+                .decrypt(
+                    &key,
+                    Some(&iv),
+                    &from_hex(expected_message),
+                    &aad[..aad.len() - 1],
+                    Some(&from_hex(expected_tag)),
+                )
+                .is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn decrypt_aes_cbc_hmac_without_iv() -> Result<()> {
+        let message = b"abcde12345";
+        let aad = b"test";
+
+        let enc = AescbcHmacJweEncryption::A128cbcHs256;
+        let key = util::random_bytes(enc.key_len())?;
+        let iv = util::random_bytes(enc.iv_len())?;
+        let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), message, aad)?;
+
+        assert!(matches!(
+            enc.decrypt(&key, None, &encrypted_message, aad, tag.as_deref()),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+        assert!(matches!(
+            enc.decrypt(
+                &key,
+                Some(&iv[..8]),
+                &encrypted_message,
+                aad,
+                tag.as_deref()
+            ),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn decrypt_aes_cbc_hmac_to_writer_verifies_before_release() -> Result<()> {
+        let message = vec![0x5a; 10000];
+        let aad = b"test";
+
+        for enc in [
+            AescbcHmacJweEncryption::A128cbcHs256,
+            AescbcHmacJweEncryption::A192cbcHs384,
+            AescbcHmacJweEncryption::A256cbcHs512,
+        ] {
+            let key = util::random_bytes(enc.key_len())?;
+            let iv = util::random_bytes(enc.iv_len())?;
+            let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), &message, aad)?;
+
+            let mut out = Vec::new();
+            let len = enc.decrypt_to_writer(
+                &key,
+                Some(&iv),
+                &encrypted_message,
+                aad,
+                tag.as_deref(),
+                &mut out,
+            )?;
+            assert_eq!(len, message.len());
+            assert_eq!(out, message);
+
+            // A tampered block anywhere in the ciphertext releases no plaintext.
+            for pos in [0, encrypted_message.len() / 2, encrypted_message.len() - 1] {
+                let mut tampered = encrypted_message.clone();
+                tampered[pos] ^= 1;
+
+                let mut out = Vec::new();
+                let res = enc.decrypt_to_writer(
+                    &key,
+                    Some(&iv),
+                    &tampered,
+                    aad,
+                    tag.as_deref(),
+                    &mut out,
+                );
+                assert!(matches!(res, Err(JoseError::InvalidSignature(_))));
+                assert!(out.is_empty());
+            }
+
+            let mut out = Vec::new();
+            let res =
+                enc.decrypt_to_writer(&key, Some(&iv), &encrypted_message, aad, None, &mut out);
+            assert!(res.is_err());
+            assert!(out.is_empty());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn decrypt_aes_cbc_hmac_to_writer_reports_write_errors() -> Result<()> {
+        struct FullWriter;
+
+        impl Write for FullWriter {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk full"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let enc = AescbcHmacJweEncryption::A128cbcHs256;
+        let key = [1u8; 32];
+        let iv = [2u8; 16];
+        let aad = b"test";
+        let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), b"abcde12345", aad)?;
+
+        let res = enc.decrypt_to_writer(
+            &key,
+            Some(&iv),
+            &encrypted_message,
+            aad,
+            tag.as_deref(),
+            &mut FullWriter,
+        );
+        assert!(matches!(res, Err(JoseError::Io(_))));
+
+        // The tag still matches under the same MAC key, but the padding decrypted
+        // by another AES key is broken.
+        let bad_key = [&key[..16], &[0u8; 16][..]].concat();
+        let res = enc.decrypt_to_writer(
+            &bad_key,
+            Some(&iv),
+            &encrypted_message,
+            aad,
+            tag.as_deref(),
+            &mut Vec::new(),
+        );
+        assert!(matches!(res, Err(JoseError::InvalidJweFormat(_))));
+
+        Ok(())
+    }
 }
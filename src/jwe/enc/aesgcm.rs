@@ -1,5 +1,7 @@
+use std::collections::HashSet;
 use std::fmt::Display;
 use std::ops::Deref;
+use std::sync::{Mutex, PoisonError};
 
 use anyhow::bail;
 use openssl::symm::{self, Cipher};
@@ -7,6 +9,12 @@ use openssl::symm::{self, Cipher};
 use crate::jwe::JweContentEncryption;
 use crate::JoseError;
 
+/// Represents AES GCM content encryption algorithms.
+///
+/// GCM verifies the tag only after the whole ciphertext is processed, so the decrypted
+/// message is held in a buffer and returned only when the tag matches. A caller that
+/// decrypts GCM content in pieces by other means must never release plaintext before
+/// the tag is verified.
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum AesgcmJweEncryption {
     /// AES GCM using 128-bit key
@@ -25,6 +33,128 @@ impl AesgcmJweEncryption {
             Self::A256gcm => Cipher::aes_256_gcm(),
         }
     }
+
+    /// Return the encrypted message and the tag by an explicit IV.
+    ///
+    /// A caller outside of this crate supplies an IV through [`Self::encrypter`],
+    /// which rejects a reused IV.
+    pub(crate) fn encrypt_with_iv(
+        &self,
+        key: &[u8],
+        iv: &[u8],
+        message: &[u8],
+        aad: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>), JoseError> {
+        (|| -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+            let expected_len = self.key_len();
+            if key.len() != expected_len {
+                bail!(
+                    "The length of content encryption key must be {}: {}",
+                    expected_len,
+                    key.len()
+                );
+            }
+
+            if iv.len() != self.iv_len() {
+                bail!("The length of IV must be {}: {}", self.iv_len(), iv.len());
+            }
+
+            let cipher = self.cipher();
+            let mut tag = [0; 16];
+            let encrypted_message =
+                symm::encrypt_aead(cipher, key, Some(iv), aad, message, &mut tag)?;
+            Ok((encrypted_message, tag.to_vec()))
+        })()
+        .map_err(JoseError::InvalidKeyFormat)
+    }
+
+    /// Return a content encrypter that is bound to a content encryption key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The content encryption key.
+    pub fn encrypter(&self, key: &[u8]) -> Result<AesgcmContentEncrypter, JoseError> {
+        let expected_len = self.key_len();
+        if key.len() != expected_len {
+            return Err(JoseError::InvalidKeyFormat(anyhow::anyhow!(
+                "The length of content encryption key must be {}: {}",
+                expected_len,
+                key.len()
+            )));
+        }
+
+        Ok(AesgcmContentEncrypter {
+            encryption: *self,
+            key: key.to_vec(),
+            supplied_ivs: Mutex::new(HashSet::new()),
+        })
+    }
+}
+
+/// The maximum number of IVs that a content encrypter records.
+const MAX_SUPPLIED_IVS: usize = 4096;
+
+/// A AES GCM content encrypter that is bound to a content encryption key.
+///
+/// The result is deterministic, so this is useful for known answer tests.
+/// The JWE serialization always generates a random IV instead.
+///
+/// The IVs that a caller supplies are recorded by the instance, and reusing one of
+/// them is rejected. The record is not shared with other instances. Up to 4096 IVs
+/// are recorded, and an instance rejects more IVs than that instead of forgetting
+/// old ones.
+#[derive(Debug)]
+pub struct AesgcmContentEncrypter {
+    encryption: AesgcmJweEncryption,
+    key: Vec<u8>,
+    supplied_ivs: Mutex<HashSet<Vec<u8>>>,
+}
+
+impl AesgcmContentEncrypter {
+    /// Return the content encryption algorithm.
+    pub fn encryption(&self) -> AesgcmJweEncryption {
+        self.encryption
+    }
+
+    /// Return the encrypted message and the tag by an explicit IV.
+    ///
+    /// A IV that is already supplied to this instance is rejected, and so is any IV
+    /// after 4096 IVs are supplied.
+    ///
+    /// # Arguments
+    ///
+    /// * `iv` - The 96-bit initialization vector.
+    /// * `message` - The message.
+    /// * `aad` - The additional authenticated data.
+    pub fn encrypt_with_iv(
+        &self,
+        iv: &[u8],
+        message: &[u8],
+        aad: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>), JoseError> {
+        let mut supplied_ivs = self
+            .supplied_ivs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if supplied_ivs.contains(iv) {
+            return Err(JoseError::InvalidKeyFormat(anyhow::anyhow!(
+                "The IV is reused with the same key for {}.",
+                self.encryption.name()
+            )));
+        }
+        if supplied_ivs.len() >= MAX_SUPPLIED_IVS {
+            return Err(JoseError::InvalidKeyFormat(anyhow::anyhow!(
+                "The number of IVs for the same key exceeds {}.",
+                MAX_SUPPLIED_IVS
+            )));
+        }
+
+        let result = self
+            .encryption
+            .encrypt_with_iv(&self.key, iv, message, aad)?;
+        supplied_ivs.insert(iv.to_vec());
+        Ok(result)
+    }
 }
 
 impl JweContentEncryption for AesgcmJweEncryption {
@@ -55,22 +185,16 @@ impl JweContentEncryption for AesgcmJweEncryption {
         message: &[u8],
         aad: &[u8],
     ) -> Result<(Vec<u8>, Option<Vec<u8>>), JoseError> {
-        (|| -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
-            let expected_len = self.key_len();
-            if key.len() != expected_len {
-                bail!(
-                    "The length of content encryption key must be {}: {}",
-                    expected_len,
-                    key.len()
-                );
+        let iv = match iv {
+            Some(val) => val,
+            None => {
+                return Err(JoseError::InvalidKeyFormat(anyhow::anyhow!(
+                    "A IV is required."
+                )))
             }
-
-            let cipher = self.cipher();
-            let mut tag = [0; 16];
-            let encrypted_message = symm::encrypt_aead(cipher, key, iv, aad, message, &mut tag)?;
-            Ok((encrypted_message, Some(tag.to_vec())))
-        })()
-        .map_err(|err| JoseError::InvalidKeyFormat(err))
+        };
+        let (encrypted_message, tag) = self.encrypt_with_iv(key, iv, message, aad)?;
+        Ok((encrypted_message, Some(tag)))
     }
 
     fn decrypt(
@@ -91,13 +215,21 @@ impl JweContentEncryption for AesgcmJweEncryption {
                 );
             }
 
+            let iv = match iv {
+                Some(val) if val.len() == self.iv_len() => val,
+                Some(val) => bail!("The length of IV must be {}: {}", self.iv_len(), val.len()),
+                None => bail!("A IV is required."),
+            };
+
+            // A truncated tag is accepted by OpenSSL and weakens the authentication.
             let tag = match tag {
-                Some(val) => val,
+                Some(val) if val.len() == 16 => val,
+                Some(val) => bail!("The length of tag must be 16: {}", val.len()),
                 None => bail!("A tag value is required."),
             };
 
             let cipher = self.cipher();
-            let message = symm::decrypt_aead(cipher, key, iv, aad, encrypted_message, tag)?;
+            let message = symm::decrypt_aead(cipher, key, Some(iv), aad, encrypted_message, tag)?;
             Ok(message)
         })()
         .map_err(|err| JoseError::InvalidJweFormat(err))
@@ -127,7 +259,9 @@ mod tests {
     use anyhow::Result;
 
     use super::AesgcmJweEncryption;
+    use crate::jwe::{self, Dir, JweHeader};
     use crate::util;
+    use crate::JoseError;
 
     #[test]
     fn encrypt_and_decrypt_aes_gcm() -> Result<()> {
@@ -139,8 +273,8 @@ mod tests {
             AesgcmJweEncryption::A192gcm,
             AesgcmJweEncryption::A256gcm,
         ] {
-            let key = util::random_bytes(enc.key_len());
-            let iv = util::random_bytes(enc.iv_len());
+            let key = util::random_bytes(enc.key_len())?;
+            let iv = util::random_bytes(enc.iv_len())?;
 
             let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), message, aad)?;
             let decrypted_message = enc.decrypt(
@@ -156,4 +290,144 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn encrypt_aes_gcm_without_iv() -> Result<()> {
+        let message = b"abcde12345";
+        let aad = b"test";
+
+        let enc = AesgcmJweEncryption::A256gcm;
+        let key = util::random_bytes(enc.key_len())?;
+        assert!(enc.encrypt(&key, None, message, aad).is_err());
+        assert!(enc
+            .encrypt(&key, Some(&util::random_bytes(16)?), message, aad)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn decrypt_aes_gcm_with_tampered_ciphertext() -> Result<()> {
+        let message = b"abcde12345";
+        let aad = b"test";
+
+        let enc = AesgcmJweEncryption::A256gcm;
+        let key = util::random_bytes(enc.key_len())?;
+        let iv = util::random_bytes(enc.iv_len())?;
+        let (mut encrypted_message, tag) = enc.encrypt(&key, Some(&iv), message, aad)?;
+        encrypted_message[0] ^= 1;
+
+        // No partial plaintext is returned when the tag doesn't match.
+        assert!(enc
+            .decrypt(&key, Some(&iv), &encrypted_message, aad, tag.as_deref())
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn decrypt_aes_gcm_with_invalid_iv_or_tag() -> Result<()> {
+        let message = b"abcde12345";
+        let aad = b"test";
+
+        let enc = AesgcmJweEncryption::A256gcm;
+        let key = util::random_bytes(enc.key_len())?;
+        let iv = util::random_bytes(enc.iv_len())?;
+        let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), message, aad)?;
+        let tag = tag.unwrap();
+
+        assert!(enc
+            .decrypt(&key, None, &encrypted_message, aad, Some(&tag))
+            .is_err());
+        assert!(enc
+            .decrypt(&key, Some(&iv[..8]), &encrypted_message, aad, Some(&tag))
+            .is_err());
+        // A truncated tag is rejected even if it is a prefix of the right tag.
+        assert!(enc
+            .decrypt(&key, Some(&iv), &encrypted_message, aad, Some(&tag[..4]))
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_aes_gcm_rfc7516_vector() -> Result<()> {
+        // RFC 7516 Appendix A.1
+        let enc = AesgcmJweEncryption::A256gcm;
+        let key = [
+            177, 161, 244, 128, 84, 143, 225, 115, 63, 180, 3, 255, 107, 154, 212, 246, 138, 7,
+            110, 91, 112, 46, 34, 105, 47, 130, 203, 46, 122, 234, 64, 252,
+        ];
+        let iv = util::decode_base64_urlsafe_no_pad("48V1_ALb6US04U3b")?;
+        let aad = b"eyJhbGciOiJSU0EtT0FFUCIsImVuYyI6IkEyNTZHQ00ifQ";
+        let message = b"The true sign of intelligence is not knowledge but imagination.";
+        let encrypted_message = util::decode_base64_urlsafe_no_pad(
+            "5eym8TW_c8SuK0ltJ3rpYIzOeDQz7TALvtu6UG9oMo4vpzs9tX_EFShS8iB7j6jiSdiwkIr3ajwQzaBtQD_A",
+        )?;
+        let tag = util::decode_base64_urlsafe_no_pad("XFBoMYUZodetZdvTiFvSkQ")?;
+
+        let decrypted_message =
+            enc.decrypt(&key, Some(&iv), &encrypted_message, aad, Some(&tag))?;
+        assert_eq!(&decrypted_message[..], &message[..]);
+
+        let (dst_encrypted_message, dst_tag) = enc.encrypt_with_iv(&key, &iv, message, aad)?;
+        assert_eq!(dst_encrypted_message, encrypted_message);
+        assert_eq!(dst_tag, tag);
+
+        assert!(enc.encrypt_with_iv(&key, &iv[..8], message, aad).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn serialize_with_random_aes_gcm_iv() -> Result<()> {
+        let enc = AesgcmJweEncryption::A256gcm;
+        let key = util::random_bytes(enc.key_len())?;
+        let encrypter = Dir.encrypter_from_bytes(&key)?;
+        let mut header = JweHeader::new();
+        header.set_content_encryption(enc.name());
+
+        let jwe1 = jwe::serialize_compact(b"payload", &header, &encrypter)?;
+        let jwe2 = jwe::serialize_compact(b"payload", &header, &encrypter)?;
+        let iv1 = jwe1.split('.').nth(2).unwrap();
+        let iv2 = jwe2.split('.').nth(2).unwrap();
+        assert_eq!(util::decode_base64_urlsafe_no_pad(iv1)?.len(), enc.iv_len());
+        assert_ne!(iv1, iv2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn content_encrypter_rejects_reused_iv() -> Result<()> {
+        let enc = AesgcmJweEncryption::A256gcm;
+        let key = util::random_bytes(enc.key_len())?;
+        let iv = util::random_bytes(enc.iv_len())?;
+
+        let encrypter = enc.encrypter(&key)?;
+        let (encrypted_message, tag) = encrypter.encrypt_with_iv(&iv, b"payload", b"aad")?;
+        let message = enc.decrypt(&key, Some(&iv), &encrypted_message, b"aad", Some(&tag))?;
+        assert_eq!(message, b"payload");
+        assert!(matches!(
+            encrypter.encrypt_with_iv(&iv, b"payload", b"aad"),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+
+        // Another instance has its own record.
+        enc.encrypter(&key)?
+            .encrypt_with_iv(&iv, b"payload", b"aad")?;
+
+        // The record is bounded, and no IV is forgotten.
+        let encrypter = enc.encrypter(&key)?;
+        for i in 0..super::MAX_SUPPLIED_IVS as u32 {
+            let mut iv = [0; 12];
+            iv[..4].copy_from_slice(&i.to_be_bytes());
+            encrypter.encrypt_with_iv(&iv, b"", b"")?;
+        }
+        assert!(encrypter.encrypt_with_iv(&[0xff; 12], b"", b"").is_err());
+        assert!(encrypter.encrypt_with_iv(&[0; 12], b"", b"").is_err());
+
+        assert!(enc.encrypter(&key[..16]).is_err());
+
+        Ok(())
+    }
 }
@@ -1,7 +1,7 @@
 use std::borrow::Cow;
 use std::fmt::Debug;
 
-use crate::jwe::{JweContentEncryption, JweHeader};
+use crate::jwe::{JweContentEncryption, JweDecryptionOptions, JweHeader};
 use crate::JoseError;
 
 /// Represent a algorithm of JWE alg header claim.
@@ -34,6 +34,18 @@ pub trait JweEncrypter: Debug + Send + Sync {
     /// The default value is a value of kid parameter in JWK.
     fn key_id(&self) -> Option<&str>;
 
+    /// Return the key type (kty) of the encrypting key: "EC", "RSA", "OKP" or "oct".
+    ///
+    /// The default implementation returns None, which means the key type is unknown.
+    fn key_type(&self) -> Option<&str> {
+        None
+    }
+
+    /// Return the curve (crv) of the encrypting key when the key type has curves.
+    fn curve(&self) -> Option<&str> {
+        None
+    }
+
     /// Compute a content encryption key.
     ///
     /// # Arguments
@@ -46,7 +58,7 @@ pub trait JweEncrypter: Debug + Send + Sync {
         cencryption: &dyn JweContentEncryption,
         in_header: &JweHeader,
         out_header: &mut JweHeader,
-    ) -> Result<Option<Cow<[u8]>>, JoseError>;
+    ) -> Result<Option<Cow<'_, [u8]>>, JoseError>;
 
     /// Return a encypted key.
     ///
@@ -91,7 +103,27 @@ pub trait JweDecrypter: Debug + Send + Sync {
         encrypted_key: Option<&[u8]>,
         cencryption: &dyn JweContentEncryption,
         header: &JweHeader,
-    ) -> Result<Cow<[u8]>, JoseError>;
+    ) -> Result<Cow<'_, [u8]>, JoseError>;
+
+    /// Return a decrypted key with the limits of decryption.
+    ///
+    /// The default implementation ignores the options and calls decrypt.
+    ///
+    /// # Arguments
+    ///
+    /// * `encrypted_key` - The encrypted key.
+    /// * `cencryption` - The content encryption method.
+    /// * `header` - The header
+    /// * `options` - The limits of decryption.
+    fn decrypt_with_options(
+        &self,
+        encrypted_key: Option<&[u8]>,
+        cencryption: &dyn JweContentEncryption,
+        header: &JweHeader,
+        _options: &JweDecryptionOptions,
+    ) -> Result<Cow<'_, [u8]>, JoseError> {
+        self.decrypt(encrypted_key, cencryption, header)
+    }
 
     fn box_clone(&self) -> Box<dyn JweDecrypter>;
 }
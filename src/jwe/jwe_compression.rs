@@ -16,7 +16,7 @@ pub trait JweCompression: Debug + Send + Sync {
 
 impl PartialEq for Box<dyn JweCompression> {
     fn eq(&self, other: &Self) -> bool {
-        self == other
+        self.name() == other.name()
     }
 }
 
@@ -8,22 +8,40 @@ use anyhow::bail;
 use crate::jwe::enc::{A128CBC_HS256, A128GCM, A192CBC_HS384, A192GCM, A256CBC_HS512, A256GCM};
 use crate::jwe::zip::Def;
 use crate::jwe::{
-    JweCompression, JweContentEncryption, JweDecrypter, JweEncrypter, JweHeader, JweHeaderSet,
+    JweCompression, JweContentEncryption, JweDecrypter, JweDecryptionOptions, JweEncrypter,
+    JweHeader, JweHeaderSet,
 };
-use crate::util;
+use crate::util::{self, OpensslRand, RandomSource};
 use crate::{JoseError, JoseHeader, Map, Value};
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Clone)]
 pub struct JweContext {
     acceptable_criticals: BTreeSet<String>,
+    expected_types: Option<BTreeSet<String>>,
     compressions: BTreeMap<String, Box<dyn JweCompression>>,
     content_encryptions: BTreeMap<String, Box<dyn JweContentEncryption>>,
+    decryption_options: JweDecryptionOptions,
+    random_source: Box<dyn RandomSource>,
 }
 
+/// The random source is not compared.
+impl PartialEq for JweContext {
+    fn eq(&self, other: &Self) -> bool {
+        self.acceptable_criticals == other.acceptable_criticals
+            && self.expected_types == other.expected_types
+            && self.compressions == other.compressions
+            && self.content_encryptions == other.content_encryptions
+            && self.decryption_options == other.decryption_options
+    }
+}
+
+impl Eq for JweContext {}
+
 impl JweContext {
     pub fn new() -> Self {
         Self {
             acceptable_criticals: BTreeSet::new(),
+            expected_types: None,
             compressions: {
                 let compressions: Vec<Box<dyn JweCompression>> = vec![Box::new(Def)];
 
@@ -49,6 +67,8 @@ impl JweContext {
                 }
                 map
             },
+            decryption_options: JweDecryptionOptions::default(),
+            random_source: Box::new(OpensslRand),
         }
     }
 
@@ -79,6 +99,38 @@ impl JweContext {
         self.acceptable_criticals.remove(name);
     }
 
+    /// Set token types that the typ header claim must match when deserializing.
+    ///
+    /// The comparison is case-insensitive and the "application/" prefix is ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - expected token types (e.g. "JWT")
+    pub fn set_expected_types(&mut self, values: &[&str]) {
+        let types = values
+            .iter()
+            .map(|val| util::normalize_media_type(val))
+            .collect();
+        self.expected_types = Some(types);
+    }
+
+    /// Remove the restriction of token types.
+    pub fn remove_expected_types(&mut self) {
+        self.expected_types = None;
+    }
+
+    /// Test a token type is acceptable.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a value of typ header claim
+    pub fn is_expected_type(&self, value: &str) -> bool {
+        match &self.expected_types {
+            Some(types) => types.contains(&util::normalize_media_type(value)),
+            None => true,
+        }
+    }
+
     /// Get a compression algorithm for zip header claim value.
     ///
     /// # Arguments
@@ -141,6 +193,37 @@ impl JweContext {
         self.content_encryptions.remove(name);
     }
 
+    /// Return the limits that are enforced while decrypting.
+    pub fn decryption_options(&self) -> &JweDecryptionOptions {
+        &self.decryption_options
+    }
+
+    /// Set limits that are enforced while decrypting.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - a decryption options
+    pub fn set_decryption_options(&mut self, options: JweDecryptionOptions) {
+        self.decryption_options = options;
+    }
+
+    /// Return the random source used to generate content encryption keys and IVs.
+    pub fn random_source(&self) -> &dyn RandomSource {
+        self.random_source.as_ref()
+    }
+
+    /// Set a random source used to generate content encryption keys and IVs.
+    ///
+    /// Ephemeral key pairs of ECDH-ES are drawn from the random source of the encrypter.
+    /// See [`crate::jwe::alg::ecdh_es::EcdhEsJweEncrypter::set_random_source`].
+    ///
+    /// # Arguments
+    ///
+    /// * `random_source` - a random source
+    pub fn set_random_source(&mut self, random_source: Box<dyn RandomSource>) {
+        self.random_source = random_source;
+    }
+
     /// Return a representation of the data that is formatted by compact serialization.
     ///
     /// # Arguments
@@ -179,13 +262,7 @@ impl JweContext {
                 None => bail!("A encrypter is not found."),
             };
 
-            let cencryption = match header.content_encryption() {
-                Some(enc) => match self.get_content_encryption(enc) {
-                    Some(val) => val,
-                    None => bail!("A content encryption is not registered: {}", enc),
-                },
-                None => bail!("A enc header claim is required."),
-            };
+            let cencryption = self.content_encryption_for(header)?;
 
             let compression = match header.compression() {
                 Some(zip) => match self.get_compression(zip) {
@@ -204,7 +281,10 @@ impl JweContext {
                 &mut out_header,
             )? {
                 Some(val) => val,
-                None => Cow::Owned(util::random_bytes(key_len)),
+                None => Cow::Owned(util::random_bytes_from(
+                    self.random_source.as_ref(),
+                    key_len,
+                )?),
             };
 
             let encrypted_key = encrypter.encrypt(&key, &header, &mut out_header)?;
@@ -229,7 +309,8 @@ impl JweContext {
 
             let iv_vec;
             let iv = if cencryption.iv_len() > 0 {
-                iv_vec = util::random_bytes(cencryption.iv_len());
+                iv_vec =
+                    util::random_bytes_from(self.random_source.as_ref(), cencryption.iv_len())?;
                 Some(iv_vec.as_slice())
             } else {
                 None
@@ -346,9 +427,9 @@ impl JweContext {
                 }
             };
 
-            let merged_map = match header {
-                Some(val) => val.to_map(),
-                None => Map::new(),
+            let shared_header = match header {
+                Some(val) => JweHeader::from_map(val.to_map())?,
+                None => JweHeader::new(),
             };
 
             let mut merged_list = Vec::new();
@@ -358,41 +439,20 @@ impl JweContext {
             let mut selected_cencryption: Option<&dyn JweContentEncryption> = None;
             let mut selected_key: Option<Cow<[u8]>> = None;
             for (i, recipient_header) in recipient_headers.iter().enumerate() {
-                let mut merged_map = merged_map.clone();
+                let merged = match recipient_header {
+                    Some(val) => shared_header.merged_with(*val)?,
+                    None => shared_header.clone(),
+                };
 
-                if let Some(val) = recipient_header {
-                    for (key, value) in val.claims_set() {
-                        if merged_map.contains_key(key) {
-                            bail!("Duplicate key exists: {}", key);
-                        }
-                        merged_map.insert(key.clone(), value.clone());
+                let cencryption = self.content_encryption_for(&merged)?;
+                match selected_cencryption {
+                    Some(val) if val.name() != cencryption.name() => {
+                        bail!("A content encryption must be same for all recipients.");
                     }
+                    Some(_) => {}
+                    None => selected_cencryption = Some(cencryption),
                 }
 
-                let merged = JweHeader::from_map(merged_map)?;
-
-                let cencryption = match merged.claim("enc") {
-                    Some(Value::String(enc)) => {
-                        if let Some(selected_cencryption) = selected_cencryption {
-                            if enc.as_str() != selected_cencryption.name() {
-                                bail!("A content encryption must be same for all recipients.");
-                            } else {
-                                selected_cencryption
-                            }
-                        } else {
-                            match self.get_content_encryption(enc) {
-                                Some(val) => {
-                                    selected_cencryption = Some(val);
-                                    val
-                                }
-                                None => bail!("A content encryption is not registered: {}", enc),
-                            }
-                        }
-                    }
-                    Some(_) => bail!("A enc header claim must be a string."),
-                    None => bail!("A enc header claim is required."),
-                };
-
                 let encrypter = match selector(i, &merged) {
                     Some(val) => val,
                     None => bail!("A encrypter is not found."),
@@ -443,11 +503,17 @@ impl JweContext {
 
             let key = match &selected_key {
                 Some(val) => Cow::Borrowed(val.as_ref()),
-                None => Cow::Owned(util::random_bytes(cencryption.key_len())),
+                None => Cow::Owned(util::random_bytes_from(
+                    self.random_source.as_ref(),
+                    cencryption.key_len(),
+                )?),
             };
 
             let iv = if cencryption.iv_len() > 0 {
-                Some(util::random_bytes(cencryption.iv_len()))
+                Some(util::random_bytes_from(
+                    self.random_source.as_ref(),
+                    cencryption.iv_len(),
+                )?)
             } else {
                 None
             };
@@ -642,31 +708,17 @@ impl JweContext {
                 }
             };
 
-            let mut merged_map = match header {
-                Some(val) => val.to_map(),
-                None => Map::new(),
+            let merged = match header {
+                Some(val) => JweHeader::from_map(val.to_map())?,
+                None => JweHeader::new(),
             };
-
-            if let Some(val) = recipient_header {
-                for (key, value) in val.claims_set() {
-                    if merged_map.contains_key(key) {
-                        bail!("Duplicate key exists: {}", key);
-                    }
-                    merged_map.insert(key.clone(), value.clone());
-                }
-            }
-
-            let merged = JweHeader::from_map(merged_map)?;
-
-            let cencryption = match merged.claim("enc") {
-                Some(Value::String(enc)) => match self.get_content_encryption(enc) {
-                    Some(val) => val,
-                    None => bail!("A content encryption is not registered: {}", enc),
-                },
-                Some(_) => bail!("A enc header claim must be a string."),
-                None => bail!("A enc header claim is required."),
+            let merged = match recipient_header {
+                Some(val) => merged.merged_with(val)?,
+                None => merged,
             };
 
+            let cencryption = self.content_encryption_for(&merged)?;
+
             let encrypter = match selector(&merged) {
                 Some(val) => val,
                 None => bail!("A encrypter is not found."),
@@ -683,7 +735,10 @@ impl JweContext {
                 &mut protected,
             )? {
                 Some(val) => val,
-                None => Cow::Owned(util::random_bytes(cencryption.key_len())),
+                None => Cow::Owned(util::random_bytes_from(
+                    self.random_source.as_ref(),
+                    cencryption.key_len(),
+                )?),
             };
 
             let encrypted_key = encrypter.encrypt(&key, &merged, &mut protected)?;
@@ -704,7 +759,8 @@ impl JweContext {
 
             let iv_vec;
             let iv = if cencryption.iv_len() > 0 {
-                iv_vec = util::random_bytes(cencryption.iv_len());
+                iv_vec =
+                    util::random_bytes_from(self.random_source.as_ref(), cencryption.iv_len())?;
                 Some(iv_vec.as_slice())
             } else {
                 None
@@ -855,7 +911,8 @@ impl JweContext {
                 .collect();
             if indexies.len() != 4 {
                 bail!(
-                    "The compact serialization form of JWE must be five parts separated by colon."
+                    "The compact serialization form of JWE must be five parts separated by period: {} parts",
+                    indexies.len() + 1
                 );
             }
 
@@ -864,7 +921,7 @@ impl JweContext {
             let encrypted_key_b64 = &input[(indexies[0] + 1)..(indexies[1])];
             let encrypted_key_vec;
             let encrypted_key = if encrypted_key_b64.len() > 0 {
-                encrypted_key_vec = util::decode_base64_urlsafe_no_pad(encrypted_key_b64)?;
+                encrypted_key_vec = self.decode_base64(encrypted_key_b64)?;
                 Some(encrypted_key_vec.as_slice())
             } else {
                 None
@@ -873,41 +930,35 @@ impl JweContext {
             let iv_b64 = &input[(indexies[1] + 1)..(indexies[2])];
             let iv_vec;
             let iv = if iv_b64.len() > 0 {
-                iv_vec = util::decode_base64_urlsafe_no_pad(iv_b64)?;
+                iv_vec = self.decode_base64(iv_b64)?;
                 Some(iv_vec.as_slice())
             } else {
                 None
             };
 
             let ciphertext_b64 = &input[(indexies[2] + 1)..(indexies[3])];
-            let ciphertext = util::decode_base64_urlsafe_no_pad(ciphertext_b64)?;
+            let ciphertext = self.decode_base64(ciphertext_b64)?;
 
             let tag_b64 = &input[(indexies[3] + 1)..];
             let tag_vec;
             let tag = if tag_b64.len() > 0 {
-                tag_vec = util::decode_base64_urlsafe_no_pad(tag_b64)?;
+                tag_vec = self.decode_base64(tag_b64)?;
                 Some(tag_vec.as_slice())
             } else {
                 None
             };
 
-            let header = util::decode_base64_urlsafe_no_pad(header_b64)?;
-            let merged: Map<String, Value> = serde_json::from_slice(&header)?;
+            let header = self.decode_base64(header_b64)?;
+            let merged = self.parse_json_object(&header)?;
             let merged = JweHeader::from_map(merged)?;
+            self.check_type(&merged)?;
 
             let decrypter = match selector(&merged)? {
                 Some(val) => val,
                 None => bail!("A decrypter is not found."),
             };
 
-            let cencryption = match merged.claim("enc") {
-                Some(Value::String(val)) => match self.get_content_encryption(val) {
-                    Some(val2) => val2,
-                    None => bail!("A content encryption is not registered: {}", val),
-                },
-                Some(_) => bail!("A enc header claim must be a string."),
-                None => bail!("A enc header claim is required."),
-            };
+            let cencryption = self.content_encryption_for(&merged)?;
 
             let compression = match merged.claim("zip") {
                 Some(Value::String(val)) => match self.get_compression(val) {
@@ -938,7 +989,12 @@ impl JweContext {
                 None => {}
             }
 
-            let key = decrypter.decrypt(encrypted_key, cencryption, &merged)?;
+            let key = decrypter.decrypt_with_options(
+                encrypted_key,
+                cencryption,
+                &merged,
+                &self.decryption_options,
+            )?;
             if key.len() != cencryption.key_len() {
                 bail!(
                     "The key size is expected to be {}: {}",
@@ -995,6 +1051,42 @@ impl JweContext {
         })
     }
 
+    /// Deserialize the input that is formatted by json serialization
+    /// and has the expected additional authenticated data.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `aad` - The expected JWE additional authenticated data.
+    /// * `decrypter` - The JWE decrypter.
+    pub fn deserialize_json_with_aad(
+        &self,
+        input: impl AsRef<[u8]>,
+        aad: Option<&[u8]>,
+        decrypter: &dyn JweDecrypter,
+    ) -> Result<(Vec<u8>, JweHeader), JoseError> {
+        self.deserialize_json_with_aad_and_selector(input, Some(aad), |header| {
+            match header.algorithm() {
+                Some(val) => {
+                    let expected_alg = decrypter.algorithm().name();
+                    if val != expected_alg {
+                        return Ok(None);
+                    }
+                }
+                _ => return Ok(None),
+            }
+
+            if let Some(expected) = decrypter.key_id() {
+                match header.key_id() {
+                    Some(actual) if expected == actual => {}
+                    _ => return Ok(None),
+                }
+            }
+
+            Ok(Some(decrypter))
+        })
+    }
+
     /// Deserialize the input that is formatted by json serialization.
     ///
     /// # Arguments
@@ -1006,6 +1098,18 @@ impl JweContext {
         input: impl AsRef<[u8]>,
         selector: F,
     ) -> Result<(Vec<u8>, JweHeader), JoseError>
+    where
+        F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
+    {
+        self.deserialize_json_with_aad_and_selector(input, None, selector)
+    }
+
+    fn deserialize_json_with_aad_and_selector<'a, F>(
+        &self,
+        input: impl AsRef<[u8]>,
+        expected_aad: Option<Option<&[u8]>>,
+        selector: F,
+    ) -> Result<(Vec<u8>, JweHeader), JoseError>
     where
         F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
     {
@@ -1018,8 +1122,8 @@ impl JweContext {
                     if val.len() == 0 {
                         bail!("The protected field must be empty.");
                     }
-                    let vec = util::decode_base64_urlsafe_no_pad(&val)?;
-                    let json: Map<String, Value> = serde_json::from_slice(&vec)?;
+                    let vec = self.decode_base64(&val)?;
+                    let json = self.parse_json_object(&vec)?;
                     (Some(json), Some(val))
                 }
                 Some(_) => bail!("The protected field must be a string."),
@@ -1047,13 +1151,19 @@ impl JweContext {
                 Some(_) => bail!("The JWE aad field must be string."),
                 None => None,
             };
+            if let Some(expected_aad) = expected_aad {
+                let expected_aad_b64 = expected_aad.map(util::encode_base64_urlsafe_nopad);
+                if expected_aad_b64 != aad_b64 {
+                    bail!("The JWE aad field is mismatched.");
+                }
+            }
             let iv_vec;
             let iv = match map.remove("iv") {
                 Some(Value::String(val)) => {
                     if val.len() == 0 {
                         bail!("The iv field must be empty.");
                     }
-                    iv_vec = util::decode_base64_urlsafe_no_pad(&val)?;
+                    iv_vec = self.decode_base64(&val)?;
                     Some(iv_vec.as_slice())
                 }
                 Some(_) => bail!("The iv field must be string."),
@@ -1064,7 +1174,7 @@ impl JweContext {
                     if val.len() == 0 {
                         bail!("The ciphertext field must be empty.");
                     }
-                    util::decode_base64_urlsafe_no_pad(&val)?
+                    self.decode_base64(&val)?
                 }
                 Some(_) => bail!("The ciphertext field must be string."),
                 None => bail!("The ciphertext field is required."),
@@ -1075,7 +1185,7 @@ impl JweContext {
                     if val.len() == 0 {
                         bail!("The tag field must be empty.");
                     }
-                    tag_vec = util::decode_base64_urlsafe_no_pad(&val)?;
+                    tag_vec = self.decode_base64(&val)?;
                     Some(tag_vec.as_slice())
                 }
                 Some(_) => bail!("The tag field must be string."),
@@ -1105,6 +1215,15 @@ impl JweContext {
                 }
             };
 
+            let protected_header = match &protected {
+                Some(val) => Some(JweHeader::from_map(val.clone())?),
+                None => None,
+            };
+            let unprotected_header = match &unprotected {
+                Some(val) => Some(JweHeader::from_map(val.clone())?),
+                None => None,
+            };
+
             for mut recipient in recipients {
                 let header = recipient.remove("header");
 
@@ -1114,7 +1233,7 @@ impl JweContext {
                         if val.len() == 0 {
                             bail!("The encrypted_key field must be empty.");
                         }
-                        encrypted_key_vec = util::decode_base64_urlsafe_no_pad(&val)?;
+                        encrypted_key_vec = self.decode_base64(val)?;
                         Some(encrypted_key_vec.as_slice())
                     }
                     Some(_) => bail!("The encrypted_key field must be a string."),
@@ -1122,46 +1241,24 @@ impl JweContext {
                 };
 
                 let mut merged = match header {
-                    Some(Value::Object(val)) => val,
+                    Some(Value::Object(val)) => JweHeader::from_map(val)?,
                     Some(_) => bail!("The protected field must be a object."),
-                    None => Map::new(),
+                    None => JweHeader::new(),
                 };
-
-                if let Some(val) = &unprotected {
-                    for (key, value) in val {
-                        if merged.contains_key(key) {
-                            bail!("A duplicate key exists: {}", key);
-                        } else {
-                            merged.insert(key.clone(), value.clone());
-                        }
-                    }
+                if let Some(val) = &unprotected_header {
+                    merged = merged.merged_with(val)?;
                 }
-
-                if let Some(val) = &protected {
-                    for (key, value) in val {
-                        if merged.contains_key(key) {
-                            bail!("A duplicate key exists: {}", key);
-                        } else {
-                            merged.insert(key.clone(), value.clone());
-                        }
-                    }
+                if let Some(val) = &protected_header {
+                    merged = merged.merged_with(val)?;
                 }
-
-                let merged = JweHeader::from_map(merged)?;
+                self.check_type(&merged)?;
 
                 let decrypter = match selector(&merged)? {
                     Some(val) => val,
                     None => continue,
                 };
 
-                let cencryption = match merged.claim("enc") {
-                    Some(Value::String(val)) => match self.get_content_encryption(val) {
-                        Some(val2) => val2,
-                        None => bail!("A content encryption is not registered: {}", val),
-                    },
-                    Some(_) => bail!("A enc header claim must be string."),
-                    None => bail!("A enc header claim is required."),
-                };
+                let cencryption = self.content_encryption_for(&merged)?;
 
                 let compression = match merged.claim("zip") {
                     Some(Value::String(val)) => match self.get_compression(val) {
@@ -1200,7 +1297,12 @@ impl JweContext {
                     full_aad.push_str(&val);
                 }
 
-                let key = decrypter.decrypt(encrypted_key, cencryption, &merged)?;
+                let key = decrypter.decrypt_with_options(
+                    encrypted_key,
+                    cencryption,
+                    &merged,
+                    &self.decryption_options,
+                )?;
                 if key.len() != cencryption.key_len() {
                     bail!(
                         "The key size is expected to be {}: {}",
@@ -1226,15 +1328,86 @@ impl JweContext {
             Err(err) => JoseError::InvalidJweFormat(err),
         })
     }
+
+    fn content_encryption_for(
+        &self,
+        header: &JweHeader,
+    ) -> anyhow::Result<&dyn JweContentEncryption> {
+        let cencryption = match header.content_encryption_typed() {
+            Some(enc) => self.get_content_encryption(enc.name()),
+            // A custom content encryption that is added to this context.
+            None => match header.claim("enc") {
+                Some(Value::String(enc)) => self.get_content_encryption(enc),
+                Some(_) => bail!("A enc header claim must be a string."),
+                None => bail!("A enc header claim is required."),
+            },
+        };
+        match cencryption {
+            Some(val) => Ok(val),
+            None => bail!(
+                "A content encryption is not registered: {}",
+                header.content_encryption().unwrap_or_default()
+            ),
+        }
+    }
+
+    fn check_type(&self, header: &JweHeader) -> anyhow::Result<()> {
+        if self.expected_types.is_some() {
+            match header.token_type() {
+                Some(val) if self.is_expected_type(val) => {}
+                Some(val) => bail!("The JWE typ header claim is not expected: {}", val),
+                None => bail!("The JWE typ header claim is required."),
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_json_object(&self, input: &[u8]) -> anyhow::Result<Map<String, Value>> {
+        if self.decryption_options.is_strict_json() {
+            util::parse_json_object_strict(input)
+        } else {
+            Ok(serde_json::from_slice(input)?)
+        }
+    }
+
+    fn decode_base64(&self, input: impl AsRef<[u8]>) -> Result<Vec<u8>, base64::DecodeError> {
+        if self.decryption_options.is_base64_padding_tolerant() {
+            util::decode_base64_urlsafe_padding_tolerant(input)
+        } else {
+            util::decode_base64_urlsafe_no_pad(input)
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::jwe::{
-        alg::direct::DirectJweAlgorithm, deserialize_compact, deserialize_json, serialize_compact,
-        serialize_flattened_json, serialize_general_json, JweHeader, JweHeaderSet,
+        alg::aesgcmkw::AesgcmkwJweAlgorithm, alg::direct::DirectJweAlgorithm, deserialize_compact,
+        deserialize_json, enc::aescbc_hmac::AescbcHmacJweEncryption,
+        enc::aesgcm::AesgcmJweEncryption, serialize_compact, serialize_flattened_json,
+        serialize_general_json, JweContentEncryption, JweContext, JweDecryptionOptions, JweHeader,
+        JweHeaderSet, PBES2_HS256_A128KW,
     };
+    use crate::util::{self, RandomSource};
+    use crate::{JoseError, Map, Value};
     use anyhow::Result;
+    use std::sync::atomic::{AtomicU8, Ordering};
+
+    #[derive(Debug)]
+    struct CounterRand(AtomicU8);
+
+    impl RandomSource for CounterRand {
+        fn fill(&self, buf: &mut [u8]) -> Result<(), JoseError> {
+            for b in buf.iter_mut() {
+                *b = self.0.fetch_add(1, Ordering::SeqCst);
+            }
+            Ok(())
+        }
+
+        fn box_clone(&self) -> Box<dyn RandomSource> {
+            Box::new(CounterRand(AtomicU8::new(self.0.load(Ordering::SeqCst))))
+        }
+    }
 
     const CONTENT_CIPHERS: [(&str, usize); 6] = [
         ("A128CBC-HS256", 32),
@@ -1301,4 +1474,249 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn compact_with_deterministic_random_source() -> Result<()> {
+        let payload = b"hello world";
+        let key = vec![0; 16];
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128GCM");
+
+        let mut jwes = Vec::new();
+        for _ in 0..2 {
+            let mut context = JweContext::new();
+            context.set_random_source(Box::new(CounterRand(AtomicU8::new(0))));
+            assert_eq!(context, context.clone());
+
+            let mut encrypter = AesgcmkwJweAlgorithm::A128gcmkw.encrypter_from_bytes(&key)?;
+            encrypter.set_random_source(Box::new(CounterRand(AtomicU8::new(0x80))));
+
+            let jwe = context.serialize_compact(payload, &header, &encrypter)?;
+            jwes.push(jwe);
+        }
+        assert_eq!(jwes[0], jwes[1]);
+        assert_eq!(jwes[0], "eyJlbmMiOiJBMTI4R0NNIiwiaXYiOiJnSUdDZzRTRmhvZUlpWXFMIiwidGFnIjoiOG0tSU02QmlFb1M5emZ0eERyUTZmdyIsImFsZyI6IkExMjhHQ01LVyJ9.tLt0uut-bTVe8VqbqAxDFQ.EBESExQVFhcYGRob.rEtvw2BvwYBlsTk.O-HeBeHx2JThHAVFDS4Rgw");
+
+        let decrypter = AesgcmkwJweAlgorithm::A128gcmkw.decrypter_from_bytes(&key)?;
+        let (data, _header) = deserialize_compact(&jwes[0], &decrypter)?;
+        assert_eq!(data, payload);
+
+        Ok(())
+    }
+
+    #[test]
+    fn compact_gcm_generates_fresh_iv() -> Result<()> {
+        let payload = b"hello world";
+        let key = vec![0; 32];
+        let alg = DirectJweAlgorithm::Dir;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A256GCM");
+
+        let context = JweContext::new();
+        let encrypter = alg.encrypter_from_bytes(&key)?;
+        let jwe1 = context.serialize_compact(payload, &header, &encrypter)?;
+        let jwe2 = context.serialize_compact(payload, &header, &encrypter)?;
+
+        let iv1 = jwe1.split('.').nth(2).unwrap();
+        let iv2 = jwe2.split('.').nth(2).unwrap();
+        assert_eq!(iv1.len(), 16);
+        assert_ne!(iv1, iv2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn compact_iv_length_matches_content_encryption() -> Result<()> {
+        let payload = b"hello world";
+        let alg = DirectJweAlgorithm::Dir;
+        let context = JweContext::new();
+
+        let encs: Vec<(Box<dyn JweContentEncryption>, usize)> = vec![
+            (Box::new(AescbcHmacJweEncryption::A128cbcHs256), 16),
+            (Box::new(AescbcHmacJweEncryption::A192cbcHs384), 16),
+            (Box::new(AescbcHmacJweEncryption::A256cbcHs512), 16),
+            (Box::new(AesgcmJweEncryption::A128gcm), 12),
+            (Box::new(AesgcmJweEncryption::A192gcm), 12),
+            (Box::new(AesgcmJweEncryption::A256gcm), 12),
+        ];
+        for (enc, iv_len) in encs {
+            let mut header = JweHeader::new();
+            header.set_content_encryption(enc.name());
+
+            let key = util::random_bytes(enc.key_len())?;
+            let encrypter = alg.encrypter_from_bytes(&key)?;
+            let jwe = context.serialize_compact(payload, &header, &encrypter)?;
+
+            let iv = util::decode_base64_urlsafe_no_pad(jwe.split('.').nth(2).unwrap())?;
+            assert_eq!(iv.len(), iv_len, "{}", enc.name());
+
+            let decrypter = alg.decrypter_from_bytes(&key)?;
+            let (data, _) = context.deserialize_compact(&jwe, &decrypter)?;
+            assert_eq!(data, payload);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn compact_with_wrong_segment_count() -> Result<()> {
+        let key = vec![0; 32];
+        let alg = DirectJweAlgorithm::Dir;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A256GCM");
+
+        let context = JweContext::new();
+        let jwe =
+            context.serialize_compact(b"hello world", &header, &alg.encrypter_from_bytes(&key)?)?;
+        let decrypter = alg.decrypter_from_bytes(&key)?;
+
+        let parts: Vec<&str> = jwe.split('.').collect();
+        for (input, count) in [(parts[..3].join("."), 3), (format!("{}.AA", jwe), 6)] {
+            match context.deserialize_compact(&input, &decrypter) {
+                Err(JoseError::InvalidJweFormat(err)) => assert_eq!(
+                    err.to_string(),
+                    format!("The compact serialization form of JWE must be five parts separated by period: {} parts", count)
+                ),
+                res => panic!("{:?}", res),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn compact_with_base64_padding() -> Result<()> {
+        let key = vec![0; 32];
+        let alg = DirectJweAlgorithm::Dir;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A256GCM");
+
+        let context = JweContext::new();
+        let jwe =
+            context.serialize_compact(b"hello world", &header, &alg.encrypter_from_bytes(&key)?)?;
+        let decrypter = alg.decrypter_from_bytes(&key)?;
+
+        // The protected header is kept as is because it is the additional authenticated data.
+        let mut parts: Vec<String> = jwe.split('.').map(|val| val.to_string()).collect();
+        for part in parts.iter_mut().skip(2) {
+            let len = (4 - part.len() % 4) % 4;
+            part.push_str(&"=".repeat(len));
+        }
+        let padded = parts.join(".");
+        assert_ne!(padded, jwe);
+
+        assert!(matches!(
+            context.deserialize_compact(&padded, &decrypter),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        let mut context = JweContext::new();
+        let mut options = JweDecryptionOptions::new();
+        options.set_base64_padding_tolerant(true);
+        context.set_decryption_options(options);
+        let (payload, _) = context.deserialize_compact(&padded, &decrypter)?;
+        assert_eq!(payload, b"hello world");
+
+        Ok(())
+    }
+
+    #[test]
+    fn flattened_json_with_aad() -> Result<()> {
+        let payload = b"hello world";
+        let aad = b"additional data";
+        let key = vec![0; 32];
+        let alg = DirectJweAlgorithm::Dir;
+
+        let mut hs = JweHeaderSet::new();
+        hs.set_content_encryption("A128CBC-HS256", true);
+
+        let context = JweContext::new();
+        let encrypter = alg.encrypter_from_bytes(&key)?;
+        let decrypter = alg.decrypter_from_bytes(&key)?;
+        let jwe =
+            context.serialize_flattened_json(payload, Some(&hs), None, Some(aad), &encrypter)?;
+
+        let (data, _) = context.deserialize_json(&jwe, &decrypter)?;
+        assert_eq!(data, payload);
+        let (data, _) = context.deserialize_json_with_aad(&jwe, Some(aad), &decrypter)?;
+        assert_eq!(data, payload);
+
+        assert!(context
+            .deserialize_json_with_aad(&jwe, Some(b"other data"), &decrypter)
+            .is_err());
+        assert!(context
+            .deserialize_json_with_aad(&jwe, None, &decrypter)
+            .is_err());
+
+        let mut map: Map<String, Value> = serde_json::from_str(&jwe)?;
+        map.insert(
+            "aad".to_string(),
+            Value::String(util::encode_base64_urlsafe_nopad(b"other data")),
+        );
+        let tampered = serde_json::to_string(&map)?;
+        assert!(context.deserialize_json(&tampered, &decrypter).is_err());
+
+        map.remove("aad");
+        let removed = serde_json::to_string(&map)?;
+        assert!(context.deserialize_json(&removed, &decrypter).is_err());
+
+        let jwe = context.serialize_flattened_json(payload, Some(&hs), None, None, &encrypter)?;
+        let (data, _) = context.deserialize_json_with_aad(&jwe, None, &decrypter)?;
+        assert_eq!(data, payload);
+        assert!(context
+            .deserialize_json_with_aad(&jwe, Some(aad), &decrypter)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn compact_rejects_too_large_p2c() -> Result<()> {
+        let payload = b"hello world";
+        let key = b"password";
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128CBC-HS256");
+
+        let encrypter = PBES2_HS256_A128KW.encrypter_from_bytes(key)?;
+        let decrypter = PBES2_HS256_A128KW.decrypter_from_bytes(key)?;
+        let jwe = serialize_compact(payload, &header, &encrypter)?;
+        let (data, _) = deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(data, payload);
+
+        let parts: Vec<&str> = jwe.split('.').collect();
+        let mut map: Map<String, Value> =
+            serde_json::from_slice(&util::decode_base64_urlsafe_no_pad(parts[0])?)?;
+        map.insert("p2c".to_string(), Value::from(10_000_000));
+        let header_b64 = util::encode_base64_urlsafe_nopad(serde_json::to_vec(&map)?);
+        let crafted = format!("{}.{}", header_b64, parts[1..].join("."));
+
+        assert!(matches!(
+            deserialize_compact(&crafted, &decrypter),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        let mut context = JweContext::new();
+        let mut options = JweDecryptionOptions::new();
+        options.set_max_pbes2_iterations(999);
+        context.set_decryption_options(options);
+        assert!(matches!(
+            context.deserialize_compact(&jwe, &decrypter),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        let mut options = JweDecryptionOptions::new();
+        options.set_max_key_len(16);
+        context.set_decryption_options(options);
+        assert!(matches!(
+            context.deserialize_compact(&jwe, &decrypter),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,88 @@
+/// Represents limits that are enforced while decrypting a JWE.
+///
+/// The limits are checked before expensive operations like key derivation,
+/// so that a crafted header cannot make a decrypter do unbounded work.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct JweDecryptionOptions {
+    max_pbes2_iterations: usize,
+    max_key_len: usize,
+    base64_padding_tolerant: bool,
+    strict_json: bool,
+}
+
+impl JweDecryptionOptions {
+    pub fn new() -> Self {
+        Self {
+            max_pbes2_iterations: 1_000_000,
+            max_key_len: 128,
+            base64_padding_tolerant: false,
+            strict_json: true,
+        }
+    }
+
+    /// Return the maximum value of the p2c header claim.
+    pub fn max_pbes2_iterations(&self) -> usize {
+        self.max_pbes2_iterations
+    }
+
+    /// Set the maximum value of the p2c header claim.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The maximum iteration count of PBES2.
+    pub fn set_max_pbes2_iterations(&mut self, value: usize) {
+        self.max_pbes2_iterations = value;
+    }
+
+    /// Return the maximum length in bytes of a content encryption key or a derived key.
+    pub fn max_key_len(&self) -> usize {
+        self.max_key_len
+    }
+
+    /// Set the maximum length in bytes of a content encryption key or a derived key.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The maximum key length.
+    pub fn set_max_key_len(&mut self, value: usize) {
+        self.max_key_len = value;
+    }
+
+    /// Test the "=" padding of base64url encoded parts is accepted.
+    pub fn is_base64_padding_tolerant(&self) -> bool {
+        self.base64_padding_tolerant
+    }
+
+    /// Set whether the "=" padding of base64url encoded parts is accepted.
+    ///
+    /// JOSE doesn't allow padding, so it is rejected by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - true to accept padded parts
+    pub fn set_base64_padding_tolerant(&mut self, value: bool) {
+        self.base64_padding_tolerant = value;
+    }
+
+    /// Test duplicate member names in a decoded protected header are rejected.
+    pub fn is_strict_json(&self) -> bool {
+        self.strict_json
+    }
+
+    /// Set whether duplicate member names in a decoded protected header are rejected.
+    ///
+    /// They are rejected by default. When disabled, the last value is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - false to accept duplicate member names
+    pub fn set_strict_json(&mut self, value: bool) {
+        self.strict_json = value;
+    }
+}
+
+impl Default for JweDecryptionOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -3,8 +3,11 @@ use std::convert::Into;
 use std::fmt::{Debug, Display};
 use std::ops::Deref;
 
-use anyhow::bail;
+use anyhow::{anyhow, bail};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
+use crate::jose_header::{merge_claims, sorted_claims};
+use crate::jwe::ContentEncryptionAlgorithm;
 use crate::jwk::Jwk;
 use crate::util;
 use crate::{JoseError, JoseHeader, Map, Value};
@@ -87,6 +90,13 @@ impl JweHeader {
         }
     }
 
+    /// Return the typed value for content encryption header claim (enc).
+    ///
+    /// This returns None if the value is not a registered algorithm.
+    pub fn content_encryption_typed(&self) -> Option<ContentEncryptionAlgorithm> {
+        ContentEncryptionAlgorithm::from_name(self.content_encryption()?)
+    }
+
     /// Set a value for compression header claim (zip).
     ///
     /// # Arguments
@@ -259,6 +269,23 @@ impl JweHeader {
         }
     }
 
+    /// Return a raw value for key ID header claim (kid).
+    pub fn key_id_value(&self) -> Option<&Value> {
+        self.claims.get("kid")
+    }
+
+    /// Return the value for key ID header claim (kid) or a error when it is not a string.
+    pub fn key_id_strict(&self) -> Result<Option<&str>, JoseError> {
+        match self.claims.get("kid") {
+            Some(Value::String(val)) => Ok(Some(val)),
+            Some(val) => Err(JoseError::InvalidJweFormat(anyhow::anyhow!(
+                "The JWE kid header claim must be string: {}",
+                val
+            ))),
+            None => Ok(None),
+        }
+    }
+
     /// Set a value for token type header claim (typ).
     ///
     /// # Arguments
@@ -277,6 +304,13 @@ impl JweHeader {
         }
     }
 
+    /// Remove the token type header claim (typ).
+    ///
+    /// Some profiles forbid the typ header claim, so it can be omitted explicitly.
+    pub fn remove_token_type(&mut self) {
+        self.claims.remove("typ");
+    }
+
     /// Set a value for content type header claim (cty).
     ///
     /// # Arguments
@@ -410,6 +444,38 @@ impl JweHeader {
         }
     }
 
+    /// Return the value for ephemeral public key header claim (epk).
+    ///
+    /// The value is parsed as a public key of EC or OKP key type.
+    pub fn ephemeral_public_key(&self) -> Option<Result<Jwk, JoseError>> {
+        let map = match self.claims.get("epk") {
+            Some(Value::Object(val)) => val,
+            Some(_) => {
+                return Some(Err(JoseError::InvalidJweFormat(anyhow!(
+                    "The epk header claim must be object."
+                ))))
+            }
+            None => return None,
+        };
+
+        let jwk = (|| -> anyhow::Result<Jwk> {
+            let jwk = Jwk::from_map(map.clone())?;
+            match jwk.key_type() {
+                "EC" | "OKP" => {}
+                val => bail!("The kty parameter in epk header claim is invalid: {}", val),
+            }
+            if jwk.parameter("d").is_some() {
+                bail!("The epk header claim must not have a private key.");
+            }
+            Ok(jwk)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJweFormat(err),
+        });
+        Some(jwk)
+    }
+
     /// Set a value for issuer header claim (iss).
     ///
     /// # Arguments
@@ -511,6 +577,32 @@ impl JweHeader {
         Ok(())
     }
 
+    /// Remove a header claim of a specified key and return the removed value.
+    ///
+    /// The required header claims and the header claims that are listed in
+    /// the crit header claim cannot be removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - a key name of header claim
+    pub fn remove_claim(&mut self, key: &str) -> Result<Option<Value>, JoseError> {
+        (|| -> anyhow::Result<Option<Value>> {
+            if matches!(key, "alg" | "enc") {
+                bail!("The JWE {} header claim is required.", key);
+            }
+            if let Some(vals) = self.critical() {
+                if vals.contains(&key) {
+                    bail!(
+                        "The JWE {} header claim is listed in the crit header claim.",
+                        key
+                    );
+                }
+            }
+            Ok(self.claims.remove(key))
+        })()
+        .map_err(JoseError::InvalidJweFormat)
+    }
+
     /// Return values for header claims set
     pub fn claims_set(&self) -> &Map<String, Value> {
         &self.claims
@@ -521,10 +613,22 @@ impl JweHeader {
         self.claims
     }
 
+    /// Return the union of the header claims and the other header claims.
+    ///
+    /// It is an error that a header claim name exists in both headers.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other header claims.
+    pub fn merged_with(&self, other: &dyn JoseHeader) -> Result<Self, JoseError> {
+        let claims = merge_claims(&self.claims, other).map_err(JoseError::InvalidJweFormat)?;
+        Self::from_map(claims)
+    }
+
     pub(crate) fn check_claim(key: &str, value: &Value) -> Result<(), JoseError> {
         (|| -> anyhow::Result<()> {
             match key {
-                "alg" | "enc" | "zip" | "jku" | "x5u" | "kid" | "typ" | "cty" | "url" | "iss"
+                "alg" | "enc" | "zip" | "jku" | "x5u" | "typ" | "cty" | "url" | "iss"
                 | "sub" => match &value {
                     Value::String(_) => {}
                     _ => bail!("The JWE {} header claim must be string.", key),
@@ -558,7 +662,7 @@ impl JweHeader {
                     }
                     _ => bail!("The JWE {} header claim must be a array.", key),
                 },
-                "x5t" | "x5t#S256" | "nonce" | "apu" | "apv" => match &value {
+                "x5t" | "x5t#S256" | "nonce" | "apu" | "apv" | "iv" | "tag" | "p2s" => match &value {
                     Value::String(val) => {
                         if !util::is_base64_urlsafe_nopad(val) {
                             bail!("The JWE {} header claim must be a base64 string.", key);
@@ -591,6 +695,62 @@ impl JweHeader {
                     Value::Object(vals) => Jwk::check_map(vals)?,
                     _ => bail!("The JWE {} header claim must be a string.", key),
                 },
+                "epk" => match &value {
+                    Value::Object(vals) => {
+                        let names: &[&str] = match vals.get("kty") {
+                            Some(Value::String(val)) if val == "EC" => &["crv", "x", "y"],
+                            Some(Value::String(val)) if val == "OKP" => &["crv", "x"],
+                            Some(Value::String(val)) => bail!(
+                                "The kty parameter in JWE {} header claim is invalid: {}",
+                                key,
+                                val
+                            ),
+                            Some(_) => bail!(
+                                "The kty parameter in JWE {} header claim must be a string.",
+                                key
+                            ),
+                            None => bail!(
+                                "The kty parameter in JWE {} header claim is required.",
+                                key
+                            ),
+                        };
+                        for name in names {
+                            match vals.get(*name) {
+                                Some(Value::String(val)) => {
+                                    if *name != "crv" && !util::is_base64_urlsafe_nopad(val) {
+                                        bail!(
+                                            "The {} parameter in JWE {} header claim must be a base64 string.",
+                                            name,
+                                            key
+                                        );
+                                    }
+                                }
+                                Some(_) => bail!(
+                                    "The {} parameter in JWE {} header claim must be a string.",
+                                    name,
+                                    key
+                                ),
+                                None => bail!(
+                                    "The {} parameter in JWE {} header claim is required.",
+                                    name,
+                                    key
+                                ),
+                            }
+                        }
+                        if vals.contains_key("d") {
+                            bail!("The JWE {} header claim must not have a private key.", key);
+                        }
+                        Jwk::check_map(vals)?;
+                    }
+                    _ => bail!("The JWE {} header claim must be a object.", key),
+                },
+                "p2c" => match &value {
+                    Value::Number(val) => match val.as_u64() {
+                        Some(val) if val > 0 => {}
+                        _ => bail!("The JWE {} header claim must be a positive integer.", key),
+                    },
+                    _ => bail!("The JWE {} header claim must be a number.", key),
+                },
                 _ => {}
             }
 
@@ -609,6 +769,10 @@ impl JoseHeader for JweHeader {
         self.claims.get(key)
     }
 
+    fn claims_iter<'a>(&'a self) -> Box<dyn Iterator<Item = (&'a str, &'a Value)> + 'a> {
+        sorted_claims(self.claims.iter())
+    }
+
     fn box_clone(&self) -> Box<dyn JoseHeader> {
         Box::new(self.clone())
     }
@@ -626,6 +790,19 @@ impl Into<Map<String, Value>> for JweHeader {
     }
 }
 
+impl Serialize for JweHeader {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.claims.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for JweHeader {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let map = Map::<String, Value>::deserialize(deserializer)?;
+        Self::from_map(map).map_err(de::Error::custom)
+    }
+}
+
 impl Display for JweHeader {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         let val = serde_json::to_string(&self.claims).map_err(|_e| std::fmt::Error {})?;
@@ -648,7 +825,57 @@ mod tests {
 
     use crate::jwe::JweHeader;
     use crate::jwk::Jwk;
-    use crate::{Map, Value};
+    use crate::{JoseError, JoseHeader, Map, Value};
+
+    #[test]
+    fn test_remove_claim() -> Result<()> {
+        let mut header = JweHeader::new();
+        header.set_algorithm("dir");
+        header.set_content_encryption("A128GCM");
+        header.set_claim("custom", Some(json!("value")))?;
+
+        assert!(header.contains_claim("custom"));
+        assert_eq!(header.remove_claim("custom")?, Some(json!("value")));
+        assert!(!header.contains_claim("custom"));
+
+        for key in ["alg", "enc"] {
+            assert!(matches!(
+                header.remove_claim(key),
+                Err(JoseError::InvalidJweFormat(_))
+            ));
+            assert!(header.contains_claim(key));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_string_key_id() -> Result<()> {
+        let mut header = JweHeader::new();
+        header.set_claim("kid", Some(json!(123)))?;
+        assert_eq!(header.key_id(), None);
+        assert_eq!(header.key_id_value(), Some(&json!(123)));
+        assert!(matches!(
+            header.key_id_strict(),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_serde_jwe_header() -> Result<()> {
+        let mut header = JweHeader::new();
+        header.set_algorithm("alg");
+        header.set_claim("custom", Some(json!("value")))?;
+
+        let value = serde_json::to_value(&header)?;
+        assert_eq!(value, json!({ "alg": "alg", "custom": "value" }));
+        let decoded: JweHeader = serde_json::from_value(value)?;
+        assert_eq!(decoded, header);
+
+        assert!(serde_json::from_value::<JweHeader>(json!("alg")).is_err());
+        assert!(serde_json::from_value::<JweHeader>(json!({ "alg": 1 })).is_err());
+        Ok(())
+    }
 
     #[test]
     fn test_new_jwe_header() -> Result<()> {
@@ -734,4 +961,127 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_ephemeral_public_key() -> Result<()> {
+        let mut header = JweHeader::new();
+        assert!(header.ephemeral_public_key().is_none());
+
+        header.set_claim(
+            "epk",
+            Some(json!({
+                "kty": "EC",
+                "crv": "P-256",
+                "x": "gI0GAILBdu7T53akrFmMyGcsF3n5dO7MmwNBHKW5SV0",
+                "y": "SLW_xSffzlPWrHEVI30DHM_4egVwt3NQqeUD7nMFpps",
+            })),
+        )?;
+        let epk = header.ephemeral_public_key().unwrap()?;
+        assert_eq!(epk.key_type(), "EC");
+        assert_eq!(epk.curve(), Some("P-256"));
+        assert_eq!(
+            epk.parameter("x"),
+            Some(&json!("gI0GAILBdu7T53akrFmMyGcsF3n5dO7MmwNBHKW5SV0"))
+        );
+        assert_eq!(
+            epk.parameter("y"),
+            Some(&json!("SLW_xSffzlPWrHEVI30DHM_4egVwt3NQqeUD7nMFpps"))
+        );
+        assert!(epk.parameter("d").is_none());
+
+        let mut map = Map::new();
+        map.insert("epk".to_string(), json!({"kty": "oct", "k": "AQID"}));
+        assert!(JweHeader::from_map(map).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_reserved_claim() -> Result<()> {
+        let mut header = JweHeader::new();
+        header.set_claim(
+            "epk",
+            Some(json!({
+                "kty": "EC",
+                "crv": "P-256",
+                "x": "f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU",
+                "y": "x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0",
+            })),
+        )?;
+        header.set_claim(
+            "epk",
+            Some(json!({"kty": "OKP", "crv": "X25519", "x": "AQID"})),
+        )?;
+        header.set_claim("iv", Some(json!("AQID")))?;
+        header.set_claim("tag", Some(json!("AQID")))?;
+        header.set_claim("p2s", Some(json!("AQID")))?;
+        header.set_claim("p2c", Some(json!(1000)))?;
+        header.set_claim("x-private", Some(json!({"iv": 1})))?;
+
+        for (key, value) in vec![
+            ("epk", json!("AQID")),
+            ("epk", json!({"crv": "P-256", "x": "AQID", "y": "AQID"})),
+            ("epk", json!({"kty": "RSA", "n": "AQID", "e": "AQAB"})),
+            ("epk", json!({"kty": "EC", "crv": "P-256", "x": "AQID"})),
+            (
+                "epk",
+                json!({"kty": "EC", "crv": "P-256", "x": "AQID", "y": 1}),
+            ),
+            ("epk", json!({"kty": "OKP", "crv": "X25519", "x": "+/+/"})),
+            (
+                "epk",
+                json!({"kty": "OKP", "crv": "X25519", "x": "AQID", "d": "AQID"}),
+            ),
+            ("iv", json!(1)),
+            ("iv", json!("+/+/")),
+            ("tag", json!(["AQID"])),
+            ("tag", json!("AQID=")),
+            ("apu", json!("+/+/")),
+            ("apv", json!(true)),
+            ("p2s", json!({})),
+            ("p2c", json!(0)),
+            ("p2c", json!(-1)),
+            ("p2c", json!(1.5)),
+            ("p2c", json!("1000")),
+            ("zip", json!(1)),
+        ] {
+            assert!(
+                matches!(
+                    header.set_claim(key, Some(value.clone())),
+                    Err(JoseError::InvalidJweFormat(_))
+                ),
+                "{}: {}",
+                key,
+                value
+            );
+        }
+
+        let mut map = Map::new();
+        map.insert("p2c".to_string(), json!(0));
+        assert!(JweHeader::from_map(map).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merged_with() -> Result<()> {
+        let mut protected = JweHeader::new();
+        protected.set_content_encryption("A128GCM");
+        let mut unprotected = JweHeader::new();
+        unprotected.set_algorithm("A128KW");
+        unprotected.set_key_id("key-1");
+
+        let merged = unprotected.merged_with(&protected)?;
+        assert_eq!(merged.content_encryption(), Some("A128GCM"));
+        assert_eq!(merged.algorithm(), Some("A128KW"));
+        assert_eq!(merged.key_id(), Some("key-1"));
+
+        protected.set_key_id("key-2");
+        assert!(matches!(
+            unprotected.merged_with(&protected),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        Ok(())
+    }
 }
@@ -1,6 +1,7 @@
 use std::fmt::{Debug, Display};
 use std::ops::Deref;
 
+use crate::jose_header::sorted_claims;
 use crate::jwe::JweHeader;
 use crate::jwk::Jwk;
 use crate::{util, JoseError, JoseHeader, Map, Value};
@@ -669,6 +670,16 @@ impl JoseHeader for JweHeaderSet {
         }
     }
 
+    fn claims_iter<'a>(&'a self) -> Box<dyn Iterator<Item = (&'a str, &'a Value)> + 'a> {
+        sorted_claims(
+            self.protected.iter().chain(
+                self.unprotected
+                    .iter()
+                    .filter(|(key, _)| !self.protected.contains_key(key.as_str())),
+            ),
+        )
+    }
+
     fn box_clone(&self) -> Box<dyn JoseHeader> {
         Box::new(self.clone())
     }
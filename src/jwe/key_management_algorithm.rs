@@ -0,0 +1,161 @@
+use crate::jwe::alg::aesgcmkw::AesgcmkwJweAlgorithm;
+use crate::jwe::alg::aeskw::AeskwJweAlgorithm;
+use crate::jwe::alg::direct::DirectJweAlgorithm;
+use crate::jwe::alg::ecdh_es::EcdhEsJweAlgorithm;
+use crate::jwe::alg::pbes2_hmac_aeskw::Pbes2HmacAeskwJweAlgorithm;
+use crate::jwe::alg::rsaes::RsaesJweAlgorithm;
+use crate::jwe::{JweAlgorithm, JweDecrypter, JweEncrypter};
+use crate::jwk::Jwk;
+use crate::JoseError;
+
+/// Represent a key management algorithm that is identified by a JWE alg header claim value.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum KeyManagementAlgorithm {
+    Direct(DirectJweAlgorithm),
+    EcdhEs(EcdhEsJweAlgorithm),
+    Aeskw(AeskwJweAlgorithm),
+    Aesgcmkw(AesgcmkwJweAlgorithm),
+    Pbes2HmacAeskw(Pbes2HmacAeskwJweAlgorithm),
+    Rsaes(RsaesJweAlgorithm),
+}
+
+impl KeyManagementAlgorithm {
+    /// Return the key management algorithm for a alg header claim value.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a alg header claim value
+    #[allow(deprecated)]
+    pub fn from_name(name: &str) -> Option<Self> {
+        let alg = match name {
+            "dir" => Self::Direct(DirectJweAlgorithm::Dir),
+            "ECDH-ES" => Self::EcdhEs(EcdhEsJweAlgorithm::EcdhEs),
+            "ECDH-ES+A128KW" => Self::EcdhEs(EcdhEsJweAlgorithm::EcdhEsA128kw),
+            "ECDH-ES+A192KW" => Self::EcdhEs(EcdhEsJweAlgorithm::EcdhEsA192kw),
+            "ECDH-ES+A256KW" => Self::EcdhEs(EcdhEsJweAlgorithm::EcdhEsA256kw),
+            "A128KW" => Self::Aeskw(AeskwJweAlgorithm::A128kw),
+            "A192KW" => Self::Aeskw(AeskwJweAlgorithm::A192kw),
+            "A256KW" => Self::Aeskw(AeskwJweAlgorithm::A256kw),
+            "A128GCMKW" => Self::Aesgcmkw(AesgcmkwJweAlgorithm::A128gcmkw),
+            "A192GCMKW" => Self::Aesgcmkw(AesgcmkwJweAlgorithm::A192gcmkw),
+            "A256GCMKW" => Self::Aesgcmkw(AesgcmkwJweAlgorithm::A256gcmkw),
+            "PBES2-HS256+A128KW" => {
+                Self::Pbes2HmacAeskw(Pbes2HmacAeskwJweAlgorithm::Pbes2Hs256A128kw)
+            }
+            "PBES2-HS384+A192KW" => {
+                Self::Pbes2HmacAeskw(Pbes2HmacAeskwJweAlgorithm::Pbes2Hs384A192kw)
+            }
+            "PBES2-HS512+A256KW" => {
+                Self::Pbes2HmacAeskw(Pbes2HmacAeskwJweAlgorithm::Pbes2Hs512A256kw)
+            }
+            "RSA1_5" => Self::Rsaes(RsaesJweAlgorithm::Rsa1_5),
+            "RSA-OAEP" => Self::Rsaes(RsaesJweAlgorithm::RsaOaep),
+            "RSA-OAEP-256" => Self::Rsaes(RsaesJweAlgorithm::RsaOaep256),
+            "RSA-OAEP-384" => Self::Rsaes(RsaesJweAlgorithm::RsaOaep384),
+            "RSA-OAEP-512" => Self::Rsaes(RsaesJweAlgorithm::RsaOaep512),
+            _ => return None,
+        };
+        Some(alg)
+    }
+
+    /// Return the alg header claim value.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Direct(val) => val.name(),
+            Self::EcdhEs(val) => val.name(),
+            Self::Aeskw(val) => val.name(),
+            Self::Aesgcmkw(val) => val.name(),
+            Self::Pbes2HmacAeskw(val) => val.name(),
+            Self::Rsaes(val) => val.name(),
+        }
+    }
+
+    /// Return a encrypter from a key that is formatted by a JWK.
+    ///
+    /// # Arguments
+    ///
+    /// * `jwk` - A key that is formatted by a JWK.
+    pub fn encrypter_from_jwk(&self, jwk: &Jwk) -> Result<Box<dyn JweEncrypter>, JoseError> {
+        let encrypter: Box<dyn JweEncrypter> = match self {
+            Self::Direct(val) => Box::new(val.encrypter_from_jwk(jwk)?),
+            Self::EcdhEs(val) => Box::new(val.encrypter_from_jwk(jwk)?),
+            Self::Aeskw(val) => Box::new(val.encrypter_from_jwk(jwk)?),
+            Self::Aesgcmkw(val) => Box::new(val.encrypter_from_jwk(jwk)?),
+            Self::Pbes2HmacAeskw(val) => Box::new(val.encrypter_from_jwk(jwk)?),
+            Self::Rsaes(val) => Box::new(val.encrypter_from_jwk(jwk)?),
+        };
+        Ok(encrypter)
+    }
+
+    /// Return a decrypter from a private key that is formatted by a JWK.
+    ///
+    /// # Arguments
+    ///
+    /// * `jwk` - A private key that is formatted by a JWK.
+    pub fn decrypter_from_jwk(&self, jwk: &Jwk) -> Result<Box<dyn JweDecrypter>, JoseError> {
+        let decrypter: Box<dyn JweDecrypter> = match self {
+            Self::Direct(val) => Box::new(val.decrypter_from_jwk(jwk)?),
+            Self::EcdhEs(val) => Box::new(val.decrypter_from_jwk(jwk)?),
+            Self::Aeskw(val) => Box::new(val.decrypter_from_jwk(jwk)?),
+            Self::Aesgcmkw(val) => Box::new(val.decrypter_from_jwk(jwk)?),
+            Self::Pbes2HmacAeskw(val) => Box::new(val.decrypter_from_jwk(jwk)?),
+            Self::Rsaes(val) => Box::new(val.decrypter_from_jwk(jwk)?),
+        };
+        Ok(decrypter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::KeyManagementAlgorithm;
+    use crate::jwe::{self, JweHeader};
+    use crate::jwk::Jwk;
+
+    #[test]
+    fn test_key_management_algorithm_from_name() -> Result<()> {
+        for name in [
+            "dir",
+            "ECDH-ES",
+            "ECDH-ES+A128KW",
+            "ECDH-ES+A192KW",
+            "ECDH-ES+A256KW",
+            "A128KW",
+            "A192KW",
+            "A256KW",
+            "A128GCMKW",
+            "A192GCMKW",
+            "A256GCMKW",
+            "PBES2-HS256+A128KW",
+            "PBES2-HS384+A192KW",
+            "PBES2-HS512+A256KW",
+            "RSA1_5",
+            "RSA-OAEP",
+            "RSA-OAEP-256",
+            "RSA-OAEP-384",
+            "RSA-OAEP-512",
+        ] {
+            let alg = jwe::key_management_algorithm_from_name(name).unwrap();
+            assert_eq!(alg.name(), name);
+        }
+        assert_eq!(jwe::key_management_algorithm_from_name("none"), None);
+        assert_eq!(jwe::key_management_algorithm_from_name("a128kw"), None);
+
+        let jwk = Jwk::generate_oct_key(16)?;
+        let alg = KeyManagementAlgorithm::from_name("A128KW").unwrap();
+        let encrypter = alg.encrypter_from_jwk(&jwk)?;
+        let decrypter = alg.decrypter_from_jwk(&jwk)?;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128GCM");
+        let jwe = jwe::serialize_compact(b"payload", &header, &*encrypter)?;
+        let (payload, _) = jwe::deserialize_compact(&jwe, &*decrypter)?;
+        assert_eq!(payload, b"payload");
+
+        let alg = KeyManagementAlgorithm::from_name("RSA-OAEP").unwrap();
+        assert!(alg.encrypter_from_jwk(&jwk).is_err());
+
+        Ok(())
+    }
+}
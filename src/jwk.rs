@@ -1,17 +1,23 @@
 //! JSON Web Key (JWK) support.
 
 pub mod alg;
+#[cfg(feature = "cose")]
+pub mod cose;
 
+mod issuer_key_store;
 mod jwk;
 mod jwk_set;
 mod key_info;
+mod key_ops;
 mod key_pair;
 
+pub use crate::jwk::issuer_key_store::IssuerKeyStore;
 pub use crate::jwk::jwk::Jwk;
 pub use crate::jwk::jwk_set::JwkSet;
 pub use crate::jwk::key_info::KeyAlg;
 pub use crate::jwk::key_info::KeyFormat;
 pub use crate::jwk::key_info::KeyInfo;
+pub use crate::jwk::key_ops::KeyOps;
 pub use crate::jwk::key_pair::KeyPair;
 
 pub use crate::jwk::alg::ec::EcCurve::Secp256k1;
@@ -3,17 +3,17 @@ use std::ops::Deref;
 
 use anyhow::bail;
 use openssl::bn::{BigNum, BigNumContext};
-use openssl::ec::{EcGroup, EcKey};
+use openssl::ec::{EcGroup, EcKey, EcPoint};
 use openssl::nid::Nid;
 use openssl::pkey::{PKey, Private};
 
 use crate::jwk::{Jwk, KeyPair};
-use crate::util;
 use crate::util::der::{DerBuilder, DerClass, DerReader, DerType};
 use crate::util::oid::{
     ObjectIdentifier, OID_ID_EC_PUBLIC_KEY, OID_PRIME256V1, OID_SECP256K1, OID_SECP384R1,
     OID_SECP521R1,
 };
+use crate::util::{self, HashAlgorithm, RandomSource};
 use crate::{JoseError, Value};
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
@@ -25,7 +25,7 @@ pub enum EcCurve {
 }
 
 impl EcCurve {
-    pub fn name(&self) -> &str {
+    pub fn name(&self) -> &'static str {
         match self {
             Self::P256 => "P-256",
             Self::P384 => "P-384",
@@ -34,6 +34,28 @@ impl EcCurve {
         }
     }
 
+    /// Return the curve that is identified by a crv parameter value of JWK.
+    ///
+    /// # Arguments
+    ///
+    /// * `crv` - a crv parameter value
+    pub fn from_crv_str(crv: &str) -> Option<Self> {
+        match crv {
+            "P-256" => Some(Self::P256),
+            "P-384" => Some(Self::P384),
+            "P-521" => Some(Self::P521),
+            "secp256k1" => Some(Self::Secp256k1),
+            _ => None,
+        }
+    }
+
+    /// Return the crv parameter value of JWK for the curve.
+    ///
+    /// This is the inverse of [`Self::from_crv_str`] and the same as [`Self::name`].
+    pub fn to_crv_str(&self) -> &'static str {
+        self.name()
+    }
+
     pub fn oid(&self) -> &ObjectIdentifier {
         match self {
             Self::P256 => &OID_PRIME256V1,
@@ -43,7 +65,13 @@ impl EcCurve {
         }
     }
 
-    fn nid(&self) -> Nid {
+    pub(crate) fn from_nid(nid: Nid) -> Option<Self> {
+        [Self::P256, Self::P384, Self::P521, Self::Secp256k1]
+            .into_iter()
+            .find(|curve| curve.nid() == nid)
+    }
+
+    pub(crate) fn nid(&self) -> Nid {
         match self {
             Self::P256 => Nid::X9_62_PRIME256V1,
             Self::P384 => Nid::SECP384R1,
@@ -52,7 +80,7 @@ impl EcCurve {
         }
     }
 
-    fn coordinate_size(&self) -> usize {
+    pub(crate) fn coordinate_size(&self) -> usize {
         match self {
             Self::P256 | Self::Secp256k1 => 32,
             Self::P384 => 48,
@@ -129,16 +157,19 @@ impl EcKeyPair {
             let (pkcs8_der, curve) = match Self::detect_pkcs8(input, false) {
                 Some(val) => match curve {
                     Some(val2) if val2 == val => (input, val),
-                    Some(val2) => bail!("The curve is mismatched: {}", val2),
+                    Some(val2) => bail!("The curve must be {} but {}", val2, val),
                     None => (input, val),
                 },
-                None => match curve {
-                    Some(val) => {
-                        pkcs8_der_vec = Self::to_pkcs8(input.as_ref(), false, val);
-                        (pkcs8_der_vec.as_slice(), val)
-                    }
-                    None => bail!("A curve is required for raw format."),
-                },
+                None => {
+                    let curve = match (Self::detect_ec_curve(input), curve) {
+                        (Some(val), Some(val2)) if val2 == val => val,
+                        (Some(val), Some(val2)) => bail!("The curve must be {} but {}", val2, val),
+                        (Some(val), None) | (None, Some(val)) => val,
+                        (None, None) => bail!("A curve is required for raw format."),
+                    };
+                    pkcs8_der_vec = Self::to_pkcs8(input, false, curve);
+                    (pkcs8_der_vec.as_slice(), curve)
+                }
             };
 
             let private_key = PKey::private_key_from_der(pkcs8_der)?;
@@ -156,6 +187,129 @@ impl EcKeyPair {
         })
     }
 
+    /// Derive a EC key pair deterministically from a seed.
+    ///
+    /// The private scalar is expanded from the seed by HKDF-SHA256 and reduced into
+    /// the range of 1 to n - 1. The same seed always returns the same key pair.
+    ///
+    /// This is only for reproducible test fixtures and tools. A key is exactly as
+    /// secret as its seed, so use [`EcKeyPair::generate`] for production keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `curve` - EC curve
+    /// * `seed` - A seed that is not empty.
+    pub fn from_seed(curve: EcCurve, seed: impl AsRef<[u8]>) -> Result<Self, JoseError> {
+        (|| -> anyhow::Result<Self> {
+            let seed = seed.as_ref();
+            if seed.is_empty() {
+                bail!("A seed is required.");
+            }
+
+            // The extra 8 bytes make the bias of the modular reduction negligible.
+            let okm = util::hkdf(
+                HashAlgorithm::Sha256,
+                seed,
+                b"josekit EcKeyPair::from_seed",
+                curve.name().as_bytes(),
+                curve.coordinate_size() + 8,
+            )?;
+            Self::from_wide_scalar(&okm, curve)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    /// Generate a EC key pair whose private scalar is drawn from a random source.
+    ///
+    /// # Arguments
+    ///
+    /// * `curve` - EC curve
+    /// * `source` - a random source
+    pub(crate) fn generate_from(
+        curve: EcCurve,
+        source: &dyn RandomSource,
+    ) -> Result<Self, JoseError> {
+        let bytes = util::random_bytes_from(source, curve.coordinate_size() + 8)?;
+        Self::from_wide_scalar(&bytes, curve).map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    /// Create a EC key pair from bytes that are reduced into the range of 1 to n - 1.
+    fn from_wide_scalar(input: &[u8], curve: EcCurve) -> anyhow::Result<Self> {
+        let ec_group = EcGroup::from_curve_name(curve.nid())?;
+        let mut ctx = BigNumContext::new()?;
+        let mut order = BigNum::new()?;
+        ec_group.order(&mut order, &mut ctx)?;
+
+        let mut order_minus_one = BigNum::new()?;
+        order_minus_one.checked_sub(&order, BigNum::from_u32(1)?.as_ref())?;
+        let mut d = BigNum::new()?;
+        d.nnmod(
+            BigNum::from_slice(input)?.as_ref(),
+            &order_minus_one,
+            &mut ctx,
+        )?;
+        d.add_word(1)?;
+
+        let d = d.to_vec_padded(curve.coordinate_size() as i32)?;
+        Ok(Self::from_raw_private_key(d, curve)?)
+    }
+
+    /// Create a EC key pair from a raw private key that is a big-endian encoded scalar.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - A raw private key that has the same length as the curve's field size.
+    /// * `curve` - EC curve
+    pub fn from_raw_private_key(
+        input: impl AsRef<[u8]>,
+        curve: EcCurve,
+    ) -> Result<Self, JoseError> {
+        (|| -> anyhow::Result<Self> {
+            let input = input.as_ref();
+            if input.len() != curve.coordinate_size() {
+                bail!(
+                    "The raw private key length must be {}: {}",
+                    curve.coordinate_size(),
+                    input.len()
+                );
+            }
+
+            let ec_group = EcGroup::from_curve_name(curve.nid())?;
+            let mut ctx = BigNumContext::new()?;
+            let mut order = BigNum::new()?;
+            ec_group.order(&mut order, &mut ctx)?;
+
+            let d = BigNum::from_slice(input)?;
+            if d.num_bits() == 0 || d >= order {
+                bail!("The raw private key is out of range.");
+            }
+
+            let mut public_key = EcPoint::new(&ec_group)?;
+            public_key.mul_generator2(&ec_group, &d, &mut ctx)?;
+
+            let ec_key = EcKey::from_private_components(&ec_group, &d, &public_key)?;
+            ec_key.check_key()?;
+            let private_key = PKey::from_ec_key(ec_key)?;
+
+            Ok(EcKeyPair {
+                private_key,
+                curve,
+                algorithm: None,
+                key_id: None,
+            })
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
     /// Return a signer from a private key that is formatted by a JWK of EC type.
     ///
     /// # Arguments
@@ -168,12 +322,9 @@ impl EcKeyPair {
                 val => bail!("A parameter kty must be EC: {}", val),
             }
             let curve = match jwk.parameter("crv") {
-                Some(Value::String(val)) => match val.as_str() {
-                    "P-256" => EcCurve::P256,
-                    "P-384" => EcCurve::P384,
-                    "P-521" => EcCurve::P521,
-                    "secp256k1" => EcCurve::Secp256k1,
-                    _ => bail!("A Unknown curve: {}", val),
+                Some(Value::String(val)) => match EcCurve::from_crv_str(val) {
+                    Some(val) => val,
+                    None => bail!("A Unknown curve: {}", val),
                 },
                 Some(_) => bail!("A parameter crv must be a string."),
                 None => bail!("A parameter crv is required."),
@@ -246,6 +397,68 @@ impl EcKeyPair {
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
+    /// Return the raw shared secret of ECDH with a peer public key without any KDF.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer` - A public key of the same curve that is formatted by a JWK of EC type.
+    pub fn diffie_hellman(&self, peer: &Jwk) -> Result<Vec<u8>, JoseError> {
+        (|| -> anyhow::Result<Vec<u8>> {
+            match peer.key_type() {
+                "EC" => {}
+                val => bail!("A parameter kty must be EC: {}", val),
+            }
+            match peer.parameter("crv") {
+                Some(Value::String(val)) if val == self.curve.name() => {}
+                Some(Value::String(val)) => {
+                    bail!("The curve of the peer key is mismatched: {}", val)
+                }
+                Some(_) => bail!("A parameter crv must be a string."),
+                None => bail!("A parameter crv is required."),
+            }
+            let x = match peer.parameter("x") {
+                Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
+                Some(_) => bail!("A parameter x must be a string."),
+                None => bail!("A parameter x is required."),
+            };
+            let y = match peer.parameter("y") {
+                Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
+                Some(_) => bail!("A parameter y must be a string."),
+                None => bail!("A parameter y is required."),
+            };
+
+            let mut vec = Vec::with_capacity(1 + x.len() + y.len());
+            vec.push(0x04);
+            vec.extend_from_slice(&x);
+            vec.extend_from_slice(&y);
+
+            let pkcs8 = EcKeyPair::to_pkcs8(&vec, true, self.curve);
+            let public_key = PKey::public_key_from_der(&pkcs8)?;
+            util::derive_shared_secret(&self.private_key, &public_key)
+        })()
+        .map_err(JoseError::InvalidKeyFormat)
+    }
+
+    /// Create a EC key pair from a private key of encrypted PKCS#8 PEM format.
+    ///
+    /// Encrypted PKCS#8 PEM format is a DER and base64 encoded EncryptedPrivateKeyInfo
+    /// that surrounded by "-----BEGIN/END ENCRYPTED PRIVATE KEY----".
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - A private key of encrypted PKCS#8 PEM format.
+    /// * `passphrase` - A passphrase for decrypting the private key.
+    /// * `curve` - EC curve
+    pub fn from_encrypted_pem(
+        input: impl AsRef<[u8]>,
+        passphrase: impl AsRef<[u8]>,
+        curve: Option<EcCurve>,
+    ) -> Result<Self, JoseError> {
+        let pkcs8_der = util::decrypt_pkcs8_pem(input.as_ref(), passphrase.as_ref())
+            .map_err(JoseError::InvalidKeyFormat)?;
+        Self::from_der(pkcs8_der, curve)
+    }
+
     /// Create a Ec key pair from a private key of common or traditinal PEM format.
     ///
     /// Common PEM format is a DER and base64 encoded PKCS#8 PrivateKeyInfo
@@ -268,7 +481,7 @@ impl EcKeyPair {
                     let curve = match Self::detect_pkcs8(&data, false) {
                         Some(val) => match curve {
                             Some(val2) if val2 == val => val2,
-                            Some(val2) => bail!("The curve is mismatched: {}", val2),
+                            Some(val2) => bail!("The curve must be {} but {}", val2, val),
                             None => val,
                         },
                         None => bail!("PEM contents is expected PKCS#8 wrapped key."),
@@ -279,7 +492,7 @@ impl EcKeyPair {
                     let curve = match Self::detect_ec_curve(data.as_slice()) {
                         Some(val) => match curve {
                             Some(val2) if val2 == val => val,
-                            Some(val2) => bail!("The curve is mismatched: {}", val2),
+                            Some(val2) => bail!("The curve must be {} but {}", val2, val),
                             None => val,
                         },
                         None => match curve {
@@ -537,6 +750,15 @@ impl KeyPair for EcKeyPair {
     }
 }
 
+impl From<&EcKeyPair> for Jwk {
+    /// Convert into a JWK of the public key.
+    ///
+    /// Use KeyPair::to_jwk_private_key or KeyPair::to_jwk_key_pair for the private key.
+    fn from(key_pair: &EcKeyPair) -> Self {
+        key_pair.to_jwk_public_key()
+    }
+}
+
 impl Deref for EcKeyPair {
     type Target = dyn KeyPair;
 
@@ -548,8 +770,28 @@ impl Deref for EcKeyPair {
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
+    use std::fs;
+    use std::path::PathBuf;
 
     use super::{EcCurve, EcKeyPair};
+    use crate::jwk::KeyPair;
+    use crate::JoseError;
+
+    #[test]
+    fn test_ec_curve_crv_str() {
+        for (crv, curve) in [
+            ("P-256", EcCurve::P256),
+            ("P-384", EcCurve::P384),
+            ("P-521", EcCurve::P521),
+            ("secp256k1", EcCurve::Secp256k1),
+        ] {
+            assert_eq!(EcCurve::from_crv_str(crv), Some(curve));
+            assert_eq!(curve.name(), crv);
+            assert_eq!(curve.to_crv_str(), crv);
+            assert_eq!(EcCurve::from_crv_str(curve.to_crv_str()), Some(curve));
+        }
+        assert_eq!(EcCurve::from_crv_str("P-192"), None);
+    }
 
     #[test]
     fn test_ec_jwt() -> Result<()> {
@@ -575,4 +817,92 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_ec_from_sec1_and_pkcs8_pem() -> Result<()> {
+        let pkcs8 = EcKeyPair::from_pem(load_file("pem/EC_P-256_private.pem")?, None)?;
+        let sec1 = EcKeyPair::from_pem(load_file("pem/EC_P-256_traditional_private.pem")?, None)?;
+        let encrypted = EcKeyPair::from_encrypted_pem(
+            load_file("pem/EC_P-256_encrypted_private.pem")?,
+            "passphrase",
+            None,
+        )?;
+
+        assert_eq!(sec1.curve(), EcCurve::P256);
+        assert_eq!(pkcs8.to_jwk_private_key(), sec1.to_jwk_private_key());
+        assert_eq!(pkcs8.to_jwk_private_key(), encrypted.to_jwk_private_key());
+
+        assert!(matches!(
+            EcKeyPair::from_encrypted_pem(
+                load_file("pem/EC_P-256_encrypted_private.pem")?,
+                "wrong",
+                None
+            ),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_ec_diffie_hellman() -> Result<()> {
+        for curve in [
+            EcCurve::P256,
+            EcCurve::P384,
+            EcCurve::P521,
+            EcCurve::Secp256k1,
+        ] {
+            let alice = EcKeyPair::generate(curve)?;
+            let bob = EcKeyPair::generate(curve)?;
+
+            let secret1 = alice.diffie_hellman(&bob.to_jwk_public_key())?;
+            let secret2 = bob.diffie_hellman(&alice.to_jwk_public_key())?;
+            assert_eq!(secret1, secret2);
+            assert_eq!(secret1.len(), curve.coordinate_size());
+        }
+
+        let alice = EcKeyPair::generate(EcCurve::P256)?;
+        let bob = EcKeyPair::generate(EcCurve::P384)?;
+        assert!(matches!(
+            alice.diffie_hellman(&bob.to_jwk_public_key()),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ec_from_seed() -> Result<()> {
+        for curve in [
+            EcCurve::P256,
+            EcCurve::P384,
+            EcCurve::P521,
+            EcCurve::Secp256k1,
+        ] {
+            let key_pair_1 = EcKeyPair::from_seed(curve, b"seed-1")?;
+            let key_pair_2 = EcKeyPair::from_seed(curve, b"seed-1")?;
+            let key_pair_3 = EcKeyPair::from_seed(curve, b"seed-2")?;
+            assert_eq!(key_pair_1.curve(), curve);
+            assert_eq!(
+                key_pair_1.to_jwk_private_key(),
+                key_pair_2.to_jwk_private_key()
+            );
+            assert_ne!(
+                key_pair_1.to_jwk_private_key(),
+                key_pair_3.to_jwk_private_key()
+            );
+        }
+
+        assert!(EcKeyPair::from_seed(EcCurve::P256, b"").is_err());
+
+        Ok(())
+    }
+
+    fn load_file(path: &str) -> Result<Vec<u8>> {
+        let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        pb.push("data");
+        pb.push(path);
+
+        let data = fs::read(&pb)?;
+        Ok(data)
+    }
 }
@@ -5,9 +5,9 @@ use anyhow::bail;
 use openssl::pkey::{PKey, Private};
 
 use crate::jwk::{Jwk, KeyPair};
-use crate::util;
 use crate::util::der::{DerBuilder, DerReader, DerType};
 use crate::util::oid::{ObjectIdentifier, OID_X25519, OID_X448};
+use crate::util::{self, RandomSource};
 use crate::{JoseError, Value};
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
@@ -17,13 +17,33 @@ pub enum EcxCurve {
 }
 
 impl EcxCurve {
-    pub fn name(&self) -> &str {
+    pub fn name(&self) -> &'static str {
         match self {
             Self::X25519 => "X25519",
             Self::X448 => "X448",
         }
     }
 
+    /// Return the curve that is identified by a crv parameter value of JWK.
+    ///
+    /// # Arguments
+    ///
+    /// * `crv` - a crv parameter value
+    pub fn from_crv_str(crv: &str) -> Option<Self> {
+        match crv {
+            "X25519" => Some(Self::X25519),
+            "X448" => Some(Self::X448),
+            _ => None,
+        }
+    }
+
+    /// Return the crv parameter value of JWK for the curve.
+    ///
+    /// This is the inverse of [`Self::from_crv_str`] and the same as [`Self::name`].
+    pub fn to_crv_str(&self) -> &'static str {
+        self.name()
+    }
+
     pub fn oid(&self) -> &ObjectIdentifier {
         match self {
             Self::X25519 => &*OID_X25519,
@@ -91,6 +111,38 @@ impl EcxKeyPair {
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
+    /// Generate a Montgomery curve key pair whose private key is drawn from a random source.
+    ///
+    /// # Arguments
+    /// * `curve` - Montgomery curve curve algorithm
+    /// * `source` - a random source
+    pub(crate) fn generate_from(
+        curve: EcxCurve,
+        source: &dyn RandomSource,
+    ) -> Result<EcxKeyPair, JoseError> {
+        let key_len = match curve {
+            EcxCurve::X25519 => 32,
+            EcxCurve::X448 => 56,
+        };
+        let d = util::random_bytes_from(source, key_len)?;
+
+        (|| -> anyhow::Result<EcxKeyPair> {
+            let mut builder = DerBuilder::new();
+            builder.append_octed_string_from_bytes(&d);
+
+            let pkcs8 = Self::to_pkcs8(&builder.build(), false, curve);
+            let private_key = PKey::private_key_from_der(&pkcs8)?;
+
+            Ok(EcxKeyPair {
+                curve,
+                private_key,
+                algorithm: None,
+                key_id: None,
+            })
+        })()
+        .map_err(|err| JoseError::InvalidKeyFormat(err))
+    }
+
     /// Create a Montgomery curve key pair from a private key that is a DER encoded PKCS#8 PrivateKeyInfo.
     ///
     /// # Arguments
@@ -118,6 +170,23 @@ impl EcxKeyPair {
         })
     }
 
+    /// Create a Montgomery curve key pair from a private key of encrypted PKCS#8 PEM format.
+    ///
+    /// Encrypted PKCS#8 PEM format is a DER and base64 encoded EncryptedPrivateKeyInfo
+    /// that surrounded by "-----BEGIN/END ENCRYPTED PRIVATE KEY----".
+    ///
+    /// # Arguments
+    /// * `input` - A private key of encrypted PKCS#8 PEM format.
+    /// * `passphrase` - A passphrase for decrypting the private key.
+    pub fn from_encrypted_pem(
+        input: impl AsRef<[u8]>,
+        passphrase: impl AsRef<[u8]>,
+    ) -> Result<Self, JoseError> {
+        let pkcs8_der = util::decrypt_pkcs8_pem(input.as_ref(), passphrase.as_ref())
+            .map_err(JoseError::InvalidKeyFormat)?;
+        Self::from_der(pkcs8_der)
+    }
+
     /// Create a Montgomery curve key pair from a private key of common or traditinal PEM format.
     ///
     /// Common PEM format is a DER and base64 encoded PKCS#8 PrivateKeyInfo
@@ -178,10 +247,9 @@ impl EcxKeyPair {
                 val => bail!("A parameter kty must be OKP: {}", val),
             }
             let curve = match jwk.parameter("crv") {
-                Some(Value::String(val)) => match val.as_str() {
-                    "X25519" => EcxCurve::X25519,
-                    "X448" => EcxCurve::X448,
-                    _ => bail!("A parameter crv is unrecognized: {}", val),
+                Some(Value::String(val)) => match EcxCurve::from_crv_str(val) {
+                    Some(val) => val,
+                    None => bail!("A parameter crv is unrecognized: {}", val),
                 },
                 Some(_) => bail!("A parameter crv must be a string."),
                 None => bail!("A parameter crv is required."),
@@ -210,6 +278,38 @@ impl EcxKeyPair {
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
+    /// Return the raw shared secret of ECDH with a peer public key without any KDF.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer` - A public key of the same curve that is formatted by a JWK of OKP type.
+    pub fn diffie_hellman(&self, peer: &Jwk) -> Result<Vec<u8>, JoseError> {
+        (|| -> anyhow::Result<Vec<u8>> {
+            match peer.key_type() {
+                "OKP" => {}
+                val => bail!("A parameter kty must be OKP: {}", val),
+            }
+            match peer.parameter("crv") {
+                Some(Value::String(val)) if val == self.curve.name() => {}
+                Some(Value::String(val)) => {
+                    bail!("The curve of the peer key is mismatched: {}", val)
+                }
+                Some(_) => bail!("A parameter crv must be a string."),
+                None => bail!("A parameter crv is required."),
+            }
+            let x = match peer.parameter("x") {
+                Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
+                Some(_) => bail!("A parameter x must be a string."),
+                None => bail!("A parameter x is required."),
+            };
+
+            let pkcs8 = Self::to_pkcs8(&x, true, self.curve);
+            let public_key = PKey::public_key_from_der(&pkcs8)?;
+            util::derive_shared_secret(&self.private_key, &public_key)
+        })()
+        .map_err(JoseError::InvalidKeyFormat)
+    }
+
     pub fn to_traditional_pem_private_key(&self) -> Vec<u8> {
         let der = self.private_key.private_key_to_der().unwrap();
         let der = util::encode_base64_standard(&der);
@@ -458,6 +558,15 @@ impl KeyPair for EcxKeyPair {
     }
 }
 
+impl From<&EcxKeyPair> for Jwk {
+    /// Convert into a JWK of the public key.
+    ///
+    /// Use KeyPair::to_jwk_private_key or KeyPair::to_jwk_key_pair for the private key.
+    fn from(key_pair: &EcxKeyPair) -> Self {
+        key_pair.to_jwk_public_key()
+    }
+}
+
 impl Deref for EcxKeyPair {
     type Target = dyn KeyPair;
 
@@ -474,6 +583,17 @@ mod tests {
 
     use super::{EcxCurve, EcxKeyPair};
 
+    #[test]
+    fn test_ecx_curve_crv_str() {
+        for (crv, curve) in [("X25519", EcxCurve::X25519), ("X448", EcxCurve::X448)] {
+            assert_eq!(EcxCurve::from_crv_str(crv), Some(curve));
+            assert_eq!(curve.name(), crv);
+            assert_eq!(curve.to_crv_str(), crv);
+            assert_eq!(EcxCurve::from_crv_str(curve.to_crv_str()), Some(curve));
+        }
+        assert_eq!(EcxCurve::from_crv_str("Ed25519"), None);
+    }
+
     #[test]
     fn test_generate_ecx() -> Result<()> {
         for curve in vec![EcxCurve::X25519, EcxCurve::X448] {
@@ -525,6 +645,24 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_ecx_diffie_hellman() -> Result<()> {
+        for curve in [EcxCurve::X25519, EcxCurve::X448] {
+            let alice = EcxKeyPair::generate(curve)?;
+            let bob = EcxKeyPair::generate(curve)?;
+
+            let secret1 = alice.diffie_hellman(&bob.to_jwk_public_key())?;
+            let secret2 = bob.diffie_hellman(&alice.to_jwk_public_key())?;
+            assert_eq!(secret1, secret2);
+        }
+
+        let alice = EcxKeyPair::generate(EcxCurve::X25519)?;
+        let bob = EcxKeyPair::generate(EcxCurve::X448)?;
+        assert!(alice.diffie_hellman(&bob.to_jwk_public_key()).is_err());
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
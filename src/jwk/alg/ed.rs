@@ -30,6 +30,13 @@ impl EdCurve {
             Self::Ed448 => &*OID_ED448,
         }
     }
+
+    pub(crate) fn key_len(&self) -> usize {
+        match self {
+            Self::Ed25519 => 32,
+            Self::Ed448 => 57,
+        }
+    }
 }
 
 impl Display for EdCurve {
@@ -117,6 +124,61 @@ impl EdKeyPair {
         })
     }
 
+    /// Create a EdDSA key pair from a raw private key.
+    ///
+    /// # Arguments
+    /// * `input` - A raw private key (32 bytes for Ed25519, 57 bytes for Ed448).
+    /// * `curve` - EdDSA curve
+    pub fn from_raw_private_key(
+        input: impl AsRef<[u8]>,
+        curve: EdCurve,
+    ) -> Result<Self, JoseError> {
+        (|| -> anyhow::Result<Self> {
+            let input = input.as_ref();
+            if input.len() != curve.key_len() {
+                bail!(
+                    "The raw private key length must be {}: {}",
+                    curve.key_len(),
+                    input.len()
+                );
+            }
+
+            let mut builder = DerBuilder::new();
+            builder.append_octed_string_from_bytes(input);
+
+            let pkcs8 = Self::to_pkcs8(&builder.build(), false, curve);
+            let private_key = PKey::private_key_from_der(&pkcs8)?;
+
+            Ok(Self {
+                private_key,
+                curve,
+                algorithm: None,
+                key_id: None,
+            })
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    /// Create a EdDSA key pair from a private key of encrypted PKCS#8 PEM format.
+    ///
+    /// Encrypted PKCS#8 PEM format is a DER and base64 encoded EncryptedPrivateKeyInfo
+    /// that surrounded by "-----BEGIN/END ENCRYPTED PRIVATE KEY----".
+    ///
+    /// # Arguments
+    /// * `input` - A private key of encrypted PKCS#8 PEM format.
+    /// * `passphrase` - A passphrase for decrypting the private key.
+    pub fn from_encrypted_pem(
+        input: impl AsRef<[u8]>,
+        passphrase: impl AsRef<[u8]>,
+    ) -> Result<Self, JoseError> {
+        let pkcs8_der = util::decrypt_pkcs8_pem(input.as_ref(), passphrase.as_ref())
+            .map_err(JoseError::InvalidKeyFormat)?;
+        Self::from_der(pkcs8_der)
+    }
+
     /// Create a EdDSA key pair from a private key of common or traditinal PEM format.
     ///
     /// Common PEM format is a DER and base64 encoded PKCS#8 PrivateKeyInfo
@@ -457,6 +519,15 @@ impl KeyPair for EdKeyPair {
     }
 }
 
+impl From<&EdKeyPair> for Jwk {
+    /// Convert into a JWK of the public key.
+    ///
+    /// Use KeyPair::to_jwk_private_key or KeyPair::to_jwk_key_pair for the private key.
+    fn from(key_pair: &EdKeyPair) -> Self {
+        key_pair.to_jwk_public_key()
+    }
+}
+
 impl Deref for EdKeyPair {
     type Target = dyn KeyPair;
 
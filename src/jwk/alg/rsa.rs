@@ -1,6 +1,7 @@
 use std::ops::Deref;
 
 use anyhow::bail;
+use openssl::bn::{BigNum, BigNumContext};
 use openssl::pkey::{PKey, Private};
 use openssl::rsa::Rsa;
 
@@ -109,6 +110,23 @@ impl RsaKeyPair {
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
+    /// Create a RSA key pair from a private key of encrypted PKCS#8 PEM format.
+    ///
+    /// Encrypted PKCS#8 PEM format is a DER and base64 encoded EncryptedPrivateKeyInfo
+    /// that surrounded by "-----BEGIN/END ENCRYPTED PRIVATE KEY----".
+    ///
+    /// # Arguments
+    /// * `input` - A private key of encrypted PKCS#8 PEM format.
+    /// * `passphrase` - A passphrase for decrypting the private key.
+    pub fn from_encrypted_pem(
+        input: impl AsRef<[u8]>,
+        passphrase: impl AsRef<[u8]>,
+    ) -> Result<Self, JoseError> {
+        let pkcs8_der = util::decrypt_pkcs8_pem(input.as_ref(), passphrase.as_ref())
+            .map_err(JoseError::InvalidKeyFormat)?;
+        Self::from_der(pkcs8_der)
+    }
+
     /// Create a RSA key pair from a private key of common or traditinal PEM format.
     ///
     /// Common PEM format is a DER and base64 encoded PKCS#8 PrivateKeyInfo
@@ -175,31 +193,7 @@ impl RsaKeyPair {
                 Some(_) => bail!("A parameter d must be a string."),
                 None => bail!("A parameter d is required."),
             };
-            let p = match jwk.parameter("p") {
-                Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
-                Some(_) => bail!("A parameter p must be a string."),
-                None => bail!("A parameter p is required."),
-            };
-            let q = match jwk.parameter("q") {
-                Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
-                Some(_) => bail!("A parameter q must be a string."),
-                None => bail!("A parameter q is required."),
-            };
-            let dp = match jwk.parameter("dp") {
-                Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
-                Some(_) => bail!("A parameter dp must be a string."),
-                None => bail!("A parameter dp is required."),
-            };
-            let dq = match jwk.parameter("dq") {
-                Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
-                Some(_) => bail!("A parameter dq must be a string."),
-                None => bail!("A parameter dq is required."),
-            };
-            let qi = match jwk.parameter("qi") {
-                Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
-                Some(_) => bail!("A parameter qi must be a string."),
-                None => bail!("A parameter qi is required."),
-            };
+            let [p, q, dp, dq, qi] = Self::crt_params_from_jwk(jwk, &n, &e, &d)?;
 
             let mut builder = DerBuilder::new();
             builder.begin(DerType::Sequence);
@@ -377,6 +371,156 @@ impl RsaKeyPair {
 
         builder.build()
     }
+
+    /// Return the CRT parameters (p, q, dp, dq, qi) of a private key that is formatted by a JWK.
+    ///
+    /// If the JWK has the parameters, they are validated against n, e and d.
+    /// Otherwise they are computed from n, e and d.
+    pub(crate) fn crt_params_from_jwk(
+        jwk: &Jwk,
+        n: &[u8],
+        e: &[u8],
+        d: &[u8],
+    ) -> anyhow::Result<[Vec<u8>; 5]> {
+        let mut params = Vec::with_capacity(5);
+        for name in ["p", "q", "dp", "dq", "qi"] {
+            let param = match jwk.parameter(name) {
+                Some(Value::String(val)) => Some(util::decode_base64_urlsafe_no_pad(val)?),
+                Some(_) => bail!("A parameter {} must be a string.", name),
+                None => None,
+            };
+            params.push(param);
+        }
+
+        let mut ctx = BigNumContext::new()?;
+        let n = BigNum::from_slice(n)?;
+        let e = BigNum::from_slice(e)?;
+        let d = BigNum::from_slice(d)?;
+        let one = BigNum::from_u32(1)?;
+
+        let (p, q) = match (&params[0], &params[1]) {
+            (Some(p), Some(q)) => {
+                let p = BigNum::from_slice(p)?;
+                let q = BigNum::from_slice(q)?;
+                let mut pq = BigNum::new()?;
+                pq.checked_mul(&p, &q, &mut ctx)?;
+                if pq != n || p <= one || q <= one {
+                    bail!("The parameters p and q must be the prime factors of the parameter n.");
+                }
+                (p, q)
+            }
+            (None, None) if params.iter().all(|param| param.is_none()) => {
+                Self::factor_modulus(&n, &e, &d, &mut ctx)?
+            }
+            _ => bail!("The parameters p, q, dp, dq and qi must be all present or all absent."),
+        };
+
+        let mut p1 = BigNum::new()?;
+        p1.checked_sub(&p, &one)?;
+        let mut q1 = BigNum::new()?;
+        q1.checked_sub(&q, &one)?;
+
+        let mut dp = BigNum::new()?;
+        dp.nnmod(&d, &p1, &mut ctx)?;
+        let mut dq = BigNum::new()?;
+        dq.nnmod(&d, &q1, &mut ctx)?;
+        let mut qi = BigNum::new()?;
+        qi.mod_inverse(&q, &p, &mut ctx)?;
+
+        // A d that is wrong modulo p - 1 or q - 1 makes CRT signatures that leak a factor.
+        for (name, exp, m) in [("dp", &dp, &p1), ("dq", &dq, &q1)] {
+            let mut ed = BigNum::new()?;
+            ed.mod_mul(&e, exp, m, &mut ctx)?;
+            if ed != one {
+                bail!(
+                    "A parameter {} is not the inverse of the parameter e.",
+                    name
+                );
+            }
+        }
+
+        for (name, expected, param) in [
+            ("dp", &dp, &params[2]),
+            ("dq", &dq, &params[3]),
+            ("qi", &qi, &params[4]),
+        ] {
+            if let Some(param) = param {
+                if &BigNum::from_slice(param)? != expected {
+                    bail!(
+                        "A parameter {} is inconsistent with the other parameters.",
+                        name
+                    );
+                }
+            }
+        }
+
+        Ok([
+            p.to_vec(),
+            q.to_vec(),
+            dp.to_vec(),
+            dq.to_vec(),
+            qi.to_vec(),
+        ])
+    }
+
+    /// Recover the prime factors of n from the public and private exponents.
+    ///
+    /// See NIST SP 800-56B Appendix C.
+    fn factor_modulus(
+        n: &BigNum,
+        e: &BigNum,
+        d: &BigNum,
+        ctx: &mut BigNumContext,
+    ) -> anyhow::Result<(BigNum, BigNum)> {
+        let one = BigNum::from_u32(1)?;
+        let mut n1 = BigNum::new()?;
+        n1.checked_sub(n, &one)?;
+
+        // d * e - 1 = 2^t * r (r is odd)
+        let mut de = BigNum::new()?;
+        de.checked_mul(d, e, ctx)?;
+        let mut r = BigNum::new()?;
+        r.checked_sub(&de, &one)?;
+        let mut t = 0;
+        while r > one && !r.is_bit_set(0) {
+            let val = r.to_owned()?;
+            r.rshift1(&val)?;
+            t += 1;
+        }
+        if t == 0 {
+            bail!("The parameters n, e and d are inconsistent.");
+        }
+
+        for g in 2..100 {
+            let g = BigNum::from_u32(g)?;
+            let mut y = BigNum::new()?;
+            y.mod_exp(&g, &r, n, ctx)?;
+            if y == one || y == n1 {
+                continue;
+            }
+
+            for _ in 0..t {
+                let mut x = BigNum::new()?;
+                x.mod_sqr(&y, n, ctx)?;
+                if x == one {
+                    // y is a nontrivial square root of 1 mod n.
+                    let mut y1 = BigNum::new()?;
+                    y1.checked_sub(&y, &one)?;
+                    let mut p = BigNum::new()?;
+                    p.gcd(&y1, n, ctx)?;
+                    let mut q = BigNum::new()?;
+                    q.checked_div(n, &p, ctx)?;
+                    return Ok(if p > q { (p, q) } else { (q, p) });
+                }
+                if x == n1 {
+                    break;
+                }
+                y = x;
+            }
+        }
+
+        bail!("The prime factors cannot be computed from the parameters n, e and d.");
+    }
 }
 
 impl KeyPair for RsaKeyPair {
@@ -427,6 +571,15 @@ impl KeyPair for RsaKeyPair {
     }
 }
 
+impl From<&RsaKeyPair> for Jwk {
+    /// Convert into a JWK of the public key.
+    ///
+    /// Use KeyPair::to_jwk_private_key or KeyPair::to_jwk_key_pair for the private key.
+    fn from(key_pair: &RsaKeyPair) -> Self {
+        key_pair.to_jwk_public_key()
+    }
+}
+
 impl Deref for RsaKeyPair {
     type Target = dyn KeyPair;
 
@@ -438,8 +591,15 @@ impl Deref for RsaKeyPair {
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
+    use openssl::bn::{BigNum, BigNumContext};
+
+    use std::fs;
+    use std::path::PathBuf;
 
     use super::RsaKeyPair;
+    use crate::jwk::{Jwk, KeyPair};
+    use crate::util;
+    use crate::{JoseError, Value};
 
     #[test]
     fn test_rsa_jwt() -> Result<()> {
@@ -460,4 +620,149 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_rsa_from_pkcs1_and_pkcs8() -> Result<()> {
+        let pkcs8 = RsaKeyPair::from_pem(load_file("pem/RSA_2048bit_private.pem")?)?;
+        let pkcs1 = RsaKeyPair::from_pem(load_file("pem/RSA_2048bit_traditional_private.pem")?)?;
+        assert_eq!(pkcs1.to_jwk_key_pair(), pkcs8.to_jwk_key_pair());
+
+        let pkcs1_der = pkcs8.to_raw_private_key();
+        let from_der = RsaKeyPair::from_der(&pkcs1_der)?;
+        assert_eq!(from_der.to_jwk_key_pair(), pkcs8.to_jwk_key_pair());
+
+        let spki = Jwk::from_public_pem(load_file("pem/RSA_2048bit_public.pem")?)?;
+        let pkcs1 = Jwk::from_public_pem(load_file("pem/RSA_2048bit_traditional_public.pem")?)?;
+        assert_eq!(pkcs1, spki);
+        assert_eq!(spki, pkcs8.to_jwk_public_key());
+
+        let pkcs1 = Jwk::from_public_der(pkcs8.to_raw_public_key())?;
+        assert_eq!(pkcs1, spki);
+        assert!(Jwk::from_public_der(b"invalid").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rsa_jwk_with_crt_params() -> Result<()> {
+        let key_pair = RsaKeyPair::generate(2048)?;
+        let jwk = key_pair.to_jwk_private_key();
+
+        // The consistent parameters are accepted as is.
+        let key_pair_2 = RsaKeyPair::from_jwk(&jwk)?;
+        assert_eq!(
+            key_pair.to_der_private_key(),
+            key_pair_2.to_der_private_key()
+        );
+
+        // The missing parameters are computed from n, e and d.
+        let mut jwk_2 = jwk.clone();
+        for name in ["p", "q", "dp", "dq", "qi"] {
+            jwk_2.set_parameter(name, None)?;
+        }
+        let key_pair_3 = RsaKeyPair::from_jwk(&jwk_2)?;
+        let jwk_3 = key_pair_3.to_jwk_private_key();
+        for name in ["n", "e", "d", "p", "q", "dp", "dq", "qi"] {
+            let expected = jwk.parameter(name).unwrap();
+            match name {
+                "p" | "q" => assert!(
+                    jwk_3.parameter("p") == Some(expected)
+                        || jwk_3.parameter("q") == Some(expected)
+                ),
+                "dp" | "dq" => assert!(
+                    jwk_3.parameter("dp") == Some(expected)
+                        || jwk_3.parameter("dq") == Some(expected)
+                ),
+                "qi" => {}
+                _ => assert_eq!(jwk_3.parameter(name), Some(expected)),
+            }
+        }
+
+        // The partial parameters are rejected.
+        let mut jwk_4 = jwk.clone();
+        jwk_4.set_parameter("q", None)?;
+        assert!(RsaKeyPair::from_jwk(&jwk_4).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rsa_jwk_with_tampered_crt_params() -> Result<()> {
+        let key_pair = RsaKeyPair::generate(2048)?;
+        let jwk = key_pair.to_jwk_private_key();
+
+        for name in ["p", "q", "dp", "dq", "qi"] {
+            let mut val = match jwk.parameter(name) {
+                Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
+                _ => unreachable!(),
+            };
+            let last = val.len() - 1;
+            val[last] ^= 0x02;
+
+            let mut jwk_2 = jwk.clone();
+            jwk_2.set_parameter(
+                name,
+                Some(Value::String(util::encode_base64_urlsafe_nopad(&val))),
+            )?;
+            match RsaKeyPair::from_jwk(&jwk_2) {
+                Err(JoseError::InvalidKeyFormat(_)) => {}
+                res => panic!("{}: {:?}", name, res),
+            }
+        }
+
+        // A d that is wrong modulo q - 1 is rejected even if dq is derived from it.
+        let param = |name: &str| -> Result<BigNum> {
+            match jwk.parameter(name) {
+                Some(Value::String(val)) => Ok(BigNum::from_slice(
+                    &util::decode_base64_urlsafe_no_pad(val)?,
+                )?),
+                _ => unreachable!(),
+            }
+        };
+        let mut ctx = BigNumContext::new()?;
+        let one = BigNum::from_u32(1)?;
+        let (d, p, q) = (param("d")?, param("p")?, param("q")?);
+        let mut p1 = BigNum::new()?;
+        p1.checked_sub(&p, &one)?;
+        let mut q1 = BigNum::new()?;
+        q1.checked_sub(&q, &one)?;
+        let mut tampered_d = BigNum::new()?;
+        tampered_d.checked_add(&d, &p1)?;
+        let mut tampered_dq = BigNum::new()?;
+        tampered_dq.nnmod(&tampered_d, &q1, &mut ctx)?;
+
+        let mut jwk_4 = jwk.clone();
+        for (name, val) in [("d", &tampered_d), ("dq", &tampered_dq)] {
+            jwk_4.set_parameter(
+                name,
+                Some(Value::String(util::encode_base64_urlsafe_nopad(
+                    val.to_vec_padded(256)?,
+                ))),
+            )?;
+        }
+        match RsaKeyPair::from_jwk(&jwk_4) {
+            Err(JoseError::InvalidKeyFormat(err)) => assert_eq!(
+                err.to_string(),
+                "A parameter dq is not the inverse of the parameter e."
+            ),
+            res => panic!("{:?}", res),
+        }
+
+        // The swapped factors have the inconsistent CRT exponents.
+        let mut jwk_3 = jwk.clone();
+        jwk_3.set_parameter("p", jwk.parameter("q").cloned())?;
+        jwk_3.set_parameter("q", jwk.parameter("p").cloned())?;
+        assert!(RsaKeyPair::from_jwk(&jwk_3).is_err());
+
+        Ok(())
+    }
+
+    fn load_file(path: &str) -> Result<Vec<u8>> {
+        let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        pb.push("data");
+        pb.push(path);
+
+        let data = fs::read(&pb)?;
+        Ok(data)
+    }
 }
@@ -184,6 +184,29 @@ impl RsaPssKeyPair {
         })
     }
 
+    /// Create a RSA-PSS key pair from a private key of encrypted PKCS#8 PEM format.
+    ///
+    /// Encrypted PKCS#8 PEM format is a DER and base64 encoded EncryptedPrivateKeyInfo
+    /// that surrounded by "-----BEGIN/END ENCRYPTED PRIVATE KEY----".
+    ///
+    /// # Arguments
+    /// * `input` - A private key of encrypted PKCS#8 PEM format.
+    /// * `passphrase` - A passphrase for decrypting the private key.
+    /// * `hash` A hash algorithm for signing
+    /// * `mgf1_hash` A hash algorithm for MGF1
+    /// * `salt_len` A salt length
+    pub fn from_encrypted_pem(
+        input: impl AsRef<[u8]>,
+        passphrase: impl AsRef<[u8]>,
+        hash: Option<HashAlgorithm>,
+        mgf1_hash: Option<HashAlgorithm>,
+        salt_len: Option<u8>,
+    ) -> Result<Self, JoseError> {
+        let pkcs8_der = util::decrypt_pkcs8_pem(input.as_ref(), passphrase.as_ref())
+            .map_err(JoseError::InvalidKeyFormat)?;
+        Self::from_der(pkcs8_der, hash, mgf1_hash, salt_len)
+    }
+
     /// Create a RSA-PSS key pair from a private key of common or traditinal PEM format.
     ///
     /// Common PEM format is a DER and base64 encoded PKCS#8 PrivateKeyInfo
@@ -307,31 +330,7 @@ impl RsaPssKeyPair {
                 Some(_) => bail!("A parameter d must be a string."),
                 None => bail!("A parameter d is required."),
             };
-            let p = match jwk.parameter("p") {
-                Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
-                Some(_) => bail!("A parameter p must be a string."),
-                None => bail!("A parameter p is required."),
-            };
-            let q = match jwk.parameter("q") {
-                Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
-                Some(_) => bail!("A parameter q must be a string."),
-                None => bail!("A parameter q is required."),
-            };
-            let dp = match jwk.parameter("dp") {
-                Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
-                Some(_) => bail!("A parameter dp must be a string."),
-                None => bail!("A parameter dp is required."),
-            };
-            let dq = match jwk.parameter("dq") {
-                Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
-                Some(_) => bail!("A parameter dq must be a string."),
-                None => bail!("A parameter dq is required."),
-            };
-            let qi = match jwk.parameter("qi") {
-                Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
-                Some(_) => bail!("A parameter qi must be a string."),
-                None => bail!("A parameter qi is required."),
-            };
+            let [p, q, dp, dq, qi] = RsaKeyPair::crt_params_from_jwk(jwk, &n, &e, &d)?;
 
             let mut builder = DerBuilder::new();
             builder.begin(DerType::Sequence);
@@ -509,7 +508,12 @@ impl RsaPssKeyPair {
                                 _ => break,
                             }
 
+                            // The parameters of the hash algorithm may be NULL.
                             match reader.next() {
+                                Ok(Some(DerType::Null)) => match reader.next() {
+                                    Ok(Some(DerType::EndOfContents)) => {}
+                                    _ => break,
+                                },
                                 Ok(Some(DerType::EndOfContents)) => {}
                                 _ => break,
                             }
@@ -558,7 +562,12 @@ impl RsaPssKeyPair {
                                 _ => break,
                             }
 
+                            // The parameters of the hash algorithm may be NULL.
                             match reader.next() {
+                                Ok(Some(DerType::Null)) => match reader.next() {
+                                    Ok(Some(DerType::EndOfContents)) => {}
+                                    _ => break,
+                                },
                                 Ok(Some(DerType::EndOfContents)) => {}
                                 _ => break,
                             }
@@ -581,7 +590,12 @@ impl RsaPssKeyPair {
                                 _ => break,
                             }
 
+                            // The parameters of the hash algorithm may be NULL.
                             match reader.next() {
+                                Ok(Some(DerType::Null)) => match reader.next() {
+                                    Ok(Some(DerType::EndOfContents)) => {}
+                                    _ => break,
+                                },
                                 Ok(Some(DerType::EndOfContents)) => {}
                                 _ => break,
                             }
@@ -754,6 +768,15 @@ impl KeyPair for RsaPssKeyPair {
     }
 }
 
+impl From<&RsaPssKeyPair> for Jwk {
+    /// Convert into a JWK of the public key.
+    ///
+    /// Use KeyPair::to_jwk_private_key or KeyPair::to_jwk_key_pair for the private key.
+    fn from(key_pair: &RsaPssKeyPair) -> Self {
+        key_pair.to_jwk_public_key()
+    }
+}
+
 impl Deref for RsaPssKeyPair {
     type Target = dyn KeyPair;
 
@@ -0,0 +1,450 @@
+//! COSE key (RFC 9052) support.
+
+use anyhow::bail;
+use ciborium::value::{Integer, Value as CborValue};
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcPoint};
+
+use crate::jwk::alg::ec::EcCurve;
+use crate::jwk::Jwk;
+use crate::util;
+use crate::{JoseError, Map, Value};
+
+const LABEL_KTY: i64 = 1;
+const LABEL_KID: i64 = 2;
+const LABEL_ALG: i64 = 3;
+const LABEL_KEY_OPS: i64 = 4;
+
+const KTY_OKP: i64 = 1;
+const KTY_EC2: i64 = 2;
+const KTY_RSA: i64 = 3;
+const KTY_SYMMETRIC: i64 = 4;
+
+const CURVES: &[(i64, &str)] = &[
+    (1, "P-256"),
+    (2, "P-384"),
+    (3, "P-521"),
+    (4, "X25519"),
+    (5, "X448"),
+    (6, "Ed25519"),
+    (7, "Ed448"),
+    (8, "secp256k1"),
+];
+
+// The COSE ECDH-ES algorithms (-25, -29, -30 and -31) use HKDF instead of
+// the Concat KDF of JOSE, so they have no JOSE counterpart.
+const ALGORITHMS: &[(i64, &str)] = &[
+    (-7, "ES256"),
+    (-35, "ES384"),
+    (-36, "ES512"),
+    (-47, "ES256K"),
+    (-8, "EdDSA"),
+    (-37, "PS256"),
+    (-38, "PS384"),
+    (-39, "PS512"),
+    (-257, "RS256"),
+    (-258, "RS384"),
+    (-259, "RS512"),
+    (5, "HS256"),
+    (6, "HS384"),
+    (7, "HS512"),
+    (-3, "A128KW"),
+    (-4, "A192KW"),
+    (-5, "A256KW"),
+    (-6, "dir"),
+    (-40, "RSA-OAEP"),
+    (-41, "RSA-OAEP-256"),
+    (-42, "RSA-OAEP-512"),
+];
+
+const KEY_OPERATIONS: &[(i64, &str)] = &[
+    (1, "sign"),
+    (2, "verify"),
+    (3, "encrypt"),
+    (4, "decrypt"),
+    (5, "wrapKey"),
+    (6, "unwrapKey"),
+    (7, "deriveKey"),
+    (8, "deriveBits"),
+];
+
+/// Return the key type specific labels and the JWK parameter names.
+fn key_parameters(kty: &str) -> anyhow::Result<&'static [(i64, &'static str)]> {
+    let params: &[(i64, &str)] = match kty {
+        "OKP" => &[(-2, "x"), (-4, "d")],
+        "EC" => &[(-2, "x"), (-3, "y"), (-4, "d")],
+        "RSA" => &[
+            (-1, "n"),
+            (-2, "e"),
+            (-3, "d"),
+            (-4, "p"),
+            (-5, "q"),
+            (-6, "dp"),
+            (-7, "dq"),
+            (-8, "qi"),
+        ],
+        "oct" => &[(-1, "k")],
+        val => bail!("Unsupported key type: {}", val),
+    };
+    Ok(params)
+}
+
+fn find_name(table: &[(i64, &'static str)], label: i64) -> Option<&'static str> {
+    table.iter().find(|(l, _)| *l == label).map(|(_, n)| *n)
+}
+
+fn find_label(table: &[(i64, &str)], name: &str) -> Option<i64> {
+    table.iter().find(|(_, n)| *n == name).map(|(l, _)| *l)
+}
+
+fn to_i64(value: &CborValue) -> Option<i64> {
+    match value {
+        CborValue::Integer(val) => i64::try_from(*val).ok(),
+        _ => None,
+    }
+}
+
+/// Return the y coordinate of a compressed EC2 point from the x coordinate and the sign bit.
+fn decompress_y(crv: &str, x: &[u8], sign: bool) -> anyhow::Result<Vec<u8>> {
+    let curve = match EcCurve::from_crv_str(crv) {
+        Some(val) => val,
+        None => bail!("Unsupported curve: {}", crv),
+    };
+    if x.len() != curve.coordinate_size() {
+        bail!(
+            "A parameter x of COSE_Key must be {} bytes: {}",
+            curve.coordinate_size(),
+            x.len()
+        );
+    }
+
+    let mut compressed = Vec::with_capacity(x.len() + 1);
+    compressed.push(if sign { 0x03 } else { 0x02 });
+    compressed.extend_from_slice(x);
+
+    let group = EcGroup::from_curve_name(curve.nid())?;
+    let mut ctx = BigNumContext::new()?;
+    let point = EcPoint::from_bytes(&group, &compressed, &mut ctx)?;
+    let mut px = BigNum::new()?;
+    let mut py = BigNum::new()?;
+    point.affine_coordinates_gfp(&group, &mut px, &mut py, &mut ctx)?;
+    Ok(util::num_to_vec(&py, curve.coordinate_size()))
+}
+
+impl Jwk {
+    /// Return a JWK from a COSE_Key (RFC 9052) that is encoded by CBOR.
+    ///
+    /// A kid that is not valid UTF-8 is encoded by base64url. A compressed EC2
+    /// point, whose y is a sign bit, is decompressed.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - A COSE_Key that is encoded by CBOR.
+    pub fn from_cose_key(input: impl AsRef<[u8]>) -> Result<Self, JoseError> {
+        (|| -> anyhow::Result<Self> {
+            let cbor: CborValue = ciborium::de::from_reader(input.as_ref())?;
+            let entries = match cbor {
+                CborValue::Map(val) => val,
+                _ => bail!("A COSE_Key must be a map."),
+            };
+
+            let mut labels = Vec::with_capacity(entries.len());
+            for (key, value) in entries {
+                match to_i64(&key) {
+                    Some(val) => labels.push((val, value)),
+                    None => bail!("A label of COSE_Key must be a integer."),
+                }
+            }
+            let get = |label: i64| labels.iter().find(|(l, _)| *l == label).map(|(_, v)| v);
+
+            let kty = match get(LABEL_KTY).and_then(to_i64) {
+                Some(KTY_OKP) => "OKP",
+                Some(KTY_EC2) => "EC",
+                Some(KTY_RSA) => "RSA",
+                Some(KTY_SYMMETRIC) => "oct",
+                Some(val) => bail!("Unsupported COSE key type: {}", val),
+                None => bail!("A COSE_Key must have a integer kty."),
+            };
+
+            let mut map = Map::new();
+            map.insert("kty".to_string(), Value::String(kty.to_string()));
+
+            match get(LABEL_KID) {
+                Some(CborValue::Bytes(val)) => {
+                    let kid = match std::str::from_utf8(val) {
+                        Ok(val) => val.to_string(),
+                        Err(_) => util::encode_base64_urlsafe_nopad(val),
+                    };
+                    map.insert("kid".to_string(), Value::String(kid));
+                }
+                Some(_) => bail!("A kid of COSE_Key must be a byte string."),
+                None => {}
+            }
+
+            match get(LABEL_ALG) {
+                Some(CborValue::Text(val)) => {
+                    map.insert("alg".to_string(), Value::String(val.clone()));
+                }
+                Some(val) => match to_i64(val).and_then(|val| find_name(ALGORITHMS, val)) {
+                    Some(val) => {
+                        map.insert("alg".to_string(), Value::String(val.to_string()));
+                    }
+                    None => bail!("Unsupported COSE algorithm: {:?}", val),
+                },
+                None => {}
+            }
+
+            match get(LABEL_KEY_OPS) {
+                Some(CborValue::Array(vals)) => {
+                    let mut key_ops = Vec::with_capacity(vals.len());
+                    for val in vals {
+                        let name = match val {
+                            CborValue::Text(val) => val.as_str(),
+                            val => match to_i64(val).and_then(|val| find_name(KEY_OPERATIONS, val))
+                            {
+                                Some(val) => val,
+                                None => bail!("Unsupported COSE key operation: {:?}", val),
+                            },
+                        };
+                        key_ops.push(Value::String(name.to_string()));
+                    }
+                    map.insert("key_ops".to_string(), Value::Array(key_ops));
+                }
+                Some(_) => bail!("A key_ops of COSE_Key must be an array."),
+                None => {}
+            }
+
+            let mut crv = None;
+            if kty == "OKP" || kty == "EC" {
+                let name = match get(-1)
+                    .and_then(to_i64)
+                    .and_then(|val| find_name(CURVES, val))
+                {
+                    Some(val) => val,
+                    None => bail!("A COSE_Key must have a supported crv."),
+                };
+                let is_okp = matches!(name, "X25519" | "X448" | "Ed25519" | "Ed448");
+                if is_okp != (kty == "OKP") {
+                    bail!(
+                        "The curve {} cannot be used for the key type {}.",
+                        name,
+                        kty
+                    );
+                }
+                map.insert("crv".to_string(), Value::String(name.to_string()));
+                crv = Some(name);
+            }
+
+            for (label, name) in key_parameters(kty)? {
+                match get(*label) {
+                    Some(CborValue::Bytes(val)) => {
+                        let val = util::encode_base64_urlsafe_nopad(val);
+                        map.insert(name.to_string(), Value::String(val));
+                    }
+                    Some(CborValue::Bool(sign)) if kty == "EC" && *name == "y" => {
+                        let x = match get(-2) {
+                            Some(CborValue::Bytes(val)) => val,
+                            _ => bail!("A parameter x of COSE_Key must be a byte string."),
+                        };
+                        let y = decompress_y(crv.unwrap_or_default(), x, *sign)?;
+                        let y = util::encode_base64_urlsafe_nopad(y);
+                        map.insert(name.to_string(), Value::String(y));
+                    }
+                    Some(_) => bail!("A parameter {} of COSE_Key must be a byte string.", name),
+                    None => {}
+                }
+            }
+
+            Ok(Jwk::from_map(map)?)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    /// Return a COSE_Key (RFC 9052) that is encoded by CBOR.
+    pub fn to_cose_key(&self) -> Result<Vec<u8>, JoseError> {
+        (|| -> anyhow::Result<Vec<u8>> {
+            let kty = self.key_type();
+            let kty_label = match kty {
+                "OKP" => KTY_OKP,
+                "EC" => KTY_EC2,
+                "RSA" => KTY_RSA,
+                "oct" => KTY_SYMMETRIC,
+                val => bail!("Unsupported key type: {}", val),
+            };
+
+            let label = |val: i64| CborValue::Integer(Integer::from(val));
+
+            let mut entries = Vec::new();
+            entries.push((label(LABEL_KTY), label(kty_label)));
+
+            if let Some(val) = self.key_id() {
+                entries.push((label(LABEL_KID), CborValue::Bytes(val.as_bytes().to_vec())));
+            }
+
+            if let Some(val) = self.algorithm() {
+                match find_label(ALGORITHMS, val) {
+                    Some(val) => entries.push((label(LABEL_ALG), label(val))),
+                    None => entries.push((label(LABEL_ALG), CborValue::Text(val.to_string()))),
+                }
+            }
+
+            if let Some(vals) = self.key_operations() {
+                let mut key_ops = Vec::with_capacity(vals.len());
+                for val in vals {
+                    match find_label(KEY_OPERATIONS, val) {
+                        Some(val) => key_ops.push(label(val)),
+                        None => key_ops.push(CborValue::Text(val.to_string())),
+                    }
+                }
+                entries.push((label(LABEL_KEY_OPS), CborValue::Array(key_ops)));
+            }
+
+            if kty == "OKP" || kty == "EC" {
+                let crv = match self.curve() {
+                    Some(val) => val,
+                    None => bail!("The key type {} must have a crv parameter.", kty),
+                };
+                match find_label(CURVES, crv) {
+                    Some(val) => entries.push((label(-1), label(val))),
+                    None => bail!("Unsupported curve: {}", crv),
+                }
+            }
+
+            for (name_label, name) in key_parameters(kty)? {
+                match self.parameter(name) {
+                    Some(Value::String(val)) => {
+                        let val = util::decode_base64_urlsafe_no_pad(val)?;
+                        entries.push((label(*name_label), CborValue::Bytes(val)));
+                    }
+                    Some(_) => bail!("A parameter {} must be a string.", name),
+                    None => {}
+                }
+            }
+
+            let mut cbor = Vec::new();
+            ciborium::ser::into_writer(&CborValue::Map(entries), &mut cbor)?;
+            Ok(cbor)
+        })()
+        .map_err(JoseError::InvalidKeyFormat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::jwk::Jwk;
+    use crate::util::{self, from_hex};
+
+    #[test]
+    fn test_cose_key_ec2_p256() -> Result<()> {
+        // The public key of RFC 9052 Appendix C.7.1.
+        let x = "65eda5a12577c2bae829437fe338701a10aaa375e1bb5b5de108de439c08551d";
+        let y = "1e52ed75701163f7f9e40ddf9f341b3dc9ba860af7e0ca7ca7e9eecd0084d19c";
+        let kid = "meriadoc.brandybuck@buckland.example";
+        let cose_key = from_hex(&format!(
+            "a50102025824{}2001215820{}225820{}",
+            "6d65726961646f632e6272616e64796275636b406275636b6c616e642e6578616d706c65", x, y
+        ));
+
+        let jwk = Jwk::from_cose_key(&cose_key)?;
+        assert_eq!(jwk.key_type(), "EC");
+        assert_eq!(jwk.key_id(), Some(kid));
+        assert_eq!(jwk.curve(), Some("P-256"));
+        assert_eq!(
+            jwk.parameter("x").and_then(|val| val.as_str()),
+            Some(util::encode_base64_urlsafe_nopad(from_hex(x)).as_str())
+        );
+        assert_eq!(
+            jwk.parameter("y").and_then(|val| val.as_str()),
+            Some(util::encode_base64_urlsafe_nopad(from_hex(y)).as_str())
+        );
+        assert_eq!(jwk.to_cose_key()?, cose_key);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cose_key_ec2_compressed_point() -> Result<()> {
+        // The public key of RFC 9052 Appendix C.7.1 with a sign bit instead of y.
+        let x = "65eda5a12577c2bae829437fe338701a10aaa375e1bb5b5de108de439c08551d";
+        let y_even = "1e52ed75701163f7f9e40ddf9f341b3dc9ba860af7e0ca7ca7e9eecd0084d19c";
+        let y_odd = "e1ad12898fee9c09061bf22060cbe4c2364579f6081f358358161132ff7b2e63";
+
+        for (sign, y) in [("f4", y_even), ("f5", y_odd)] {
+            let cose_key = from_hex(&format!("a401022001215820{}22{}", x, sign));
+            let jwk = Jwk::from_cose_key(&cose_key)?;
+            assert_eq!(
+                jwk.parameter("y").and_then(|val| val.as_str()),
+                Some(util::encode_base64_urlsafe_nopad(from_hex(y)).as_str())
+            );
+        }
+
+        // A x coordinate that is not on the curve.
+        let cose_key = from_hex(&format!("a40102200121582000{}22f4", &x[2..]));
+        assert!(Jwk::from_cose_key(&cose_key).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cose_key_binary_kid_and_ecdh_algorithm() -> Result<()> {
+        let k = "000102030405060708090a0b0c0d0e0f";
+
+        // {1: 4, 2: h'ff00', -1: k}
+        let cose_key = from_hex(&format!("a301040242ff002050{}", k));
+        let jwk = Jwk::from_cose_key(&cose_key)?;
+        assert_eq!(jwk.key_id(), Some("_wA"));
+
+        // {1: 2, 3: -25 (ECDH-ES + HKDF-256), -1: 1, -2: x, -3: false}
+        let x = "65eda5a12577c2bae829437fe338701a10aaa375e1bb5b5de108de439c08551d";
+        for alg in ["3818", "381c", "381d", "381e"] {
+            let cose_key = from_hex(&format!("a5010203{}2001215820{}22f4", alg, x));
+            assert!(Jwk::from_cose_key(&cose_key).is_err(), "{}", alg);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cose_key_okp_ed25519() -> Result<()> {
+        // The private key of RFC 8037 Appendix A.1.
+        let x = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+        let d = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+        let cose_key = from_hex(&format!("a4010120062158{}{}2358{}{}", "20", x, "20", d));
+
+        let jwk = Jwk::from_cose_key(&cose_key)?;
+        assert_eq!(jwk.key_type(), "OKP");
+        assert_eq!(jwk.curve(), Some("Ed25519"));
+        assert_eq!(
+            jwk.parameter("x").and_then(|val| val.as_str()),
+            Some("11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo")
+        );
+        assert_eq!(
+            jwk.parameter("d").and_then(|val| val.as_str()),
+            Some("nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A")
+        );
+        assert_eq!(jwk.to_cose_key()?, cose_key);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cose_key_rsa_and_symmetric() -> Result<()> {
+        let mut jwk = Jwk::generate_rsa_key(2048)?;
+        jwk.set_algorithm("RS256");
+        assert_eq!(Jwk::from_cose_key(jwk.to_cose_key()?)?, jwk);
+
+        let mut jwk = Jwk::generate_oct_key(32)?;
+        jwk.set_key_id("oct-1");
+        jwk.set_key_operations(vec!["sign", "verify"]);
+        assert_eq!(Jwk::from_cose_key(jwk.to_cose_key()?)?, jwk);
+
+        assert!(Jwk::from_cose_key(b"\x01").is_err());
+
+        Ok(())
+    }
+}
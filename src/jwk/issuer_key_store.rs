@@ -0,0 +1,325 @@
+use std::collections::BTreeMap;
+
+use anyhow::bail;
+
+use crate::jwk::{JwkSet, KeyOps};
+use crate::jws::{JwsHeader, JwsVerifier, SigningAlgorithm};
+use crate::JoseError;
+
+/// Represents JWK sets that are owned by issuers.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct IssuerKeyStore {
+    issuers: BTreeMap<String, JwkSet>,
+}
+
+impl IssuerKeyStore {
+    pub fn new() -> Self {
+        Self {
+            issuers: BTreeMap::new(),
+        }
+    }
+
+    /// Return the JWK set of a specified issuer.
+    ///
+    /// # Arguments
+    ///
+    /// * `issuer` - a issuer
+    pub fn get(&self, issuer: &str) -> Option<&JwkSet> {
+        self.issuers.get(issuer)
+    }
+
+    /// Set a JWK set for a issuer.
+    ///
+    /// # Arguments
+    ///
+    /// * `issuer` - a issuer
+    /// * `jwk_set` - a JWK set that is owned by the issuer
+    pub fn insert(&mut self, issuer: impl Into<String>, jwk_set: JwkSet) {
+        self.issuers.insert(issuer.into(), jwk_set);
+    }
+
+    /// Remove the JWK set of a specified issuer.
+    ///
+    /// # Arguments
+    ///
+    /// * `issuer` - a issuer
+    pub fn remove(&mut self, issuer: &str) -> Option<JwkSet> {
+        self.issuers.remove(issuer)
+    }
+
+    /// Return the registered issuers.
+    pub fn issuers(&self) -> Vec<&str> {
+        self.issuers.keys().map(|e| e.as_str()).collect()
+    }
+
+    /// Return a verifier that is selected by the issuer and the kid and alg header claims.
+    ///
+    /// When the kid header claim is present, only keys that have the same kid are used.
+    /// A key that has a alg parameter is used only for the same algorithm. Keys that are
+    /// designated for encryption by the use parameter, or whose key_ops parameter lacks
+    /// verify, are never used. When no verifier can be created from the candidate keys,
+    /// the errors of the candidates are reported. A unregistered issuer is reported
+    /// as a invalid claim.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload_iss` - the iss claim of the payload.
+    /// * `header` - the JWS header.
+    pub fn verifier_for(
+        &self,
+        payload_iss: &str,
+        header: &JwsHeader,
+    ) -> Result<Box<dyn JwsVerifier>, JoseError> {
+        (|| -> anyhow::Result<Box<dyn JwsVerifier>> {
+            let jwk_set = match self.issuers.get(payload_iss) {
+                Some(val) => val,
+                None => {
+                    return Err(anyhow::Error::new(JoseError::InvalidClaim(
+                        anyhow::anyhow!("The iss claim is not a registered issuer: {}", payload_iss),
+                    )))
+                }
+            };
+
+            let alg = match header.algorithm() {
+                Some(val) => val,
+                None => bail!("The JWS alg header claim is required."),
+            };
+
+            let signing_algorithm = match SigningAlgorithm::from_name(alg) {
+                Some(val) => val,
+                None => {
+                    return Err(anyhow::Error::new(
+                        JoseError::UnsupportedSignatureAlgorithm(anyhow::anyhow!(
+                            "Unsupported algorithm: {}",
+                            alg
+                        )),
+                    ))
+                }
+            };
+
+            let jwks = match header.key_id() {
+                Some(val) => jwk_set.get(val),
+                None => jwk_set.keys(),
+            };
+
+            let mut errors = Vec::new();
+            for jwk in jwks {
+                match jwk.key_use() {
+                    Some(val) if val != "sig" => continue,
+                    _ => {}
+                }
+                if !jwk.is_for_key_operations(KeyOps::VERIFY) {
+                    continue;
+                }
+                if let Some(val) = jwk.algorithm() {
+                    if val != alg {
+                        continue;
+                    }
+                }
+
+                match signing_algorithm.verifier_from_jwk(jwk) {
+                    Ok(val) => return Ok(val),
+                    Err(err) => errors.push(err.to_string()),
+                }
+            }
+
+            if !errors.is_empty() {
+                bail!(
+                    "A verifier cannot be created from the keys of the issuer: iss = {}, alg = {}: {}",
+                    payload_iss,
+                    alg,
+                    errors.join("; ")
+                );
+            }
+
+            match header.key_id() {
+                Some(val) => bail!(
+                    "A key of the issuer is not found: iss = {}, kid = {}, alg = {}",
+                    payload_iss,
+                    val,
+                    alg
+                ),
+                None => bail!(
+                    "A key of the issuer is not found: iss = {}, alg = {}",
+                    payload_iss,
+                    alg
+                ),
+            }
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+}
+
+impl Default for IssuerKeyStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::IssuerKeyStore;
+    use crate::jwk::JwkSet;
+    use crate::jws::{JwsHeader, ES256, HS256};
+    use crate::jwt::{self, JwtPayload};
+    use crate::JoseError;
+
+    #[test]
+    fn test_verifier_for_issuers() -> Result<()> {
+        let oct_key = HS256.to_jwk(b"0123456789ABCDEF0123456789ABCDEF");
+        let mut oct_key_with_id = oct_key.clone();
+        oct_key_with_id.set_key_id("oct-1");
+
+        let mut ec_key_pair = ES256.generate_key_pair()?;
+        ec_key_pair.set_key_id(Some("ec-1"));
+        let ec_private_key = ec_key_pair.to_jwk_private_key();
+
+        let mut set_a = JwkSet::new();
+        set_a.push_key(oct_key_with_id.clone());
+        let mut set_b = JwkSet::new();
+        set_b.push_key(ec_key_pair.to_jwk_public_key());
+
+        let mut store = IssuerKeyStore::new();
+        store.insert("https://a.example.com", set_a);
+        store.insert("https://b.example.com", set_b);
+        assert_eq!(
+            store.issuers(),
+            vec!["https://a.example.com", "https://b.example.com"]
+        );
+
+        let mut payload = JwtPayload::new();
+        payload.set_issuer("https://a.example.com");
+        let mut header = JwsHeader::new();
+        header.set_key_id("oct-1");
+        let signer = HS256.signer_from_jwk(&oct_key)?;
+        let jwt_a = jwt::encode_with_signer(&payload, &header, &signer)?;
+
+        let mut payload = JwtPayload::new();
+        payload.set_issuer("https://b.example.com");
+        let mut header = JwsHeader::new();
+        header.set_key_id("ec-1");
+        let signer = ES256.signer_from_jwk(&ec_private_key)?;
+        let jwt_b = jwt::encode_with_signer(&payload, &header, &signer)?;
+
+        for (jwt, iss) in [
+            (&jwt_a, "https://a.example.com"),
+            (&jwt_b, "https://b.example.com"),
+        ] {
+            let header = jwt::decode_header(jwt)?;
+            let mut jws_header = JwsHeader::new();
+            jws_header.set_algorithm(header.claim("alg").unwrap().as_str().unwrap());
+            jws_header.set_key_id(header.claim("kid").unwrap().as_str().unwrap());
+            let verifier = store.verifier_for(iss, &jws_header)?;
+            let (payload, _) = jwt::decode_with_verifier(jwt, &*verifier)?;
+            assert_eq!(payload.issuer(), Some(iss));
+        }
+
+        // A key of the other issuer is never used.
+        let mut header = JwsHeader::new();
+        header.set_algorithm("ES256");
+        header.set_key_id("ec-1");
+        assert!(matches!(
+            store.verifier_for("https://a.example.com", &header),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+
+        // The alg header claim must match the key. The secret is long enough for HS384,
+        // so only the alg parameter of the key rejects it.
+        let secret = b"0123456789ABCDEF0123456789ABCDEF0123456789ABCDEF0123456789ABCDEF";
+        let mut long_key = HS256.to_jwk(secret);
+        long_key.set_key_id("oct-2");
+        let mut long_key_without_alg = long_key.clone();
+        long_key_without_alg.set_parameter("alg", None)?;
+        long_key_without_alg.set_key_id("oct-3");
+        let mut set_c = JwkSet::new();
+        set_c.push_key(long_key);
+        set_c.push_key(long_key_without_alg);
+        store.insert("https://c.example.com", set_c);
+
+        let mut header = JwsHeader::new();
+        header.set_algorithm("HS384");
+        header.set_key_id("oct-2");
+        match store.verifier_for("https://c.example.com", &header) {
+            Err(JoseError::InvalidKeyFormat(err)) => assert_eq!(
+                err.to_string(),
+                "A key of the issuer is not found: iss = https://c.example.com, kid = oct-2, alg = HS384"
+            ),
+            res => panic!("{:?}", res),
+        }
+        header.set_key_id("oct-3");
+        let verifier = store.verifier_for("https://c.example.com", &header)?;
+        assert_eq!(verifier.algorithm().name(), "HS384");
+        store.remove("https://c.example.com");
+
+        // The errors of the candidate keys are reported.
+        let mut header = JwsHeader::new();
+        header.set_algorithm("ES256");
+        let mut oct_key_without_alg = oct_key.clone();
+        oct_key_without_alg.set_parameter("alg", None)?;
+        let mut set_d = JwkSet::new();
+        set_d.push_key(oct_key_without_alg);
+        store.insert("https://d.example.com", set_d);
+        match store.verifier_for("https://d.example.com", &header) {
+            Err(JoseError::InvalidKeyFormat(err)) => assert!(err
+                .to_string()
+                .starts_with("A verifier cannot be created from the keys of the issuer")),
+            res => panic!("{:?}", res),
+        }
+        store.remove("https://d.example.com");
+
+        // A unknown issuer is rejected.
+        let mut header = JwsHeader::new();
+        header.set_algorithm("HS256");
+        match store.verifier_for("https://c.example.com", &header) {
+            Err(JoseError::InvalidClaim(err)) => assert_eq!(
+                err.to_string(),
+                "The iss claim is not a registered issuer: https://c.example.com"
+            ),
+            res => panic!("{:?}", res),
+        }
+
+        // The kid header claim is optional.
+        let verifier = store.verifier_for("https://a.example.com", &header)?;
+        jwt::decode_with_verifier(&jwt_a, &*verifier)?;
+
+        // Keys for encryption are never used to verify signatures.
+        let mut enc_key = ec_key_pair.to_jwk_public_key();
+        enc_key.set_key_use("enc");
+        let mut wrap_key = ec_key_pair.to_jwk_public_key();
+        wrap_key.set_key_operations(vec!["wrapKey"]);
+        let mut set_e = JwkSet::new();
+        set_e.push_key(enc_key);
+        set_e.push_key(wrap_key);
+        store.insert("https://e.example.com", set_e.clone());
+        let mut header = JwsHeader::new();
+        header.set_algorithm("ES256");
+        header.set_key_id("ec-1");
+        match store.verifier_for("https://e.example.com", &header) {
+            Err(JoseError::InvalidKeyFormat(err)) => assert_eq!(
+                err.to_string(),
+                "A key of the issuer is not found: iss = https://e.example.com, kid = ec-1, alg = ES256"
+            ),
+            res => panic!("{:?}", res),
+        }
+        let mut sig_key = ec_key_pair.to_jwk_public_key();
+        sig_key.set_key_use("sig");
+        set_e.push_key(sig_key);
+        store.insert("https://e.example.com", set_e);
+        let verifier = store.verifier_for("https://e.example.com", &header)?;
+        jwt::decode_with_verifier(&jwt_b, &*verifier)?;
+
+        let mut header = JwsHeader::new();
+        header.set_algorithm("none");
+        assert!(matches!(
+            store.verifier_for("https://a.example.com", &header),
+            Err(JoseError::UnsupportedSignatureAlgorithm(_))
+        ));
+
+        Ok(())
+    }
+}
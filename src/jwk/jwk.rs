@@ -3,15 +3,20 @@ use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 use std::io::Read;
 use std::string::ToString;
+use std::time::{Duration, SystemTime};
 
 use anyhow::bail;
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::pkey::{HasPublic, Id, PKey, PKeyRef, Public};
+use openssl::rsa::Rsa;
 
 use crate::jwk::alg::ec::{EcCurve, EcKeyPair};
 use crate::jwk::alg::ecx::{EcxCurve, EcxKeyPair};
 use crate::jwk::alg::ed::{EdCurve, EdKeyPair};
 use crate::jwk::alg::rsa::RsaKeyPair;
-use crate::util;
-use crate::{JoseError, Map, Value};
+use crate::jwk::KeyOps;
+use crate::util::{self, HashAlgorithm};
+use crate::{JoseError, Map, Number, Value};
 
 /// Represents JWK object.
 #[derive(Debug, Eq, PartialEq, Clone, Deserialize, Serialize)]
@@ -21,6 +26,10 @@ pub struct Jwk {
 }
 
 impl Jwk {
+    /// The names of the private key parameters of all key types.
+    pub(crate) const PRIVATE_PARAMETERS: [&'static str; 8] =
+        ["d", "p", "q", "dp", "dq", "qi", "oth", "k"];
+
     pub fn new(key_type: &str) -> Self {
         Self {
             map: {
@@ -65,7 +74,7 @@ impl Jwk {
     /// # Arguments
     /// * `key_len` - A key byte length
     pub fn generate_oct_key(key_len: u8) -> Result<Self, JoseError> {
-        let k = util::random_bytes(key_len as usize);
+        let k = util::random_bytes(key_len as usize)?;
 
         let mut jwk = Self::new("oct");
         jwk.map.insert(
@@ -213,6 +222,210 @@ impl Jwk {
         .map_err(|err| JoseError::InvalidJwkFormat(err))
     }
 
+    /// Return a public key JWK that has only the required members of a key.
+    pub(crate) fn from_public_key<T: HasPublic>(key: &PKeyRef<T>) -> Result<Self, JoseError> {
+        (|| -> anyhow::Result<Jwk> {
+            let jwk = match key.id() {
+                Id::RSA | Id::RSA_PSS => {
+                    let rsa = key.rsa()?;
+                    let mut jwk = Jwk::new("RSA");
+                    jwk.map.insert(
+                        "e".to_string(),
+                        Value::String(util::encode_base64_urlsafe_nopad(rsa.e().to_vec())),
+                    );
+                    jwk.map.insert(
+                        "n".to_string(),
+                        Value::String(util::encode_base64_urlsafe_nopad(rsa.n().to_vec())),
+                    );
+                    jwk
+                }
+                Id::EC => {
+                    let ec_key = key.ec_key()?;
+                    let curve = match ec_key.group().curve_name().and_then(EcCurve::from_nid) {
+                        Some(val) => val,
+                        None => bail!("Unsupported curve."),
+                    };
+                    let mut x = BigNum::new()?;
+                    let mut y = BigNum::new()?;
+                    let mut ctx = BigNumContext::new()?;
+                    ec_key.public_key().affine_coordinates_gfp(
+                        ec_key.group(),
+                        &mut x,
+                        &mut y,
+                        &mut ctx,
+                    )?;
+                    let x = util::num_to_vec(&x, curve.coordinate_size());
+                    let y = util::num_to_vec(&y, curve.coordinate_size());
+
+                    let mut jwk = Jwk::new("EC");
+                    jwk.set_curve(curve.name());
+                    jwk.map.insert(
+                        "x".to_string(),
+                        Value::String(util::encode_base64_urlsafe_nopad(x)),
+                    );
+                    jwk.map.insert(
+                        "y".to_string(),
+                        Value::String(util::encode_base64_urlsafe_nopad(y)),
+                    );
+                    jwk
+                }
+                id @ (Id::ED25519 | Id::ED448 | Id::X25519 | Id::X448) => {
+                    let curve = match id {
+                        Id::ED25519 => "Ed25519",
+                        Id::ED448 => "Ed448",
+                        Id::X25519 => "X25519",
+                        _ => "X448",
+                    };
+                    let mut jwk = Jwk::new("OKP");
+                    jwk.set_curve(curve);
+                    jwk.map.insert(
+                        "x".to_string(),
+                        Value::String(util::encode_base64_urlsafe_nopad(key.raw_public_key()?)),
+                    );
+                    jwk
+                }
+                _ => bail!("Unsupported key type."),
+            };
+            Ok(jwk)
+        })()
+        .map_err(JoseError::InvalidKeyFormat)
+    }
+
+    /// Return a public key JWK from a public key that is a DER encoded SubjectPublicKeyInfo.
+    ///
+    /// The key type is detected by the algorithm identifier and the kty and crv parameters
+    /// are set appropriately.
+    ///
+    /// A DER encoded PKCS#1 RSAPublicKey is also accepted.
+    ///
+    /// # Arguments
+    /// * `input` - A public key that is a DER encoded SubjectPublicKeyInfo or RSAPublicKey.
+    pub fn from_public_der(input: impl AsRef<[u8]>) -> Result<Self, JoseError> {
+        let input = input.as_ref();
+        let public_key = match PKey::public_key_from_der(input) {
+            Ok(val) => val,
+            Err(err) => match Rsa::public_key_from_der_pkcs1(input) {
+                Ok(rsa) => {
+                    PKey::from_rsa(rsa).map_err(|err| JoseError::InvalidKeyFormat(err.into()))?
+                }
+                Err(_) => return Err(JoseError::InvalidKeyFormat(err.into())),
+            },
+        };
+        Self::from_public_key(&public_key)
+    }
+
+    /// Return a public key JWK from a public key that is formatted by PEM.
+    ///
+    /// The PEM may be a SubjectPublicKeyInfo (PUBLIC KEY) or a PKCS#1 RSA public key
+    /// (RSA PUBLIC KEY).
+    ///
+    /// # Arguments
+    /// * `input` - A public key that is formatted by PEM.
+    pub fn from_public_pem(input: impl AsRef<[u8]>) -> Result<Self, JoseError> {
+        let public_key = (|| -> anyhow::Result<PKey<Public>> {
+            let (alg, data) = util::parse_pem(input.as_ref())?;
+            let public_key = match alg.as_str() {
+                "PUBLIC KEY" => PKey::public_key_from_der(&data)?,
+                "RSA PUBLIC KEY" => PKey::from_rsa(Rsa::public_key_from_der_pkcs1(&data)?)?,
+                alg => bail!("Inappropriate algorithm: {}", alg),
+            };
+            Ok(public_key)
+        })()
+        .map_err(JoseError::InvalidKeyFormat)?;
+        Self::from_public_key(&public_key)
+    }
+
+    /// Return the JWK thumbprint (RFC 7638) that is encoded by base64url.
+    ///
+    /// # Arguments
+    /// * `hash_algorithm` - A hash algorithm to digest required members
+    pub fn thumbprint(&self, hash_algorithm: HashAlgorithm) -> Result<String, JoseError> {
+        (|| -> anyhow::Result<String> {
+            let map = self.thumbprint_members()?;
+            let json = serde_json::to_vec(&map)?;
+            let digest = openssl::hash::hash(hash_algorithm.message_digest(), &json)?;
+            Ok(util::encode_base64_urlsafe_nopad(digest))
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwkFormat(err),
+        })
+    }
+
+    /// Return the JWK thumbprint (RFC 7638) using SHA-256 that is encoded by base64url.
+    pub fn thumbprint_sha256(&self) -> Result<String, JoseError> {
+        self.thumbprint(HashAlgorithm::Sha256)
+    }
+
+    /// Return true if the JWK has the same key as the other JWK.
+    ///
+    /// Only the required members of the JWK thumbprint (RFC 7638) are compared,
+    /// so metadata like kid, use and alg is ignored and a private key has
+    /// the same key as its public key. JWKs of unknown key types never match.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other JWK.
+    pub fn same_key(&self, other: &Jwk) -> bool {
+        match (self.thumbprint_members(), other.thumbprint_members()) {
+            (Ok(val1), Ok(val2)) => val1 == val2,
+            _ => false,
+        }
+    }
+
+    fn thumbprint_members(&self) -> anyhow::Result<Map<String, Value>> {
+        let key_type = self.key_type();
+        let names: &[&str] = match key_type {
+            "oct" => &["k", "kty"],
+            "RSA" => &["e", "kty", "n"],
+            "EC" => &["crv", "kty", "x", "y"],
+            "OKP" => &["crv", "kty", "x"],
+            val => bail!("Unknown key type: {}", val),
+        };
+
+        // The required members are inserted in lexicographic order.
+        let mut map = Map::new();
+        for name in names {
+            match self.map.get(*name) {
+                Some(Value::String(val)) => {
+                    map.insert(name.to_string(), Value::String(val.clone()));
+                }
+                Some(_) => bail!("The parameter '{}' must be a string.", name),
+                None => bail!(
+                    "The key type '{}' must have parameter '{}'.",
+                    key_type,
+                    name
+                ),
+            }
+        }
+
+        Ok(map)
+    }
+
+    /// Return true if the JWK has any private key parameter of the key type.
+    ///
+    /// A symmetric key (oct) with the k parameter is always private.
+    pub fn is_private(&self) -> bool {
+        let keys: &[&str] = match self.key_type() {
+            "RSA" => &["d", "p", "q", "dp", "dq", "qi", "oth"],
+            "EC" | "OKP" => &["d"],
+            "oct" => &["k"],
+            _ => &Self::PRIVATE_PARAMETERS,
+        };
+        keys.iter().any(|key| self.map.contains_key(*key))
+    }
+
+    /// Return true if the JWK has no private key parameter, so it is safe to publish.
+    pub fn is_public(&self) -> bool {
+        !self.is_private()
+    }
+
+    pub(crate) fn remove_private_parameters(&mut self) {
+        for key in Self::PRIVATE_PARAMETERS {
+            self.map.remove(key);
+        }
+    }
+
     /// Set a value for a key type parameter (kty).
     ///
     /// # Arguments
@@ -289,6 +502,119 @@ impl Jwk {
         }
     }
 
+    /// Set values for a key operations parameter (key_ops) from a typed set.
+    ///
+    /// # Arguments
+    /// * `values` - key operations
+    pub fn set_key_operations_typed(&mut self, values: KeyOps) {
+        self.set_key_operations(values.names());
+    }
+
+    /// Return the permitted key operations as a typed set.
+    ///
+    /// All the operations are permitted when the key_ops parameter is absent.
+    /// Unregistered values are ignored.
+    pub fn key_operations_typed(&self) -> KeyOps {
+        match self.map.get("key_ops") {
+            Some(Value::Array(vals)) => {
+                KeyOps::from_names(vals.iter().filter_map(|val| val.as_str()))
+            }
+            Some(_) => KeyOps::empty(),
+            None => KeyOps::all(),
+        }
+    }
+
+    /// Return true if the key is permitted for all of the key operations.
+    ///
+    /// # Arguments
+    /// * `key_operations` - required key operations
+    pub fn is_for_key_operations(&self, key_operations: KeyOps) -> bool {
+        self.key_operations_typed().contains(key_operations)
+    }
+
+    /// Set a system time for a not before parameter (nbf).
+    ///
+    /// This is not a registered parameter but used for key rotation.
+    ///
+    /// # Arguments
+    /// * `value` - A time before which the key must not be used.
+    pub fn set_not_before(&mut self, value: &SystemTime) {
+        let val = value
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        self.map
+            .insert("nbf".to_string(), Value::Number(Number::from(val)));
+    }
+
+    /// Return the system time for a not before parameter (nbf).
+    ///
+    /// This returns None when the parameter is absent or is not a valid NumericDate.
+    pub fn not_before(&self) -> Option<SystemTime> {
+        self.numeric_date("nbf").ok().flatten()
+    }
+
+    /// Set a system time for a expires at parameter (exp).
+    ///
+    /// This is not a registered parameter but used for key rotation.
+    ///
+    /// # Arguments
+    /// * `value` - A time on or after which the key must not be used.
+    pub fn set_expires_at(&mut self, value: &SystemTime) {
+        let val = value
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        self.map
+            .insert("exp".to_string(), Value::Number(Number::from(val)));
+    }
+
+    /// Return the system time for a expires at parameter (exp).
+    ///
+    /// This returns None when the parameter is absent or is not a valid NumericDate.
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        self.numeric_date("exp").ok().flatten()
+    }
+
+    /// Return whether the key is valid at a specified time by the nbf and exp parameters.
+    ///
+    /// A key that has a nbf or exp parameter that is not a valid NumericDate
+    /// (a non-negative integer) is never valid.
+    ///
+    /// # Arguments
+    /// * `time` - A time to be checked.
+    pub fn is_valid_at(&self, time: &SystemTime) -> bool {
+        let (not_before, expires_at) = match (self.numeric_date("nbf"), self.numeric_date("exp")) {
+            (Ok(nbf), Ok(exp)) => (nbf, exp),
+            _ => return false,
+        };
+        if let Some(val) = not_before {
+            if *time < val {
+                return false;
+            }
+        }
+        if let Some(val) = expires_at {
+            if *time >= val {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn numeric_date(&self, key: &str) -> anyhow::Result<Option<SystemTime>> {
+        match self.map.get(key) {
+            Some(Value::Number(val)) => match val
+                .as_u64()
+                .and_then(|val| SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(val)))
+            {
+                Some(val) => Ok(Some(val)),
+                None => bail!("The JWK {} parameter must be a NumericDate: {}", key, val),
+            },
+            Some(val) => bail!("The JWK {} parameter must be a NumericDate: {}", key, val),
+            None => Ok(None),
+        }
+    }
+
     /// Set a value for a algorithm parameter (alg).
     ///
     /// # Arguments
@@ -317,11 +643,29 @@ impl Jwk {
     }
 
     /// Return a value for a key ID parameter (kid).
+    ///
+    /// If the kid parameter is not a string, this method returns None.
     pub fn key_id(&self) -> Option<&str> {
         match self.map.get("kid") {
             Some(Value::String(val)) => Some(val),
-            None => None,
-            _ => unreachable!(),
+            _ => None,
+        }
+    }
+
+    /// Return a raw value for a key ID parameter (kid).
+    pub fn key_id_value(&self) -> Option<&Value> {
+        self.map.get("kid")
+    }
+
+    /// Return a value for a key ID parameter (kid) or a error when it is not a string.
+    pub fn key_id_strict(&self) -> Result<Option<&str>, JoseError> {
+        match self.map.get("kid") {
+            Some(Value::String(val)) => Ok(Some(val)),
+            Some(val) => Err(JoseError::InvalidJwkFormat(anyhow::anyhow!(
+                "The JWK kid parameter must be a string: {}",
+                val
+            ))),
+            None => Ok(None),
         }
     }
 
@@ -512,7 +856,7 @@ impl Jwk {
     fn check_parameter(key: &str, value: &Value) -> Result<(), JoseError> {
         (|| -> anyhow::Result<()> {
             match key {
-                "kty" | "use" | "alg" | "kid" | "x5u" | "crv" => match &value {
+                "kty" | "use" | "alg" | "x5u" | "crv" => match &value {
                     Value::String(_) => {}
                     _ => bail!("The JWK {} parameter must be a string.", key),
                 },
@@ -590,9 +934,70 @@ impl Display for Jwk {
 mod tests {
     use anyhow::Result;
 
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::jwk::alg::ec::EcCurve;
+    use crate::jwk::alg::ecx::EcxCurve;
+    use crate::jwk::alg::ed::EdCurve;
     use crate::jwk::Jwk;
+    use crate::util::{self, HashAlgorithm};
     use crate::Value;
 
+    #[test]
+    fn test_from_public_pem_and_der() -> Result<()> {
+        for (path, kty, crv) in [
+            ("pem/RSA_2048bit_public.pem", "RSA", None),
+            ("pem/RSA_2048bit_traditional_public.pem", "RSA", None),
+            ("pem/RSA-PSS_2048bit_SHA-256_public.pem", "RSA", None),
+            ("pem/EC_P-256_public.pem", "EC", Some("P-256")),
+            ("pem/EC_P-384_public.pem", "EC", Some("P-384")),
+            ("pem/EC_P-521_public.pem", "EC", Some("P-521")),
+            ("pem/EC_secp256k1_public.pem", "EC", Some("secp256k1")),
+            ("pem/ED25519_public.pem", "OKP", Some("Ed25519")),
+            ("pem/ED448_public.pem", "OKP", Some("Ed448")),
+            ("pem/X25519_public.pem", "OKP", Some("X25519")),
+            ("pem/X448_public.pem", "OKP", Some("X448")),
+        ] {
+            let pem = load_file(path)?;
+            let jwk = Jwk::from_public_pem(&pem)?;
+            assert_eq!(jwk.key_type(), kty, "{}", path);
+            assert_eq!(jwk.curve(), crv, "{}", path);
+            assert!(jwk.parameter("d").is_none());
+
+            let (label, der) = util::parse_pem(&pem)?;
+            if label == "PUBLIC KEY" {
+                assert_eq!(Jwk::from_public_der(&der)?, jwk, "{}", path);
+            }
+        }
+
+        assert!(Jwk::from_public_pem(load_file("pem/EC_P-256_private.pem")?).is_err());
+        assert!(Jwk::from_public_der(b"public key").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_string_key_id() -> Result<()> {
+        for kid in [Value::from(123), serde_json::json!(["a", "b"])] {
+            let mut jwk = Jwk::new("oct");
+            jwk.set_parameter("kid", Some(kid.clone()))?;
+            assert_eq!(jwk.key_id(), None);
+            assert_eq!(jwk.key_id_value(), Some(&kid));
+            assert!(jwk.key_id_strict().is_err());
+
+            let json = format!(r#"{{"keys":[{{"kty":"oct","kid":{},"k":"AAECAw"}}]}}"#, kid);
+            let jwk_set = crate::jwk::JwkSet::from_bytes(json.as_bytes())?;
+            assert_eq!(jwk_set.keys().len(), 1);
+            assert_eq!(jwk_set.keys()[0].key_id(), None);
+        }
+
+        let mut jwk = Jwk::new("oct");
+        assert_eq!(jwk.key_id_strict()?, None);
+        jwk.set_key_id("kid");
+        assert_eq!(jwk.key_id_strict()?, Some("kid"));
+        Ok(())
+    }
+
     #[test]
     fn test_new_jws_header() -> Result<()> {
         let mut jwk = Jwk::new("oct");
@@ -641,4 +1046,105 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_is_private_and_is_public() -> Result<()> {
+        let jwks = [
+            (Jwk::generate_rsa_key(2048)?, "RSA"),
+            (Jwk::generate_ec_key(EcCurve::P256)?, "EC"),
+            (Jwk::generate_ed_key(EdCurve::Ed25519)?, "OKP"),
+            (Jwk::generate_ecx_key(EcxCurve::X25519)?, "OKP"),
+        ];
+        for (private_key, kty) in jwks {
+            assert_eq!(private_key.key_type(), kty);
+            assert!(private_key.is_private());
+            assert!(!private_key.is_public());
+
+            let public_key = private_key.to_public_key()?;
+            assert!(!public_key.is_private());
+            assert!(public_key.is_public());
+        }
+
+        let oct = Jwk::generate_oct_key(32)?;
+        assert!(oct.is_private());
+        assert!(!Jwk::new("oct").is_private());
+
+        // A CRT parameter alone is private material of RSA.
+        let mut rsa = Jwk::generate_rsa_key(2048)?.to_public_key()?;
+        rsa.set_parameter("p", Some(Value::from("AQAB")))?;
+        assert!(rsa.is_private());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_thumbprint() -> Result<()> {
+        // RFC 7638 3.1. Example JWK Thumbprint Computation
+        let jwk = Jwk::from_bytes(concat!(
+            r#"{"kty":"RSA","#,
+            r#""n":"0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw","#,
+            r#""e":"AQAB","alg":"RS256","kid":"2011-04-29"}"#
+        ))?;
+        assert_eq!(
+            jwk.thumbprint_sha256()?,
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+
+        let mut other = jwk.clone();
+        other.set_key_id("other");
+        other.set_key_use("sig");
+        assert_eq!(
+            other.thumbprint(HashAlgorithm::Sha256)?,
+            jwk.thumbprint_sha256()?
+        );
+        assert_ne!(
+            other.thumbprint(HashAlgorithm::Sha384)?,
+            jwk.thumbprint_sha256()?
+        );
+
+        let mut broken = Jwk::new("EC");
+        broken.set_curve("P-256");
+        assert!(broken.thumbprint_sha256().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_same_key() -> Result<()> {
+        let private_key = Jwk::from_bytes(load_file("jwk/EC_P-256_private.jwk")?)?;
+        let public_key = Jwk::from_bytes(load_file("jwk/EC_P-256_public.jwk")?)?;
+
+        let mut rotated = public_key.clone();
+        rotated.set_key_id("2024-01-01");
+        rotated.set_key_use("sig");
+        assert_ne!(rotated, public_key);
+        assert!(rotated.same_key(&public_key));
+        assert!(private_key.same_key(&rotated));
+
+        let other = Jwk::from_bytes(load_file("jwk/EC_P-384_public.jwk")?)?;
+        assert!(!other.same_key(&public_key));
+
+        let mut oct1 = Jwk::new("oct");
+        oct1.set_parameter("k", Some(Value::String("AAAA".to_string())))?;
+        oct1.set_key_id("1");
+        let mut oct2 = oct1.clone();
+        oct2.set_key_id("2");
+        assert!(oct1.same_key(&oct2));
+        oct2.set_parameter("k", Some(Value::String("AAAB".to_string())))?;
+        assert!(!oct1.same_key(&oct2));
+
+        let unknown = Jwk::new("unknown");
+        assert!(!unknown.same_key(&unknown));
+
+        Ok(())
+    }
+
+    fn load_file(path: &str) -> Result<Vec<u8>> {
+        let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        pb.push("data");
+        pb.push(path);
+
+        let data = fs::read(&pb)?;
+        Ok(data)
+    }
 }
@@ -4,6 +4,7 @@ use std::io::Read;
 use std::ops::Bound::Included;
 use std::string::ToString;
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use anyhow::bail;
 
@@ -20,13 +21,50 @@ pub struct JwkSet {
 
 impl JwkSet {
     pub fn new() -> Self {
+        let mut params = Map::new();
+        params.insert("keys".to_string(), Value::Array(Vec::new()));
+
         Self {
             keys: Vec::new(),
-            params: Map::new(),
+            params,
             kid_map: BTreeMap::new(),
         }
     }
 
+    /// Return a JWK set of the public keys, e.g. for publishing a JWKS endpoint.
+    ///
+    /// A private key is converted into its public key with the kid and alg parameters kept.
+    /// A key that has no kid is assigned its JWK thumbprint (RFC 7638) using SHA-256.
+    /// Symmetric keys (oct) cannot be published, so they are rejected.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - JWKs of public keys or key pairs.
+    pub fn from_public_keys(keys: &[&Jwk]) -> Result<Self, JoseError> {
+        let mut jwk_set = Self::new();
+        for jwk in keys {
+            let public_key = if jwk.key_type() == "oct" {
+                return Err(JoseError::InvalidJwkFormat(anyhow::anyhow!(
+                    "The key type 'oct' doesn't have public key."
+                )));
+            } else if jwk.is_private() {
+                let mut public_key = jwk.to_public_key()?;
+                if let Some(val) = jwk.key_id() {
+                    public_key.set_key_id(val);
+                }
+                if let Some(val) = jwk.algorithm() {
+                    public_key.set_algorithm(val);
+                }
+                public_key
+            } else {
+                (*jwk).clone()
+            };
+            jwk_set.push_key(public_key);
+        }
+        jwk_set.set_key_ids_to_thumbprint()?;
+        Ok(jwk_set)
+    }
+
     pub fn from_map(map: Map<String, Value>) -> Result<Self, JoseError> {
         (|| -> anyhow::Result<Self> {
             let mut kid_map = BTreeMap::new();
@@ -87,6 +125,66 @@ impl JwkSet {
         })
     }
 
+    /// Return a JWK set that skips keys that cannot be parsed or have a unsupported kty parameter.
+    ///
+    /// Unlike `from_bytes`, a malformed key doesn't fail the whole set.
+    /// The errors of the skipped keys are returned with the set.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a JWK set formatted by JSON.
+    pub fn from_bytes_lossy(input: impl AsRef<[u8]>) -> (Self, Vec<JoseError>) {
+        let mut map: Map<String, Value> = match serde_json::from_slice(input.as_ref()) {
+            Ok(val) => val,
+            Err(err) => return (Self::new(), vec![JoseError::InvalidJwkFormat(err.into())]),
+        };
+
+        let vals = match map.remove("keys") {
+            Some(Value::Array(vals)) => vals,
+            Some(_) => {
+                let err = anyhow::anyhow!("The JWT keys parameter must be a array.");
+                return (Self::new(), vec![JoseError::InvalidJwkFormat(err)]);
+            }
+            None => {
+                let err = anyhow::anyhow!("The JWK set must have a keys parameter.");
+                return (Self::new(), vec![JoseError::InvalidJwkFormat(err)]);
+            }
+        };
+
+        let mut errors = Vec::new();
+        let mut keys = Vec::new();
+        for val in vals {
+            let result = (|| -> anyhow::Result<Value> {
+                let jwk = match &val {
+                    Value::Object(val) => Jwk::from_map(val.clone())?,
+                    _ => bail!("An element of the JWK set keys parameter must be a object."),
+                };
+                match jwk.key_type() {
+                    "oct" | "RSA" | "EC" | "OKP" => {}
+                    val => bail!("Unsupported key type: {}", val),
+                }
+                Ok(val)
+            })()
+            .map_err(|err| match err.downcast::<JoseError>() {
+                Ok(err) => err,
+                Err(err) => JoseError::InvalidJwkFormat(err),
+            });
+            match result {
+                Ok(val) => keys.push(val),
+                Err(err) => errors.push(err),
+            }
+        }
+        map.insert("keys".to_string(), Value::Array(keys));
+
+        match Self::from_map(map) {
+            Ok(val) => (val, errors),
+            Err(err) => {
+                errors.push(err);
+                (Self::new(), errors)
+            }
+        }
+    }
+
     pub fn get(&self, key_id: &str) -> Vec<&Jwk> {
         let mut vec = Vec::new();
         for (_, val) in self.kid_map.range((
@@ -99,6 +197,22 @@ impl JwkSet {
         vec
     }
 
+    /// Return the keys that have a specified key ID and are valid at a specified time.
+    ///
+    /// Keys that are not yet valid by the nbf parameter or are expired by the exp parameter
+    /// are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - a key ID.
+    /// * `time` - a time at which keys are used for verification.
+    pub fn get_for_verification(&self, key_id: &str, time: &SystemTime) -> Vec<&Jwk> {
+        self.get(key_id)
+            .into_iter()
+            .filter(|jwk| jwk.is_valid_at(time))
+            .collect()
+    }
+
     pub fn keys(&self) -> Vec<&Jwk> {
         self.keys.iter().map(|e| e.as_ref()).collect()
     }
@@ -129,6 +243,92 @@ impl JwkSet {
                 _ => unreachable!(),
             }
             self.keys.remove(index);
+            self.rebuild_kid_map();
+        }
+    }
+
+    /// Append the keys of another JWK set, skipping keys that already exist in this set.
+    ///
+    /// A key is regarded as a duplicate when it has the same JWK thumbprint (RFC 7638)
+    /// and the same members as an existing key. So keys that differ only in
+    /// non-cryptographic members like kid or use are kept as distinct keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - a JWK set to be merged.
+    pub fn merge(&mut self, other: JwkSet) {
+        for jwk in other.keys {
+            if !self.contains_same_key(&jwk, self.keys.len()) {
+                let jwk = Arc::try_unwrap(jwk).unwrap_or_else(|jwk| jwk.as_ref().clone());
+                self.push_key(jwk);
+            }
+        }
+    }
+
+    /// Remove duplicate keys in this set, keeping the first occurrence.
+    ///
+    /// Duplicates are detected in the same way as [`JwkSet::merge`].
+    pub fn dedup(&mut self) {
+        let mut index = 0;
+        while index < self.keys.len() {
+            if self.contains_same_key(&self.keys[index], index) {
+                match self.params.get_mut("keys") {
+                    Some(Value::Array(keys)) => {
+                        keys.remove(index);
+                    }
+                    _ => unreachable!(),
+                }
+                self.keys.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+        self.rebuild_kid_map();
+    }
+
+    /// Set the JWK thumbprint (RFC 7638) using SHA-256 as the kid of keys that have no kid.
+    pub fn set_key_ids_to_thumbprint(&mut self) -> Result<(), JoseError> {
+        let mut keys = Vec::with_capacity(self.keys.len());
+        for jwk in &self.keys {
+            let mut jwk = jwk.as_ref().clone();
+            if jwk.key_id().is_none() {
+                let thumbprint = jwk.thumbprint_sha256()?;
+                jwk.set_key_id(thumbprint);
+            }
+            keys.push(jwk);
+        }
+
+        self.params
+            .insert("keys".to_string(), Value::Array(Vec::new()));
+        self.keys.clear();
+        self.kid_map.clear();
+        for jwk in keys {
+            self.push_key(jwk);
+        }
+        Ok(())
+    }
+
+    /// Return the JSON representation of the JWK set: {"keys":[...]}.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_string().into_bytes()
+    }
+
+    fn contains_same_key(&self, jwk: &Jwk, end: usize) -> bool {
+        let thumbprint = jwk.thumbprint_sha256().ok();
+        self.keys[..end].iter().any(|e| {
+            if thumbprint.is_some() && e.thumbprint_sha256().ok() != thumbprint {
+                return false;
+            }
+            e.as_ref() == jwk
+        })
+    }
+
+    fn rebuild_kid_map(&mut self) {
+        self.kid_map.clear();
+        for (i, jwk) in self.keys.iter().enumerate() {
+            if let Some(kid) = jwk.key_id() {
+                self.kid_map.insert((kid.to_string(), i), Arc::clone(jwk));
+            }
         }
     }
 }
@@ -169,9 +369,37 @@ impl Display for JwkSet {
 mod tests {
     use super::*;
     use anyhow::Result;
+    use serde_json::json;
     use std::fs::File;
     use std::path::PathBuf;
 
+    #[test]
+    fn test_from_public_keys() -> Result<()> {
+        let ec_key = Jwk::from_reader(&mut load_file("jwk/EC_P-256_private.jwk")?)?;
+        let mut rsa_key = Jwk::from_reader(&mut load_file("jwk/RSA_public.jwk")?)?;
+        rsa_key.set_key_id("rsa-1");
+
+        let jwk_set = JwkSet::from_public_keys(&[&ec_key, &rsa_key])?;
+        let parsed = JwkSet::from_bytes(jwk_set.to_bytes())?;
+        assert_eq!(parsed, jwk_set);
+
+        let keys = parsed.keys();
+        assert_eq!(keys.len(), 2);
+        assert!(keys.iter().all(|jwk| jwk.is_public()));
+        assert_eq!(keys[0].key_type(), "EC");
+        assert_eq!(keys[0].key_id(), Some(ec_key.thumbprint_sha256()?.as_str()));
+        assert!(keys[0].same_key(&ec_key));
+        assert_eq!(keys[1].key_type(), "RSA");
+        assert_eq!(keys[1].key_id(), Some("rsa-1"));
+        assert_eq!(parsed.get("rsa-1").len(), 1);
+
+        let mut oct_key = Jwk::new("oct");
+        oct_key.set_key_value(b"0123456789ABCDEF0123456789ABCDEF");
+        assert!(JwkSet::from_public_keys(&[&rsa_key, &oct_key]).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_load_jwt_set() -> Result<()> {
         let mut file = load_file("jwks/test.jwks")?;
@@ -186,6 +414,157 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_merge_and_dedup() -> Result<()> {
+        let jwks1 = JwkSet::from_bytes(
+            br#"{"keys":[
+                {"kty":"oct","kid":"1","k":"AAECAwQFBgcICQoLDA0ODw"},
+                {"kty":"oct","kid":"2","k":"EBESExQVFhcYGRobHB0eHw"}
+            ]}"#,
+        )?;
+        let jwks2 = JwkSet::from_bytes(
+            br#"{"keys":[
+                {"kty":"oct","kid":"2","k":"EBESExQVFhcYGRobHB0eHw"},
+                {"kty":"oct","kid":"3","k":"EBESExQVFhcYGRobHB0eHw"},
+                {"kty":"oct","kid":"4","k":"ICEiIyQlJicoKSorLC0uLw"},
+                {"kty":"oct","kid":"4","k":"ICEiIyQlJicoKSorLC0uLw"}
+            ]}"#,
+        )?;
+
+        let mut merged = jwks1.clone();
+        merged.merge(jwks2.clone());
+        let kids: Vec<&str> = merged.keys().iter().filter_map(|e| e.key_id()).collect();
+        assert_eq!(kids, vec!["1", "2", "3", "4"]);
+        assert_eq!(merged.get("4").len(), 1);
+        assert_eq!(
+            merged
+                .as_ref()
+                .get("keys")
+                .and_then(|e| e.as_array())
+                .unwrap()
+                .len(),
+            4
+        );
+
+        let mut deduped = jwks2;
+        deduped.dedup();
+        let kids: Vec<&str> = deduped.keys().iter().filter_map(|e| e.key_id()).collect();
+        assert_eq!(kids, vec!["2", "3", "4"]);
+        assert_eq!(deduped.get("4").len(), 1);
+        assert_eq!(deduped.get("3")[0].key_id(), Some("3"));
+
+        let mut empty = JwkSet::new();
+        empty.merge(jwks1);
+        assert_eq!(empty.keys().len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_for_verification() -> Result<()> {
+        let now = SystemTime::now();
+        let day = std::time::Duration::from_secs(24 * 60 * 60);
+
+        let mut expired = Jwk::generate_oct_key(32)?;
+        expired.set_key_id("rotating");
+        expired.set_not_before(&(now - day * 2));
+        expired.set_expires_at(&(now - day));
+        assert!(expired.expires_at().unwrap() <= now - day);
+        assert!(!expired.is_valid_at(&now));
+
+        let mut active = Jwk::generate_oct_key(32)?;
+        active.set_key_id("rotating");
+        active.set_not_before(&(now - day));
+        active.set_expires_at(&(now + day));
+
+        let mut not_yet_valid = Jwk::generate_oct_key(32)?;
+        not_yet_valid.set_key_id("rotating");
+        not_yet_valid.set_not_before(&(now + day));
+
+        let mut jwks = JwkSet::new();
+        jwks.push_key(expired.clone());
+        jwks.push_key(active.clone());
+        jwks.push_key(not_yet_valid.clone());
+
+        assert_eq!(jwks.get("rotating").len(), 3);
+        assert_eq!(jwks.get_for_verification("rotating", &now), vec![&active]);
+        assert_eq!(
+            jwks.get_for_verification("rotating", &(now - day - day / 2)),
+            vec![&expired]
+        );
+        assert_eq!(
+            jwks.get_for_verification("rotating", &(now + day * 2)),
+            vec![&not_yet_valid]
+        );
+
+        // A malformed nbf or exp never makes a key unbounded.
+        let mut jwks = JwkSet::new();
+        for (name, value) in [
+            ("exp", json!("2000000000")),
+            ("exp", json!(-1)),
+            ("exp", json!(1.5)),
+            ("nbf", json!("0")),
+            ("nbf", json!(-1)),
+            ("nbf", json!(u64::MAX)),
+        ] {
+            let mut jwk = Jwk::generate_oct_key(32)?;
+            jwk.set_key_id("malformed");
+            jwk.set_parameter(name, Some(value))?;
+            assert!(
+                !jwk.is_valid_at(&now),
+                "{} = {:?}",
+                name,
+                jwk.parameter(name)
+            );
+            jwks.push_key(jwk);
+        }
+        assert_eq!(jwks.get("malformed").len(), 6);
+        assert!(jwks.get_for_verification("malformed", &now).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_bytes_lossy() -> Result<()> {
+        let json = r#"{
+            "keys": [
+                {
+                    "kty": "EC",
+                    "kid": "ec-1",
+                    "crv": "P-256",
+                    "x": "f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU",
+                    "y": "x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0"
+                },
+                {
+                    "kty": "PQC",
+                    "kid": "unknown-1",
+                    "pub": "AQID"
+                },
+                "not a key"
+            ],
+            "x-extra": true
+        }"#;
+
+        let (jwks, errors) = JwkSet::from_bytes_lossy(json);
+        assert_eq!(jwks.keys().len(), 1);
+        assert_eq!(jwks.get("ec-1").len(), 1);
+        assert!(jwks.get("unknown-1").is_empty());
+        assert_eq!(jwks.as_ref().get("x-extra"), Some(&Value::Bool(true)));
+        assert_eq!(errors.len(), 2);
+        assert!(errors
+            .iter()
+            .all(|err| matches!(err, JoseError::InvalidJwkFormat(_))));
+
+        // The strict parser fails for the whole set.
+        assert!(JwkSet::from_bytes(json).is_err());
+
+        let (jwks, errors) = JwkSet::from_bytes_lossy("{}");
+        assert!(jwks.keys().is_empty());
+        assert_eq!(errors.len(), 1);
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<File> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
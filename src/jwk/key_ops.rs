@@ -0,0 +1,174 @@
+use std::fmt::Display;
+use std::ops::{BitAnd, BitOr, BitOrAssign, Sub};
+
+/// Represents a set of values of a key operations parameter (key_ops).
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub struct KeyOps(u8);
+
+impl KeyOps {
+    pub const SIGN: KeyOps = KeyOps(1 << 0);
+    pub const VERIFY: KeyOps = KeyOps(1 << 1);
+    pub const ENCRYPT: KeyOps = KeyOps(1 << 2);
+    pub const DECRYPT: KeyOps = KeyOps(1 << 3);
+    pub const WRAP_KEY: KeyOps = KeyOps(1 << 4);
+    pub const UNWRAP_KEY: KeyOps = KeyOps(1 << 5);
+    pub const DERIVE_KEY: KeyOps = KeyOps(1 << 6);
+    pub const DERIVE_BITS: KeyOps = KeyOps(1 << 7);
+
+    const NAMES: [(KeyOps, &'static str); 8] = [
+        (Self::SIGN, "sign"),
+        (Self::VERIFY, "verify"),
+        (Self::ENCRYPT, "encrypt"),
+        (Self::DECRYPT, "decrypt"),
+        (Self::WRAP_KEY, "wrapKey"),
+        (Self::UNWRAP_KEY, "unwrapKey"),
+        (Self::DERIVE_KEY, "deriveKey"),
+        (Self::DERIVE_BITS, "deriveBits"),
+    ];
+
+    /// Return a empty set.
+    pub const fn empty() -> Self {
+        KeyOps(0)
+    }
+
+    /// Return a set of all the registered key operations.
+    pub const fn all() -> Self {
+        KeyOps(u8::MAX)
+    }
+
+    /// Return a key operation for a registered value of key_ops.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A value of key_ops
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find(|(_, val)| *val == name)
+            .map(|(ops, _)| *ops)
+    }
+
+    /// Return a set of the registered values. Unregistered values are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `names` - Values of key_ops
+    pub fn from_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        names
+            .into_iter()
+            .filter_map(Self::from_name)
+            .fold(Self::empty(), |acc, val| acc | val)
+    }
+
+    /// Return the values of key_ops in the order of RFC 7517.
+    pub fn names(&self) -> Vec<&'static str> {
+        Self::NAMES
+            .iter()
+            .filter(|(ops, _)| self.contains(*ops))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Return true if this set contains all the key operations of other.
+    pub fn contains(&self, other: KeyOps) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Return true if this set contains any key operation of other.
+    pub fn intersects(&self, other: KeyOps) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub fn insert(&mut self, other: KeyOps) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: KeyOps) {
+        self.0 &= !other.0;
+    }
+}
+
+impl BitOr for KeyOps {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        KeyOps(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for KeyOps {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for KeyOps {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        KeyOps(self.0 & rhs.0)
+    }
+}
+
+impl Sub for KeyOps {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        KeyOps(self.0 & !rhs.0)
+    }
+}
+
+impl Display for KeyOps {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.write_str(&self.names().join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::KeyOps;
+    use crate::jwk::Jwk;
+
+    #[test]
+    fn test_key_ops() -> Result<()> {
+        let ops = KeyOps::SIGN | KeyOps::VERIFY;
+        assert!(ops.contains(KeyOps::SIGN));
+        assert!(!ops.contains(KeyOps::SIGN | KeyOps::ENCRYPT));
+        assert!(ops.intersects(KeyOps::SIGN | KeyOps::ENCRYPT));
+        assert_eq!(ops - KeyOps::SIGN, KeyOps::VERIFY);
+        assert_eq!(ops.names(), vec!["sign", "verify"]);
+        assert_eq!(KeyOps::from_name("wrapKey"), Some(KeyOps::WRAP_KEY));
+        assert_eq!(KeyOps::from_name("WrapKey"), None);
+        assert_eq!(KeyOps::all().names().len(), 8);
+        assert!(KeyOps::default().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwk_key_operations_typed() -> Result<()> {
+        let jwk = Jwk::from_bytes(
+            br#"{"kty":"oct","k":"AAAAAAAAAAAAAAAAAAAAAA","key_ops":["encrypt","decrypt","x-custom"]}"#,
+        )?;
+        let ops = jwk.key_operations_typed();
+        assert_eq!(ops, KeyOps::ENCRYPT | KeyOps::DECRYPT);
+        assert!(jwk.is_for_key_operations(KeyOps::ENCRYPT));
+        assert!(!jwk.is_for_key_operations(KeyOps::ENCRYPT | KeyOps::WRAP_KEY));
+
+        // A required operation is enforced by the algorithms.
+        assert!(crate::jwe::A128KW.encrypter_from_jwk(&jwk).is_err());
+
+        let mut jwk = Jwk::new("oct");
+        assert_eq!(jwk.key_operations_typed(), KeyOps::all());
+        jwk.set_key_operations_typed(KeyOps::WRAP_KEY | KeyOps::UNWRAP_KEY);
+        assert_eq!(jwk.key_operations(), Some(vec!["wrapKey", "unwrapKey"]));
+        assert!(jwk.is_for_key_operation("wrapKey"));
+
+        Ok(())
+    }
+}
@@ -17,6 +17,16 @@ pub trait KeyPair: Debug + Send + Sync {
     fn to_jwk_public_key(&self) -> Jwk;
     fn to_jwk_key_pair(&self) -> Jwk;
 
+    /// Return the public key as a JWK that is safe to publish (e.g. in a JWK set endpoint).
+    ///
+    /// The private key parameters (d, p, q, dp, dq, qi, oth and k) are never included,
+    /// even if the JWK of the public key of an implementation has them.
+    fn public_key_jwk_from_private(&self) -> Jwk {
+        let mut jwk = self.to_jwk_public_key();
+        jwk.remove_private_parameters();
+        jwk
+    }
+
     fn box_clone(&self) -> Box<dyn KeyPair>;
 }
 
@@ -33,3 +43,86 @@ impl Clone for Box<dyn KeyPair> {
         self.box_clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::KeyPair;
+    use crate::jwk::alg::ec::{EcCurve, EcKeyPair};
+    use crate::jwk::alg::ecx::{EcxCurve, EcxKeyPair};
+    use crate::jwk::alg::ed::{EdCurve, EdKeyPair};
+    use crate::jwk::alg::rsa::RsaKeyPair;
+    use crate::jwk::alg::rsapss::RsaPssKeyPair;
+    use crate::jwk::Jwk;
+    use crate::util::SHA_256;
+
+    #[test]
+    fn test_from_key_pair_for_jwk() -> Result<()> {
+        let ec_key_pair = EcKeyPair::generate(EcCurve::P384)?;
+        let ecx_key_pair = EcxKeyPair::generate(EcxCurve::X448)?;
+        let ed_key_pair = EdKeyPair::generate(EdCurve::Ed25519)?;
+        let rsa_key_pair = RsaKeyPair::generate(2048)?;
+        let rsapss_key_pair = RsaPssKeyPair::generate(2048, SHA_256, SHA_256, 32)?;
+
+        let jwks: [(Jwk, &dyn KeyPair, &str, Option<&str>); 5] = [
+            ((&ec_key_pair).into(), &ec_key_pair, "EC", Some("P-384")),
+            ((&ecx_key_pair).into(), &ecx_key_pair, "OKP", Some("X448")),
+            ((&ed_key_pair).into(), &ed_key_pair, "OKP", Some("Ed25519")),
+            ((&rsa_key_pair).into(), &rsa_key_pair, "RSA", None),
+            ((&rsapss_key_pair).into(), &rsapss_key_pair, "RSA", None),
+        ];
+        for (jwk, key_pair, kty, crv) in jwks {
+            assert_eq!(jwk.key_type(), kty);
+            assert_eq!(jwk.curve(), crv);
+            assert_eq!(jwk, key_pair.to_jwk_public_key());
+            assert!(jwk.is_public());
+
+            let private_key = key_pair.to_jwk_private_key();
+            assert_eq!(private_key.key_type(), kty);
+            assert!(private_key.is_private());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_public_key_jwk_from_private() -> Result<()> {
+        let mut ec_key_pair = EcKeyPair::generate(EcCurve::P256)?;
+        ec_key_pair.set_key_id(Some("ec-1"));
+
+        let key_pairs: Vec<(Box<dyn KeyPair>, &[&str])> = vec![
+            (Box::new(ec_key_pair), &["crv", "x", "y"]),
+            (
+                Box::new(EcxKeyPair::generate(EcxCurve::X25519)?),
+                &["crv", "x"],
+            ),
+            (
+                Box::new(EdKeyPair::generate(EdCurve::Ed25519)?),
+                &["crv", "x"],
+            ),
+            (Box::new(RsaKeyPair::generate(2048)?), &["n", "e"]),
+            (
+                Box::new(RsaPssKeyPair::generate(2048, SHA_256, SHA_256, 32)?),
+                &["n", "e"],
+            ),
+        ];
+
+        for (key_pair, public_parameters) in key_pairs {
+            let private_jwk = key_pair.to_jwk_key_pair();
+            assert!(private_jwk.parameter("d").is_some());
+
+            let jwk = key_pair.public_key_jwk_from_private();
+            for key in Jwk::PRIVATE_PARAMETERS {
+                assert!(jwk.parameter(key).is_none(), "{} {}", jwk.key_type(), key);
+            }
+            for key in public_parameters {
+                assert_eq!(jwk.parameter(key), private_jwk.parameter(key));
+            }
+            assert_eq!(jwk.key_id(), key_pair.key_id());
+            assert_eq!(jwk, key_pair.to_jwk_public_key());
+        }
+
+        Ok(())
+    }
+}
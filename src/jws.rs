@@ -1,21 +1,31 @@
 //! JSON Web Signature (JWS) support.
 
 pub mod alg;
+mod external_signer;
 mod jws_algorithm;
 mod jws_context;
 mod jws_header;
 mod jws_header_set;
+mod signing_algorithm;
+mod verifier_registry;
+mod x509_url_resolver;
 
 use once_cell::sync::Lazy;
 
+use crate::jwk::Jwk;
 use crate::JoseError;
 
+pub use crate::jws::external_signer::ExternalJwsSigner;
 pub use crate::jws::jws_algorithm::JwsAlgorithm;
 pub use crate::jws::jws_algorithm::JwsSigner;
 pub use crate::jws::jws_algorithm::JwsVerifier;
 pub use crate::jws::jws_context::JwsContext;
 pub use crate::jws::jws_header::JwsHeader;
 pub use crate::jws::jws_header_set::JwsHeaderSet;
+pub use crate::jws::signing_algorithm::SigningAlgorithm;
+pub use crate::jws::verifier_registry::VerifierRegistry;
+pub use crate::jws::x509_url_resolver::X509UrlFetcher;
+pub use crate::jws::x509_url_resolver::X509UrlResolver;
 
 use crate::jws::alg::hmac::HmacJwsAlgorithm;
 pub use HmacJwsAlgorithm::Hs256 as HS256;
@@ -173,6 +183,91 @@ where
     DEFAULT_CONTEXT.deserialize_compact_with_selector(input, selector)
 }
 
+/// Deserialize the input that is formatted by compact serialization
+/// with one of the allowed verifiers.
+///
+/// Each verifier whose algorithm matches the alg header claim is tried in order,
+/// and the input is rejected if no verifier matches or verifies the signature.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `verifiers` - The allowed JWS verifiers.
+pub fn deserialize_compact_with_allowed(
+    input: impl AsRef<[u8]>,
+    verifiers: &[&dyn JwsVerifier],
+) -> Result<(Vec<u8>, JwsHeader), JoseError> {
+    DEFAULT_CONTEXT.deserialize_compact_with_allowed(input, verifiers)
+}
+
+/// Return a representation of the data that is formatted by compact serialization
+/// with a detached payload (RFC 7515 Appendix F).
+///
+/// # Arguments
+///
+/// * `payload` - The payload data.
+/// * `header` - The JWS heaser claims.
+/// * `signer` - The JWS signer.
+pub fn serialize_compact_detached(
+    payload: &[u8],
+    header: &JwsHeader,
+    signer: &dyn JwsSigner,
+) -> Result<String, JoseError> {
+    DEFAULT_CONTEXT.serialize_compact_detached(payload, header, signer)
+}
+
+/// Deserialize the input that is formatted by compact serialization
+/// with a detached payload (RFC 7515 Appendix F).
+///
+/// The default context accepts no critical header claims. Use a JwsContext to accept
+/// the b64 header claim and profile specific ones.
+///
+/// # Arguments
+///
+/// * `input` - The input data that has the empty payload part.
+/// * `payload` - The detached payload data.
+/// * `verifier` - The JWS verifier.
+pub fn deserialize_compact_detached(
+    input: impl AsRef<[u8]>,
+    payload: &[u8],
+    verifier: &dyn JwsVerifier,
+) -> Result<JwsHeader, JoseError> {
+    DEFAULT_CONTEXT.deserialize_compact_detached(input, payload, verifier)
+}
+
+/// Deserialize the input that is formatted by compact serialization with a verifier that
+/// is built from the jwk header claim.
+///
+/// The JWK thumbprint of the embedded key must match the pinned one.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `pinned_thumbprint` - The expected base64url encoded JWK thumbprint (SHA-256) of the key.
+pub fn verify_with_embedded_jwk(
+    input: impl AsRef<[u8]>,
+    pinned_thumbprint: &str,
+) -> Result<(Vec<u8>, JwsHeader), JoseError> {
+    DEFAULT_CONTEXT.deserialize_compact_with_embedded_jwk(input, pinned_thumbprint)
+}
+
+/// Deserialize the input that is formatted by compact serialization with a verifier that
+/// is built from the jwk header claim, whatever the embedded key is.
+///
+/// # Security
+///
+/// A successful verification only proves that the JWS is signed by the embedded key.
+/// Use [`verify_with_embedded_jwk`] unless the key is trusted by other means.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+pub fn verify_with_embedded_jwk_unpinned(
+    input: impl AsRef<[u8]>,
+) -> Result<(Vec<u8>, JwsHeader), JoseError> {
+    DEFAULT_CONTEXT.deserialize_compact_with_embedded_jwk_unpinned(input)
+}
+
 /// Deserialize the input that is formatted by json serialization.
 ///
 /// # Arguments
@@ -204,16 +299,55 @@ where
     DEFAULT_CONTEXT.deserialize_json_with_selector(input, selector)
 }
 
+/// Return the signing algorithm for a alg header claim value.
+///
+/// # Arguments
+///
+/// * `name` - a alg header claim value
+pub fn signing_algorithm_from_name(name: &str) -> Option<SigningAlgorithm> {
+    SigningAlgorithm::from_name(name)
+}
+
+/// Return the default signing algorithm for the key type and curve of a JWK.
+///
+/// P-256, P-384, P-521 and secp256k1 keys use ES256, ES384, ES512 and ES256K,
+/// Ed25519 and Ed448 keys use EdDSA, and RSA keys use RS256. Other keys, including
+/// symmetric keys, have no default. The alg parameter of the JWK is not considered.
+///
+/// # Arguments
+///
+/// * `jwk` - a JWK of a key pair or a public key.
+pub fn default_algorithm_for(jwk: &Jwk) -> Option<&'static str> {
+    let alg = match (jwk.key_type(), jwk.curve()) {
+        ("EC", Some("P-256")) => "ES256",
+        ("EC", Some("P-384")) => "ES384",
+        ("EC", Some("P-521")) => "ES512",
+        ("EC", Some("secp256k1")) => "ES256K",
+        ("OKP", Some("Ed25519")) | ("OKP", Some("Ed448")) => "EdDSA",
+        ("RSA", _) => "RS256",
+        _ => return None,
+    };
+    Some(alg)
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
     use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::thread;
 
     use anyhow::Result;
     use once_cell::sync::OnceCell;
 
-    use crate::jws::{self, EdDSA, JwsHeader, JwsHeaderSet, JwsVerifier, ES256, RS256};
-    use crate::Value;
+    use crate::jwk::alg::ed::EdCurve;
+    use crate::jwk::Jwk;
+    use crate::jws::{
+        self, EdDSA, JwsContext, JwsHeader, JwsHeaderSet, JwsSigner, JwsVerifier, ES256, ES256K,
+        ES384, HS256, PS256, RS256,
+    };
+    use crate::util;
+    use crate::{JoseError, JoseHeader, Value};
 
     #[test]
     fn test_jws_compact_serialization() -> Result<()> {
@@ -291,6 +425,275 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_jws_json_serialization_with_unprotected_key_id() -> Result<()> {
+        let private_key = load_file("pem/EC_P-256_private.pem")?;
+        let public_key = load_file("pem/EC_P-256_public.pem")?;
+
+        let mut src_header = JwsHeaderSet::new();
+        src_header.set_key_id("key-1", false);
+        let signer = ES256.signer_from_pem(&private_key)?;
+        let json = jws::serialize_flattened_json(b"payload", &src_header, &signer)?;
+
+        let map: Value = serde_json::from_str(&json)?;
+        assert_eq!(map["header"], serde_json::json!({ "kid": "key-1" }));
+        let protected = util::decode_base64_urlsafe_no_pad(map["protected"].as_str().unwrap())?;
+        let protected: Value = serde_json::from_slice(&protected)?;
+        assert_eq!(protected, serde_json::json!({ "alg": "ES256" }));
+
+        // The kid is resolved from the unprotected header.
+        let mut verifier = ES256.verifier_from_pem(&public_key)?;
+        verifier.set_key_id("key-1");
+        let (payload, header) = jws::deserialize_json(&json, &verifier)?;
+        assert_eq!(payload, b"payload");
+        assert_eq!(header.key_id(), Some("key-1"));
+
+        // The kid can be changed without breaking the signature.
+        let json2 = json.replace("key-1", "key-2");
+        verifier.set_key_id("key-2");
+        let (_, header) = jws::deserialize_json(&json2, &verifier)?;
+        assert_eq!(header.key_id(), Some("key-2"));
+
+        // The alg header claim must be protected.
+        let mut src_header = JwsHeaderSet::new();
+        src_header.set_algorithm("ES256", false);
+        assert!(jws::serialize_flattened_json(b"payload", &src_header, &signer).is_err());
+
+        let mut map = map;
+        map["header"] = serde_json::json!({ "kid": "key-1", "alg": "ES256" });
+        verifier.set_key_id("key-1");
+        match jws::deserialize_json(map.to_string(), &verifier) {
+            Err(JoseError::InvalidJwsFormat(err)) => assert_eq!(
+                err.to_string(),
+                "The JWS alg header claim must be in protected."
+            ),
+            res => panic!("{:?}", res),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_acme_profile() -> Result<()> {
+        let private_key = load_file("pem/EC_P-256_private.pem")?;
+        let public_key = load_file("pem/EC_P-256_public.pem")?;
+        let signer = ES256.signer_from_pem(&private_key)?;
+        let verifier = ES256.verifier_from_pem(&public_key)?;
+        let jwk = ES256.key_pair_from_pem(&private_key)?.to_jwk_public_key();
+
+        let mut context = JwsContext::new();
+        context.set_acme_profile(true);
+        assert!(context.is_acme_profile());
+
+        let new_header = || {
+            let mut header = JwsHeaderSet::new();
+            header.set_jwk(jwk.clone(), true);
+            header.set_url("https://example.com/acme/new-account", true);
+            header.set_nonce(b"nonce", true);
+            header
+        };
+
+        let json = context.serialize_flattened_json(b"{}", &new_header(), &signer)?;
+        let (payload, header) = context.deserialize_json(&json, &verifier)?;
+        assert_eq!(payload, b"{}");
+        assert_eq!(header.url(), Some("https://example.com/acme/new-account"));
+
+        let mut header = new_header();
+        header.set_key_id("https://example.com/acme/acct/1", true);
+        let json = context.serialize_flattened_json(b"{}", &header, &signer)?;
+        match context.deserialize_json(&json, &verifier) {
+            Err(JoseError::InvalidJwsFormat(err)) => assert_eq!(
+                err.to_string(),
+                "The ACME JWS must not have both of the jwk and kid header claims."
+            ),
+            res => panic!("{:?}", res),
+        }
+
+        let mut header = new_header();
+        header.set_claim("url", None, true)?;
+        let json = context.serialize_flattened_json(b"{}", &header, &signer)?;
+        match context.deserialize_json(&json, &verifier) {
+            Err(JoseError::InvalidJwsFormat(err)) => assert_eq!(
+                err.to_string(),
+                "The ACME JWS url header claim is required."
+            ),
+            res => panic!("{:?}", res),
+        }
+
+        let mut header = new_header();
+        header.set_claim("jwk", None, true)?;
+        let json = context.serialize_flattened_json(b"{}", &header, &signer)?;
+        assert!(context.deserialize_json(&json, &verifier).is_err());
+
+        let mut header = new_header();
+        header.set_token_type("JWT", true);
+        let json = context.serialize_flattened_json(b"{}", &header, &signer)?;
+        assert!(context.deserialize_json(&json, &verifier).is_err());
+
+        let mut header = new_header();
+        header.set_claim("custom", Some(Value::from("value")), false)?;
+        let json = context.serialize_flattened_json(b"{}", &header, &signer)?;
+        assert!(context.deserialize_json(&json, &verifier).is_err());
+
+        // The other serializations are rejected.
+        let json = context.serialize_general_json(b"{}", &[(&new_header(), &signer)])?;
+        assert!(context.deserialize_json(&json, &verifier).is_err());
+        let mut header = JwsHeader::new();
+        header.set_jwk(jwk.clone());
+        header.set_url("https://example.com/acme/new-account");
+        header.set_nonce(b"nonce");
+        let compact = context.serialize_compact(b"{}", &header, &signer)?;
+        assert!(context.deserialize_compact(&compact, &verifier).is_err());
+        assert!(jws::deserialize_compact(&compact, &verifier).is_ok());
+
+        let secret = b"0123456789ABCDEF0123456789ABCDEF";
+        let json = context.serialize_flattened_json(
+            b"{}",
+            &new_header(),
+            &HS256.signer_from_bytes(secret)?,
+        )?;
+        assert!(context
+            .deserialize_json(&json, &HS256.verifier_from_bytes(secret)?)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_detached_matches_compact() -> Result<()> {
+        let signer = HS256.signer_from_bytes(b"0123456789ABCDEF0123456789ABCDEF")?;
+        let payload = b"$.02";
+
+        for b64 in [true, false] {
+            let mut header = JwsHeader::new();
+            header.set_base64url_encode_payload(b64);
+            header.set_critical(&vec!["b64"]);
+
+            let compact = jws::serialize_compact(payload, &header, &signer)?;
+            let detached = jws::serialize_compact_detached(payload, &header, &signer)?;
+            let parts: Vec<&str> = compact.split('.').collect();
+            assert_eq!(detached, format!("{}..{}", parts[0], parts[2]));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_detached_unencoded_payload_for_open_banking() -> Result<()> {
+        let private_key = load_file("pem/RSA-PSS_2048bit_SHA-256_private.pem")?;
+        let public_key = load_file("pem/RSA-PSS_2048bit_SHA-256_public.pem")?;
+        let signer = PS256.signer_from_pem(&private_key)?;
+        let verifier = PS256.verifier_from_pem(&public_key)?;
+
+        let ob_claims = [
+            "http://openbanking.org.uk/iat",
+            "http://openbanking.org.uk/iss",
+            "http://openbanking.org.uk/tan",
+        ];
+        let mut header = JwsHeader::new();
+        header.set_key_id("ob-signing-key");
+        header.set_base64url_encode_payload(false);
+        header.set_claim(ob_claims[0], Some(Value::from(1_700_000_000)))?;
+        header.set_claim(ob_claims[1], Some(Value::from("0015800001041REAAY")))?;
+        header.set_claim(ob_claims[2], Some(Value::from("openbanking.org.uk")))?;
+        header.set_critical(&vec!["b64", ob_claims[0], ob_claims[1], ob_claims[2]]);
+
+        // The unencoded body may contain dots because it is not in the JWS.
+        let body = br#"{"Data":{"Amount":"10.00","Currency":"GBP"}}"#;
+        let x_jws_signature = jws::serialize_compact_detached(body, &header, &signer)?;
+        assert!(x_jws_signature.contains(".."));
+
+        // The critical header claims are rejected until they are understood.
+        assert!(jws::deserialize_compact_detached(&x_jws_signature, body, &verifier).is_err());
+
+        let mut context = JwsContext::new();
+        context.add_acceptable_critical("b64");
+        for name in ob_claims {
+            context.add_acceptable_critical(name);
+        }
+        let dst_header = context.deserialize_compact_detached(&x_jws_signature, body, &verifier)?;
+        assert_eq!(dst_header.base64url_encode_payload(), Some(false));
+        assert_eq!(
+            dst_header.claim_str(ob_claims[2]),
+            Some("openbanking.org.uk")
+        );
+
+        let tampered = br#"{"Data":{"Amount":"99.00","Currency":"GBP"}}"#;
+        assert!(context
+            .deserialize_compact_detached(&x_jws_signature, tampered, &verifier)
+            .is_err());
+
+        // A attached payload is not accepted as a detached one.
+        let mut header = JwsHeader::new();
+        header.set_key_id("ob-signing-key");
+        let attached = jws::serialize_compact(b"payload", &header, &signer)?;
+        assert!(context
+            .deserialize_compact_detached(&attached, b"payload", &verifier)
+            .is_err());
+
+        // The b64 encoded payload is also supported.
+        let detached = jws::serialize_compact_detached(b"payload", &header, &signer)?;
+        jws::deserialize_compact_detached(&detached, b"payload", &verifier)?;
+        let (header_b64, signature_b64) = detached.split_once("..").unwrap();
+        let payload_b64 = util::encode_base64_urlsafe_nopad(b"payload");
+        let attached = format!("{}.{}.{}", header_b64, payload_b64, signature_b64);
+        let (payload, _) = jws::deserialize_compact(&attached, &verifier)?;
+        assert_eq!(payload, b"payload");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_with_embedded_jwk() -> Result<()> {
+        let key_pair = ES256.generate_key_pair()?;
+        let signer = ES256.signer_from_jwk(&key_pair.to_jwk_private_key())?;
+
+        // The private key is never embedded.
+        let mut header = JwsHeader::new();
+        header.set_jwk(key_pair.to_jwk_key_pair());
+        let jwk = header.jwk_strict()?.unwrap();
+        assert_eq!(jwk.parameter("d"), None);
+        assert_eq!(jwk, key_pair.to_jwk_public_key());
+        assert_eq!(header.jwk(), Some(jwk.clone()));
+
+        let input = jws::serialize_compact(b"payload", &header, &signer)?;
+        let thumbprint = jwk.thumbprint_sha256()?;
+        let (payload, dst_header) = jws::verify_with_embedded_jwk(&input, &thumbprint)?;
+        assert_eq!(payload, b"payload");
+        assert_eq!(dst_header.jwk(), Some(jwk));
+
+        // A key of an attacker verifies its own signature, but is not pinned.
+        let other = ES256.generate_key_pair()?;
+        let mut header = JwsHeader::new();
+        header.set_jwk(other.to_jwk_public_key());
+        let forged = jws::serialize_compact(
+            b"forged",
+            &header,
+            &ES256.signer_from_jwk(&other.to_jwk_private_key())?,
+        )?;
+        match jws::verify_with_embedded_jwk(&forged, &thumbprint) {
+            Err(JoseError::InvalidJwsFormat(err)) => assert!(err
+                .to_string()
+                .starts_with("The thumbprint of the JWS jwk header claim is not pinned")),
+            res => panic!("{:?}", res),
+        }
+
+        // Only the explicitly unpinned variant accepts any embedded key.
+        let (payload, _) = jws::verify_with_embedded_jwk_unpinned(&forged)?;
+        assert_eq!(payload, b"forged");
+
+        let mut map = header.into_map();
+        map.insert("jwk".to_string(), Value::from("key"));
+        assert!(JwsHeader::from_map(map).is_err());
+        assert!(jws::verify_with_embedded_jwk(
+            jws::serialize_compact(b"payload", &JwsHeader::new(), &signer)?,
+            &thumbprint
+        )
+        .is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_jws_general_json_serialization() -> Result<()> {
         let private_key_1 = load_file("pem/RSA_2048bit_private.pem")?;
@@ -336,6 +739,370 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_jws_compact_rejects_duplicate_header_names() -> Result<()> {
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let signer = HS256.signer_from_bytes(key)?;
+        let verifier = HS256.verifier_from_bytes(key)?;
+
+        let header = util::encode_base64_urlsafe_nopad(r#"{"alg":"HS256","alg":"HS256"}"#);
+        let payload = util::encode_base64_urlsafe_nopad("test payload!");
+        let message = format!("{}.{}", header, payload);
+        let signature = util::encode_base64_urlsafe_nopad(signer.sign(message.as_bytes())?);
+        let jws = format!("{}.{}", message, signature);
+
+        assert!(matches!(
+            jws::deserialize_compact(&jws, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        let mut context = JwsContext::new();
+        assert!(context.is_strict_json());
+        context.set_strict_json(false);
+        let (payload, header) = context.deserialize_compact(&jws, &verifier)?;
+        assert_eq!(payload, b"test payload!");
+        assert_eq!(header.algorithm(), Some("HS256"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_compact_with_allowed_verifiers() -> Result<()> {
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let key_pair = ES256.generate_key_pair()?;
+        let hs256 = HS256.verifier_from_bytes(key)?;
+        let es256 = ES256.verifier_from_jwk(&key_pair.to_jwk_public_key())?;
+        let verifiers: [&dyn JwsVerifier; 2] = [&hs256, &es256];
+
+        let mut header = JwsHeader::new();
+        header.set_token_type("JWT");
+
+        let signer = ES256.signer_from_jwk(&key_pair.to_jwk_private_key())?;
+        let jws = jws::serialize_compact(b"payload", &header, &signer)?;
+        let (payload, header) = jws::deserialize_compact_with_allowed(&jws, &verifiers)?;
+        assert_eq!(payload, b"payload");
+        assert_eq!(header.algorithm(), Some("ES256"));
+
+        let signer = ES384.generate_key_pair()?.to_jwk_private_key();
+        let signer = ES384.signer_from_jwk(&signer)?;
+        let jws = jws::serialize_compact(b"payload", &header, &signer)?;
+        match jws::deserialize_compact_with_allowed(&jws, &verifiers) {
+            Err(JoseError::InvalidJwsFormat(err)) => {
+                assert_eq!(
+                    err.to_string(),
+                    "The JWS alg header claim is not allowed: ES384"
+                );
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(jws::deserialize_compact_with_allowed(&jws, &[]).is_err());
+
+        // During a key rotation, every key of the same algorithm is tried.
+        let old_key = HS256.verifier_from_bytes(b"FEDCBA9876543210FEDCBA9876543210")?;
+        let verifiers: [&dyn JwsVerifier; 2] = [&old_key, &hs256];
+        let signer = HS256.signer_from_bytes(key)?;
+        let jws = jws::serialize_compact(b"payload", &JwsHeader::new(), &signer)?;
+        let (payload, _) = jws::deserialize_compact_with_allowed(&jws, &verifiers)?;
+        assert_eq!(payload, b"payload");
+        assert!(matches!(
+            jws::deserialize_compact_with_allowed(&jws, &verifiers[..1]),
+            Err(JoseError::InvalidSignature(_))
+        ));
+
+        // A verifier that has a key ID is only used for the same kid header claim.
+        let mut es256_with_kid = es256.clone();
+        es256_with_kid.set_key_id("key-1");
+        let verifiers: [&dyn JwsVerifier; 1] = [&es256_with_kid];
+        let mut header = JwsHeader::new();
+        header.set_key_id("key-2");
+        let signer = ES256.signer_from_jwk(&key_pair.to_jwk_private_key())?;
+        let jws = jws::serialize_compact(b"payload", &header, &signer)?;
+        match jws::deserialize_compact_with_allowed(&jws, &verifiers) {
+            Err(JoseError::InvalidJwsFormat(err)) => assert_eq!(
+                err.to_string(),
+                "A verifier is not found for the JWS kid header claim: key-2"
+            ),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_compact_signing_input_covers_payload() -> Result<()> {
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let signer = HS256.signer_from_bytes(key)?;
+        let verifier = HS256.verifier_from_bytes(key)?;
+
+        let jws = jws::serialize_compact(b"test payload!", &JwsHeader::new(), &signer)?;
+        let parts: Vec<&str> = jws.split('.').collect();
+        let message = format!("{}.{}", parts[0], parts[1]);
+        assert_eq!(
+            util::decode_base64_urlsafe_no_pad(parts[2])?,
+            signer.sign(message.as_bytes())?
+        );
+
+        let tampered = format!(
+            "{}.{}.{}",
+            parts[0],
+            util::encode_base64_urlsafe_nopad("test payload?"),
+            parts[2]
+        );
+        assert!(matches!(
+            jws::deserialize_compact(&tampered, &verifier),
+            Err(JoseError::InvalidSignature(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_compact_unencoded_payload() -> Result<()> {
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let signer = HS256.signer_from_bytes(key)?;
+        let verifier = HS256.verifier_from_bytes(key)?;
+
+        let mut header = JwsHeader::new();
+        header.set_base64url_encode_payload(false);
+        header.set_critical(&vec!["b64"]);
+
+        let mut context = JwsContext::new();
+        context.add_acceptable_critical("b64");
+
+        for payload in [&b"$.02"[..], &b"\xff"[..], &b"$02"[..]] {
+            let jws = jws::serialize_compact(payload, &header, &signer)?;
+            let parts: Vec<&str> = jws.split('.').collect();
+            assert_eq!(parts.len(), 3);
+            assert_eq!(parts[1], "");
+
+            let mut message = format!("{}.", parts[0]).into_bytes();
+            message.extend_from_slice(payload);
+            assert_eq!(
+                util::decode_base64_urlsafe_no_pad(parts[2])?,
+                signer.sign(&message)?
+            );
+
+            let header = context.deserialize_compact_detached(&jws, payload, &verifier)?;
+            assert_eq!(header.base64url_encode_payload(), Some(false));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_compact_with_wrong_segment_count() -> Result<()> {
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let verifier = HS256.verifier_from_bytes(key)?;
+
+        let jws = jws::serialize_compact(
+            b"payload",
+            &JwsHeader::new(),
+            &HS256.signer_from_bytes(key)?,
+        )?;
+        for (input, count) in [
+            (format!("{}.AA.AA", jws), 5),
+            (jws[..jws.rfind('.').unwrap()].to_string(), 2),
+            ("".to_string(), 1),
+        ] {
+            match jws::deserialize_compact(&input, &verifier) {
+                Err(JoseError::InvalidJwsFormat(err)) => assert_eq!(
+                    err.to_string(),
+                    format!("The compact serialization form of JWS must be three parts separated by period: {} parts", count)
+                ),
+                res => panic!("{:?}", res),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_compact_with_base64_padding() -> Result<()> {
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let signer = HS256.signer_from_bytes(key)?;
+        let verifier = HS256.verifier_from_bytes(key)?;
+
+        // Some producers sign the padded parts and pad the signature.
+        let pad = |val: String| {
+            let len = (4 - val.len() % 4) % 4;
+            val + &"=".repeat(len)
+        };
+        let header = pad(util::encode_base64_urlsafe_nopad(r#"{"alg":"HS256"}"#));
+        let payload = pad(util::encode_base64_urlsafe_nopad("test payload!"));
+        let message = format!("{}.{}", header, payload);
+        let signature = pad(util::encode_base64_urlsafe_nopad(
+            signer.sign(message.as_bytes())?,
+        ));
+        let jws = format!("{}.{}", message, signature);
+        assert!(jws.contains('='));
+
+        assert!(matches!(
+            jws::deserialize_compact(&jws, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        let mut context = JwsContext::new();
+        context.set_base64_padding_tolerant(true);
+        let (payload, header) = context.deserialize_compact(&jws, &verifier)?;
+        assert_eq!(payload, b"test payload!");
+        assert_eq!(header.algorithm(), Some("HS256"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_signer_key_type_and_curve() -> Result<()> {
+        let signer = HS256.signer_from_bytes(b"0123456789ABCDEF0123456789ABCDEF")?;
+        assert_eq!(signer.key_type(), Some("oct"));
+        assert_eq!(signer.curve(), None);
+
+        let key_pair = RS256.generate_key_pair(2048)?;
+        let signer = RS256.signer_from_der(key_pair.to_der_private_key())?;
+        assert_eq!(signer.key_type(), Some("RSA"));
+        assert_eq!(signer.curve(), None);
+
+        for (alg, curve) in [(ES256, "P-256"), (ES384, "P-384"), (ES256K, "secp256k1")] {
+            let key_pair = alg.generate_key_pair()?;
+            let signer = alg.signer_from_der(key_pair.to_der_private_key())?;
+            assert_eq!(signer.key_type(), Some("EC"));
+            assert_eq!(signer.curve(), Some(curve));
+        }
+
+        let key_pair = EdDSA.generate_key_pair(EdCurve::Ed448)?;
+        let signer = EdDSA.signer_from_der(key_pair.to_der_private_key())?;
+        assert_eq!(signer.key_type(), Some("OKP"));
+        assert_eq!(signer.curve(), Some("Ed448"));
+
+        // Generic code can check the key before signing.
+        let signer: Box<dyn JwsSigner> = Box::new(signer);
+        assert_eq!(signer.key_type(), Some("OKP"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_verify_concurrently() -> Result<()> {
+        let private_key = load_file("pem/EC_P-256_private.pem")?;
+        let public_key = load_file("pem/EC_P-256_public.pem")?;
+        let secret = b"0123456789abcdef0123456789abcdef";
+
+        let signers: Vec<Box<dyn JwsSigner>> = vec![
+            Box::new(ES256.signer_from_pem(&private_key)?),
+            Box::new(HS256.signer_from_bytes(secret)?),
+        ];
+        let verifiers: Vec<Arc<dyn JwsVerifier>> = vec![
+            Arc::new(ES256.verifier_from_pem(&public_key)?),
+            Arc::new(HS256.verifier_from_bytes(secret)?),
+        ];
+
+        for (signer, verifier) in signers.iter().zip(verifiers) {
+            let header = JwsHeader::new();
+            let mut handles = Vec::new();
+            for i in 0..8 {
+                let payload = format!("payload {}", i).into_bytes();
+                let jws = jws::serialize_compact(&payload, &header, signer.as_ref())?;
+                let verifier = Arc::clone(&verifier);
+                handles.push(thread::spawn(move || -> Result<()> {
+                    for _ in 0..16 {
+                        let (dst_payload, _) = jws::deserialize_compact(&jws, verifier.as_ref())?;
+                        assert_eq!(dst_payload, payload);
+                    }
+                    Ok(())
+                }));
+            }
+            for handle in handles {
+                handle.join().unwrap()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_sign_stream() -> Result<()> {
+        use openssl::hash::MessageDigest;
+        use openssl::pkey::PKey;
+        use openssl::sign::Signer;
+        use std::fs::File;
+
+        // A 50 MB file is signed as a stream, and the signatures are compared with
+        // the ones that are computed over the buffered bytes.
+        let data: Vec<u8> = (0..50 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+
+        // The file is removed by the guard even when an assertion fails.
+        struct TempFile(PathBuf);
+
+        impl Drop for TempFile {
+            fn drop(&mut self) {
+                let _ = fs::remove_file(&self.0);
+            }
+        }
+
+        let temp_file = TempFile(
+            std::env::temp_dir().join(format!("josekit-sign-stream-{}.bin", std::process::id())),
+        );
+        let path = &temp_file.0;
+        fs::write(path, &data)?;
+
+        let secret = b"0123456789abcdef0123456789abcdef";
+        let signer = HS256.signer_from_bytes(secret)?;
+        let pkey = PKey::hmac(secret)?;
+        let mut expected = Signer::new(MessageDigest::sha256(), &pkey)?;
+        assert_eq!(
+            signer.sign_stream(&mut File::open(path)?)?,
+            expected.sign_oneshot_to_vec(&data)?
+        );
+
+        let private_key = load_file("pem/RSA_2048bit_private.pem")?;
+        let signer = RS256.signer_from_pem(&private_key)?;
+        assert_eq!(
+            signer.sign_stream(&mut File::open(path)?)?,
+            signer.sign(&data)?
+        );
+
+        let private_key = load_file("pem/ED25519_private.pem")?;
+        let signer = EdDSA.signer_from_pem(&private_key)?;
+        assert_eq!(
+            signer.sign_stream(&mut File::open(path)?)?,
+            signer.sign(&data)?
+        );
+
+        // The randomized signatures are checked by the verifiers.
+        let signer =
+            PS256.signer_from_pem(load_file("pem/RSA-PSS_2048bit_SHA-256_private.pem")?)?;
+        let verifier =
+            PS256.verifier_from_pem(load_file("pem/RSA-PSS_2048bit_SHA-256_public.pem")?)?;
+        let signature = signer.sign_stream(&mut File::open(path)?)?;
+        verifier.verify(&data, &signature)?;
+
+        let signer = ES256.signer_from_pem(load_file("pem/EC_P-256_private.pem")?)?;
+        let verifier = ES256.verifier_from_pem(load_file("pem/EC_P-256_public.pem")?)?;
+        let signature = signer.sign_stream(&mut File::open(path)?)?;
+        verifier.verify(&data, &signature)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_algorithm_for() -> Result<()> {
+        for (path, expected) in [
+            ("jwk/EC_P-256_private.jwk", Some("ES256")),
+            ("jwk/EC_P-384_public.jwk", Some("ES384")),
+            ("jwk/EC_P-521_private.jwk", Some("ES512")),
+            ("jwk/EC_secp256k1_private.jwk", Some("ES256K")),
+            ("jwk/OKP_Ed25519_private.jwk", Some("EdDSA")),
+            ("jwk/OKP_Ed448_public.jwk", Some("EdDSA")),
+            ("jwk/RSA_private.jwk", Some("RS256")),
+            ("jwk/OKP_X25519_private.jwk", None),
+            ("jwk/oct_256bit_private.jwk", None),
+        ] {
+            let jwk = Jwk::from_bytes(load_file(path)?)?;
+            assert_eq!(jws::default_algorithm_for(&jwk), expected, "{}", path);
+        }
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
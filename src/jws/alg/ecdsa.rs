@@ -1,13 +1,20 @@
 use std::fmt::Display;
+use std::io::{self, Read};
 use std::ops::Deref;
 
 use anyhow::bail;
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::hash::Hasher;
+#[cfg(ossl320)]
+use openssl::md::Md;
 use openssl::pkey::{PKey, Private, Public};
+#[cfg(ossl320)]
+use openssl::pkey_ctx::{NonceType, PkeyCtx};
 use openssl::sign::{Signer, Verifier};
 
 use crate::jwk::{
     alg::ec::{EcCurve, EcKeyPair},
-    Jwk,
+    Jwk, KeyOps,
 };
 use crate::jws::{JwsAlgorithm, JwsSigner, JwsVerifier};
 use crate::util::der::{DerBuilder, DerReader, DerType};
@@ -70,6 +77,7 @@ impl EcdsaJwsAlgorithm {
             algorithm: self.clone(),
             private_key: key_pair.into_private_key(),
             key_id: None,
+            deterministic: false,
         })
     }
 
@@ -89,9 +97,28 @@ impl EcdsaJwsAlgorithm {
             algorithm: self.clone(),
             private_key: key_pair.into_private_key(),
             key_id: None,
+            deterministic: false,
         })
     }
 
+    /// Return a signer from a private key of encrypted PKCS#8 PEM format.
+    ///
+    /// Encrypted PKCS#8 PEM format is a DER and base64 encoded EncryptedPrivateKeyInfo
+    /// that surrounded by "-----BEGIN/END ENCRYPTED PRIVATE KEY----".
+    ///
+    /// # Arguments
+    /// * `input` - A private key of encrypted PKCS#8 PEM format.
+    /// * `passphrase` - A passphrase for decrypting the private key.
+    pub fn signer_from_encrypted_pem(
+        &self,
+        input: impl AsRef<[u8]>,
+        passphrase: impl AsRef<[u8]>,
+    ) -> Result<EcdsaJwsSigner, JoseError> {
+        let pkcs8_der = util::decrypt_pkcs8_pem(input.as_ref(), passphrase.as_ref())
+            .map_err(JoseError::InvalidKeyFormat)?;
+        self.signer_from_der(pkcs8_der)
+    }
+
     /// Return a signer from a private key that is formatted by a JWK of EC type.
     ///
     /// # Arguments
@@ -103,7 +130,7 @@ impl EcdsaJwsAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be sig: {}", val),
             }
-            if !jwk.is_for_key_operation("sign") {
+            if !jwk.is_for_key_operations(KeyOps::SIGN) {
                 bail!("A parameter key_ops must contains sign.");
             }
             match jwk.algorithm() {
@@ -113,7 +140,7 @@ impl EcdsaJwsAlgorithm {
             }
             match jwk.curve() {
                 Some(val) if val == self.curve().name() => {}
-                Some(val) => bail!("A parameter crv must be {} but {}", self.name(), val),
+                Some(val) => bail!("A parameter crv must be {} but {}", self.curve(), val),
                 None => bail!("A parameter crv is required."),
             }
 
@@ -125,11 +152,29 @@ impl EcdsaJwsAlgorithm {
                 algorithm: self.clone(),
                 private_key,
                 key_id,
+                deterministic: false,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
+    /// Return a signer from a raw private key that is a big-endian encoded scalar.
+    ///
+    /// # Arguments
+    /// * `input` - A raw private key that has the same length as the curve's field size.
+    pub fn signer_from_raw_private_key(
+        &self,
+        input: impl AsRef<[u8]>,
+    ) -> Result<EcdsaJwsSigner, JoseError> {
+        let key_pair = EcKeyPair::from_raw_private_key(input, self.curve())?;
+        Ok(EcdsaJwsSigner {
+            algorithm: *self,
+            private_key: key_pair.into_private_key(),
+            key_id: None,
+            deterministic: false,
+        })
+    }
+
     /// Return a verifier from a public key that is a DER encoded SubjectPublicKeyInfo.
     ///
     /// # Arguments
@@ -141,7 +186,7 @@ impl EcdsaJwsAlgorithm {
         (|| -> anyhow::Result<EcdsaJwsVerifier> {
             let spki_der = match EcKeyPair::detect_pkcs8(input.as_ref(), true) {
                 Some(curve) if curve == self.curve() => input.as_ref(),
-                Some(curve) => bail!("The curve is mismatched: {}", curve),
+                Some(curve) => bail!("The curve must be {} but {}", self.curve(), curve),
                 None => {
                     bail!("The ECDSA public key must be wrapped by SubjectPublicKeyInfo format.")
                 }
@@ -153,6 +198,7 @@ impl EcdsaJwsAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id: None,
+                require_low_s: false,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -173,12 +219,11 @@ impl EcdsaJwsAlgorithm {
             let (alg, data) = util::parse_pem(input.as_ref())?;
 
             let spki = match alg.as_str() {
-                "PUBLIC KEY" => {
-                    if let None = EcKeyPair::detect_pkcs8(&data, true) {
-                        bail!("PEM contents is expected SubjectPublicKeyInfo wrapped key.");
-                    }
-                    &data
-                }
+                "PUBLIC KEY" => match EcKeyPair::detect_pkcs8(&data, true) {
+                    Some(curve) if curve == self.curve() => &data,
+                    Some(curve) => bail!("The curve must be {} but {}", self.curve(), curve),
+                    None => bail!("PEM contents is expected SubjectPublicKeyInfo wrapped key."),
+                },
                 alg => bail!("Inappropriate algorithm: {}", alg),
             };
 
@@ -188,11 +233,46 @@ impl EcdsaJwsAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id: None,
+                require_low_s: false,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
+    /// Return a verifier from a raw public key that is a uncompressed EC point.
+    ///
+    /// # Arguments
+    /// * `input` - A raw public key that is formatted by 0x04 || x || y.
+    pub fn verifier_from_raw_public_key(
+        &self,
+        input: impl AsRef<[u8]>,
+    ) -> Result<EcdsaJwsVerifier, JoseError> {
+        (|| -> anyhow::Result<EcdsaJwsVerifier> {
+            let input = input.as_ref();
+            let curve = self.curve();
+            if input.len() != 1 + curve.coordinate_size() * 2 || input[0] != 0x04 {
+                bail!(
+                    "The raw public key must be a uncompressed point of {}.",
+                    curve
+                );
+            }
+
+            let pkcs8 = EcKeyPair::to_pkcs8(input, true, curve);
+            let public_key = PKey::public_key_from_der(&pkcs8)?;
+
+            Ok(EcdsaJwsVerifier {
+                algorithm: *self,
+                public_key,
+                key_id: None,
+                require_low_s: false,
+            })
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
     /// Return a verifier from a public key that is formatted by a JWK of EC type.
     ///
     /// # Arguments
@@ -210,7 +290,7 @@ impl EcdsaJwsAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be sig: {}", val),
             }
-            if !jwk.is_for_key_operation("verify") {
+            if !jwk.is_for_key_operations(KeyOps::VERIFY) {
                 bail!("A parameter key_ops must contains verify.");
             }
             match jwk.algorithm() {
@@ -250,6 +330,7 @@ impl EcdsaJwsAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id,
+                require_low_s: false,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -316,6 +397,7 @@ pub struct EcdsaJwsSigner {
     algorithm: EcdsaJwsAlgorithm,
     private_key: PKey<Private>,
     key_id: Option<String>,
+    deterministic: bool,
 }
 
 impl EcdsaJwsSigner {
@@ -323,9 +405,86 @@ impl EcdsaJwsSigner {
         self.key_id = Some(value.into());
     }
 
+    /// Set whether the nonce is generated deterministically by RFC 6979.
+    ///
+    /// The deterministic signatures can be verified by any standard verifier,
+    /// and the same message always yields the same signature.
+    /// The nonce is generated by OpenSSL, so enabling it fails when the linked OpenSSL
+    /// is older than 3.2.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - true if the nonce is generated deterministically.
+    pub fn set_deterministic(&mut self, value: bool) -> Result<(), JoseError> {
+        if value && !cfg!(ossl320) {
+            return Err(JoseError::UnsupportedSignatureAlgorithm(anyhow::anyhow!(
+                "The deterministic ECDSA signature requires OpenSSL 3.2 or later."
+            )));
+        }
+        self.deterministic = value;
+        Ok(())
+    }
+
+    /// Return whether the nonce is generated deterministically by RFC 6979.
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
     pub fn remove_key_id(&mut self) {
         self.key_id = None;
     }
+
+    /// Set the JWK thumbprint (RFC 7638) of the public key using SHA-256 as a key ID.
+    pub fn set_key_id_to_thumbprint(&mut self) -> Result<(), JoseError> {
+        let jwk = Jwk::from_public_key(&self.private_key)?;
+        self.key_id = Some(jwk.thumbprint_sha256()?);
+        Ok(())
+    }
+}
+
+impl EcdsaJwsSigner {
+    #[cfg(ossl320)]
+    fn sign_deterministic(&self, digest: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let md = self.algorithm.hash_algorithm().message_digest();
+        let mut ctx = PkeyCtx::new(&self.private_key)?;
+        ctx.sign_init()?;
+        ctx.set_signature_md(Md::from_nid(md.type_()).unwrap())?;
+        ctx.set_nonce_type(NonceType::DETERMINISTIC_K)?;
+        let mut der_signature = Vec::new();
+        ctx.sign_to_vec(digest, &mut der_signature)?;
+        self.to_raw_signature(&der_signature)
+    }
+
+    #[cfg(not(ossl320))]
+    fn sign_deterministic(&self, _digest: &[u8]) -> anyhow::Result<Vec<u8>> {
+        bail!("The deterministic ECDSA signature requires OpenSSL 3.2 or later.");
+    }
+
+    fn to_raw_signature(&self, der_signature: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let signature_len = self.signature_len();
+        let sep = signature_len / 2;
+
+        let mut signature = Vec::with_capacity(signature_len);
+        let mut reader = DerReader::from_bytes(&der_signature);
+        match reader.next()? {
+            Some(DerType::Sequence) => {}
+            _ => unreachable!("A generated signature is invalid."),
+        }
+        match reader.next()? {
+            Some(DerType::Integer) => {
+                signature.extend_from_slice(&reader.to_be_bytes(false, sep));
+            }
+            _ => unreachable!("A generated signature is invalid."),
+        }
+        match reader.next()? {
+            Some(DerType::Integer) => {
+                signature.extend_from_slice(&reader.to_be_bytes(false, sep));
+            }
+            _ => unreachable!("A generated signature is invalid."),
+        }
+
+        Ok(signature)
+    }
 }
 
 impl JwsSigner for EcdsaJwsSigner {
@@ -344,37 +503,33 @@ impl JwsSigner for EcdsaJwsSigner {
         }
     }
 
+    fn key_type(&self) -> Option<&str> {
+        Some("EC")
+    }
+
+    fn curve(&self) -> Option<&str> {
+        Some(self.algorithm.curve().name())
+    }
+
     fn sign(&self, message: &[u8]) -> Result<Vec<u8>, JoseError> {
+        self.sign_stream(&mut &message[..])
+    }
+
+    fn sign_stream(&self, message: &mut dyn Read) -> Result<Vec<u8>, JoseError> {
         (|| -> anyhow::Result<Vec<u8>> {
             let md = self.algorithm.hash_algorithm().message_digest();
 
-            let mut signer = Signer::new(md, &self.private_key)?;
-            signer.update(message)?;
-            let der_signature = signer.sign_to_vec()?;
-
-            let signature_len = self.signature_len();
-            let sep = signature_len / 2;
-
-            let mut signature = Vec::with_capacity(signature_len);
-            let mut reader = DerReader::from_bytes(&der_signature);
-            match reader.next()? {
-                Some(DerType::Sequence) => {}
-                _ => unreachable!("A generated signature is invalid."),
-            }
-            match reader.next()? {
-                Some(DerType::Integer) => {
-                    signature.extend_from_slice(&reader.to_be_bytes(false, sep));
-                }
-                _ => unreachable!("A generated signature is invalid."),
-            }
-            match reader.next()? {
-                Some(DerType::Integer) => {
-                    signature.extend_from_slice(&reader.to_be_bytes(false, sep));
-                }
-                _ => unreachable!("A generated signature is invalid."),
+            if self.deterministic {
+                let mut hasher = Hasher::new(md)?;
+                io::copy(message, &mut hasher)?;
+                let digest = hasher.finish()?;
+                return self.sign_deterministic(&digest);
             }
 
-            Ok(signature)
+            let mut signer = Signer::new(md, &self.private_key)?;
+            io::copy(message, &mut signer)?;
+            let der_signature = signer.sign_to_vec()?;
+            self.to_raw_signature(&der_signature)
         })()
         .map_err(|err| JoseError::InvalidSignature(err))
     }
@@ -397,6 +552,7 @@ pub struct EcdsaJwsVerifier {
     algorithm: EcdsaJwsAlgorithm,
     public_key: PKey<Public>,
     key_id: Option<String>,
+    require_low_s: bool,
 }
 
 impl EcdsaJwsVerifier {
@@ -407,6 +563,22 @@ impl EcdsaJwsVerifier {
     pub fn remove_key_id(&mut self) {
         self.key_id = None;
     }
+
+    /// Set whether signatures with a S value greater than the half of the curve order are rejected.
+    ///
+    /// Such signatures are valid but malleable. Some protocols (e.g. blockchains) require low-S form.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - true if the low-S form is required.
+    pub fn set_require_low_s(&mut self, value: bool) {
+        self.require_low_s = value;
+    }
+
+    /// Return whether signatures must be low-S form.
+    pub fn is_low_s_required(&self) -> bool {
+        self.require_low_s
+    }
 }
 
 impl JwsVerifier for EcdsaJwsVerifier {
@@ -445,6 +617,20 @@ impl JwsVerifier for EcdsaJwsVerifier {
             der_builder.end();
             let der_signature = der_builder.build();
 
+            if self.require_low_s {
+                let ec_key = self.public_key.ec_key()?;
+                let mut ctx = BigNumContext::new()?;
+                let mut order = BigNum::new()?;
+                ec_key.group().order(&mut order, &mut ctx)?;
+                let mut half_order = BigNum::new()?;
+                half_order.rshift1(&order)?;
+
+                let s = BigNum::from_slice(&signature[(signature_len / 2)..])?;
+                if s > half_order {
+                    bail!("The S value of the signature must be low-S form.");
+                }
+            }
+
             let md = self.algorithm.hash_algorithm().message_digest();
 
             let mut verifier = Verifier::new(md, &self.public_key)?;
@@ -478,6 +664,11 @@ mod tests {
     use std::fs;
     use std::path::PathBuf;
 
+    use crate::jwe::ECDH_ES;
+    use crate::jwk::KeyPair;
+    #[cfg(ossl320)]
+    use crate::util::from_hex;
+
     #[test]
     fn sign_and_verify_ecdsa_generated_der() -> Result<()> {
         let input = b"abcde12345";
@@ -522,6 +713,44 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn sign_and_verify_ecdsa_raw_key() -> Result<()> {
+        let input = b"abcde12345";
+
+        for alg in &[
+            EcdsaJwsAlgorithm::Es256,
+            EcdsaJwsAlgorithm::Es384,
+            EcdsaJwsAlgorithm::Es512,
+            EcdsaJwsAlgorithm::Es256k,
+        ] {
+            let key_pair = alg.generate_key_pair()?;
+            let jwk = key_pair.to_jwk_key_pair();
+            let param = |name: &str| -> Result<Vec<u8>> {
+                match jwk.parameter(name) {
+                    Some(Value::String(val)) => Ok(util::decode_base64_urlsafe_no_pad(val)?),
+                    _ => unreachable!(),
+                }
+            };
+
+            let signer = alg.signer_from_raw_private_key(&param("d")?)?;
+            let signature = signer.sign(input)?;
+
+            let mut public_key = vec![0x04];
+            public_key.extend_from_slice(&param("x")?);
+            public_key.extend_from_slice(&param("y")?);
+            let verifier = alg.verifier_from_raw_public_key(&public_key)?;
+            verifier.verify(input, &signature)?;
+
+            assert!(alg.signer_from_raw_private_key(&param("d")?[1..]).is_err());
+            assert!(alg
+                .signer_from_raw_private_key(vec![0; alg.curve().coordinate_size()])
+                .is_err());
+            assert!(alg.verifier_from_raw_public_key(&public_key[1..]).is_err());
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn sign_and_verify_ecdsa_generated_pem() -> Result<()> {
         let input = b"abcde12345";
@@ -714,6 +943,186 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn verify_ecdsa_require_low_s() -> Result<()> {
+        let input = b"abcde12345";
+
+        for alg in &[
+            EcdsaJwsAlgorithm::Es256,
+            EcdsaJwsAlgorithm::Es384,
+            EcdsaJwsAlgorithm::Es512,
+            EcdsaJwsAlgorithm::Es256k,
+        ] {
+            let key_pair = alg.generate_key_pair()?;
+            let signer = alg.signer_from_jwk(&key_pair.to_jwk_private_key())?;
+            let mut verifier = alg.verifier_from_jwk(&key_pair.to_jwk_public_key())?;
+            assert!(!verifier.is_low_s_required());
+
+            let signature = signer.sign(input)?;
+            let sep = signature.len() / 2;
+
+            // n - s is also a valid S value for the same R.
+            let ec_key = key_pair.into_private_key().ec_key()?;
+            let mut ctx = BigNumContext::new()?;
+            let mut order = BigNum::new()?;
+            ec_key.group().order(&mut order, &mut ctx)?;
+            let mut half_order = BigNum::new()?;
+            half_order.rshift1(&order)?;
+            let s = BigNum::from_slice(&signature[sep..])?;
+            let mut negated_s = BigNum::new()?;
+            negated_s.checked_sub(&order, &s)?;
+
+            let mut other = signature[..sep].to_vec();
+            other.extend_from_slice(&util::num_to_vec(&negated_s, sep));
+            let (low, high) = if s > half_order {
+                (other, signature)
+            } else {
+                (signature, other)
+            };
+
+            verifier.verify(input, &low)?;
+            verifier.verify(input, &high)?;
+
+            verifier.set_require_low_s(true);
+            verifier.verify(input, &low)?;
+            assert!(matches!(
+                verifier.verify(input, &high),
+                Err(JoseError::InvalidSignature(_))
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_and_verify_ecdsa_secp256k1_guards() -> Result<()> {
+        let private_key = Jwk::generate_ec_key(EcCurve::Secp256k1)?;
+        let public_key = private_key.to_public_key()?;
+        let key_pair = EcKeyPair::from_jwk(&private_key)?;
+
+        // ES256K accepts the secp256k1 key in every format.
+        let alg = EcdsaJwsAlgorithm::Es256k;
+        let signer = alg.signer_from_jwk(&private_key)?;
+        let signature = signer.sign(b"message")?;
+        alg.verifier_from_jwk(&public_key)?
+            .verify(b"message", &signature)?;
+        alg.verifier_from_der(key_pair.to_der_public_key())?
+            .verify(b"message", &signature)?;
+        alg.verifier_from_pem(key_pair.to_pem_public_key())?
+            .verify(b"message", &signature)?;
+
+        // The P-curve algorithms reject it explicitly.
+        for alg in [
+            EcdsaJwsAlgorithm::Es256,
+            EcdsaJwsAlgorithm::Es384,
+            EcdsaJwsAlgorithm::Es512,
+        ] {
+            let expected = format!("The curve must be {} but secp256k1", alg.curve());
+            for res in [
+                alg.signer_from_der(key_pair.to_der_private_key())
+                    .map(|_| ()),
+                alg.signer_from_pem(key_pair.to_pem_private_key())
+                    .map(|_| ()),
+                alg.verifier_from_der(key_pair.to_der_public_key())
+                    .map(|_| ()),
+                alg.verifier_from_pem(key_pair.to_pem_public_key())
+                    .map(|_| ()),
+            ] {
+                match res {
+                    Err(JoseError::InvalidKeyFormat(err)) => assert_eq!(err.to_string(), expected),
+                    res => panic!("{}: {:?}", alg, res),
+                }
+            }
+
+            let expected = format!("A parameter crv must be {} but secp256k1", alg.curve());
+            for res in [
+                alg.signer_from_jwk(&private_key).map(|_| ()),
+                alg.verifier_from_jwk(&public_key).map(|_| ()),
+            ] {
+                match res {
+                    Err(JoseError::InvalidKeyFormat(err)) => assert_eq!(err.to_string(), expected),
+                    res => panic!("{}: {:?}", alg, res),
+                }
+            }
+        }
+
+        // The same key is accepted by ECDH-ES.
+        ECDH_ES.encrypter_from_jwk(&public_key)?;
+        ECDH_ES.decrypter_from_jwk(&private_key)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_and_verify_ecdsa_deterministic() -> Result<()> {
+        let input = b"abcde12345";
+
+        for alg in &[
+            EcdsaJwsAlgorithm::Es256,
+            EcdsaJwsAlgorithm::Es384,
+            EcdsaJwsAlgorithm::Es512,
+            EcdsaJwsAlgorithm::Es256k,
+        ] {
+            let key_pair = alg.generate_key_pair()?;
+            let verifier = alg.verifier_from_der(key_pair.to_der_public_key())?;
+
+            let mut signer = alg.signer_from_der(key_pair.to_der_private_key())?;
+            let signature1 = signer.sign(input)?;
+            let signature2 = signer.sign(input)?;
+            assert_ne!(signature1, signature2);
+            verifier.verify(input, &signature1)?;
+
+            #[cfg(ossl320)]
+            {
+                signer.set_deterministic(true)?;
+                let signature1 = signer.sign(input)?;
+                let signature2 = signer.sign(input)?;
+                assert_eq!(signature1, signature2);
+                assert_eq!(signature1.len(), alg.signature_len());
+                verifier.verify(input, &signature1)?;
+            }
+            #[cfg(not(ossl320))]
+            {
+                assert!(matches!(
+                    signer.set_deterministic(true),
+                    Err(JoseError::UnsupportedSignatureAlgorithm(_))
+                ));
+                assert!(!signer.is_deterministic());
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(ossl320)]
+    fn sign_ecdsa_deterministic_rfc6979_vectors() -> Result<()> {
+        // RFC 6979 Appendix A.2.5 (P-256, SHA-256)
+        let alg = EcdsaJwsAlgorithm::Es256;
+        let mut signer = alg.signer_from_raw_private_key(from_hex(
+            "c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721",
+        ))?;
+        signer.set_deterministic(true)?;
+
+        for (message, r, s) in [
+            (
+                "sample",
+                "efd48b2aacb6a8fd1140dd9cd45e81d69d2c877b56aaf991c34d0ea84eaf3716",
+                "f7cb1c942d657c41d436c7a1b6e29f65f3e900dbb9aff4064dc4ab2f843acda8",
+            ),
+            (
+                "test",
+                "f1abb023518351cd71d881567b1ea663ed3efcf6c5132b354f28d3b0b7d38367",
+                "019f4113742a2b14bd25926b49c649155f267e60d3814b4c0cc84250e46f0083",
+            ),
+        ] {
+            let signature = signer.sign(message.as_bytes())?;
+            assert_eq!(signature, [from_hex(r), from_hex(s)].concat());
+        }
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
@@ -722,4 +1131,34 @@ mod tests {
         let data = fs::read(&pb)?;
         Ok(data)
     }
+
+    #[test]
+    fn sign_and_verify_ecdsa_with_thumbprint_key_id() -> Result<()> {
+        for alg in &[
+            EcdsaJwsAlgorithm::Es256,
+            EcdsaJwsAlgorithm::Es384,
+            EcdsaJwsAlgorithm::Es512,
+            EcdsaJwsAlgorithm::Es256k,
+        ] {
+            let key_pair = alg.generate_key_pair()?;
+            let public_key = key_pair.to_jwk_public_key();
+
+            let mut signer = alg.signer_from_jwk(&key_pair.to_jwk_private_key())?;
+            signer.set_key_id_to_thumbprint()?;
+            let jws = crate::jws::serialize_compact(
+                b"abcde12345",
+                &crate::jws::JwsHeader::new(),
+                &signer,
+            )?;
+
+            let verifier = alg.verifier_from_jwk(&public_key)?;
+            let (_, header) = crate::jws::deserialize_compact(&jws, &verifier)?;
+            assert_eq!(
+                header.key_id(),
+                Some(public_key.thumbprint_sha256()?.as_str())
+            );
+        }
+
+        Ok(())
+    }
 }
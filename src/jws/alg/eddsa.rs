@@ -7,7 +7,7 @@ use openssl::sign::{Signer, Verifier};
 
 use crate::jwk::{
     alg::ed::{EdCurve, EdKeyPair},
-    Jwk,
+    Jwk, KeyOps,
 };
 use crate::jws::{JwsAlgorithm, JwsSigner, JwsVerifier};
 use crate::util;
@@ -90,6 +90,24 @@ impl EddsaJwsAlgorithm {
         })
     }
 
+    /// Return a signer from a private key of encrypted PKCS#8 PEM format.
+    ///
+    /// Encrypted PKCS#8 PEM format is a DER and base64 encoded EncryptedPrivateKeyInfo
+    /// that surrounded by "-----BEGIN/END ENCRYPTED PRIVATE KEY----".
+    ///
+    /// # Arguments
+    /// * `input` - A private key of encrypted PKCS#8 PEM format.
+    /// * `passphrase` - A passphrase for decrypting the private key.
+    pub fn signer_from_encrypted_pem(
+        &self,
+        input: impl AsRef<[u8]>,
+        passphrase: impl AsRef<[u8]>,
+    ) -> Result<EddsaJwsSigner, JoseError> {
+        let pkcs8_der = util::decrypt_pkcs8_pem(input.as_ref(), passphrase.as_ref())
+            .map_err(JoseError::InvalidKeyFormat)?;
+        self.signer_from_der(pkcs8_der)
+    }
+
     /// Return a signer from a private key that is formatted by a JWK of OKP type.
     ///
     /// # Arguments
@@ -101,7 +119,7 @@ impl EddsaJwsAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be sig: {}", val),
             }
-            if !jwk.is_for_key_operation("sign") {
+            if !jwk.is_for_key_operations(KeyOps::SIGN) {
                 bail!("A parameter key_ops must contains sign.");
             }
             match jwk.algorithm() {
@@ -125,6 +143,25 @@ impl EddsaJwsAlgorithm {
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
+    /// Return a signer from a raw private key.
+    ///
+    /// # Arguments
+    /// * `input` - A raw private key (32 bytes for Ed25519, 57 bytes for Ed448).
+    /// * `curve` - EdDSA curve algorithm
+    pub fn signer_from_raw_private_key(
+        &self,
+        input: impl AsRef<[u8]>,
+        curve: EdCurve,
+    ) -> Result<EddsaJwsSigner, JoseError> {
+        let key_pair = EdKeyPair::from_raw_private_key(input, curve)?;
+        Ok(EddsaJwsSigner {
+            algorithm: *self,
+            curve,
+            private_key: key_pair.into_private_key(),
+            key_id: None,
+        })
+    }
+
     /// Return a verifier from a public key that is a DER encoded SubjectPublicKeyInfo.
     ///
     /// # Arguments
@@ -184,6 +221,41 @@ impl EddsaJwsAlgorithm {
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
+    /// Return a verifier from a raw public key.
+    ///
+    /// # Arguments
+    /// * `input` - A raw public key (32 bytes for Ed25519, 57 bytes for Ed448).
+    /// * `curve` - EdDSA curve algorithm
+    pub fn verifier_from_raw_public_key(
+        &self,
+        input: impl AsRef<[u8]>,
+        curve: EdCurve,
+    ) -> Result<EddsaJwsVerifier, JoseError> {
+        (|| -> anyhow::Result<EddsaJwsVerifier> {
+            let input = input.as_ref();
+            if input.len() != curve.key_len() {
+                bail!(
+                    "The raw public key length must be {}: {}",
+                    curve.key_len(),
+                    input.len()
+                );
+            }
+
+            let pkcs8 = EdKeyPair::to_pkcs8(input, true, curve);
+            let public_key = PKey::public_key_from_der(&pkcs8)?;
+
+            Ok(EddsaJwsVerifier {
+                algorithm: *self,
+                public_key,
+                key_id: None,
+            })
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
     /// Return a verifier from a public key that is formatted by a JWK of OKP type.
     ///
     /// # Arguments
@@ -199,7 +271,7 @@ impl EddsaJwsAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be sig: {}", val),
             }
-            if !jwk.is_for_key_operation("verify") {
+            if !jwk.is_for_key_operations(KeyOps::VERIFY) {
                 bail!("A parameter key_ops must contains verify.");
             }
             match jwk.algorithm() {
@@ -274,6 +346,13 @@ impl EddsaJwsSigner {
     pub fn remove_key_id(&mut self) {
         self.key_id = None;
     }
+
+    /// Set the JWK thumbprint (RFC 7638) of the public key using SHA-256 as a key ID.
+    pub fn set_key_id_to_thumbprint(&mut self) -> Result<(), JoseError> {
+        let jwk = Jwk::from_public_key(&self.private_key)?;
+        self.key_id = Some(jwk.thumbprint_sha256()?);
+        Ok(())
+    }
 }
 
 impl JwsSigner for EddsaJwsSigner {
@@ -295,6 +374,14 @@ impl JwsSigner for EddsaJwsSigner {
         }
     }
 
+    fn key_type(&self) -> Option<&str> {
+        Some("OKP")
+    }
+
+    fn curve(&self) -> Option<&str> {
+        Some(self.curve.name())
+    }
+
     fn sign(&self, message: &[u8]) -> Result<Vec<u8>, JoseError> {
         (|| -> anyhow::Result<Vec<u8>> {
             let mut signer = Signer::new_without_digest(&self.private_key)?;
@@ -451,6 +538,38 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn sign_and_verify_eddsa_raw_key() -> Result<()> {
+        let input = b"abcde12345";
+
+        for curve in [EdCurve::Ed25519, EdCurve::Ed448] {
+            let alg = EddsaJwsAlgorithm::Eddsa;
+            let key_pair = alg.generate_key_pair(curve)?;
+            let jwk = key_pair.to_jwk_key_pair();
+            let param = |name: &str| -> Result<Vec<u8>> {
+                match jwk.parameter(name) {
+                    Some(Value::String(val)) => Ok(util::decode_base64_urlsafe_no_pad(val)?),
+                    _ => unreachable!(),
+                }
+            };
+
+            let signer = alg.signer_from_raw_private_key(&param("d")?, curve)?;
+            let signature = signer.sign(input)?;
+
+            let verifier = alg.verifier_from_raw_public_key(&param("x")?, curve)?;
+            verifier.verify(input, &signature)?;
+
+            assert!(alg
+                .signer_from_raw_private_key(&param("d")?[1..], curve)
+                .is_err());
+            assert!(alg
+                .verifier_from_raw_public_key(&param("x")?[1..], curve)
+                .is_err());
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn sign_and_verify_eddsa_jwt() -> Result<()> {
         let input = b"abcde12345";
@@ -458,14 +577,62 @@ mod tests {
         let alg = EddsaJwsAlgorithm::Eddsa;
 
         let private_key = load_file("jwk/OKP_Ed25519_private.jwk")?;
-        let public_key = load_file("jwk/OKP_Ed25519_private.jwk")?;
+        let public_key = load_file("jwk/OKP_Ed25519_public.jwk")?;
 
         let signer = alg.signer_from_jwk(&Jwk::from_bytes(&private_key)?)?;
         let signature = signer.sign(input)?;
 
-        let verifier = alg.verifier_from_jwk(&Jwk::from_bytes(&public_key)?)?;
+        let public_key = Jwk::from_bytes(&public_key)?;
+        assert!(public_key.is_public());
+        let verifier = alg.verifier_from_jwk(&public_key)?;
+        verifier.verify(input, &signature)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_eddsa_with_public_jwk_rfc8037_vector() -> Result<()> {
+        // RFC 8037 Appendix A.1 and A.4
+        let public_key = Jwk::from_bytes(
+            br#"{"kty":"OKP","crv":"Ed25519",
+            "x":"11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"}"#,
+        )?;
+        let verifier = EddsaJwsAlgorithm::Eddsa.verifier_from_jwk(&public_key)?;
+
+        let input = "eyJhbGciOiJFZERTQSJ9.RXhhbXBsZSBvZiBFZDI1NTE5IHNpZ25pbmc\
+            .hgyY0il_MGCjP0JzlnLWG1PPOt7-09PGcvMg3AIbQR6dWbhijcNR4ki4iylGjg5BhVsPt9g7sVvpAr_MuM0KAg";
+        let (payload, header) = crate::jws::deserialize_compact(input, &verifier)?;
+        assert_eq!(payload, b"Example of Ed25519 signing");
+        assert_eq!(header.algorithm(), Some("EdDSA"));
+
+        // A public key without x cannot be used.
+        let public_key = Jwk::from_bytes(br#"{"kty":"OKP","crv":"Ed25519"}"#)?;
+        assert!(EddsaJwsAlgorithm::Eddsa
+            .verifier_from_jwk(&public_key)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_and_verify_eddsa_encrypted_pem() -> Result<()> {
+        let input = b"abcde12345";
+
+        let private_key = load_file("pem/ED25519_encrypted_private.pem")?;
+        let public_key = load_file("pem/ED25519_public.pem")?;
+
+        let alg = EddsaJwsAlgorithm::Eddsa;
+        let signer = alg.signer_from_encrypted_pem(&private_key, "passphrase")?;
+        let signature = signer.sign(input)?;
+
+        let verifier = alg.verifier_from_pem(&public_key)?;
         verifier.verify(input, &signature)?;
 
+        assert!(matches!(
+            alg.signer_from_encrypted_pem(&private_key, "wrong"),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+
         Ok(())
     }
 
@@ -530,6 +697,42 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn verify_eddsa_non_canonical_s() -> Result<()> {
+        let input = b"abcde12345";
+
+        let alg = EddsaJwsAlgorithm::Eddsa;
+        let key_pair = alg.generate_key_pair(EdCurve::Ed25519)?;
+        let signer = alg.signer_from_der(key_pair.to_der_private_key())?;
+        let verifier = alg.verifier_from_der(key_pair.to_der_public_key())?;
+
+        let signature = signer.sign(input)?;
+        verifier.verify(input, &signature)?;
+
+        // S + L is congruent to S but is not canonical.
+        let order: [u8; 32] = [
+            0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9,
+            0xde, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x10,
+        ];
+        let mut malleated = signature.clone();
+        let mut carry = 0u16;
+        for i in 0..32 {
+            let sum = malleated[32 + i] as u16 + order[i] as u16 + carry;
+            malleated[32 + i] = sum as u8;
+            carry = sum >> 8;
+        }
+        assert_eq!(carry, 0);
+        assert_ne!(malleated, signature);
+
+        assert!(matches!(
+            verifier.verify(input, &malleated),
+            Err(JoseError::InvalidSignature(_))
+        ));
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
@@ -1,11 +1,13 @@
 use std::fmt::Display;
+use std::io::{self, Read};
 use std::ops::Deref;
 
 use anyhow::bail;
+use openssl::memcmp;
 use openssl::pkey::{PKey, Private};
 use openssl::sign::Signer;
 
-use crate::jwk::Jwk;
+use crate::jwk::{Jwk, KeyOps};
 use crate::jws::{JwsAlgorithm, JwsSigner, JwsVerifier};
 use crate::util::{self, HashAlgorithm};
 use crate::{JoseError, Value};
@@ -45,14 +47,7 @@ impl HmacJwsAlgorithm {
         (|| -> anyhow::Result<HmacJwsSigner> {
             let input = input.as_ref();
 
-            let min_key_len = self.hash_algorithm().output_len();
-            if input.len() < min_key_len {
-                bail!(
-                    "Secret key size must be larger than or equal to {}: {}",
-                    min_key_len,
-                    input.len()
-                );
-            }
+            self.check_key_len(input.len())?;
 
             let private_key = PKey::hmac(input)?;
 
@@ -65,6 +60,34 @@ impl HmacJwsAlgorithm {
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
+    /// Return a signer from a secret key that may be shorter than the hash output.
+    ///
+    /// RFC 7518 requires a key of the same size as the hash output or larger.
+    /// This is only for interoperability with legacy systems that use short keys.
+    ///
+    /// # Arguments
+    /// * `input` - A secret key.
+    pub fn signer_from_bytes_allow_short_key(
+        &self,
+        input: impl AsRef<[u8]>,
+    ) -> Result<HmacJwsSigner, JoseError> {
+        (|| -> anyhow::Result<HmacJwsSigner> {
+            let input = input.as_ref();
+            if input.is_empty() {
+                bail!("Secret key must not be empty.");
+            }
+
+            let private_key = PKey::hmac(input)?;
+
+            Ok(HmacJwsSigner {
+                algorithm: *self,
+                private_key,
+                key_id: None,
+            })
+        })()
+        .map_err(JoseError::InvalidKeyFormat)
+    }
+
     /// Return a signer from a secret key that is formatted by a JWK of oct type.
     ///
     /// # Arguments
@@ -80,7 +103,7 @@ impl HmacJwsAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be sig: {}", val),
             }
-            if !jwk.is_for_key_operation("sign") {
+            if !jwk.is_for_key_operations(KeyOps::SIGN) {
                 bail!("A parameter key_ops must contains sign.");
             }
             match jwk.algorithm() {
@@ -94,14 +117,7 @@ impl HmacJwsAlgorithm {
                 None => bail!("A parameter k is required."),
             };
 
-            let min_key_len = self.hash_algorithm().output_len();
-            if k.len() < min_key_len {
-                bail!(
-                    "Secret key size must be larger than or equal to {}: {}",
-                    min_key_len,
-                    k.len()
-                );
-            }
+            self.check_key_len(k.len())?;
 
             let private_key = PKey::hmac(&k)?;
             let key_id = jwk.key_id().map(|val| val.to_string());
@@ -126,14 +142,7 @@ impl HmacJwsAlgorithm {
         (|| -> anyhow::Result<HmacJwsVerifier> {
             let input = input.as_ref();
 
-            let min_key_len = self.hash_algorithm().output_len();
-            if input.len() < min_key_len {
-                bail!(
-                    "Secret key size must be larger than or equal to {}: {}",
-                    min_key_len,
-                    input.len()
-                );
-            }
+            self.check_key_len(input.len())?;
 
             let private_key = PKey::hmac(input)?;
 
@@ -146,6 +155,34 @@ impl HmacJwsAlgorithm {
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
+    /// Return a verifier from a secret key that may be shorter than the hash output.
+    ///
+    /// RFC 7518 requires a key of the same size as the hash output or larger.
+    /// This is only for interoperability with legacy systems that use short keys.
+    ///
+    /// # Arguments
+    /// * `input` - A secret key.
+    pub fn verifier_from_bytes_allow_short_key(
+        &self,
+        input: impl AsRef<[u8]>,
+    ) -> Result<HmacJwsVerifier, JoseError> {
+        (|| -> anyhow::Result<HmacJwsVerifier> {
+            let input = input.as_ref();
+            if input.is_empty() {
+                bail!("Secret key must not be empty.");
+            }
+
+            let private_key = PKey::hmac(input)?;
+
+            Ok(HmacJwsVerifier {
+                algorithm: *self,
+                private_key,
+                key_id: None,
+            })
+        })()
+        .map_err(JoseError::InvalidKeyFormat)
+    }
+
     /// Return a verifier from a secret key that is formatted by a JWK of oct type.
     ///
     /// # Arguments
@@ -161,7 +198,7 @@ impl HmacJwsAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be sig: {}", val),
             }
-            if !jwk.is_for_key_operation("verify") {
+            if !jwk.is_for_key_operations(KeyOps::VERIFY) {
                 bail!("A parameter key_ops must contains verify.");
             }
             match jwk.algorithm() {
@@ -176,14 +213,7 @@ impl HmacJwsAlgorithm {
                 None => bail!("A parameter k is required."),
             };
 
-            let min_key_len = self.hash_algorithm().output_len();
-            if k.len() < min_key_len {
-                bail!(
-                    "Secret key size must be larger than or equal to {}: {}",
-                    min_key_len,
-                    k.len()
-                );
-            }
+            self.check_key_len(k.len())?;
 
             let private_key = PKey::hmac(&k)?;
             let key_id = jwk.key_id().map(|val| val.to_string());
@@ -197,6 +227,18 @@ impl HmacJwsAlgorithm {
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
+    fn check_key_len(&self, len: usize) -> anyhow::Result<()> {
+        let min_key_len = self.hash_algorithm().output_len();
+        if len < min_key_len {
+            bail!(
+                "Secret key size must be larger than or equal to {}: {}",
+                min_key_len,
+                len
+            );
+        }
+        Ok(())
+    }
+
     fn hash_algorithm(&self) -> HashAlgorithm {
         match self {
             Self::Hs256 => HashAlgorithm::Sha256,
@@ -267,12 +309,20 @@ impl JwsSigner for HmacJwsSigner {
         }
     }
 
+    fn key_type(&self) -> Option<&str> {
+        Some("oct")
+    }
+
     fn sign(&self, message: &[u8]) -> Result<Vec<u8>, JoseError> {
+        self.sign_stream(&mut &message[..])
+    }
+
+    fn sign_stream(&self, message: &mut dyn Read) -> Result<Vec<u8>, JoseError> {
         (|| -> anyhow::Result<Vec<u8>> {
             let md = self.algorithm.hash_algorithm().message_digest();
 
             let mut signer = Signer::new(md, &self.private_key)?;
-            signer.update(message)?;
+            io::copy(message, &mut signer)?;
             let signature = signer.sign_to_vec()?;
             Ok(signature)
         })()
@@ -328,7 +378,7 @@ impl JwsVerifier for HmacJwsVerifier {
             let mut signer = Signer::new(md, &self.private_key)?;
             signer.update(message)?;
             let new_signature = signer.sign_to_vec()?;
-            if new_signature.as_slice() != signature {
+            if new_signature.len() != signature.len() || !memcmp::eq(&new_signature, signature) {
                 bail!("Failed to verify.");
             }
             Ok(())
@@ -361,7 +411,7 @@ mod tests {
 
     #[test]
     fn sign_and_verify_hmac_generated_jwk() -> Result<()> {
-        let private_key = util::random_bytes(64);
+        let private_key = util::random_bytes(64)?;
         let input = b"12345abcde";
 
         for alg in &[
@@ -405,7 +455,7 @@ mod tests {
 
     #[test]
     fn sign_and_verify_hmac_bytes() -> Result<()> {
-        let private_key = util::random_bytes(64);
+        let private_key = util::random_bytes(64)?;
         let input = b"abcde12345";
 
         for alg in &[
@@ -423,6 +473,60 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn reject_hmac_short_bytes() -> Result<()> {
+        for alg in &[
+            HmacJwsAlgorithm::Hs256,
+            HmacJwsAlgorithm::Hs384,
+            HmacJwsAlgorithm::Hs512,
+        ] {
+            let min_key_len = alg.hash_algorithm().output_len();
+            let private_key = util::random_bytes(min_key_len - 1)?;
+
+            assert!(matches!(
+                alg.signer_from_bytes(&private_key),
+                Err(JoseError::InvalidKeyFormat(_))
+            ));
+            assert!(matches!(
+                alg.verifier_from_bytes(&private_key),
+                Err(JoseError::InvalidKeyFormat(_))
+            ));
+            assert!(alg.signer_from_jwk(&alg.to_jwk(&private_key)).is_err());
+
+            let private_key = util::random_bytes(min_key_len)?;
+            alg.signer_from_bytes(&private_key)?;
+            alg.verifier_from_bytes(&private_key)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_and_verify_hmac_short_bytes_with_opt_out() -> Result<()> {
+        let input = b"abcde12345";
+        let alg = HmacJwsAlgorithm::Hs256;
+        let private_key = b"0123456789ABCDEF";
+
+        assert!(matches!(
+            alg.signer_from_bytes(private_key),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+        assert!(matches!(
+            alg.verifier_from_jwk(&alg.to_jwk(private_key)),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+
+        let signer = alg.signer_from_bytes_allow_short_key(private_key)?;
+        let signature = signer.sign(input)?;
+        let verifier = alg.verifier_from_bytes_allow_short_key(private_key)?;
+        verifier.verify(input, &signature)?;
+
+        assert!(alg.signer_from_bytes_allow_short_key(b"").is_err());
+        assert!(alg.verifier_from_bytes_allow_short_key(b"").is_err());
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
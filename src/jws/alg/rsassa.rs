@@ -1,11 +1,12 @@
 use std::fmt::Display;
+use std::io::{self, Read};
 use std::ops::Deref;
 
 use anyhow::bail;
 use openssl::pkey::{PKey, Private, Public};
 use openssl::sign::{Signer, Verifier};
 
-use crate::jwk::{alg::rsa::RsaKeyPair, Jwk};
+use crate::jwk::{alg::rsa::RsaKeyPair, Jwk, KeyOps};
 use crate::jws::{JwsAlgorithm, JwsSigner, JwsVerifier};
 use crate::util::der::{DerBuilder, DerType};
 use crate::util::{self, HashAlgorithm};
@@ -124,6 +125,24 @@ impl RsassaJwsAlgorithm {
         })
     }
 
+    /// Return a signer from a private key of encrypted PKCS#8 PEM format.
+    ///
+    /// Encrypted PKCS#8 PEM format is a DER and base64 encoded EncryptedPrivateKeyInfo
+    /// that surrounded by "-----BEGIN/END ENCRYPTED PRIVATE KEY----".
+    ///
+    /// # Arguments
+    /// * `input` - A private key of encrypted PKCS#8 PEM format.
+    /// * `passphrase` - A passphrase for decrypting the private key.
+    pub fn signer_from_encrypted_pem(
+        &self,
+        input: impl AsRef<[u8]>,
+        passphrase: impl AsRef<[u8]>,
+    ) -> Result<RsassaJwsSigner, JoseError> {
+        let pkcs8_der = util::decrypt_pkcs8_pem(input.as_ref(), passphrase.as_ref())
+            .map_err(JoseError::InvalidKeyFormat)?;
+        self.signer_from_der(pkcs8_der)
+    }
+
     /// Return a signer from a private key that is formatted by a JWK of RSA type.
     ///
     /// # Arguments
@@ -135,7 +154,7 @@ impl RsassaJwsAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be sig: {}", val),
             }
-            if !jwk.is_for_key_operation("sign") {
+            if !jwk.is_for_key_operations(KeyOps::SIGN) {
                 bail!("A parameter key_ops must contains sign.");
             }
             match jwk.algorithm() {
@@ -256,7 +275,7 @@ impl RsassaJwsAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be sig: {}", val),
             }
-            if !jwk.is_for_key_operation("verify") {
+            if !jwk.is_for_key_operations(KeyOps::VERIFY) {
                 bail!("A parameter key_ops must contains verify.");
             }
             match jwk.algorithm() {
@@ -354,6 +373,13 @@ impl RsassaJwsSigner {
     pub fn remove_key_id(&mut self) {
         self.key_id = None;
     }
+
+    /// Set the JWK thumbprint (RFC 7638) of the public key using SHA-256 as a key ID.
+    pub fn set_key_id_to_thumbprint(&mut self) -> Result<(), JoseError> {
+        let jwk = Jwk::from_public_key(&self.private_key)?;
+        self.key_id = Some(jwk.thumbprint_sha256()?);
+        Ok(())
+    }
 }
 
 impl JwsSigner for RsassaJwsSigner {
@@ -372,12 +398,20 @@ impl JwsSigner for RsassaJwsSigner {
         }
     }
 
+    fn key_type(&self) -> Option<&str> {
+        Some("RSA")
+    }
+
     fn sign(&self, message: &[u8]) -> Result<Vec<u8>, JoseError> {
+        self.sign_stream(&mut &message[..])
+    }
+
+    fn sign_stream(&self, message: &mut dyn Read) -> Result<Vec<u8>, JoseError> {
         (|| -> anyhow::Result<Vec<u8>> {
             let md = self.algorithm.hash_algorithm().message_digest();
 
             let mut signer = Signer::new(md, &self.private_key)?;
-            signer.update(message)?;
+            io::copy(message, &mut signer)?;
             let signature = signer.sign_to_vec()?;
             Ok(signature)
         })()
@@ -610,6 +644,36 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn sign_and_verify_rsassa_encrypted_pem() -> Result<()> {
+        let input = b"abcde12345";
+
+        let private_key = load_file("pem/RSA_2048bit_encrypted_private.pem")?;
+        let public_key = load_file("pem/RSA_2048bit_public.pem")?;
+
+        let alg = RsassaJwsAlgorithm::Rs256;
+        let signer = alg.signer_from_encrypted_pem(&private_key, "passphrase")?;
+        let signature = signer.sign(input)?;
+
+        let verifier = alg.verifier_from_pem(&public_key)?;
+        verifier.verify(input, &signature)?;
+
+        assert!(matches!(
+            alg.signer_from_encrypted_pem(&private_key, "wrong"),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+        assert!(matches!(
+            alg.signer_from_encrypted_pem(load_file("pem/RSA_2048bit_private.pem")?, "passphrase"),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+
+        let key_pair = RsaKeyPair::from_encrypted_pem(&private_key, "passphrase")?;
+        let expected = RsaKeyPair::from_pem(load_file("pem/RSA_2048bit_private.pem")?)?;
+        assert_eq!(key_pair.to_jwk_private_key(), expected.to_jwk_private_key());
+
+        Ok(())
+    }
+
     #[test]
     fn sign_and_verify_rsassa_pkcs8_der() -> Result<()> {
         let input = b"abcde12345";
@@ -1,12 +1,14 @@
 use std::fmt::Display;
+use std::io::{self, Read};
 use std::ops::Deref;
 
 use anyhow::bail;
 use openssl::pkey::{PKey, Private, Public};
-use openssl::rsa::Rsa;
+use openssl::rsa::{Padding, Rsa};
+use openssl::sign::RsaPssSaltlen;
 use openssl::sign::{Signer, Verifier};
 
-use crate::jwk::{alg::rsa::RsaKeyPair, alg::rsapss::RsaPssKeyPair, Jwk};
+use crate::jwk::{alg::rsa::RsaKeyPair, alg::rsapss::RsaPssKeyPair, Jwk, KeyOps};
 use crate::jws::{JwsAlgorithm, JwsSigner, JwsVerifier};
 use crate::util::der::{DerBuilder, DerType};
 use crate::util::{self, HashAlgorithm};
@@ -119,6 +121,8 @@ impl RsassaPssJwsAlgorithm {
             algorithm: self.clone(),
             private_key: key_pair.into_private_key(),
             key_id: None,
+            salt_len: self.salt_len() as usize,
+            rsa_key: None,
         })
     }
 
@@ -141,9 +145,29 @@ impl RsassaPssJwsAlgorithm {
             algorithm: self.clone(),
             private_key: key_pair.into_private_key(),
             key_id: None,
+            salt_len: self.salt_len() as usize,
+            rsa_key: None,
         })
     }
 
+    /// Return a signer from a private key of encrypted PKCS#8 PEM format.
+    ///
+    /// Encrypted PKCS#8 PEM format is a DER and base64 encoded EncryptedPrivateKeyInfo
+    /// that surrounded by "-----BEGIN/END ENCRYPTED PRIVATE KEY----".
+    ///
+    /// # Arguments
+    /// * `input` - A private key of encrypted PKCS#8 PEM format.
+    /// * `passphrase` - A passphrase for decrypting the private key.
+    pub fn signer_from_encrypted_pem(
+        &self,
+        input: impl AsRef<[u8]>,
+        passphrase: impl AsRef<[u8]>,
+    ) -> Result<RsassaPssJwsSigner, JoseError> {
+        let pkcs8_der = util::decrypt_pkcs8_pem(input.as_ref(), passphrase.as_ref())
+            .map_err(JoseError::InvalidKeyFormat)?;
+        self.signer_from_der(pkcs8_der)
+    }
+
     /// Return a signer from a private key that is formatted by a JWK of RSA type.
     ///
     /// # Arguments
@@ -155,7 +179,7 @@ impl RsassaPssJwsAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be sig: {}", val),
             }
-            if !jwk.is_for_key_operation("sign") {
+            if !jwk.is_for_key_operations(KeyOps::SIGN) {
                 bail!("A parameter key_ops must contains sign.");
             }
             match jwk.algorithm() {
@@ -181,6 +205,8 @@ impl RsassaPssJwsAlgorithm {
                 algorithm: self.clone(),
                 private_key,
                 key_id,
+                salt_len: self.salt_len() as usize,
+                rsa_key: None,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -245,6 +271,8 @@ impl RsassaPssJwsAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id: None,
+                salt_len: None,
+                rsa_key: None,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -306,6 +334,8 @@ impl RsassaPssJwsAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id: None,
+                salt_len: None,
+                rsa_key: None,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -326,7 +356,7 @@ impl RsassaPssJwsAlgorithm {
                 None => {}
                 Some(val) => bail!("A parameter use must be sig: {}", val),
             };
-            if !jwk.is_for_key_operation("verify") {
+            if !jwk.is_for_key_operations(KeyOps::VERIFY) {
                 bail!("A parameter key_ops must contains verify.");
             }
             match jwk.algorithm() {
@@ -372,6 +402,8 @@ impl RsassaPssJwsAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id,
+                salt_len: None,
+                rsa_key: None,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -427,9 +459,57 @@ pub struct RsassaPssJwsSigner {
     algorithm: RsassaPssJwsAlgorithm,
     private_key: PKey<Private>,
     key_id: Option<String>,
+    salt_len: usize,
+    /// A RSA key that is not restricted to the salt length of the algorithm.
+    rsa_key: Option<PKey<Private>>,
 }
 
 impl RsassaPssJwsSigner {
+    /// Return the salt length in bytes.
+    pub fn salt_len(&self) -> usize {
+        self.salt_len
+    }
+
+    /// Set a salt length in bytes.
+    ///
+    /// The default length is the same as the output size of the hash function
+    /// as required by JWA. Other values are only for interoperability.
+    ///
+    /// # Arguments
+    ///
+    /// * `salt_len` - a salt length
+    pub fn set_salt_len(&mut self, salt_len: usize) -> Result<(), JoseError> {
+        if salt_len == self.algorithm.salt_len() as usize {
+            self.salt_len = salt_len;
+            self.rsa_key = None;
+            return Ok(());
+        }
+
+        // A RSA-PSS key is restricted to the salt length of the algorithm.
+        let rsa_key = (|| -> anyhow::Result<PKey<Private>> {
+            let rsa = self.private_key.rsa()?;
+            let rsa = match (rsa.p(), rsa.q(), rsa.dmp1(), rsa.dmq1(), rsa.iqmp()) {
+                (Some(p), Some(q), Some(dp), Some(dq), Some(qi)) => Rsa::from_private_components(
+                    rsa.n().to_owned()?,
+                    rsa.e().to_owned()?,
+                    rsa.d().to_owned()?,
+                    p.to_owned()?,
+                    q.to_owned()?,
+                    dp.to_owned()?,
+                    dq.to_owned()?,
+                    qi.to_owned()?,
+                )?,
+                _ => bail!("The private key must have CRT parameters."),
+            };
+            Ok(PKey::from_rsa(rsa)?)
+        })()
+        .map_err(JoseError::InvalidKeyFormat)?;
+
+        self.salt_len = salt_len;
+        self.rsa_key = Some(rsa_key);
+        Ok(())
+    }
+
     pub fn set_key_id(&mut self, value: impl Into<String>) {
         self.key_id = Some(value.into());
     }
@@ -437,6 +517,13 @@ impl RsassaPssJwsSigner {
     pub fn remove_key_id(&mut self) {
         self.key_id = None;
     }
+
+    /// Set the JWK thumbprint (RFC 7638) of the public key using SHA-256 as a key ID.
+    pub fn set_key_id_to_thumbprint(&mut self) -> Result<(), JoseError> {
+        let jwk = Jwk::from_public_key(&self.private_key)?;
+        self.key_id = Some(jwk.thumbprint_sha256()?);
+        Ok(())
+    }
 }
 
 impl JwsSigner for RsassaPssJwsSigner {
@@ -455,12 +542,29 @@ impl JwsSigner for RsassaPssJwsSigner {
         }
     }
 
+    fn key_type(&self) -> Option<&str> {
+        Some("RSA")
+    }
+
     fn sign(&self, message: &[u8]) -> Result<Vec<u8>, JoseError> {
+        self.sign_stream(&mut &message[..])
+    }
+
+    fn sign_stream(&self, message: &mut dyn Read) -> Result<Vec<u8>, JoseError> {
         (|| -> anyhow::Result<Vec<u8>> {
             let md = self.algorithm.hash_algorithm().message_digest();
 
-            let mut signer = Signer::new(md, &self.private_key)?;
-            signer.update(message)?;
+            let mut signer = match &self.rsa_key {
+                Some(rsa_key) => {
+                    let mut signer = Signer::new(md, rsa_key)?;
+                    signer.set_rsa_padding(Padding::PKCS1_PSS)?;
+                    signer.set_rsa_mgf1_md(md)?;
+                    signer.set_rsa_pss_saltlen(RsaPssSaltlen::custom(self.salt_len as i32))?;
+                    signer
+                }
+                None => Signer::new(md, &self.private_key)?,
+            };
+            io::copy(message, &mut signer)?;
             let signature = signer.sign_to_vec()?;
             Ok(signature)
         })()
@@ -485,9 +589,42 @@ pub struct RsassaPssJwsVerifier {
     algorithm: RsassaPssJwsAlgorithm,
     public_key: PKey<Public>,
     key_id: Option<String>,
+    salt_len: Option<usize>,
+    /// A RSA key that is not restricted to the salt length of the algorithm.
+    rsa_key: Option<PKey<Public>>,
 }
 
 impl RsassaPssJwsVerifier {
+    /// Return the expected salt length in bytes if it is set.
+    pub fn salt_len(&self) -> Option<usize> {
+        self.salt_len
+    }
+
+    /// Set a salt length in bytes that a signature must exactly use.
+    ///
+    /// # Arguments
+    ///
+    /// * `salt_len` - a expected salt length
+    pub fn set_salt_len(&mut self, salt_len: usize) -> Result<(), JoseError> {
+        if self.rsa_key.is_none() {
+            let rsa_key = (|| -> anyhow::Result<PKey<Public>> {
+                let rsa = self.public_key.rsa()?;
+                let rsa = Rsa::from_public_components(rsa.n().to_owned()?, rsa.e().to_owned()?)?;
+                Ok(PKey::from_rsa(rsa)?)
+            })()
+            .map_err(JoseError::InvalidKeyFormat)?;
+            self.rsa_key = Some(rsa_key);
+        }
+        self.salt_len = Some(salt_len);
+        Ok(())
+    }
+
+    /// Remove the expected salt length.
+    pub fn remove_salt_len(&mut self) {
+        self.salt_len = None;
+        self.rsa_key = None;
+    }
+
     pub fn set_key_id(&mut self, value: impl Into<String>) {
         self.key_id = Some(value.into());
     }
@@ -513,7 +650,16 @@ impl JwsVerifier for RsassaPssJwsVerifier {
         (|| -> anyhow::Result<()> {
             let md = self.algorithm.hash_algorithm().message_digest();
 
-            let mut verifier = Verifier::new(md, &self.public_key)?;
+            let mut verifier = match (self.salt_len, &self.rsa_key) {
+                (Some(salt_len), Some(rsa_key)) => {
+                    let mut verifier = Verifier::new(md, rsa_key)?;
+                    verifier.set_rsa_padding(Padding::PKCS1_PSS)?;
+                    verifier.set_rsa_mgf1_md(md)?;
+                    verifier.set_rsa_pss_saltlen(RsaPssSaltlen::custom(salt_len as i32))?;
+                    verifier
+                }
+                _ => Verifier::new(md, &self.public_key)?,
+            };
             verifier.update(message)?;
             if !verifier.verify(signature)? {
                 bail!("The signature does not match.");
@@ -669,6 +815,46 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn sign_and_verify_rsassa_pss_salt_len() -> Result<()> {
+        let input = b"abcde12345";
+
+        for alg in &[
+            RsassaPssJwsAlgorithm::Ps256,
+            RsassaPssJwsAlgorithm::Ps384,
+            RsassaPssJwsAlgorithm::Ps512,
+        ] {
+            let private_key = load_file("jwk/RSA_private.jwk")?;
+            let public_key = load_file("jwk/RSA_public.jwk")?;
+            let private_key = Jwk::from_bytes(&private_key)?;
+            let public_key = Jwk::from_bytes(&public_key)?;
+            let digest_len = alg.hash_algorithm().output_len();
+
+            let signer = alg.signer_from_jwk(&private_key)?;
+            assert_eq!(signer.salt_len(), digest_len);
+            let signature = signer.sign(input)?;
+
+            let mut verifier = alg.verifier_from_jwk(&public_key)?;
+            verifier.verify(input, &signature)?;
+            verifier.set_salt_len(digest_len)?;
+            verifier.verify(input, &signature)?;
+            verifier.set_salt_len(0)?;
+            assert!(verifier.verify(input, &signature).is_err());
+
+            let mut signer = alg.signer_from_jwk(&private_key)?;
+            signer.set_salt_len(0)?;
+            let signature = signer.sign(input)?;
+
+            let mut verifier = alg.verifier_from_jwk(&public_key)?;
+            verifier.set_salt_len(0)?;
+            verifier.verify(input, &signature)?;
+            verifier.set_salt_len(digest_len)?;
+            assert!(verifier.verify(input, &signature).is_err());
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn sign_and_verify_rsassa_pss_jwt() -> Result<()> {
         let input = b"abcde12345";
@@ -721,6 +907,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn sign_and_verify_rsassa_pss_encrypted_pem() -> Result<()> {
+        let input = b"abcde12345";
+
+        let private_key = load_file("pem/RSA-PSS_2048bit_SHA-256_encrypted_private.pem")?;
+        let public_key = load_file("pem/RSA-PSS_2048bit_SHA-256_public.pem")?;
+
+        let alg = RsassaPssJwsAlgorithm::Ps256;
+        let signer = alg.signer_from_encrypted_pem(&private_key, "passphrase")?;
+        let signature = signer.sign(input)?;
+
+        let verifier = alg.verifier_from_pem(&public_key)?;
+        verifier.verify(input, &signature)?;
+
+        assert!(matches!(
+            alg.signer_from_encrypted_pem(&private_key, "wrong"),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn sign_and_verify_rsassa_pss_pkcs8_der() -> Result<()> {
         let input = b"abcde12345";
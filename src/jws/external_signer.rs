@@ -0,0 +1,123 @@
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use crate::jws::{JwsAlgorithm, JwsSigner};
+use crate::JoseError;
+
+type SignFn = dyn Fn(&[u8]) -> Result<Vec<u8>, JoseError> + Send + Sync;
+
+/// A signer that delegates the signature operation to a external function.
+///
+/// This is useful for keys that cannot be exported (e.g. keys in a HSM by PKCS#11).
+/// The function receives the JWS signing input and must return the raw JWS signature
+/// (e.g. R || S for ECDSA, not a DER-encoded one).
+#[derive(Clone)]
+pub struct ExternalJwsSigner {
+    algorithm: Box<dyn JwsAlgorithm>,
+    signature_len: usize,
+    key_id: Option<String>,
+    sign_fn: Arc<SignFn>,
+}
+
+impl ExternalJwsSigner {
+    /// Return a signer that delegates the signature operation to a function.
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - A algorithm that the function uses.
+    /// * `signature_len` - A signature length of JWS.
+    /// * `sign_fn` - A function that returns a signature of the message.
+    pub fn new<F>(algorithm: &dyn JwsAlgorithm, signature_len: usize, sign_fn: F) -> Self
+    where
+        F: Fn(&[u8]) -> Result<Vec<u8>, JoseError> + Send + Sync + 'static,
+    {
+        Self {
+            algorithm: algorithm.box_clone(),
+            signature_len,
+            key_id: None,
+            sign_fn: Arc::new(sign_fn),
+        }
+    }
+
+    pub fn set_key_id(&mut self, value: impl Into<String>) {
+        self.key_id = Some(value.into());
+    }
+
+    pub fn remove_key_id(&mut self) {
+        self.key_id = None;
+    }
+}
+
+impl JwsSigner for ExternalJwsSigner {
+    fn algorithm(&self) -> &dyn JwsAlgorithm {
+        self.algorithm.as_ref()
+    }
+
+    fn signature_len(&self) -> usize {
+        self.signature_len
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        self.key_id.as_deref()
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, JoseError> {
+        (self.sign_fn)(message)
+    }
+
+    fn box_clone(&self) -> Box<dyn JwsSigner> {
+        Box::new(self.clone())
+    }
+}
+
+impl Debug for ExternalJwsSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExternalJwsSigner")
+            .field("algorithm", &self.algorithm)
+            .field("signature_len", &self.signature_len)
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use anyhow::Result;
+
+    use super::ExternalJwsSigner;
+    use crate::jws::{self, JwsHeader, JwsSigner, ES256};
+
+    #[test]
+    fn test_external_signer() -> Result<()> {
+        let private_key = load_file("pem/EC_P-256_private.pem")?;
+        let public_key = load_file("pem/EC_P-256_public.pem")?;
+
+        // The closure stands in for a signing operation in a HSM.
+        let key = ES256.signer_from_pem(&private_key)?;
+        let mut signer = ExternalJwsSigner::new(&ES256, 64, move |message| key.sign(message));
+        signer.set_key_id("hsm-key-1");
+        assert_eq!(signer.algorithm().name(), "ES256");
+
+        let jws = jws::serialize_compact(b"payload", &JwsHeader::new(), &signer)?;
+
+        let verifier = ES256.verifier_from_pem(&public_key)?;
+        let (payload, header) = jws::deserialize_compact(&jws, &verifier)?;
+        assert_eq!(payload, b"payload");
+        assert_eq!(header.algorithm(), Some("ES256"));
+        assert_eq!(header.key_id(), Some("hsm-key-1"));
+
+        Ok(())
+    }
+
+    fn load_file(path: &str) -> Result<Vec<u8>> {
+        let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        pb.push("data");
+        pb.push(path);
+
+        let data = fs::read(&pb)?;
+        Ok(data)
+    }
+}
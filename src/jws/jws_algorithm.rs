@@ -1,4 +1,5 @@
 use std::fmt::Debug;
+use std::io::Read;
 
 use crate::JoseError;
 
@@ -31,6 +32,18 @@ pub trait JwsSigner: Debug + Send + Sync {
     /// The default value is a value of kid parameter in JWK.
     fn key_id(&self) -> Option<&str>;
 
+    /// Return the key type (kty) of the signing key: "EC", "RSA", "OKP" or "oct".
+    ///
+    /// The default implementation returns None, which means the key type is unknown.
+    fn key_type(&self) -> Option<&str> {
+        None
+    }
+
+    /// Return the curve (crv) of the signing key when the key type has curves.
+    fn curve(&self) -> Option<&str> {
+        None
+    }
+
     /// Return the signature length of JWS.
     fn signature_len(&self) -> usize;
 
@@ -41,6 +54,23 @@ pub trait JwsSigner: Debug + Send + Sync {
     /// * `message` - The message data to sign.
     fn sign(&self, message: &[u8]) -> Result<Vec<u8>, JoseError>;
 
+    /// Return a signature of the data that is read from a stream.
+    ///
+    /// The hash-then-sign algorithms process the stream incrementally,
+    /// so the whole message doesn't need to be kept in memory.
+    /// The default implementation reads all the data and calls `sign`.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The stream of the message data to sign.
+    fn sign_stream(&self, message: &mut dyn Read) -> Result<Vec<u8>, JoseError> {
+        let mut buf = Vec::new();
+        message
+            .read_to_end(&mut buf)
+            .map_err(|err| JoseError::InvalidSignature(err.into()))?;
+        self.sign(&buf)
+    }
+
     fn box_clone(&self) -> Box<dyn JwsSigner>;
 }
 
@@ -50,6 +80,8 @@ impl Clone for Box<dyn JwsSigner> {
     }
 }
 
+/// A verifier holds a parsed key and doesn't mutate it while verifying,
+/// so an instance can be shared across threads (e.g. by `Arc`) and reused for many tokens.
 pub trait JwsVerifier: Debug + Send + Sync {
     /// Return the source algrithm instance.
     fn algorithm(&self) -> &dyn JwsAlgorithm;
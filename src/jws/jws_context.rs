@@ -3,19 +3,27 @@ use std::fmt::Debug;
 
 use anyhow::bail;
 
-use crate::jws::{JwsHeader, JwsHeaderSet, JwsSigner, JwsVerifier};
+use crate::jws::{JwsHeader, JwsHeaderSet, JwsSigner, JwsVerifier, SigningAlgorithm};
 use crate::util;
-use crate::{JoseError, Map, Value};
+use crate::{JoseError, JoseHeader, Map, Value};
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct JwsContext {
     acceptable_criticals: BTreeSet<String>,
+    expected_types: Option<BTreeSet<String>>,
+    acme_profile: bool,
+    base64_padding_tolerant: bool,
+    strict_json: bool,
 }
 
 impl JwsContext {
     pub fn new() -> Self {
         Self {
             acceptable_criticals: BTreeSet::new(),
+            expected_types: None,
+            acme_profile: false,
+            base64_padding_tolerant: false,
+            strict_json: true,
         }
     }
 
@@ -46,6 +54,179 @@ impl JwsContext {
         self.acceptable_criticals.remove(name);
     }
 
+    /// Set token types that the typ header claim must match when deserializing.
+    ///
+    /// The comparison is case-insensitive and the "application/" prefix is ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - expected token types (e.g. "JWT")
+    pub fn set_expected_types(&mut self, values: &[&str]) {
+        let types = values
+            .iter()
+            .map(|val| util::normalize_media_type(val))
+            .collect();
+        self.expected_types = Some(types);
+    }
+
+    /// Remove the restriction of token types.
+    pub fn remove_expected_types(&mut self) {
+        self.expected_types = None;
+    }
+
+    /// Test a token type is acceptable.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a value of typ header claim
+    pub fn is_expected_type(&self, value: &str) -> bool {
+        match &self.expected_types {
+            Some(types) => types.contains(&util::normalize_media_type(value)),
+            None => true,
+        }
+    }
+
+    /// Test the ACME profile (RFC 8555 Section 6.2) is enforced when deserializing.
+    pub fn is_acme_profile(&self) -> bool {
+        self.acme_profile
+    }
+
+    /// Set whether the ACME profile (RFC 8555 Section 6.2) is enforced when deserializing.
+    ///
+    /// When it is enabled, a JWS must be in the flattened JSON serialization without
+    /// unprotected header, and the protected header must have alg, url, nonce and
+    /// exactly one of jwk or kid, and nothing else.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - true to enforce the ACME profile
+    pub fn set_acme_profile(&mut self, value: bool) {
+        self.acme_profile = value;
+    }
+
+    /// Test the "=" padding of base64url encoded parts is accepted when deserializing.
+    pub fn is_base64_padding_tolerant(&self) -> bool {
+        self.base64_padding_tolerant
+    }
+
+    /// Set whether the "=" padding of base64url encoded parts is accepted when deserializing.
+    ///
+    /// JOSE doesn't allow padding, so it is rejected by default. Enable it only for
+    /// producers that are known to emit padded tokens.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - true to accept padded parts
+    pub fn set_base64_padding_tolerant(&mut self, value: bool) {
+        self.base64_padding_tolerant = value;
+    }
+
+    /// Test duplicate member names in a decoded protected header are rejected.
+    pub fn is_strict_json(&self) -> bool {
+        self.strict_json
+    }
+
+    /// Set whether duplicate member names in a decoded protected header are rejected.
+    ///
+    /// They are rejected by default, because parsers that take different values for
+    /// a duplicate name may disagree about the header. Disable it only for producers
+    /// that are known to emit such headers; the last value is used then.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - false to accept duplicate member names
+    pub fn set_strict_json(&mut self, value: bool) {
+        self.strict_json = value;
+    }
+
+    fn parse_json_object(&self, input: &[u8]) -> anyhow::Result<Map<String, Value>> {
+        if self.strict_json {
+            util::parse_json_object_strict(input)
+        } else {
+            Ok(serde_json::from_slice(input)?)
+        }
+    }
+
+    fn decode_base64(&self, input: impl AsRef<[u8]>) -> Result<Vec<u8>, base64::DecodeError> {
+        if self.base64_padding_tolerant {
+            util::decode_base64_urlsafe_padding_tolerant(input)
+        } else {
+            util::decode_base64_urlsafe_no_pad(input)
+        }
+    }
+
+    fn check_acme(protected: &JwsHeader, unprotected: Option<&Value>) -> anyhow::Result<()> {
+        if unprotected.is_some() {
+            bail!("The ACME JWS must not have a unprotected header.");
+        }
+
+        for (key, _) in protected.claims_iter() {
+            match key {
+                "alg" | "jwk" | "kid" | "url" | "nonce" => {}
+                _ => bail!("The ACME JWS must not have the {} header claim.", key),
+            }
+        }
+
+        match protected.algorithm() {
+            Some(val) if val == "none" || val.starts_with("HS") => bail!(
+                "The ACME JWS alg header claim must be a asymmetric signature algorithm: {}",
+                val
+            ),
+            Some(_) => {}
+            None => bail!("The ACME JWS alg header claim is required."),
+        }
+
+        match (protected.claim("jwk"), protected.claim("kid")) {
+            (Some(_), Some(_)) => {
+                bail!("The ACME JWS must not have both of the jwk and kid header claims.")
+            }
+            (None, None) => bail!("The ACME JWS must have the jwk or kid header claim."),
+            _ => {}
+        }
+
+        if protected.url().is_none() {
+            bail!("The ACME JWS url header claim is required.");
+        }
+        if protected.nonce_str().is_none() {
+            bail!("The ACME JWS nonce header claim is required.");
+        }
+
+        Ok(())
+    }
+
+    fn check_type(&self, header: &JwsHeader) -> anyhow::Result<()> {
+        if self.expected_types.is_some() {
+            match header.token_type() {
+                Some(val) if self.is_expected_type(val) => {}
+                Some(val) => bail!("The JWS typ header claim is not expected: {}", val),
+                None => bail!("The JWS typ header claim is required."),
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the unprotected header has no claims that must be integrity protected.
+    fn check_unprotected(unprotected: &Map<String, Value>) -> anyhow::Result<()> {
+        for key in ["alg", "crit"] {
+            if unprotected.contains_key(key) {
+                bail!("The JWS {} header claim must be in protected.", key);
+            }
+        }
+        Ok(())
+    }
+
+    fn detached_signing_input(header_b64: &str, payload: &[u8], b64: bool) -> Vec<u8> {
+        let mut message = Vec::with_capacity(header_b64.len() + 1 + payload.len() * 4 / 3 + 4);
+        message.extend_from_slice(header_b64.as_bytes());
+        message.push(b'.');
+        if b64 {
+            message.extend_from_slice(util::encode_base64_urlsafe_nopad(payload).as_bytes());
+        } else {
+            message.extend_from_slice(payload);
+        }
+        message
+    }
+
     /// Return a representation of the data that is formatted by compact serialization.
     ///
     /// # Arguments
@@ -112,21 +293,17 @@ impl JwsContext {
             };
             capacity += util::ceiling(signer.signature_len() * 4, 3);
 
-            let mut message_buffer = vec![];
             let mut message = String::with_capacity(capacity);
             util::encode_base64_urlsafe_nopad_buf(header_bytes, &mut message);
             message.push_str(".");
-            message_buffer.append(unsafe {message.clone().as_mut_vec()});
-            if b64 {
+            let message_buffer = if b64 {
                 util::encode_base64_urlsafe_nopad_buf(payload, &mut message);
+                message.as_bytes().to_vec()
             } else {
-                //let payload = std::str::from_utf8_unchecked(payload);
-                //if payload.contains(".") {
-                //    bail!("A JWS payload cannot contain dot.");
-                //}
-                //message.push_str(payload);
-                message_buffer.append(payload.to_vec().as_mut());
-            }
+                let mut message_buffer = message.as_bytes().to_vec();
+                message_buffer.extend_from_slice(payload);
+                message_buffer
+            };
 
             let signature = signer.sign(&message_buffer)?;
 
@@ -141,6 +318,32 @@ impl JwsContext {
         })
     }
 
+    /// Return a representation of the data that is formatted by compact serialization
+    /// with a detached payload (RFC 7515 Appendix F).
+    ///
+    /// The payload part of the result is empty, so the payload must be transmitted
+    /// separately (e.g. as a HTTP body). When the b64 header claim is false,
+    /// the payload may contain any bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The payload data.
+    /// * `header` - The JWS heaser claims.
+    /// * `signer` - The JWS signer.
+    pub fn serialize_compact_detached(
+        &self,
+        payload: &[u8],
+        header: &JwsHeader,
+        signer: &dyn JwsSigner,
+    ) -> Result<String, JoseError> {
+        let message = self.serialize_compact(payload, header, signer)?;
+
+        // Neither the header nor the signature part contains a dot.
+        let (header_b64, rest) = message.split_once('.').unwrap_or((&message, ""));
+        let signature_b64 = rest.rsplit_once('.').map_or(rest, |(_, val)| val);
+        Ok(format!("{}..{}", header_b64, signature_b64))
+    }
+
     /// Return a representation of the data that is formatted by flattened json serialization.
     ///
     /// # Arguments
@@ -195,6 +398,7 @@ impl JwsContext {
                     None => bail!("A signer is not found."),
                 };
 
+                Self::check_unprotected(header.claims_set(false))?;
                 let mut protected_map = header.claims_set(true).clone();
 
                 match merged.algorithm() {
@@ -286,6 +490,7 @@ impl JwsContext {
         F: Fn(&JwsHeader) -> Option<&'a dyn JwsSigner>,
     {
         (|| -> anyhow::Result<String> {
+            Self::check_unprotected(header.claims_set(false))?;
             let protected_map = header.claims_set(true);
             let mut b64 = true;
             match protected_map.get("crit") {
@@ -398,6 +603,144 @@ impl JwsContext {
         input: impl AsRef<[u8]>,
         selector: F,
     ) -> Result<(Vec<u8>, JwsHeader), JoseError>
+    where
+        F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
+    {
+        self.deserialize_compact_parts(input, None, selector)
+    }
+
+    /// Deserialize the input that is formatted by compact serialization
+    /// with one of the allowed verifiers.
+    ///
+    /// Each verifier whose algorithm matches the alg header claim is tried in order,
+    /// and the result of the first one that verifies the signature is returned.
+    /// When a verifier has a key ID, it must also match the kid header claim.
+    /// The input is rejected if no verifier matches, so this is the recommended
+    /// entry point to pin algorithms.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `verifiers` - The allowed JWS verifiers.
+    pub fn deserialize_compact_with_allowed(
+        &self,
+        input: impl AsRef<[u8]>,
+        verifiers: &[&dyn JwsVerifier],
+    ) -> Result<(Vec<u8>, JwsHeader), JoseError> {
+        let input = input.as_ref();
+        let candidates = (|| -> anyhow::Result<Vec<&dyn JwsVerifier>> {
+            let header = self.decode_compact_header(input)?;
+            let alg = match header.claim("alg") {
+                Some(Value::String(val)) => val,
+                Some(_) => bail!("The JWS alg header claim must be a string."),
+                None => bail!("The JWS alg header claim is required."),
+            };
+
+            let allowed: Vec<&dyn JwsVerifier> = verifiers
+                .iter()
+                .copied()
+                .filter(|verifier| verifier.algorithm().name() == alg)
+                .collect();
+            if allowed.is_empty() {
+                bail!("The JWS alg header claim is not allowed: {}", alg);
+            }
+
+            let candidates: Vec<&dyn JwsVerifier> = allowed
+                .into_iter()
+                .filter(|verifier| match verifier.key_id() {
+                    Some(expected) => header.key_id() == Some(expected),
+                    None => true,
+                })
+                .collect();
+            if candidates.is_empty() {
+                match header.key_id() {
+                    Some(val) => bail!(
+                        "A verifier is not found for the JWS kid header claim: {}",
+                        val
+                    ),
+                    None => bail!("A verifier is not found without the JWS kid header claim."),
+                }
+            }
+
+            Ok(candidates)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwsFormat(err),
+        })?;
+
+        let mut last_err = None;
+        for verifier in candidates {
+            match self.deserialize_compact(input, verifier) {
+                Ok(val) => return Ok(val),
+                Err(err @ JoseError::InvalidSignature(_)) => last_err = Some(err),
+                Err(err) => return Err(err),
+            }
+        }
+        Err(last_err.unwrap())
+    }
+
+    /// Deserialize the input that is formatted by compact serialization
+    /// with a detached payload (RFC 7515 Appendix F).
+    ///
+    /// The b64 header claim and the other critical header claims must be declared
+    /// as acceptable by [`JwsContext::add_acceptable_critical`].
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data that has the empty payload part.
+    /// * `payload` - The detached payload data.
+    /// * `verifier` - The JWS verifier.
+    pub fn deserialize_compact_detached(
+        &self,
+        input: impl AsRef<[u8]>,
+        payload: &[u8],
+        verifier: &dyn JwsVerifier,
+    ) -> Result<JwsHeader, JoseError> {
+        self.deserialize_compact_detached_with_selector(input, payload, |_header| {
+            Ok(Some(verifier))
+        })
+    }
+
+    /// Deserialize the input that is formatted by compact serialization
+    /// with a detached payload (RFC 7515 Appendix F).
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data that has the empty payload part.
+    /// * `payload` - The detached payload data.
+    /// * `selector` - a function for selecting the verifying algorithm.
+    pub fn deserialize_compact_detached_with_selector<'a, F>(
+        &self,
+        input: impl AsRef<[u8]>,
+        payload: &[u8],
+        selector: F,
+    ) -> Result<JwsHeader, JoseError>
+    where
+        F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
+    {
+        let (_, header) = self.deserialize_compact_parts(input, Some(payload), selector)?;
+        Ok(header)
+    }
+
+    fn decode_compact_header(&self, input: &[u8]) -> anyhow::Result<JwsHeader> {
+        let header = match input.iter().position(|b| *b == b'.') {
+            Some(pos) => &input[..pos],
+            None => bail!(
+                "The compact serialization form of JWS must be three parts separated by period: 1 parts"
+            ),
+        };
+        let header = self.decode_base64(header)?;
+        let header = self.parse_json_object(&header)?;
+        Ok(JwsHeader::from_map(header)?)
+    }
+
+    fn deserialize_compact_parts<'a, F>(
+        &self,
+        input: impl AsRef<[u8]>,
+        detached_payload: Option<&[u8]>,
+        selector: F,
+    ) -> Result<(Vec<u8>, JwsHeader), JoseError>
     where
         F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
     {
@@ -411,7 +754,8 @@ impl JwsContext {
                 .collect();
             if indexies.len() != 2 {
                 bail!(
-                    "The compact serialization form of JWS must be three parts separated by colon."
+                    "The compact serialization form of JWS must be three parts separated by period: {} parts",
+                    indexies.len() + 1
                 );
             }
 
@@ -419,9 +763,13 @@ impl JwsContext {
             let payload = &input[(indexies[0] + 1)..(indexies[1])];
             let signature = &input[(indexies[1] + 1)..];
 
-            let header = util::decode_base64_urlsafe_no_pad(header)?;
-            let header: Map<String, Value> = serde_json::from_slice(&header)?;
+            let header = self.decode_base64(header)?;
+            let header = self.parse_json_object(&header)?;
             let header = JwsHeader::from_map(header)?;
+            if self.acme_profile {
+                bail!("The ACME JWS must be in the flattened JSON serialization.");
+            }
+            self.check_type(&header)?;
 
             let verifier = match selector(&header)? {
                 Some(val) => val,
@@ -465,14 +813,27 @@ impl JwsContext {
                 }
             }
 
-            let message = &input[..(indexies[1])];
-            let signature = util::decode_base64_urlsafe_no_pad(signature)?;
-            verifier.verify(message, &signature)?;
+            let signature = self.decode_base64(signature)?;
+            let payload = match detached_payload {
+                Some(detached_payload) => {
+                    if !payload.is_empty() {
+                        bail!("The payload part of a JWS with a detached payload must be empty.");
+                    }
+                    let header_b64 = std::str::from_utf8(&input[0..indexies[0]])?;
+                    let message = Self::detached_signing_input(header_b64, detached_payload, b64);
+                    verifier.verify(&message, &signature)?;
+                    detached_payload.to_vec()
+                }
+                None => {
+                    let message = &input[..(indexies[1])];
+                    verifier.verify(message, &signature)?;
 
-            let payload = if b64 {
-                util::decode_base64_urlsafe_no_pad(payload)?
-            } else {
-                payload.to_vec()
+                    if b64 {
+                        self.decode_base64(payload)?
+                    } else {
+                        payload.to_vec()
+                    }
+                }
             };
 
             Ok((payload, header))
@@ -483,6 +844,91 @@ impl JwsContext {
         })
     }
 
+    /// Deserialize the input that is formatted by compact serialization with a verifier that
+    /// is built from the jwk header claim.
+    ///
+    /// Anyone can embed a key in a JWS, so the JWK thumbprint (RFC 7638) of the embedded key
+    /// must match the pinned one that is trusted by other means.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `pinned_thumbprint` - The expected base64url encoded JWK thumbprint (SHA-256) of the key.
+    pub fn deserialize_compact_with_embedded_jwk(
+        &self,
+        input: impl AsRef<[u8]>,
+        pinned_thumbprint: &str,
+    ) -> Result<(Vec<u8>, JwsHeader), JoseError> {
+        let input = input.as_ref();
+        let verifier = self.embedded_jwk_verifier(input, Some(pinned_thumbprint))?;
+        self.deserialize_compact(input, &*verifier)
+    }
+
+    /// Deserialize the input that is formatted by compact serialization with a verifier that
+    /// is built from the jwk header claim, whatever the embedded key is.
+    ///
+    /// # Security
+    ///
+    /// Anyone can embed a key in a JWS, so a successful verification only proves that
+    /// the JWS is signed by the embedded key, not who signed it. Use it only when the key
+    /// itself is the subject (e.g. a new ACME account) or is checked by the caller after
+    /// the verification. Otherwise use [`Self::deserialize_compact_with_embedded_jwk`].
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    pub fn deserialize_compact_with_embedded_jwk_unpinned(
+        &self,
+        input: impl AsRef<[u8]>,
+    ) -> Result<(Vec<u8>, JwsHeader), JoseError> {
+        let input = input.as_ref();
+        let verifier = self.embedded_jwk_verifier(input, None)?;
+        self.deserialize_compact(input, &*verifier)
+    }
+
+    fn embedded_jwk_verifier(
+        &self,
+        input: &[u8],
+        pinned_thumbprint: Option<&str>,
+    ) -> Result<Box<dyn JwsVerifier>, JoseError> {
+        (|| -> anyhow::Result<Box<dyn JwsVerifier>> {
+            let header = self.decode_compact_header(input)?;
+
+            let mut jwk = match header.jwk_strict()? {
+                Some(val) => val,
+                None => bail!("The JWS jwk header claim is required."),
+            };
+            if jwk.key_type() == "oct" || jwk.is_private() {
+                bail!("The JWS jwk header claim must be a public key.");
+            }
+            if let Some(expected) = pinned_thumbprint {
+                let actual = jwk.thumbprint_sha256()?;
+                if actual != expected {
+                    bail!(
+                        "The thumbprint of the JWS jwk header claim is not pinned: {}",
+                        actual
+                    );
+                }
+            }
+
+            let alg = match header.algorithm() {
+                Some(val) => val,
+                None => bail!("The JWS alg header claim is required."),
+            };
+            let alg = match SigningAlgorithm::from_name(alg) {
+                Some(val) => val,
+                None => bail!("The JWS alg header claim is not supported: {}", alg),
+            };
+            // The key is identified by itself, not by the kid header claim.
+            jwk.set_parameter("kid", None)?;
+            Ok(alg.verifier_from_jwk(&jwk)?)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwsFormat(err),
+        })
+    }
+
     /// Deserialize the input that is formatted by json serialization.
     ///
     /// # Arguments
@@ -544,6 +990,9 @@ impl JwsContext {
             };
 
             let signatures = match map.remove("signatures") {
+                Some(Value::Array(_)) if self.acme_profile => {
+                    bail!("The ACME JWS must be in the flattened JSON serialization.")
+                }
                 Some(Value::Array(vals)) => {
                     let mut vec = Vec::with_capacity(vals.len());
                     for val in vals {
@@ -572,8 +1021,8 @@ impl JwsContext {
                     None => bail!("The JWS alg header claim must be in protected."),
                 };
 
-                let protected_vec = util::decode_base64_urlsafe_no_pad(&protected_b64)?;
-                let protected_map: Map<String, Value> = serde_json::from_slice(&protected_vec)?;
+                let protected_vec = self.decode_base64(protected_b64)?;
+                let protected_map = self.parse_json_object(&protected_vec)?;
 
                 let mut b64 = true;
                 if let Some(Value::Array(vals)) = protected_map.get("critical") {
@@ -599,32 +1048,31 @@ impl JwsContext {
                     }
                 }
 
-                let merged_map = match header {
-                    Some(Value::Object(mut val)) => {
-                        for (key, value) in protected_map {
-                            if val.contains_key(&key) {
-                                bail!("A duplicate key exists: {}", key);
-                            } else {
-                                val.insert(key.clone(), value.clone());
-                            }
-                        }
-                        val
+                let protected = JwsHeader::from_map(protected_map)?;
+                if self.acme_profile {
+                    Self::check_acme(&protected, header.as_ref())?;
+                }
+                let merged = match header {
+                    Some(Value::Object(val)) => {
+                        Self::check_unprotected(&val)?;
+                        JwsHeader::from_map(val)?.merged_with(&protected)?
                     }
                     Some(_) => bail!("The protected field must be a object."),
-                    None => protected_map.clone(),
+                    None => protected,
                 };
 
-                if let None = merged_map.get("alg") {
+                if merged.claim("alg").is_none() {
                     bail!("The JWS alg header claim must be in protected.");
                 }
 
                 let signature = match sig.get("signature") {
-                    Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
+                    Some(Value::String(val)) => self.decode_base64(val)?,
                     Some(_) => bail!("The signature field must be string."),
                     None => bail!("The signature field is required."),
                 };
 
-                let merged = JwsHeader::from_map(merged_map)?;
+                self.check_type(&merged)?;
+
                 let verifier = match selector(&merged)? {
                     Some(val) => val,
                     None => continue,
@@ -654,7 +1102,7 @@ impl JwsContext {
                 verifier.verify(message.as_bytes(), &signature)?;
 
                 let payload = if b64 {
-                    util::decode_base64_urlsafe_no_pad(&payload_b64)?
+                    self.decode_base64(&payload_b64)?
                 } else {
                     payload_b64.into_bytes()
                 };
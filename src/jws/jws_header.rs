@@ -2,7 +2,9 @@ use std::fmt::{Debug, Display};
 use std::ops::Deref;
 
 use anyhow::bail;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
+use crate::jose_header::{merge_claims, sorted_claims};
 use crate::jwk::Jwk;
 use crate::util;
 use crate::{JoseError, JoseHeader, Map, Value};
@@ -99,11 +101,14 @@ impl JwsHeader {
 
     /// Set a value for JWK header claim (jwk).
     ///
+    /// Only the public members are stored even if the JWK has private key parameters.
+    ///
     /// # Arguments
     ///
     /// * `value` - a JWK
-    pub fn set_jwk(&mut self, value: Jwk) {
+    pub fn set_jwk(&mut self, mut value: Jwk) {
         let key = "jwk";
+        value.remove_private_parameters();
         let value: Map<String, Value> = value.into();
         self.claims.insert(key.to_string(), Value::Object(value));
     }
@@ -119,6 +124,18 @@ impl JwsHeader {
         }
     }
 
+    /// Return the value for JWK header claim (jwk) or a error when it is not a valid JWK.
+    pub fn jwk_strict(&self) -> Result<Option<Jwk>, JoseError> {
+        match self.claims.get("jwk") {
+            Some(Value::Object(vals)) => Ok(Some(Jwk::from_map(vals.clone())?)),
+            Some(val) => Err(JoseError::InvalidJwsFormat(anyhow::anyhow!(
+                "The JWS jwk header claim must be a object: {}",
+                val
+            ))),
+            None => Ok(None),
+        }
+    }
+
     /// Set a value for X.509 URL header claim (x5u).
     ///
     /// # Arguments
@@ -233,6 +250,23 @@ impl JwsHeader {
         }
     }
 
+    /// Return a raw value for key ID header claim (kid).
+    pub fn key_id_value(&self) -> Option<&Value> {
+        self.claims.get("kid")
+    }
+
+    /// Return the value for key ID header claim (kid) or a error when it is not a string.
+    pub fn key_id_strict(&self) -> Result<Option<&str>, JoseError> {
+        match self.claims.get("kid") {
+            Some(Value::String(val)) => Ok(Some(val)),
+            Some(val) => Err(JoseError::InvalidJwsFormat(anyhow::anyhow!(
+                "The JWS kid header claim must be string: {}",
+                val
+            ))),
+            None => Ok(None),
+        }
+    }
+
     /// Set a value for token type header claim (typ).
     ///
     /// # Arguments
@@ -251,6 +285,13 @@ impl JwsHeader {
         }
     }
 
+    /// Remove the token type header claim (typ).
+    ///
+    /// Some profiles forbid the typ header claim, so it can be omitted explicitly.
+    pub fn remove_token_type(&mut self) {
+        self.claims.remove("typ");
+    }
+
     /// Set a value for content type header claim (cty).
     ///
     /// # Arguments
@@ -357,6 +398,55 @@ impl JwsHeader {
         }
     }
 
+    /// Set a value for a nonce header claim (nonce) as is.
+    ///
+    /// This is for an opaque nonce that is already encoded by the server
+    /// (e.g. a Replay-Nonce of ACME or a DPoP-Nonce).
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - A base64url encoded nonce
+    pub fn set_nonce_str(&mut self, value: impl Into<String>) {
+        let value: String = value.into();
+        self.claims
+            .insert("nonce".to_string(), Value::String(value));
+    }
+
+    /// Return the value for nonce header claim (nonce) as is.
+    pub fn nonce_str(&self) -> Option<&str> {
+        match self.claims.get("nonce") {
+            Some(Value::String(val)) => Some(val),
+            _ => None,
+        }
+    }
+
+    /// Check the nonce header claim with a predicate.
+    ///
+    /// This can be called in a verifier selector to reject a replayed nonce
+    /// before the signature is verified.
+    ///
+    /// The selector runs before the signature is verified, so the predicate must
+    /// only look the nonce up. Record the nonce as used only after the
+    /// verification succeeds, otherwise a forged token can burn a nonce that is
+    /// still outstanding.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` - a function that returns true when the nonce is acceptable.
+    pub fn verify_nonce<F>(&self, predicate: F) -> Result<(), JoseError>
+    where
+        F: Fn(&str) -> bool,
+    {
+        (|| -> anyhow::Result<()> {
+            match self.nonce_str() {
+                Some(val) if predicate(val) => Ok(()),
+                Some(val) => bail!("The JWS nonce header claim is not acceptable: {}", val),
+                None => bail!("The JWS nonce header claim is required."),
+            }
+        })()
+        .map_err(JoseError::InvalidJwsFormat)
+    }
+
     /// Set a value for header claim of a specified key.
     ///
     /// # Arguments
@@ -377,6 +467,32 @@ impl JwsHeader {
         Ok(())
     }
 
+    /// Remove a header claim of a specified key and return the removed value.
+    ///
+    /// The required header claims and the header claims that are listed in
+    /// the crit header claim cannot be removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - a key name of header claim
+    pub fn remove_claim(&mut self, key: &str) -> Result<Option<Value>, JoseError> {
+        (|| -> anyhow::Result<Option<Value>> {
+            if matches!(key, "alg") {
+                bail!("The JWS {} header claim is required.", key);
+            }
+            if let Some(vals) = self.critical() {
+                if vals.contains(&key) {
+                    bail!(
+                        "The JWS {} header claim is listed in the crit header claim.",
+                        key
+                    );
+                }
+            }
+            Ok(self.claims.remove(key))
+        })()
+        .map_err(JoseError::InvalidJwsFormat)
+    }
+
     /// Return values for header claims set
     pub fn claims_set(&self) -> &Map<String, Value> {
         &self.claims
@@ -387,10 +503,22 @@ impl JwsHeader {
         self.claims
     }
 
+    /// Return the union of the header claims and the other header claims.
+    ///
+    /// It is an error that a header claim name exists in both headers.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other header claims.
+    pub fn merged_with(&self, other: &dyn JoseHeader) -> Result<Self, JoseError> {
+        let claims = merge_claims(&self.claims, other).map_err(JoseError::InvalidJwsFormat)?;
+        Self::from_map(claims)
+    }
+
     pub(crate) fn check_claim(key: &str, value: &Value) -> Result<(), JoseError> {
         (|| -> anyhow::Result<()> {
             match key {
-                "alg" | "jku" | "x5u" | "kid" | "typ" | "cty" | "url" => match &value {
+                "alg" | "jku" | "x5u" | "typ" | "cty" | "url" => match &value {
                     Value::String(_) => {}
                     _ => bail!("The JWS {} header claim must be string.", key),
                 },
@@ -459,6 +587,10 @@ impl JoseHeader for JwsHeader {
         self.claims.get(key)
     }
 
+    fn claims_iter<'a>(&'a self) -> Box<dyn Iterator<Item = (&'a str, &'a Value)> + 'a> {
+        sorted_claims(self.claims.iter())
+    }
+
     fn box_clone(&self) -> Box<dyn JoseHeader> {
         Box::new(self.clone())
     }
@@ -476,6 +608,19 @@ impl Into<Map<String, Value>> for JwsHeader {
     }
 }
 
+impl Serialize for JwsHeader {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.claims.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for JwsHeader {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let map = Map::<String, Value>::deserialize(deserializer)?;
+        Self::from_map(map).map_err(de::Error::custom)
+    }
+}
+
 impl Display for JwsHeader {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         let val = serde_json::to_string(&self.claims).map_err(|_e| std::fmt::Error {})?;
@@ -493,12 +638,171 @@ impl Deref for JwsHeader {
 
 #[cfg(test)]
 mod tests {
+    use std::cell::RefCell;
+
     use anyhow::Result;
     use serde_json::json;
 
     use crate::jwk::Jwk;
-    use crate::jws::JwsHeader;
-    use crate::{Map, Value};
+    use crate::jws::{self, JwsHeader, HS256};
+    use crate::{JoseError, JoseHeader, Map, Value};
+
+    #[test]
+    fn test_claim_str() -> Result<()> {
+        let mut header = JwsHeader::new();
+        header.set_claim("str", Some(json!("value")))?;
+        header.set_claim("num", Some(json!(1)))?;
+
+        assert_eq!(header.claim_str("str"), Some("value"));
+        assert_eq!(header.claim_str("num"), None);
+        assert_eq!(header.claim_str("none"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_claim() -> Result<()> {
+        let mut header = JwsHeader::new();
+        header.set_algorithm("ES256");
+        header.set_claim("custom", Some(json!("value")))?;
+        header.set_claim("exp", Some(json!(1)))?;
+        header.set_critical(&vec!["exp"]);
+
+        assert!(header.contains_claim("custom"));
+        assert_eq!(header.remove_claim("custom")?, Some(json!("value")));
+        assert!(!header.contains_claim("custom"));
+        assert_eq!(header.remove_claim("custom")?, None);
+
+        assert!(matches!(
+            header.remove_claim("alg"),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+        assert!(matches!(
+            header.remove_claim("exp"),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+        assert_eq!(header.algorithm(), Some("ES256"));
+        assert!(header.contains_claim("exp"));
+
+        header.remove_claim("crit")?;
+        assert_eq!(header.remove_claim("exp")?, Some(json!(1)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_token_type() -> Result<()> {
+        let mut header = JwsHeader::new();
+        header.set_token_type("JWT");
+        assert_eq!(header.token_type(), Some("JWT"));
+
+        header.remove_token_type();
+        assert_eq!(header.token_type(), None);
+        assert_eq!(header.claim("typ"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nonce_str() -> Result<()> {
+        let mut header = JwsHeader::new();
+        assert_eq!(header.nonce_str(), None);
+        assert!(header.verify_nonce(|_| true).is_err());
+
+        header.set_nonce_str("oFvnlFP1wIhRlYS2jTaXbA");
+        assert_eq!(header.nonce_str(), Some("oFvnlFP1wIhRlYS2jTaXbA"));
+        assert_eq!(
+            header.claim("nonce"),
+            Some(&json!("oFvnlFP1wIhRlYS2jTaXbA"))
+        );
+
+        let map: Map<String, Value> = header.clone().into();
+        assert_eq!(JwsHeader::from_map(map)?, header);
+
+        let used = ["bWFyaw"];
+        header.verify_nonce(|nonce| !used.contains(&nonce))?;
+        header.set_nonce_str("bWFyaw");
+        assert!(header.verify_nonce(|nonce| !used.contains(&nonce)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_nonce_during_verification() -> Result<()> {
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let mut header = JwsHeader::new();
+        header.set_nonce_str("bWFyaw");
+        let jws = jws::serialize_compact(b"payload", &header, &HS256.signer_from_bytes(key)?)?;
+        let forged = jws::serialize_compact(
+            b"payload",
+            &header,
+            &HS256.signer_from_bytes(b"FEDCBA9876543210FEDCBA9876543210")?,
+        )?;
+
+        let verifier = HS256.verifier_from_bytes(key)?;
+        let used: RefCell<Vec<String>> = RefCell::new(Vec::new());
+        let verify = |input: &str| -> Result<Vec<u8>, JoseError> {
+            let (payload, header) = jws::deserialize_compact_with_selector(input, |header| {
+                header.verify_nonce(|nonce| !used.borrow().iter().any(|val| val == nonce))?;
+                Ok(Some(&verifier))
+            })?;
+            // The nonce is consumed only after the signature is verified.
+            used.borrow_mut()
+                .push(header.nonce_str().unwrap().to_string());
+            Ok(payload)
+        };
+
+        // A forged token does not consume the nonce.
+        assert!(matches!(
+            verify(&forged),
+            Err(JoseError::InvalidSignature(_))
+        ));
+        assert!(used.borrow().is_empty());
+
+        assert_eq!(verify(&jws)?, b"payload");
+
+        // The replayed nonce is rejected.
+        match verify(&jws) {
+            Err(JoseError::InvalidJwsFormat(err)) => assert_eq!(
+                err.to_string(),
+                "The JWS nonce header claim is not acceptable: bWFyaw"
+            ),
+            res => panic!("{:?}", res),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_string_key_id() -> Result<()> {
+        for kid in [json!(123), json!(["a", "b"])] {
+            let mut map = Map::new();
+            map.insert("alg".to_string(), json!("HS256"));
+            map.insert("kid".to_string(), kid.clone());
+            let header = JwsHeader::from_map(map)?;
+            assert_eq!(header.key_id(), None);
+            assert_eq!(header.key_id_value(), Some(&kid));
+            assert!(header.key_id_strict().is_err());
+        }
+
+        let mut header = JwsHeader::new();
+        assert_eq!(header.key_id_strict()?, None);
+        header.set_key_id("kid");
+        assert_eq!(header.key_id_strict()?, Some("kid"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_serde_jws_header() -> Result<()> {
+        let mut header = JwsHeader::new();
+        header.set_algorithm("alg");
+        header.set_claim("custom", Some(json!("value")))?;
+
+        let value = serde_json::to_value(&header)?;
+        assert_eq!(value, json!({ "alg": "alg", "custom": "value" }));
+        let decoded: JwsHeader = serde_json::from_value(value)?;
+        assert_eq!(decoded, header);
+
+        assert!(serde_json::from_value::<JwsHeader>(json!("alg")).is_err());
+        assert!(serde_json::from_value::<JwsHeader>(json!({ "alg": 1 })).is_err());
+        Ok(())
+    }
 
     #[test]
     fn test_new_jws_header() -> Result<()> {
@@ -570,4 +874,44 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_jws_header_claims_iter() -> Result<()> {
+        let mut header = JwsHeader::new();
+        header.set_token_type("JWT");
+        header.set_claim("zzz", Some(json!(1)))?;
+        header.set_algorithm("HS256");
+        header.set_key_id("kid");
+        header.set_claim("aaa", Some(json!("private")))?;
+
+        assert_eq!(
+            header.claim_names(),
+            vec!["aaa", "alg", "kid", "typ", "zzz"]
+        );
+
+        let claims: Vec<(&str, &Value)> = header.claims_iter().collect();
+        assert_eq!(claims.len(), header.len());
+        assert_eq!(claims[0], ("aaa", &json!("private")));
+        assert_eq!(claims[4], ("zzz", &json!(1)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merged_with() -> Result<()> {
+        let mut protected = JwsHeader::new();
+        protected.set_algorithm("HS256");
+        let mut unprotected = JwsHeader::new();
+        unprotected.set_key_id("key-1");
+
+        let merged = unprotected.merged_with(&protected)?;
+        assert_eq!(merged.algorithm(), Some("HS256"));
+        assert_eq!(merged.key_id(), Some("key-1"));
+        assert_eq!(merged.len(), 2);
+
+        unprotected.set_algorithm("none");
+        assert!(unprotected.merged_with(&protected).is_err());
+
+        Ok(())
+    }
 }
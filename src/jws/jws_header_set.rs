@@ -1,6 +1,7 @@
 use std::fmt::{Debug, Display};
 use std::ops::Deref;
 
+use crate::jose_header::sorted_claims;
 use crate::jwk::Jwk;
 use crate::jws::JwsHeader;
 use crate::util::{self, decode_base64_urlsafe_no_pad};
@@ -77,11 +78,14 @@ impl JwsHeaderSet {
 
     /// Set a value for JWK header claim (jwk).
     ///
+    /// Only the public members are stored even if the JWK has private key parameters.
+    ///
     /// # Arguments
     ///
     /// * `value` - a JWK
-    pub fn set_jwk(&mut self, value: Jwk, protection: bool) {
+    pub fn set_jwk(&mut self, mut value: Jwk, protection: bool) {
         let key = "jwk";
+        value.remove_private_parameters();
         let value: Map<String, Value> = value.into();
         if protection {
             self.unprotected.remove(key);
@@ -477,6 +481,16 @@ impl JoseHeader for JwsHeaderSet {
         }
     }
 
+    fn claims_iter<'a>(&'a self) -> Box<dyn Iterator<Item = (&'a str, &'a Value)> + 'a> {
+        sorted_claims(
+            self.protected.iter().chain(
+                self.unprotected
+                    .iter()
+                    .filter(|(key, _)| !self.protected.contains_key(key.as_str())),
+            ),
+        )
+    }
+
     fn box_clone(&self) -> Box<dyn JoseHeader> {
         Box::new(self.clone())
     }
@@ -582,4 +596,18 @@ mod tests {
         assert_eq!(header.claim("header_claim"), Some(&json!("header_claim")));
         Ok(())
     }
+
+    #[test]
+    fn test_jws_header_set_claims_iter() -> Result<()> {
+        let mut header = JwsHeaderSet::new();
+        header.set_algorithm("HS256", true);
+        header.set_key_id("kid", false);
+        header.set_claim("b2b", Some(json!("protected")), true)?;
+        header.set_claim("a1a", Some(json!("unprotected")), false)?;
+
+        assert_eq!(header.claim_names(), vec!["a1a", "alg", "b2b", "kid"]);
+        assert_eq!(header.claims_iter().count(), header.len());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,123 @@
+use crate::jwk::Jwk;
+use crate::jws::alg::ecdsa::EcdsaJwsAlgorithm;
+use crate::jws::alg::eddsa::EddsaJwsAlgorithm;
+use crate::jws::alg::hmac::HmacJwsAlgorithm;
+use crate::jws::alg::rsassa::RsassaJwsAlgorithm;
+use crate::jws::alg::rsassa_pss::RsassaPssJwsAlgorithm;
+use crate::jws::{JwsAlgorithm, JwsSigner, JwsVerifier};
+use crate::JoseError;
+
+/// Represent a signing algorithm that is identified by a JWS alg header claim value.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum SigningAlgorithm {
+    Hmac(HmacJwsAlgorithm),
+    Rsassa(RsassaJwsAlgorithm),
+    RsassaPss(RsassaPssJwsAlgorithm),
+    Ecdsa(EcdsaJwsAlgorithm),
+    Eddsa(EddsaJwsAlgorithm),
+}
+
+impl SigningAlgorithm {
+    /// Return the signing algorithm for a alg header claim value.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a alg header claim value
+    pub fn from_name(name: &str) -> Option<Self> {
+        let alg = match name {
+            "HS256" => Self::Hmac(HmacJwsAlgorithm::Hs256),
+            "HS384" => Self::Hmac(HmacJwsAlgorithm::Hs384),
+            "HS512" => Self::Hmac(HmacJwsAlgorithm::Hs512),
+            "RS256" => Self::Rsassa(RsassaJwsAlgorithm::Rs256),
+            "RS384" => Self::Rsassa(RsassaJwsAlgorithm::Rs384),
+            "RS512" => Self::Rsassa(RsassaJwsAlgorithm::Rs512),
+            "PS256" => Self::RsassaPss(RsassaPssJwsAlgorithm::Ps256),
+            "PS384" => Self::RsassaPss(RsassaPssJwsAlgorithm::Ps384),
+            "PS512" => Self::RsassaPss(RsassaPssJwsAlgorithm::Ps512),
+            "ES256" => Self::Ecdsa(EcdsaJwsAlgorithm::Es256),
+            "ES384" => Self::Ecdsa(EcdsaJwsAlgorithm::Es384),
+            "ES512" => Self::Ecdsa(EcdsaJwsAlgorithm::Es512),
+            "ES256K" => Self::Ecdsa(EcdsaJwsAlgorithm::Es256k),
+            "EdDSA" => Self::Eddsa(EddsaJwsAlgorithm::Eddsa),
+            _ => return None,
+        };
+        Some(alg)
+    }
+
+    /// Return the alg header claim value.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Hmac(val) => val.name(),
+            Self::Rsassa(val) => val.name(),
+            Self::RsassaPss(val) => val.name(),
+            Self::Ecdsa(val) => val.name(),
+            Self::Eddsa(val) => val.name(),
+        }
+    }
+
+    /// Return a signer from a private key that is formatted by a JWK.
+    ///
+    /// # Arguments
+    ///
+    /// * `jwk` - A private key that is formatted by a JWK.
+    pub fn signer_from_jwk(&self, jwk: &Jwk) -> Result<Box<dyn JwsSigner>, JoseError> {
+        let signer: Box<dyn JwsSigner> = match self {
+            Self::Hmac(val) => Box::new(val.signer_from_jwk(jwk)?),
+            Self::Rsassa(val) => Box::new(val.signer_from_jwk(jwk)?),
+            Self::RsassaPss(val) => Box::new(val.signer_from_jwk(jwk)?),
+            Self::Ecdsa(val) => Box::new(val.signer_from_jwk(jwk)?),
+            Self::Eddsa(val) => Box::new(val.signer_from_jwk(jwk)?),
+        };
+        Ok(signer)
+    }
+
+    /// Return a verifier from a key that is formatted by a JWK.
+    ///
+    /// # Arguments
+    ///
+    /// * `jwk` - A key that is formatted by a JWK.
+    pub fn verifier_from_jwk(&self, jwk: &Jwk) -> Result<Box<dyn JwsVerifier>, JoseError> {
+        let verifier: Box<dyn JwsVerifier> = match self {
+            Self::Hmac(val) => Box::new(val.verifier_from_jwk(jwk)?),
+            Self::Rsassa(val) => Box::new(val.verifier_from_jwk(jwk)?),
+            Self::RsassaPss(val) => Box::new(val.verifier_from_jwk(jwk)?),
+            Self::Ecdsa(val) => Box::new(val.verifier_from_jwk(jwk)?),
+            Self::Eddsa(val) => Box::new(val.verifier_from_jwk(jwk)?),
+        };
+        Ok(verifier)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::SigningAlgorithm;
+    use crate::jws::{self, JwsHeader, HS256};
+
+    #[test]
+    fn test_signing_algorithm_from_name() -> Result<()> {
+        for name in [
+            "HS256", "HS384", "HS512", "RS256", "RS384", "RS512", "PS256", "PS384", "PS512",
+            "ES256", "ES384", "ES512", "ES256K", "EdDSA",
+        ] {
+            let alg = jws::signing_algorithm_from_name(name).unwrap();
+            assert_eq!(alg.name(), name);
+        }
+        assert_eq!(jws::signing_algorithm_from_name("none"), None);
+        assert_eq!(jws::signing_algorithm_from_name("hs256"), None);
+
+        let jwk = HS256.to_jwk(b"0123456789ABCDEF0123456789ABCDEF");
+        let alg = SigningAlgorithm::from_name("HS256").unwrap();
+        let signer = alg.signer_from_jwk(&jwk)?;
+        let verifier = alg.verifier_from_jwk(&jwk)?;
+        let jws = jws::serialize_compact(b"payload", &JwsHeader::new(), &*signer)?;
+        let (payload, _) = jws::deserialize_compact(&jws, &*verifier)?;
+        assert_eq!(payload, b"payload");
+
+        let alg = SigningAlgorithm::from_name("RS256").unwrap();
+        assert!(alg.verifier_from_jwk(&jwk).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+use anyhow::bail;
+
+use crate::jws::{self, JwsHeader, JwsVerifier};
+use crate::JoseError;
+
+/// Represents verifiers that are selected by the alg and kid header claims.
+#[derive(Debug, Clone)]
+pub struct VerifierRegistry {
+    verifiers: Vec<(String, Box<dyn JwsVerifier>)>,
+}
+
+impl VerifierRegistry {
+    pub fn new() -> Self {
+        Self {
+            verifiers: Vec::new(),
+        }
+    }
+
+    /// Register a verifier for the algorithm of the verifier.
+    ///
+    /// When the verifier has a key ID, it is used only for tokens that have the same kid.
+    ///
+    /// # Arguments
+    ///
+    /// * `verifier` - a JWS verifier
+    pub fn register(&mut self, verifier: Box<dyn JwsVerifier>) {
+        let alg = verifier.algorithm().name().to_string();
+        self.verifiers.push((alg, verifier));
+    }
+
+    /// Return the registered algorithms.
+    pub fn algorithms(&self) -> Vec<&str> {
+        let mut vec: Vec<&str> = self.verifiers.iter().map(|(alg, _)| alg.as_str()).collect();
+        vec.sort_unstable();
+        vec.dedup();
+        vec
+    }
+
+    /// Return a verifier that is selected by the alg and kid header claims.
+    ///
+    /// A verifier that has the same key ID has priority over a verifier without key ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - the JWS header.
+    pub fn verifier_for(&self, header: &JwsHeader) -> Result<&dyn JwsVerifier, JoseError> {
+        (|| -> anyhow::Result<&dyn JwsVerifier> {
+            let alg = match header.algorithm() {
+                Some(val) => val,
+                None => bail!("The JWS alg header claim is required."),
+            };
+
+            let mut candidates = self
+                .verifiers
+                .iter()
+                .filter(|(name, _)| name == alg)
+                .map(|(_, verifier)| verifier.as_ref())
+                .peekable();
+            if candidates.peek().is_none() {
+                bail!("A verifier is not registered: alg = {}", alg);
+            }
+
+            let mut fallback = None;
+            for verifier in candidates {
+                match verifier.key_id() {
+                    Some(val) if Some(val) == header.key_id() => return Ok(verifier),
+                    Some(_) => {}
+                    None => {
+                        if fallback.is_none() {
+                            fallback = Some(verifier);
+                        }
+                    }
+                }
+            }
+
+            match (fallback, header.key_id()) {
+                (Some(val), _) => Ok(val),
+                (None, Some(kid)) => {
+                    bail!("A verifier is not registered: alg = {}, kid = {}", alg, kid)
+                }
+                (None, None) => bail!(
+                    "The JWS kid header claim is required to select a verifier: alg = {}",
+                    alg
+                ),
+            }
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwsFormat(err),
+        })
+    }
+
+    /// Verify the input that is formatted by compact serialization
+    /// with a verifier that is selected by the header.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    pub fn verify_auto(&self, input: impl AsRef<[u8]>) -> Result<(Vec<u8>, JwsHeader), JoseError> {
+        jws::deserialize_compact_with_selector(input, |header| self.verifier_for(header).map(Some))
+    }
+}
+
+impl Default for VerifierRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::VerifierRegistry;
+    use crate::jwk::KeyPair;
+    use crate::jws::{self, JwsHeader, ES256, HS256, RS256};
+    use crate::JoseError;
+
+    #[test]
+    fn test_verify_auto() -> Result<()> {
+        let secret = b"0123456789ABCDEF0123456789ABCDEF";
+        let ec_key_pair = ES256.generate_key_pair()?;
+
+        let mut registry = VerifierRegistry::new();
+        registry.register(Box::new(HS256.verifier_from_bytes(secret)?));
+        registry.register(Box::new(
+            ES256.verifier_from_der(ec_key_pair.to_der_public_key())?,
+        ));
+        assert_eq!(registry.algorithms(), vec!["ES256", "HS256"]);
+
+        let hs256_jws = jws::serialize_compact(
+            b"hs256",
+            &JwsHeader::new(),
+            &HS256.signer_from_bytes(secret)?,
+        )?;
+        let es256_jws = jws::serialize_compact(
+            b"es256",
+            &JwsHeader::new(),
+            &ES256.signer_from_der(ec_key_pair.to_der_private_key())?,
+        )?;
+
+        let (payload, header) = registry.verify_auto(&hs256_jws)?;
+        assert_eq!(payload, b"hs256");
+        assert_eq!(header.algorithm(), Some("HS256"));
+
+        let (payload, header) = registry.verify_auto(&es256_jws)?;
+        assert_eq!(payload, b"es256");
+        assert_eq!(header.algorithm(), Some("ES256"));
+
+        // A unregistered algorithm is rejected.
+        let rs256_key_pair = RS256.generate_key_pair(2048)?;
+        let rs256_jws = jws::serialize_compact(
+            b"rs256",
+            &JwsHeader::new(),
+            &RS256.signer_from_der(rs256_key_pair.to_der_private_key())?,
+        )?;
+        match registry.verify_auto(&rs256_jws) {
+            Err(JoseError::InvalidJwsFormat(err)) => {
+                assert_eq!(err.to_string(), "A verifier is not registered: alg = RS256")
+            }
+            res => panic!("{:?}", res),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_auto_with_key_id() -> Result<()> {
+        let mut registry = VerifierRegistry::new();
+        for kid in ["key-1", "key-2"] {
+            let mut verifier = HS256.verifier_from_bytes(kid.repeat(8))?;
+            verifier.set_key_id(kid);
+            registry.register(Box::new(verifier));
+        }
+
+        for kid in ["key-1", "key-2"] {
+            let mut signer = HS256.signer_from_bytes(kid.repeat(8))?;
+            signer.set_key_id(kid);
+            let jws = jws::serialize_compact(b"payload", &JwsHeader::new(), &signer)?;
+            let (_, header) = registry.verify_auto(&jws)?;
+            assert_eq!(header.key_id(), Some(kid));
+        }
+
+        let mut header = JwsHeader::new();
+        header.set_algorithm("HS256");
+        assert!(registry.verifier_for(&header).is_err());
+        header.set_key_id("key-3");
+        assert!(registry.verifier_for(&header).is_err());
+
+        Ok(())
+    }
+}
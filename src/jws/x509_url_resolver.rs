@@ -0,0 +1,505 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use openssl::hash::{hash, MessageDigest};
+use openssl::x509::X509;
+
+use crate::jwk::Jwk;
+use crate::jws::{JwsHeader, JwsVerifier, SigningAlgorithm};
+use crate::util;
+use crate::{JoseError, Value};
+
+/// Represent a transport that fetches a PEM encoded certificate chain from a x5u URL.
+///
+/// This crate has no HTTP client, so the application provides one.
+pub trait X509UrlFetcher: Debug + Send + Sync {
+    /// Return the PEM encoded certificate chain that is served at the URL.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - an allowed https URL
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, JoseError>;
+}
+
+/// Represents a resolver that builds verifiers from the x5u header claim.
+///
+/// Only https URLs whose host is allowed are fetched, so that a token cannot make
+/// a verifier send requests to arbitrary hosts. A URL with a query or a fragment is
+/// rejected, so that a token cannot bypass the cache by varying them. Fetched certificate
+/// chains are cached by the normalized URL for a limited time, and the number of cached
+/// chains is bounded because the URL comes from the token. The chain is not validated against trust anchors: the leaf
+/// certificate is trusted because it is served by an allowed host.
+#[derive(Debug)]
+pub struct X509UrlResolver {
+    fetcher: Box<dyn X509UrlFetcher>,
+    allowed_hosts: BTreeSet<String>,
+    cache_ttl: Duration,
+    max_cache_entries: usize,
+    cache: Mutex<BTreeMap<String, CachedChain>>,
+}
+
+#[derive(Debug)]
+struct CachedChain {
+    chain: Vec<Vec<u8>>,
+    fetched_at: Instant,
+}
+
+impl X509UrlResolver {
+    /// Return a resolver that fetches certificate chains with the fetcher.
+    ///
+    /// No host is allowed at first. Fetched chains are cached for an hour, and up to
+    /// 64 chains are cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `fetcher` - a transport of x5u URLs
+    pub fn new(fetcher: Box<dyn X509UrlFetcher>) -> Self {
+        Self {
+            fetcher,
+            allowed_hosts: BTreeSet::new(),
+            cache_ttl: Duration::from_secs(60 * 60),
+            max_cache_entries: 64,
+            cache: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Set the time to keep a fetched certificate chain. A rotated certificate is
+    /// fetched again after this time.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a time to live of cache entries
+    pub fn set_cache_ttl(&mut self, value: Duration) {
+        self.cache_ttl = value;
+    }
+
+    /// Return the time to keep a fetched certificate chain.
+    pub fn cache_ttl(&self) -> Duration {
+        self.cache_ttl
+    }
+
+    /// Set the maximum number of cached certificate chains. When the cache is full,
+    /// the oldest chain is removed. Zero disables the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a maximum number of cache entries
+    pub fn set_max_cache_entries(&mut self, value: usize) {
+        self.max_cache_entries = value;
+    }
+
+    /// Return the maximum number of cached certificate chains.
+    pub fn max_cache_entries(&self) -> usize {
+        self.max_cache_entries
+    }
+
+    /// Allow a host of x5u URLs.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - a host name (e.g. "certs.example.com")
+    pub fn add_allowed_host(&mut self, host: &str) {
+        self.allowed_hosts.insert(host.to_ascii_lowercase());
+    }
+
+    /// Disallow a host of x5u URLs.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - a host name
+    pub fn remove_allowed_host(&mut self, host: &str) {
+        self.allowed_hosts.remove(&host.to_ascii_lowercase());
+    }
+
+    /// Test a x5u URL is a https URL of an allowed host without a query or a fragment.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - a x5u URL
+    pub fn is_allowed_url(&self, url: &str) -> bool {
+        if url.contains(['?', '#']) {
+            return false;
+        }
+        match Self::host_of(url) {
+            Some(host) => self.allowed_hosts.contains(&host),
+            None => false,
+        }
+    }
+
+    /// Remove all cached certificate chains.
+    pub fn clear_cache(&self) {
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Return the DER encoded certificate chain at the URL. The leaf certificate is first.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - a x5u URL
+    pub fn certificate_chain(&self, url: &str) -> Result<Vec<Vec<u8>>, JoseError> {
+        (|| -> anyhow::Result<Vec<Vec<u8>>> {
+            if !self.is_allowed_url(url) {
+                bail!("The x5u URL is not allowed: {}", url);
+            }
+
+            let key = Self::cache_key(url);
+            if let Some(val) = self
+                .cache
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get(&key)
+            {
+                if val.fetched_at.elapsed() < self.cache_ttl {
+                    return Ok(val.chain.clone());
+                }
+            }
+
+            let pem = self.fetcher.fetch(url)?;
+            let mut chain = Vec::new();
+            for cert in X509::stack_from_pem(&pem)? {
+                chain.push(cert.to_der()?);
+            }
+            if chain.is_empty() {
+                bail!("No certificate is found at the x5u URL: {}", url);
+            }
+
+            self.insert_cache(key, chain.clone());
+            Ok(chain)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    /// Return a verifier that is built from the leaf certificate at the x5u URL.
+    ///
+    /// When the x5t#S256 or x5t header claim is present, it must match the leaf certificate.
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - the JWS header.
+    pub fn verifier_for(&self, header: &JwsHeader) -> Result<Box<dyn JwsVerifier>, JoseError> {
+        (|| -> anyhow::Result<Box<dyn JwsVerifier>> {
+            let alg = match header.algorithm() {
+                Some(val) => val,
+                None => bail!("The JWS alg header claim is required."),
+            };
+            let signing_algorithm = match SigningAlgorithm::from_name(alg) {
+                Some(val) => val,
+                None => {
+                    return Err(anyhow::Error::new(
+                        JoseError::UnsupportedSignatureAlgorithm(anyhow::anyhow!(
+                            "Unsupported algorithm: {}",
+                            alg
+                        )),
+                    ))
+                }
+            };
+
+            let url = match header.x509_url() {
+                Some(val) => val,
+                None => bail!("The JWS x5u header claim is required."),
+            };
+            let chain = self.certificate_chain(url)?;
+            let leaf = &chain[0];
+
+            for (key, digest) in [
+                ("x5t#S256", MessageDigest::sha256()),
+                ("x5t", MessageDigest::sha1()),
+            ] {
+                let expected = match header.claim(key) {
+                    Some(Value::String(val)) => util::decode_base64_urlsafe_no_pad(val)?,
+                    Some(_) => bail!("The JWS {} header claim must be a string.", key),
+                    None => continue,
+                };
+                if hash(digest, leaf)?.as_ref() != expected.as_slice() {
+                    bail!("The JWS {} header claim is mismatched.", key);
+                }
+            }
+
+            let public_key = X509::from_der(leaf)?.public_key()?;
+            let jwk = Jwk::from_public_key(&public_key)?;
+            let verifier = signing_algorithm.verifier_from_jwk(&jwk)?;
+            Ok(verifier)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    fn insert_cache(&self, key: String, chain: Vec<Vec<u8>>) {
+        if self.max_cache_entries == 0 {
+            return;
+        }
+
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        cache.remove(&key);
+        cache.retain(|_, val| val.fetched_at.elapsed() < self.cache_ttl);
+        while cache.len() >= self.max_cache_entries {
+            let oldest = match cache.iter().min_by_key(|(_, val)| val.fetched_at) {
+                Some((key, _)) => key.clone(),
+                None => break,
+            };
+            cache.remove(&oldest);
+        }
+        cache.insert(
+            key,
+            CachedChain {
+                chain,
+                fetched_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Return the URL with the lowercase scheme and authority, without the default port.
+    /// The URL must be allowed.
+    fn cache_key(url: &str) -> String {
+        let rest = &url[8..];
+        let (authority, path) = match rest.find('/') {
+            Some(pos) => rest.split_at(pos),
+            None => (rest, "/"),
+        };
+        let authority = authority.to_ascii_lowercase();
+        let authority = authority.strip_suffix(":443").unwrap_or(&authority);
+        format!("https://{}{}", authority, path)
+    }
+
+    fn host_of(url: &str) -> Option<String> {
+        let scheme = url.get(..8)?;
+        if !scheme.eq_ignore_ascii_case("https://") {
+            return None;
+        }
+
+        let rest = &url[8..];
+        let authority = match rest.find(['/', '?', '#']) {
+            Some(pos) => &rest[..pos],
+            None => rest,
+        };
+        // A userinfo part can disguise the real host.
+        if authority.contains(['@', '\\']) {
+            return None;
+        }
+
+        let (host, port) = if authority.starts_with('[') {
+            let end = authority.find(']')?;
+            (&authority[..=end], &authority[(end + 1)..])
+        } else {
+            match authority.find(':') {
+                Some(pos) => (&authority[..pos], &authority[pos..]),
+                None => (authority, ""),
+            }
+        };
+        if host.is_empty() {
+            return None;
+        }
+        if !port.is_empty() {
+            let digits = port.strip_prefix(':')?;
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+        }
+
+        Some(host.to_ascii_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::BigNum;
+    use openssl::hash::{hash, MessageDigest};
+    use openssl::pkey::{PKey, Private};
+    use openssl::x509::{X509NameBuilder, X509};
+
+    use super::{X509UrlFetcher, X509UrlResolver};
+    use crate::jws::{self, JwsHeader, ES256, RS256};
+    use crate::JoseError;
+
+    #[derive(Debug)]
+    struct MockServer {
+        pem: Vec<u8>,
+        requests: Arc<AtomicUsize>,
+    }
+
+    impl X509UrlFetcher for MockServer {
+        fn fetch(&self, url: &str) -> Result<Vec<u8>, JoseError> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            match url {
+                url if url.starts_with("https://certs.example.com/chain.pem") => {
+                    Ok(self.pem.clone())
+                }
+                _ => Err(JoseError::InvalidKeyFormat(anyhow::anyhow!(
+                    "Not found: {}",
+                    url
+                ))),
+            }
+        }
+    }
+
+    fn certificate(subject: &str, key: &PKey<Private>, issuer_key: &PKey<Private>) -> Result<X509> {
+        let mut name = X509NameBuilder::new()?;
+        name.append_entry_by_text("CN", subject)?;
+        let name = name.build();
+
+        let mut builder = X509::builder()?;
+        builder.set_version(2)?;
+        let serial_number = BigNum::from_u32(1)?.to_asn1_integer()?;
+        builder.set_serial_number(&serial_number)?;
+        builder.set_subject_name(&name)?;
+        builder.set_issuer_name(&name)?;
+        builder.set_pubkey(key)?;
+        let not_before = Asn1Time::days_from_now(0)?;
+        let not_after = Asn1Time::days_from_now(1)?;
+        builder.set_not_before(&not_before)?;
+        builder.set_not_after(&not_after)?;
+        builder.sign(issuer_key, MessageDigest::sha256())?;
+        Ok(builder.build())
+    }
+
+    #[test]
+    fn verify_with_x5u() -> Result<()> {
+        let key_pair = ES256.generate_key_pair()?;
+        let leaf_key = PKey::private_key_from_der(&key_pair.to_der_private_key())?;
+        let ca_key = PKey::private_key_from_der(&ES256.generate_key_pair()?.to_der_private_key())?;
+        let leaf = certificate("leaf", &leaf_key, &ca_key)?;
+        let ca = certificate("ca", &ca_key, &ca_key)?;
+
+        let mut pem = leaf.to_pem()?;
+        pem.extend_from_slice(&ca.to_pem()?);
+        let requests = Arc::new(AtomicUsize::new(0));
+        let mut resolver = X509UrlResolver::new(Box::new(MockServer {
+            pem,
+            requests: requests.clone(),
+        }));
+        resolver.add_allowed_host("certs.example.com");
+
+        let mut header = JwsHeader::new();
+        header.set_x509_url("https://certs.example.com/chain.pem");
+        header.set_x509_certificate_sha256_thumbprint(hash(
+            MessageDigest::sha256(),
+            &leaf.to_der()?,
+        )?);
+        let signer = ES256.signer_from_der(key_pair.to_der_private_key())?;
+        let jws = jws::serialize_compact(b"payload", &header, &signer)?;
+
+        header.set_algorithm("ES256");
+        for _ in 0..2 {
+            let verifier = resolver.verifier_for(&header)?;
+            let (payload, _) = jws::deserialize_compact(&jws, &*verifier)?;
+            assert_eq!(payload, b"payload");
+        }
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            resolver
+                .certificate_chain("https://certs.example.com/chain.pem")?
+                .len(),
+            2
+        );
+
+        // The same URL in another form shares the cached chain.
+        resolver.certificate_chain("HTTPS://Certs.Example.com:443/chain.pem")?;
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // The thumbprint must match the leaf certificate.
+        header
+            .set_x509_certificate_sha256_thumbprint(hash(MessageDigest::sha256(), &ca.to_der()?)?);
+        assert!(resolver.verifier_for(&header).is_err());
+
+        // The algorithm must match the key of the leaf certificate.
+        let mut header = JwsHeader::new();
+        header.set_algorithm(RS256.name());
+        header.set_x509_url("https://certs.example.com/chain.pem");
+        assert!(resolver.verifier_for(&header).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn expire_and_bound_cached_x5u() -> Result<()> {
+        let key = PKey::private_key_from_der(&ES256.generate_key_pair()?.to_der_private_key())?;
+        let requests = Arc::new(AtomicUsize::new(0));
+        let mut resolver = X509UrlResolver::new(Box::new(MockServer {
+            pem: certificate("leaf", &key, &key)?.to_pem()?,
+            requests: requests.clone(),
+        }));
+        resolver.add_allowed_host("certs.example.com");
+        resolver.set_max_cache_entries(2);
+
+        // The oldest chain is removed when the cache is full.
+        for path in ["1", "2", "3", "3", "2"] {
+            resolver.certificate_chain(&format!("https://certs.example.com/chain.pem/{}", path))?;
+        }
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+        resolver.certificate_chain("https://certs.example.com/chain.pem/1")?;
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+
+        // An expired chain is fetched again.
+        resolver.set_cache_ttl(Duration::ZERO);
+        resolver.certificate_chain("https://certs.example.com/chain.pem/1")?;
+        assert_eq!(requests.load(Ordering::SeqCst), 5);
+
+        // Nothing is cached when the maximum number is zero.
+        resolver.set_cache_ttl(Duration::from_secs(60));
+        resolver.set_max_cache_entries(0);
+        resolver.clear_cache();
+        for _ in 0..2 {
+            resolver.certificate_chain("https://certs.example.com/chain.pem")?;
+        }
+        assert_eq!(requests.load(Ordering::SeqCst), 7);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_disallowed_x5u() -> Result<()> {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let mut resolver = X509UrlResolver::new(Box::new(MockServer {
+            pem: Vec::new(),
+            requests: requests.clone(),
+        }));
+        resolver.add_allowed_host("Certs.Example.com");
+
+        assert!(resolver.is_allowed_url("https://certs.example.com/chain.pem"));
+        assert!(resolver.is_allowed_url("HTTPS://CERTS.EXAMPLE.COM:8443"));
+        for url in [
+            "http://certs.example.com/chain.pem",
+            "https://169.254.169.254/latest/meta-data",
+            "https://certs.example.com@169.254.169.254/chain.pem",
+            "https://certs.example.com\\@localhost/chain.pem",
+            "https://certs.example.com.evil.com/chain.pem",
+            "https://certs.example.com:/chain.pem",
+            "https://certs.example.com:80x/chain.pem",
+            "https:///chain.pem",
+            "https://certs.example.com/chain.pem?x=1",
+            "https://certs.example.com/chain.pem#x",
+            "file:///etc/passwd",
+        ] {
+            assert!(!resolver.is_allowed_url(url), "{}", url);
+        }
+
+        let mut header = JwsHeader::new();
+        header.set_algorithm("ES256");
+        header.set_x509_url("https://169.254.169.254/latest/meta-data");
+        assert!(resolver.verifier_for(&header).is_err());
+
+        resolver.remove_allowed_host("certs.example.com");
+        header.set_x509_url("https://certs.example.com/chain.pem");
+        assert!(resolver.verifier_for(&header).is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 0);
+
+        Ok(())
+    }
+}
@@ -1,22 +1,30 @@
 //! JSON Web Token (JWT) support.
 
 pub mod alg;
+mod decoded_jwt;
 mod jwt_context;
 mod jwt_payload;
 mod jwt_payload_validator;
+mod jwt_verify_options;
 
+pub use crate::jwt::decoded_jwt::DecodedJwt;
 pub use crate::jwt::jwt_context::JwtContext;
 pub use crate::jwt::jwt_payload::JwtPayload;
 pub use crate::jwt::jwt_payload_validator::JwtPayloadValidator;
+pub use crate::jwt::jwt_verify_options::JwtVerifyOptions;
 
 pub use crate::jwt::alg::unsecured::UnsecuredJwsAlgorithm::None;
 
+use anyhow::bail;
 use once_cell::sync::Lazy;
 
 use crate::jwe::{JweDecrypter, JweEncrypter, JweHeader};
-use crate::jwk::{Jwk, JwkSet};
-use crate::jws::{JwsHeader, JwsSigner, JwsVerifier};
-use crate::{JoseError, JoseHeader};
+use crate::jwk::alg::{ec::EcKeyPair, ed::EdKeyPair, rsa::RsaKeyPair};
+use crate::jwk::{Jwk, JwkSet, KeyPair};
+use crate::jws::{
+    self, EdDSA, JwsHeader, JwsSigner, JwsVerifier, SigningAlgorithm, ES256, HS256, RS256,
+};
+use crate::{JoseError, JoseHeader, Value};
 
 static DEFAULT_CONTEXT: Lazy<JwtContext> = Lazy::new(|| JwtContext::new());
 
@@ -32,6 +40,9 @@ pub fn encode_unsecured(payload: &JwtPayload, header: &JwsHeader) -> Result<Stri
 
 /// Return the string repsentation of the JWT with the siginig algorithm.
 ///
+/// The header is used as it is; a typ header claim is added only when it is set
+/// by [`JwsHeader::set_token_type`].
+///
 /// # Arguments
 ///
 /// * `payload` - The payload data.
@@ -45,6 +56,22 @@ pub fn encode_with_signer(
     DEFAULT_CONTEXT.encode_with_signer(payload, header, signer)
 }
 
+/// Return the string repsentation of the JWT with the siginig algorithm,
+/// whose payload is serialized by JSON Canonicalization Scheme (RFC 8785).
+///
+/// # Arguments
+///
+/// * `payload` - The payload data.
+/// * `header` - The JWS heaser claims.
+/// * `signer` - a signer object.
+pub fn encode_with_signer_canonical(
+    payload: &JwtPayload,
+    header: &JwsHeader,
+    signer: &dyn JwsSigner,
+) -> Result<String, JoseError> {
+    DEFAULT_CONTEXT.encode_with_signer_canonical(payload, header, signer)
+}
+
 /// Return the string repsentation of the JWT with the encrypting algorithm.
 ///
 /// # Arguments
@@ -91,6 +118,81 @@ pub fn decode_with_verifier(
     DEFAULT_CONTEXT.decode_with_verifier(input, verifier)
 }
 
+/// Return the JWT object decoded by the selected verifier with its raw segments.
+///
+/// # Arguments
+///
+/// * `input` - a JWT string representation.
+/// * `verifier` - a verifier of the signing algorithm.
+pub fn decode_detailed_with_verifier(
+    input: impl AsRef<[u8]>,
+    verifier: &dyn JwsVerifier,
+) -> Result<DecodedJwt, JoseError> {
+    DEFAULT_CONTEXT.decode_detailed_with_verifier(input, verifier)
+}
+
+/// Return the JWT object decoded by the verifier after checking the header against the options.
+///
+/// A token whose alg header claim is not allowed is rejected before the verifier is used.
+///
+/// # Arguments
+///
+/// * `input` - a JWT string representation.
+/// * `verifier` - a verifier of the signing algorithm.
+/// * `options` - acceptable algorithms and a expected token type.
+pub fn verify_strict(
+    input: impl AsRef<[u8]>,
+    verifier: &dyn JwsVerifier,
+    options: &JwtVerifyOptions,
+) -> Result<(JwtPayload, JwsHeader), JoseError> {
+    DEFAULT_CONTEXT.verify_strict(input, verifier, options)
+}
+
+/// Validate a decoded JWT as a OAuth 2.0 access token (RFC 9068).
+///
+/// The typ header claim must be at+jwt, and the iss, exp, aud, sub, client_id, iat
+/// and jti payload claims must be present. The values (e.g. an expected issuer and
+/// audience) are validated by the validator.
+///
+/// # Arguments
+///
+/// * `payload` - a decoded JWT payload.
+/// * `header` - a decoded JWS header.
+/// * `validator` - a validator of the payload claims.
+pub fn validate_access_token(
+    payload: &JwtPayload,
+    header: &JwsHeader,
+    validator: &JwtPayloadValidator,
+) -> Result<(), JoseError> {
+    (|| -> anyhow::Result<()> {
+        match header.token_type() {
+            Some(val)
+                if val.eq_ignore_ascii_case("at+jwt")
+                    || val.eq_ignore_ascii_case("application/at+jwt") => {}
+            Some(val) => bail!("The JWT typ header claim must be at+jwt: {}", val),
+            Option::None => bail!("The JWT typ header claim is required."),
+        }
+        Ok(())
+    })()
+    .map_err(JoseError::InvalidJwtFormat)?;
+
+    // The types of the registered claims are checked by JwtPayload.
+    (|| -> anyhow::Result<()> {
+        for key in ["iss", "exp", "aud", "sub", "client_id", "iat", "jti"] {
+            match payload.claim(key) {
+                Some(Value::String(_)) => {}
+                Some(val) if key == "client_id" => bail!("Key client_id is invalid: {}", val),
+                Some(_) => {}
+                Option::None => bail!("Key {} is missing.", key),
+            }
+        }
+        Ok(())
+    })()
+    .map_err(JoseError::InvalidClaim)?;
+
+    validator.validate(payload)
+}
+
 /// Return the JWT object decoded with a selected verifying algorithm.
 ///
 /// # Arguments
@@ -107,6 +209,25 @@ where
     DEFAULT_CONTEXT.decode_with_verifier_selector(input, selector)
 }
 
+/// Return the JWT object decoded from a nested JWT and the headers of each layer.
+///
+/// # Arguments
+///
+/// * `input` - a JWT string representation.
+/// * `verifier_selector` - a function for selecting the verifying algorithm of a JWS layer.
+/// * `decrypter_selector` - a function for selecting the decrypting algorithm of a JWE layer.
+pub fn decode_nested<'a, 'b, F, G>(
+    input: impl AsRef<[u8]>,
+    verifier_selector: F,
+    decrypter_selector: G,
+) -> Result<(JwtPayload, Vec<Box<dyn JoseHeader>>), JoseError>
+where
+    F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
+    G: Fn(&JweHeader) -> Result<Option<&'b dyn JweDecrypter>, JoseError>,
+{
+    DEFAULT_CONTEXT.decode_nested(input, verifier_selector, decrypter_selector)
+}
+
 /// Return the JWT object decoded by using a JWK set.
 ///
 /// # Arguments
@@ -172,6 +293,182 @@ where
     DEFAULT_CONTEXT.decode_with_decrypter_in_jwk_set(input, jwk_set, selector)
 }
 
+/// Return the string repsentation of the JWT signed by ES256.
+///
+/// This is a convenience function. The JWS header has a "typ" header claim of "JWT"
+/// and a "kid" header claim when the key has a key ID.
+///
+/// # Arguments
+///
+/// * `payload` - The payload data.
+/// * `key_pair` - an EC key pair.
+pub fn sign_es256(payload: &JwtPayload, key_pair: &EcKeyPair) -> Result<String, JoseError> {
+    let signer = ES256.signer_from_der(key_pair.to_der_private_key())?;
+    encode_with_default_header(payload, &signer, key_pair.key_id())
+}
+
+/// Return the JWT payload verified by ES256.
+///
+/// This is a convenience function. It verifies only the signature,
+/// so claims should be validated by [`JwtPayloadValidator`].
+///
+/// # Arguments
+///
+/// * `input` - a JWT string representation.
+/// * `key_pair` - an EC key pair.
+pub fn verify_es256(
+    input: impl AsRef<[u8]>,
+    key_pair: &EcKeyPair,
+) -> Result<JwtPayload, JoseError> {
+    let verifier = ES256.verifier_from_der(key_pair.to_der_public_key())?;
+    let (payload, _) = decode_with_verifier(input, &verifier)?;
+    Ok(payload)
+}
+
+/// Return the string repsentation of the JWT signed by HS256.
+///
+/// This is a convenience function. The JWS header has a "typ" header claim of "JWT"
+/// and a "kid" header claim when the key has a key ID.
+///
+/// # Arguments
+///
+/// * `payload` - The payload data.
+/// * `key` - a shared secret key.
+pub fn sign_hs256(payload: &JwtPayload, key: impl AsRef<[u8]>) -> Result<String, JoseError> {
+    let signer = HS256.signer_from_bytes(key)?;
+    encode_with_default_header(payload, &signer, Option::None)
+}
+
+/// Return the JWT payload verified by HS256.
+///
+/// This is a convenience function. It verifies only the signature,
+/// so claims should be validated by [`JwtPayloadValidator`].
+///
+/// # Arguments
+///
+/// * `input` - a JWT string representation.
+/// * `key` - a shared secret key.
+pub fn verify_hs256(
+    input: impl AsRef<[u8]>,
+    key: impl AsRef<[u8]>,
+) -> Result<JwtPayload, JoseError> {
+    let verifier = HS256.verifier_from_bytes(key)?;
+    let (payload, _) = decode_with_verifier(input, &verifier)?;
+    Ok(payload)
+}
+
+/// Return the string repsentation of the JWT signed by RS256.
+///
+/// This is a convenience function. The JWS header has a "typ" header claim of "JWT"
+/// and a "kid" header claim when the key has a key ID.
+///
+/// # Arguments
+///
+/// * `payload` - The payload data.
+/// * `key_pair` - a RSA key pair.
+pub fn sign_rs256(payload: &JwtPayload, key_pair: &RsaKeyPair) -> Result<String, JoseError> {
+    let signer = RS256.signer_from_der(key_pair.to_der_private_key())?;
+    encode_with_default_header(payload, &signer, key_pair.key_id())
+}
+
+/// Return the JWT payload verified by RS256.
+///
+/// This is a convenience function. It verifies only the signature,
+/// so claims should be validated by [`JwtPayloadValidator`].
+///
+/// # Arguments
+///
+/// * `input` - a JWT string representation.
+/// * `key_pair` - a RSA key pair.
+pub fn verify_rs256(
+    input: impl AsRef<[u8]>,
+    key_pair: &RsaKeyPair,
+) -> Result<JwtPayload, JoseError> {
+    let verifier = RS256.verifier_from_der(key_pair.to_der_public_key())?;
+    let (payload, _) = decode_with_verifier(input, &verifier)?;
+    Ok(payload)
+}
+
+/// Return the string repsentation of the JWT signed by EdDSA.
+///
+/// This is a convenience function. The JWS header has a "typ" header claim of "JWT"
+/// and a "kid" header claim when the key has a key ID.
+///
+/// # Arguments
+///
+/// * `payload` - The payload data.
+/// * `key_pair` - an Ed25519 or Ed448 key pair.
+pub fn sign_eddsa(payload: &JwtPayload, key_pair: &EdKeyPair) -> Result<String, JoseError> {
+    let signer = EdDSA.signer_from_der(key_pair.to_der_private_key())?;
+    encode_with_default_header(payload, &signer, key_pair.key_id())
+}
+
+/// Return the JWT payload verified by EdDSA.
+///
+/// This is a convenience function. It verifies only the signature,
+/// so claims should be validated by [`JwtPayloadValidator`].
+///
+/// # Arguments
+///
+/// * `input` - a JWT string representation.
+/// * `key_pair` - an Ed25519 or Ed448 key pair.
+pub fn verify_eddsa(
+    input: impl AsRef<[u8]>,
+    key_pair: &EdKeyPair,
+) -> Result<JwtPayload, JoseError> {
+    let verifier = EdDSA.verifier_from_der(key_pair.to_der_public_key())?;
+    let (payload, _) = decode_with_verifier(input, &verifier)?;
+    Ok(payload)
+}
+
+/// Return the string representation of the JWT signed by the algorithm of a JWK.
+///
+/// This is a convenience function. The alg parameter of the JWK is used if present,
+/// otherwise the default algorithm for the key type and curve by
+/// [`jws::default_algorithm_for`](crate::jws::default_algorithm_for).
+/// The JWS header has a "typ" header claim of "JWT" and a "kid" header claim
+/// when the key has a key ID.
+///
+/// # Arguments
+///
+/// * `payload` - The payload data.
+/// * `jwk` - a JWK of a private key.
+pub fn sign_auto(payload: &JwtPayload, jwk: &Jwk) -> Result<String, JoseError> {
+    let alg = match jwk.algorithm().or_else(|| jws::default_algorithm_for(jwk)) {
+        Some(val) => val,
+        Option::None => {
+            return Err(JoseError::UnsupportedSignatureAlgorithm(anyhow::anyhow!(
+                "A default algorithm is not found: kty = {}",
+                jwk.key_type()
+            )))
+        }
+    };
+    let signing_algorithm = match SigningAlgorithm::from_name(alg) {
+        Some(val) => val,
+        Option::None => {
+            return Err(JoseError::UnsupportedSignatureAlgorithm(anyhow::anyhow!(
+                "Unsupported algorithm: {}",
+                alg
+            )))
+        }
+    };
+    let signer = signing_algorithm.signer_from_jwk(jwk)?;
+    encode_with_default_header(payload, &*signer, jwk.key_id())
+}
+
+fn encode_with_default_header(
+    payload: &JwtPayload,
+    signer: &dyn JwsSigner,
+    key_id: Option<&str>,
+) -> Result<String, JoseError> {
+    let mut header = JwsHeader::new();
+    header.set_token_type("JWT");
+    if let Some(val) = key_id {
+        header.set_key_id(val);
+    }
+    encode_with_signer(payload, &header, signer)
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
@@ -183,18 +480,76 @@ mod tests {
 
     #[allow(deprecated)]
     use crate::jwe::{
-        Dir, A128GCMKW, A128KW, A192GCMKW, A192KW, A256GCMKW, A256KW, ECDH_ES, ECDH_ES_A128KW,
-        ECDH_ES_A192KW, ECDH_ES_A256KW, PBES2_HS256_A128KW, PBES2_HS384_A192KW, PBES2_HS512_A256KW,
-        RSA1_5, RSA_OAEP, RSA_OAEP_256,
+        self, Dir, JweHeader, A128GCMKW, A128KW, A192GCMKW, A192KW, A256GCMKW, A256KW, ECDH_ES,
+        ECDH_ES_A128KW, ECDH_ES_A192KW, ECDH_ES_A256KW, PBES2_HS256_A128KW, PBES2_HS384_A192KW,
+        PBES2_HS512_A256KW, RSA1_5, RSA_OAEP, RSA_OAEP_256,
     };
-    use crate::jwk::Jwk;
+    use crate::jwk::{Ed25519, Jwk};
     use crate::jws::{
-        EdDSA, JwsHeader, ES256, ES256K, ES384, ES512, HS256, HS384, HS512, PS256, PS384, PS512,
-        RS256, RS384, RS512,
+        self, EdDSA, JwsHeader, ES256, ES256K, ES384, ES512, HS256, HS384, HS512, PS256, PS384,
+        PS512, RS256, RS384, RS512,
     };
-    use crate::jwt::{self, JwtPayload};
+    use crate::jwt::{self, JwtContext, JwtPayload, JwtPayloadValidator, JwtVerifyOptions};
     use crate::util;
-    use crate::Value;
+    use crate::{JoseError, Value};
+
+    #[test]
+    fn test_validate_access_token() -> Result<()> {
+        let now = SystemTime::now();
+        let mut payload = JwtPayload::new();
+        payload.set_issuer("https://as.example.com/");
+        payload.set_expires_at(&(now + Duration::from_secs(300)));
+        payload.set_audience(vec!["https://rs.example.com/"]);
+        payload.set_subject("5ba552d67");
+        payload.set_claim("client_id", Some(json!("s6BhdRkqt3")))?;
+        payload.set_issued_at(&now);
+        payload.set_jwt_id("dbe39bf3a3ba4238a513f51d6e1691c4");
+
+        let mut header = JwsHeader::new();
+        header.set_token_type("at+jwt");
+
+        let secret = b"0123456789ABCDEF0123456789ABCDEF";
+        let input = jwt::encode_with_signer(&payload, &header, &HS256.signer_from_bytes(secret)?)?;
+        let (payload, header) =
+            jwt::decode_with_verifier(&input, &HS256.verifier_from_bytes(secret)?)?;
+
+        let mut validator = JwtPayloadValidator::new();
+        validator.set_issuer("https://as.example.com/");
+        validator.set_audience("https://rs.example.com/");
+        jwt::validate_access_token(&payload, &header, &validator)?;
+
+        let mut media_type = header.clone();
+        media_type.set_token_type("application/at+JWT");
+        jwt::validate_access_token(&payload, &media_type, &validator)?;
+
+        let mut jwt_type = header.clone();
+        jwt_type.set_token_type("JWT");
+        assert!(matches!(
+            jwt::validate_access_token(&payload, &jwt_type, &validator),
+            Err(JoseError::InvalidJwtFormat(_))
+        ));
+
+        for key in ["iss", "exp", "aud", "sub", "client_id", "iat", "jti"] {
+            let mut missing = payload.clone();
+            missing.set_claim(key, None)?;
+            match jwt::validate_access_token(&missing, &header, &validator) {
+                Err(JoseError::InvalidClaim(err)) => {
+                    assert_eq!(err.to_string(), format!("Key {} is missing.", key))
+                }
+                res => panic!("{:?}", res),
+            }
+        }
+
+        let mut invalid = payload.clone();
+        invalid.set_claim("client_id", Some(json!(1)))?;
+        assert!(jwt::validate_access_token(&invalid, &header, &validator).is_err());
+
+        // The values are validated by the validator.
+        validator.set_audience("https://other.example.com/");
+        assert!(jwt::validate_access_token(&payload, &header, &validator).is_err());
+
+        Ok(())
+    }
 
     #[test]
     fn test_decode_header() -> Result<()> {
@@ -246,7 +601,7 @@ mod tests {
     #[test]
     fn test_jwt_with_hmac() -> Result<()> {
         for alg in &[HS256, HS384, HS512] {
-            let private_key = util::random_bytes(64);
+            let private_key = util::random_bytes(64)?;
 
             let mut src_header = JwsHeader::new();
             src_header.set_token_type("JWT");
@@ -265,6 +620,70 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_jwt_with_expected_types() -> Result<()> {
+        let alg = HS256;
+        let private_key = util::random_bytes(32)?;
+        let signer = alg.signer_from_bytes(&private_key)?;
+        let verifier = alg.verifier_from_bytes(&private_key)?;
+
+        let mut context = JwtContext::new();
+        context.set_expected_types(&["JWT"]);
+
+        let mut header = JwsHeader::new();
+        header.set_token_type("application/jwt");
+        let jwt_string = jwt::encode_with_signer(&JwtPayload::new(), &header, &signer)?;
+        context.decode_with_verifier(&jwt_string, &verifier)?;
+
+        header.set_token_type("dpop+jwt");
+        let jwt_string = jwt::encode_with_signer(&JwtPayload::new(), &header, &signer)?;
+        assert!(matches!(
+            context.decode_with_verifier(&jwt_string, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+        jwt::decode_with_verifier(&jwt_string, &verifier)?;
+
+        let jwt_string = jwt::encode_with_signer(&JwtPayload::new(), &JwsHeader::new(), &signer)?;
+        assert!(matches!(
+            context.decode_with_verifier(&jwt_string, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        context.remove_expected_types();
+        context.decode_with_verifier(&jwt_string, &verifier)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_encrypted_with_expected_types() -> Result<()> {
+        let key = util::random_bytes(16)?;
+        let encrypter = Dir.encrypter_from_bytes(&key)?;
+        let decrypter = Dir.decrypter_from_bytes(&key)?;
+
+        let mut context = JwtContext::new();
+        context.set_expected_types(&["JWT"]);
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128GCM");
+        header.set_token_type("application/jwt");
+        let jwt_string = jwt::encode_with_encrypter(&JwtPayload::new(), &header, &encrypter)?;
+        context.decode_with_decrypter(&jwt_string, &decrypter)?;
+
+        header.set_token_type("dpop+jwt");
+        let jwt_string = jwt::encode_with_encrypter(&JwtPayload::new(), &header, &encrypter)?;
+        assert!(matches!(
+            context.decode_with_decrypter(&jwt_string, &decrypter),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+        jwt::decode_with_decrypter(&jwt_string, &decrypter)?;
+
+        context.remove_expected_types();
+        context.decode_with_decrypter(&jwt_string, &decrypter)?;
+
+        Ok(())
+    }
+
     #[test]
     fn test_jwt_with_rsa_pem() -> Result<()> {
         for alg in &[RS256, RS384, RS512] {
@@ -797,6 +1216,340 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_decode_detailed_with_verifier() -> Result<()> {
+        let private_key = load_file("pem/EC_P-256_private.pem")?;
+        let public_key = load_file("pem/EC_P-256_public.pem")?;
+
+        let mut src_header = JwsHeader::new();
+        src_header.set_token_type("JWT");
+        let mut src_payload = JwtPayload::new();
+        src_payload.set_subject("subject");
+
+        let signer = ES256.signer_from_pem(&private_key)?;
+        let jwt_string = jwt::encode_with_signer(&src_payload, &src_header, &signer)?;
+
+        let verifier = ES256.verifier_from_pem(&public_key)?;
+        let decoded = jwt::decode_detailed_with_verifier(&jwt_string, &verifier)?;
+        assert_eq!(decoded.payload(), &src_payload);
+        assert_eq!(decoded.header().token_type(), Some("JWT"));
+
+        let parts: Vec<&str> = jwt_string.split('.').collect();
+        assert_eq!(decoded.raw_header(), parts[0].as_bytes());
+        assert_eq!(decoded.raw_payload(), parts[1].as_bytes());
+        assert_eq!(
+            decoded.signing_input(),
+            format!("{}.{}", parts[0], parts[1]).as_bytes()
+        );
+        assert_eq!(
+            decoded.signature(),
+            util::decode_base64_urlsafe_no_pad(parts[2])?
+        );
+        verifier.verify(decoded.signing_input(), decoded.signature())?;
+
+        let other = ES256.generate_key_pair()?;
+        let other = ES256.verifier_from_jwk(&other.to_jwk_public_key())?;
+        assert!(jwt::decode_detailed_with_verifier(&jwt_string, &other).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_with_and_without_token_type() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_subject("subject");
+        let signer = HS256.signer_from_bytes(b"0123456789ABCDEF0123456789ABCDEF")?;
+
+        let header = JwsHeader::new();
+        let jwt_string = jwt::encode_with_signer(&payload, &header, &signer)?;
+        let header = jwt::decode_header(&jwt_string)?;
+        assert_eq!(header.claim("typ"), None);
+
+        let mut header = JwsHeader::new();
+        header.set_token_type("JWT");
+        let jwt_string = jwt::encode_with_signer(&payload, &header, &signer)?;
+        let decoded = jwt::decode_header(&jwt_string)?;
+        assert_eq!(decoded.claim("typ"), Some(&json!("JWT")));
+
+        header.remove_token_type();
+        let jwt_string = jwt::encode_with_signer(&payload, &header, &signer)?;
+        let decoded = jwt::decode_header(&jwt_string)?;
+        assert_eq!(decoded.claim("typ"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_convenience_functions() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_subject("subject");
+
+        let mut key_pair = ES256.generate_key_pair()?;
+        key_pair.set_key_id(Some("ec-1"));
+        let jwt_string = jwt::sign_es256(&payload, &key_pair)?;
+        assert_eq!(jwt::verify_es256(&jwt_string, &key_pair)?, payload);
+        let header = jwt::decode_header(&jwt_string)?;
+        assert_eq!(header.claim("typ"), Some(&json!("JWT")));
+        assert_eq!(header.claim("kid"), Some(&json!("ec-1")));
+
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let jwt_string = jwt::sign_hs256(&payload, key)?;
+        assert_eq!(jwt::verify_hs256(&jwt_string, key)?, payload);
+        assert!(jwt::verify_hs256(&jwt_string, b"FEDCBA9876543210FEDCBA9876543210").is_err());
+
+        let key_pair = RS256.generate_key_pair(2048)?;
+        let jwt_string = jwt::sign_rs256(&payload, &key_pair)?;
+        assert_eq!(jwt::verify_rs256(&jwt_string, &key_pair)?, payload);
+
+        let key_pair = EdDSA.generate_key_pair(Ed25519)?;
+        let jwt_string = jwt::sign_eddsa(&payload, &key_pair)?;
+        assert_eq!(jwt::verify_eddsa(&jwt_string, &key_pair)?, payload);
+        assert!(jwt::verify_es256(&jwt_string, &ES256.generate_key_pair()?).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_strict() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_subject("subject");
+
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let mut header = JwsHeader::new();
+        header.set_token_type("JWT");
+        let jwt_string =
+            jwt::encode_with_signer(&payload, &header, &HS256.signer_from_bytes(key)?)?;
+        let verifier = HS256.verifier_from_bytes(key)?;
+
+        let options = JwtVerifyOptions::new(&["HS256", "ES256"], "JWT");
+        let (dst_payload, dst_header) = jwt::verify_strict(&jwt_string, &verifier, &options)?;
+        assert_eq!(dst_payload, payload);
+        assert_eq!(dst_header.algorithm(), Some("HS256"));
+
+        // The verifier can handle HS256, but the options do not allow it.
+        let options = JwtVerifyOptions::new(&["ES256"], "JWT");
+        match jwt::verify_strict(&jwt_string, &verifier, &options) {
+            Err(JoseError::InvalidJwtFormat(err)) => {
+                assert_eq!(
+                    err.to_string(),
+                    "The JWT alg header claim is not allowed: HS256"
+                )
+            }
+            res => panic!("{:?}", res),
+        }
+
+        let options = JwtVerifyOptions::new(&["HS256"], "at+jwt");
+        match jwt::verify_strict(&jwt_string, &verifier, &options) {
+            Err(JoseError::InvalidJwtFormat(err)) => {
+                assert_eq!(
+                    err.to_string(),
+                    "The JWT typ header claim is not expected: JWT"
+                )
+            }
+            res => panic!("{:?}", res),
+        }
+
+        let options = JwtVerifyOptions::new(&["HS256"], "application/jwt");
+        jwt::verify_strict(&jwt_string, &verifier, &options)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_with_signer_canonical() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_claim("zzz", Some(json!({ "b": [1.50, 2e-3], "a": "\u{20ac}" })))?;
+        payload.set_subject("subject");
+        payload.set_issuer("issuer");
+
+        // The same claims in the other order.
+        let mut other = JwtPayload::new();
+        other.set_issuer("issuer");
+        other.set_subject("subject");
+        other.set_claim("zzz", Some(json!({ "a": "\u{20ac}", "b": [1.5, 0.002] })))?;
+
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let signer = HS256.signer_from_bytes(key)?;
+        let header = JwsHeader::new();
+        let jwt_string1 = jwt::encode_with_signer_canonical(&payload, &header, &signer)?;
+        let jwt_string2 = jwt::encode_with_signer_canonical(&payload, &header, &signer)?;
+        let jwt_string3 = jwt::encode_with_signer_canonical(&other, &header, &signer)?;
+        assert_eq!(jwt_string1, jwt_string2);
+        assert_eq!(jwt_string1, jwt_string3);
+
+        let parts: Vec<&str> = jwt_string1.split('.').collect();
+        assert_eq!(
+            util::decode_base64_urlsafe_no_pad(parts[1])?,
+            r#"{"iss":"issuer","sub":"subject","zzz":{"a":"€","b":[1.5,0.002]}}"#.as_bytes()
+        );
+
+        let (dst_payload, _) =
+            jwt::decode_with_verifier(&jwt_string1, &HS256.verifier_from_bytes(key)?)?;
+        assert_eq!(dst_payload, payload);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_nested() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_subject("subject");
+
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let signer = HS256.signer_from_bytes(key)?;
+        let verifier = HS256.verifier_from_bytes(key)?;
+        let cek = util::random_bytes(16)?;
+        let encrypter = Dir.encrypter_from_bytes(&cek)?;
+        let decrypter = Dir.decrypter_from_bytes(&cek)?;
+
+        // JWE(JWS(JWS(claims)))
+        let mut header = JwsHeader::new();
+        header.set_key_id("inner");
+        let jwt_string = jwt::encode_with_signer(&payload, &header, &signer)?;
+        let mut header = JwsHeader::new();
+        header.set_key_id("middle");
+        header.set_content_type("JWT");
+        let jwt_string = jws::serialize_compact(jwt_string.as_bytes(), &header, &signer)?;
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128GCM");
+        header.set_content_type("JWT");
+        let jwt_string = jwe::serialize_compact(jwt_string.as_bytes(), &header, &encrypter)?;
+
+        let (dst_payload, headers) = jwt::decode_nested(
+            &jwt_string,
+            |_header| Ok(Some(&verifier)),
+            |_header| Ok(Some(&decrypter)),
+        )?;
+        assert_eq!(dst_payload, payload);
+        assert_eq!(headers.len(), 3);
+        assert_eq!(headers[0].claim_str("enc"), Some("A128GCM"));
+        assert_eq!(headers[1].claim_str("kid"), Some("middle"));
+        assert_eq!(headers[2].claim_str("kid"), Some("inner"));
+
+        // A layer that has no key is rejected.
+        assert!(jwt::decode_nested(
+            &jwt_string,
+            |header| match header.key_id() {
+                Some("inner") => Ok(None),
+                _ => Ok(Some(&verifier)),
+            },
+            |_header| Ok(Some(&decrypter)),
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_with_duplicate_payload_members() -> Result<()> {
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let signer = HS256.signer_from_bytes(key)?;
+        let verifier = HS256.verifier_from_bytes(key)?;
+
+        let jwt_string =
+            jws::serialize_compact(br#"{"sub":"a","sub":"b"}"#, &JwsHeader::new(), &signer)?;
+        assert!(matches!(
+            jwt::decode_with_verifier(&jwt_string, &verifier),
+            Err(JoseError::InvalidJwtFormat(_))
+        ));
+
+        let mut context = JwtContext::new();
+        context.set_strict_json(false);
+        assert!(!context.is_strict_json());
+        let (payload, _) = context.decode_with_verifier(&jwt_string, &verifier)?;
+        assert_eq!(payload.subject(), Some("b"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_nested_with_depth_limit() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_subject("subject");
+
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let signer = HS256.signer_from_bytes(key)?;
+        let verifier = HS256.verifier_from_bytes(key)?;
+
+        let mut header = JwsHeader::new();
+        header.set_content_type("JWT");
+        let mut jwt_string = jwt::encode_with_signer(&payload, &JwsHeader::new(), &signer)?;
+        for _ in 0..4 {
+            jwt_string = jws::serialize_compact(jwt_string.as_bytes(), &header, &signer)?;
+        }
+
+        let context = JwtContext::new();
+        assert_eq!(context.max_nested_depth(), 4);
+        match context.decode_nested(
+            &jwt_string,
+            |_header| Ok(Some(&verifier)),
+            |_header| Ok(None),
+        ) {
+            Err(JoseError::InvalidJwtFormat(err)) => {
+                assert_eq!(
+                    err.to_string(),
+                    "The nested JWT must have 4 layers or less."
+                )
+            }
+            res => panic!("{:?}", res.map(|(payload, _)| payload)),
+        }
+
+        let mut context = JwtContext::new();
+        context.set_max_nested_depth(5);
+        let (dst_payload, headers) = context.decode_nested(
+            &jwt_string,
+            |_header| Ok(Some(&verifier)),
+            |_header| Ok(None),
+        )?;
+        assert_eq!(dst_payload, payload);
+        assert_eq!(headers.len(), 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_sign_auto() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_subject("subject");
+
+        for path in [
+            "jwk/EC_P-256_private.jwk",
+            "jwk/EC_P-521_private.jwk",
+            "jwk/OKP_Ed25519_private.jwk",
+            "jwk/RSA_private.jwk",
+        ] {
+            let jwk = Jwk::from_bytes(load_file(path)?)?;
+            let jwt_string = jwt::sign_auto(&payload, &jwk)?;
+
+            let header = jwt::decode_header(&jwt_string)?;
+            let alg = jws::default_algorithm_for(&jwk).unwrap();
+            assert_eq!(header.claim("alg"), Some(&json!(alg)), "{}", path);
+            assert_eq!(header.claim("typ"), Some(&json!("JWT")));
+
+            let verifier = jws::signing_algorithm_from_name(alg)
+                .unwrap()
+                .verifier_from_jwk(&jwk.to_public_key()?)?;
+            let (decoded, _) = jwt::decode_with_verifier(&jwt_string, &*verifier)?;
+            assert_eq!(decoded, payload);
+        }
+
+        let mut jwk = Jwk::from_bytes(load_file("jwk/RSA_private.jwk")?)?;
+        jwk.set_algorithm("PS256");
+        jwk.set_key_id("rsa-1");
+        let jwt_string = jwt::sign_auto(&payload, &jwk)?;
+        let header = jwt::decode_header(&jwt_string)?;
+        assert_eq!(header.claim("alg"), Some(&json!("PS256")));
+        assert_eq!(header.claim("kid"), Some(&json!("rsa-1")));
+
+        let jwk = Jwk::from_bytes(load_file("jwk/OKP_X25519_private.jwk")?)?;
+        assert!(matches!(
+            jwt::sign_auto(&payload, &jwk),
+            Err(JoseError::UnsupportedSignatureAlgorithm(_))
+        ));
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
@@ -0,0 +1,69 @@
+use crate::jws::JwsHeader;
+use crate::jwt::JwtPayload;
+
+/// Represents a verified JWT with its raw segments.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct DecodedJwt {
+    header: JwsHeader,
+    payload: JwtPayload,
+    signing_input: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl DecodedJwt {
+    pub(crate) fn new(
+        header: JwsHeader,
+        payload: JwtPayload,
+        signing_input: Vec<u8>,
+        signature: Vec<u8>,
+    ) -> Self {
+        Self {
+            header,
+            payload,
+            signing_input,
+            signature,
+        }
+    }
+
+    /// Return the decoded JWS header.
+    pub fn header(&self) -> &JwsHeader {
+        &self.header
+    }
+
+    /// Return the decoded JWT payload.
+    pub fn payload(&self) -> &JwtPayload {
+        &self.payload
+    }
+
+    /// Return the JWS signing input that is the encoded protected header and
+    /// the encoded payload joined by a period.
+    pub fn signing_input(&self) -> &[u8] {
+        &self.signing_input
+    }
+
+    /// Return the raw encoded protected header.
+    pub fn raw_header(&self) -> &[u8] {
+        match self.signing_input.iter().position(|b| *b == b'.') {
+            Some(pos) => &self.signing_input[..pos],
+            None => &self.signing_input,
+        }
+    }
+
+    /// Return the raw encoded payload.
+    pub fn raw_payload(&self) -> &[u8] {
+        match self.signing_input.iter().position(|b| *b == b'.') {
+            Some(pos) => &self.signing_input[(pos + 1)..],
+            None => &[],
+        }
+    }
+
+    /// Return the decoded signature.
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+
+    /// Return the decoded JWS header and JWT payload.
+    pub fn into_parts(self) -> (JwtPayload, JwsHeader) {
+        (self.payload, self.header)
+    }
+}
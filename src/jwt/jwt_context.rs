@@ -1,9 +1,11 @@
+use std::time::SystemTime;
+
 use anyhow::bail;
 
 use crate::jwe::{JweContext, JweDecrypter, JweEncrypter, JweHeader};
 use crate::jwk::{Jwk, JwkSet};
 use crate::jws::{JwsContext, JwsHeader, JwsSigner, JwsVerifier};
-use crate::jwt::{self, JwtPayload};
+use crate::jwt::{self, DecodedJwt, JwtPayload, JwtVerifyOptions};
 use crate::util;
 use crate::{JoseError, JoseHeader, Map, Value};
 
@@ -11,6 +13,7 @@ use crate::{JoseError, JoseHeader, Map, Value};
 pub struct JwtContext {
     jws_context: JwsContext,
     jwe_context: JweContext,
+    max_nested_depth: usize,
 }
 
 impl JwtContext {
@@ -18,6 +21,7 @@ impl JwtContext {
         Self {
             jws_context: JwsContext::new(),
             jwe_context: JweContext::new(),
+            max_nested_depth: 4,
         }
     }
 
@@ -50,6 +54,83 @@ impl JwtContext {
         self.jwe_context.remove_acceptable_critical(name);
     }
 
+    /// Set token types that the typ header claim must match when decoding a JWT.
+    ///
+    /// The comparison is case-insensitive and the "application/" prefix is ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - expected token types (e.g. "JWT")
+    pub fn set_expected_types(&mut self, values: &[&str]) {
+        self.jws_context.set_expected_types(values);
+        self.jwe_context.set_expected_types(values);
+    }
+
+    /// Remove the restriction of token types.
+    pub fn remove_expected_types(&mut self) {
+        self.jws_context.remove_expected_types();
+        self.jwe_context.remove_expected_types();
+    }
+
+    /// Test a token type is acceptable.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a value of typ header claim
+    pub fn is_expected_type(&self, value: &str) -> bool {
+        self.jws_context.is_expected_type(value)
+    }
+
+    /// Test the "=" padding of base64url encoded parts is accepted when decoding a JWT.
+    pub fn is_base64_padding_tolerant(&self) -> bool {
+        self.jws_context.is_base64_padding_tolerant()
+    }
+
+    /// Set whether the "=" padding of base64url encoded parts is accepted when decoding a JWT.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - true to accept padded parts of both JWS and JWE
+    pub fn set_base64_padding_tolerant(&mut self, value: bool) {
+        self.jws_context.set_base64_padding_tolerant(value);
+        let mut options = *self.jwe_context.decryption_options();
+        options.set_base64_padding_tolerant(value);
+        self.jwe_context.set_decryption_options(options);
+    }
+
+    /// Test duplicate member names in a decoded header or payload are rejected.
+    pub fn is_strict_json(&self) -> bool {
+        self.jws_context.is_strict_json()
+    }
+
+    /// Set whether duplicate member names in a decoded header or payload are rejected.
+    ///
+    /// They are rejected by default. When disabled, the last value is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - false to accept duplicate member names of both JWS and JWE
+    pub fn set_strict_json(&mut self, value: bool) {
+        self.jws_context.set_strict_json(value);
+        let mut options = *self.jwe_context.decryption_options();
+        options.set_strict_json(value);
+        self.jwe_context.set_decryption_options(options);
+    }
+
+    /// Return the maximum number of layers of a nested JWT.
+    pub fn max_nested_depth(&self) -> usize {
+        self.max_nested_depth
+    }
+
+    /// Set the maximum number of layers of a nested JWT.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the maximum number of JWS and JWE layers (default 4)
+    pub fn set_max_nested_depth(&mut self, value: usize) {
+        self.max_nested_depth = value;
+    }
+
     /// Return the string repsentation of the JWT with a "none" algorithm.
     ///
     /// # Arguments
@@ -66,6 +147,9 @@ impl JwtContext {
 
     /// Return the string repsentation of the JWT with the siginig algorithm.
     ///
+    /// The header is used as it is; a typ header claim is added only when it is set
+    /// by [`JwsHeader::set_token_type`].
+    ///
     /// # Arguments
     ///
     /// * `payload` - The payload data.
@@ -96,6 +180,42 @@ impl JwtContext {
         })
     }
 
+    /// Return the string repsentation of the JWT with the siginig algorithm,
+    /// whose payload is serialized by JSON Canonicalization Scheme (RFC 8785).
+    ///
+    /// The same payload always produces the same token with a deterministic signer.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The payload data.
+    /// * `header` - The JWS heaser claims.
+    /// * `signer` - a signer object.
+    pub fn encode_with_signer_canonical(
+        &self,
+        payload: &JwtPayload,
+        header: &JwsHeader,
+        signer: &dyn JwsSigner,
+    ) -> Result<String, JoseError> {
+        (|| -> anyhow::Result<String> {
+            if let Some(vals) = header.critical() {
+                if vals.contains(&"b64") {
+                    bail!("JWT is not support b64 header claim.");
+                }
+            }
+
+            let payload_bytes =
+                util::to_canonical_json(&Value::Object(payload.claims_set().clone()));
+            let jwt =
+                self.jws_context
+                    .serialize_compact(payload_bytes.as_bytes(), header, signer)?;
+            Ok(jwt)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwtFormat(err),
+        })
+    }
+
     /// Return the string repsentation of the JWT with the encrypting algorithm.
     ///
     /// # Arguments
@@ -127,18 +247,21 @@ impl JwtContext {
             let parts: Vec<&[u8]> = input.split(|b| *b == '.' as u8).collect();
             if parts.len() == 3 {
                 // JWS
-                let header = util::decode_base64_urlsafe_no_pad(parts[0])?;
-                let header: Map<String, Value> = serde_json::from_slice(&header)?;
+                let header = self.decode_base64(parts[0])?;
+                let header = self.parse_json_object(&header)?;
                 let header = JwsHeader::from_map(header)?;
                 Ok(Box::new(header))
             } else if parts.len() == 5 {
                 // JWE
-                let header = util::decode_base64_urlsafe_no_pad(parts[0])?;
-                let header: Map<String, Value> = serde_json::from_slice(&header)?;
+                let header = self.decode_base64(parts[0])?;
+                let header = self.parse_json_object(&header)?;
                 let header = JweHeader::from_map(header)?;
                 Ok(Box::new(header))
             } else {
-                bail!("The input cannot be recognized as a JWT.");
+                bail!(
+                    "The input cannot be recognized as a JWT: {} parts separated by period",
+                    parts.len()
+                );
             }
         })()
         .map_err(|err| match err.downcast::<JoseError>() {
@@ -173,6 +296,66 @@ impl JwtContext {
         self.decode_with_verifier_selector(input, |_header| Ok(Some(verifier)))
     }
 
+    /// Return the JWT object decoded by the selected verifier with its raw segments.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a JWT string representation.
+    /// * `verifier` - a verifier of the signing algorithm.
+    pub fn decode_detailed_with_verifier(
+        &self,
+        input: impl AsRef<[u8]>,
+        verifier: &dyn JwsVerifier,
+    ) -> Result<DecodedJwt, JoseError> {
+        let input = input.as_ref();
+        let (payload, header) = self.decode_with_verifier(input, verifier)?;
+
+        (|| -> anyhow::Result<DecodedJwt> {
+            let pos = match input.iter().rposition(|b| *b == b'.') {
+                Some(val) => val,
+                None => bail!("The compact serialization form of JWS must have signature."),
+            };
+            let signing_input = input[..pos].to_vec();
+            let signature = self.decode_base64(&input[(pos + 1)..])?;
+            Ok(DecodedJwt::new(header, payload, signing_input, signature))
+        })()
+        .map_err(JoseError::InvalidJwtFormat)
+    }
+
+    /// Return the JWT object decoded by the verifier after checking the header against the options.
+    ///
+    /// A token whose alg header claim is not allowed or whose typ header claim is not
+    /// the expected one is rejected before the verifier is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a JWT string representation.
+    /// * `verifier` - a verifier of the signing algorithm.
+    /// * `options` - acceptable algorithms and a expected token type.
+    pub fn verify_strict(
+        &self,
+        input: impl AsRef<[u8]>,
+        verifier: &dyn JwsVerifier,
+        options: &JwtVerifyOptions,
+    ) -> Result<(JwtPayload, JwsHeader), JoseError> {
+        self.decode_with_verifier_selector(input, |header| {
+            (|| -> anyhow::Result<Option<&dyn JwsVerifier>> {
+                match header.algorithm() {
+                    Some(val) if options.is_allowed_algorithm(val) => {}
+                    Some(val) => bail!("The JWT alg header claim is not allowed: {}", val),
+                    None => bail!("The JWT alg header claim is required."),
+                }
+                match header.token_type() {
+                    Some(val) if options.is_expected_type(val) => {}
+                    Some(val) => bail!("The JWT typ header claim is not expected: {}", val),
+                    None => bail!("The JWT typ header claim is required."),
+                }
+                Ok(Some(verifier))
+            })()
+            .map_err(JoseError::InvalidJwtFormat)
+        })
+    }
+
     /// Return the JWT object decoded with a selected verifying algorithm.
     ///
     /// # Arguments
@@ -211,7 +394,7 @@ impl JwtContext {
                         })
                     })?;
 
-            let payload: Map<String, Value> = serde_json::from_slice(&payload)?;
+            let payload = self.parse_json_object(&payload)?;
             let payload = JwtPayload::from_map(payload)?;
 
             Ok((payload, header))
@@ -224,6 +407,8 @@ impl JwtContext {
 
     /// Return the JWT object decoded by using a JWK set.
     ///
+    /// Keys that are not valid at the current time by the nbf and exp parameters are skipped.
+    ///
     /// # Arguments
     ///
     /// * `input` - a JWT string representation.
@@ -244,7 +429,7 @@ impl JwtContext {
                 None => return Ok(None),
             };
 
-            for jwk in jwk_set.get(key_id) {
+            for jwk in jwk_set.get_for_verification(key_id, &SystemTime::now()) {
                 if let Some(val) = selector(jwk)? {
                     return Ok(Some(val));
                 }
@@ -293,7 +478,7 @@ impl JwtContext {
                         Ok(Some(decrypter))
                     })?;
 
-            let payload: Map<String, Value> = serde_json::from_slice(&payload)?;
+            let payload = self.parse_json_object(&payload)?;
             let payload = JwtPayload::from_map(payload)?;
 
             Ok((payload, header))
@@ -304,6 +489,83 @@ impl JwtContext {
         })
     }
 
+    /// Return the JWT object decoded from a nested JWT and the headers of each layer.
+    ///
+    /// While the cty header claim of a layer is "JWT", its payload is decoded as the next
+    /// layer by a verifier or a decrypter that the selectors return. The headers are
+    /// ordered from the outermost layer. A JWT that has more layers than the maximum
+    /// nested depth is rejected.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a JWT string representation.
+    /// * `verifier_selector` - a function for selecting the verifying algorithm of a JWS layer.
+    /// * `decrypter_selector` - a function for selecting the decrypting algorithm of a JWE layer.
+    pub fn decode_nested<'a, 'b, F, G>(
+        &self,
+        input: impl AsRef<[u8]>,
+        verifier_selector: F,
+        decrypter_selector: G,
+    ) -> Result<(JwtPayload, Vec<Box<dyn JoseHeader>>), JoseError>
+    where
+        F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
+        G: Fn(&JweHeader) -> Result<Option<&'b dyn JweDecrypter>, JoseError>,
+    {
+        (|| -> anyhow::Result<(JwtPayload, Vec<Box<dyn JoseHeader>>)> {
+            let mut input = input.as_ref().to_vec();
+            let mut headers: Vec<Box<dyn JoseHeader>> = Vec::new();
+            loop {
+                if headers.len() >= self.max_nested_depth {
+                    bail!(
+                        "The nested JWT must have {} layers or less.",
+                        self.max_nested_depth
+                    );
+                }
+
+                let (payload, header): (Vec<u8>, Box<dyn JoseHeader>) =
+                    match input.iter().filter(|b| **b == b'.').count() {
+                        2 => {
+                            let (payload, header) = self
+                                .jws_context
+                                .deserialize_compact_with_selector(&input, |header| {
+                                    if self.is_acceptable_critical("b64") {
+                                        return Err(JoseError::InvalidJwtFormat(anyhow::anyhow!(
+                                            "JWT is not supported b64 header claim."
+                                        )));
+                                    }
+                                    verifier_selector(header)
+                                })?;
+                            (payload, Box::new(header))
+                        }
+                        4 => {
+                            let (payload, header) = self
+                                .jwe_context
+                                .deserialize_compact_with_selector(&input, &decrypter_selector)?;
+                            (payload, Box::new(header))
+                        }
+                        _ => bail!("The input cannot be recognized as a JWT."),
+                    };
+
+                let nested = match header.claim_str("cty") {
+                    Some(val) => val.eq_ignore_ascii_case("JWT"),
+                    None => false,
+                };
+                headers.push(header);
+
+                if !nested {
+                    let payload = self.parse_json_object(&payload)?;
+                    let payload = JwtPayload::from_map(payload)?;
+                    return Ok((payload, headers));
+                }
+                input = payload;
+            }
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwtFormat(err),
+        })
+    }
+
     /// Return the JWT object decoded by using a JWK set.
     ///
     /// # Arguments
@@ -334,4 +596,20 @@ impl JwtContext {
             Ok(None)
         })
     }
+
+    fn parse_json_object(&self, input: &[u8]) -> anyhow::Result<Map<String, Value>> {
+        if self.jws_context.is_strict_json() {
+            util::parse_json_object_strict(input)
+        } else {
+            Ok(serde_json::from_slice(input)?)
+        }
+    }
+
+    fn decode_base64(&self, input: impl AsRef<[u8]>) -> Result<Vec<u8>, base64::DecodeError> {
+        if self.jws_context.is_base64_padding_tolerant() {
+            util::decode_base64_urlsafe_padding_tolerant(input)
+        } else {
+            util::decode_base64_urlsafe_no_pad(input)
+        }
+    }
 }
@@ -2,8 +2,11 @@ use std::convert::Into;
 use std::fmt::Display;
 use std::time::{Duration, SystemTime};
 
+#[cfg(feature = "sd_jwt")]
+use crate::sd_jwt::{Disclosure, SD_ALG_SHA256};
 use crate::{JoseError, Map, Number, Value};
 use anyhow::bail;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Debug, Eq, PartialEq, Clone, Default)]
 pub struct JwtPayload {
@@ -111,6 +114,22 @@ impl JwtPayload {
         }
     }
 
+    /// Return values for audience payload claim (aud) as a list.
+    ///
+    /// A single audience is returned as a list of one element and a missing claim as an
+    /// empty list.
+    pub fn audiences(&self) -> Vec<String> {
+        match self.claims.get("aud") {
+            Some(Value::Array(vals)) => vals
+                .iter()
+                .filter_map(|val| val.as_str())
+                .map(|val| val.to_string())
+                .collect(),
+            Some(Value::String(val)) => vec![val.to_string()],
+            _ => Vec::new(),
+        }
+    }
+
     /// Set a system time for expires at payload claim (exp).
     ///
     /// # Arguments
@@ -210,6 +229,102 @@ impl JwtPayload {
         }
     }
 
+    /// Set a value for JWK SHA-256 thumbprint confirmation claim (cnf.jkt).
+    ///
+    /// Other members of the confirmation claim (cnf) are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a base64url encoded JWK SHA-256 thumbprint
+    pub fn set_jkt(&mut self, value: impl Into<String>) {
+        let value: String = value.into();
+        match self.claims.get_mut("cnf") {
+            Some(Value::Object(vals)) => {
+                vals.insert("jkt".to_string(), Value::String(value));
+            }
+            _ => {
+                let mut map = Map::new();
+                map.insert("jkt".to_string(), Value::String(value));
+                self.claims.insert("cnf".to_string(), Value::Object(map));
+            }
+        }
+    }
+
+    /// Return the value for JWK SHA-256 thumbprint confirmation claim (cnf.jkt).
+    pub fn jkt(&self) -> Option<&str> {
+        match self.claims.get("cnf") {
+            Some(Value::Object(vals)) => match vals.get("jkt") {
+                Some(Value::String(val)) => Some(val),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Add the digest of a SD-JWT disclosure to the _sd claim.
+    ///
+    /// The digests are kept in sorted order, so that the original order of
+    /// the claims is hidden. The _sd_alg claim is set to "sha-256" if it is absent.
+    ///
+    /// # Arguments
+    ///
+    /// * `disclosure` - a disclosure of an object property
+    #[cfg(feature = "sd_jwt")]
+    pub fn add_disclosure(&mut self, disclosure: &Disclosure) -> Result<(), JoseError> {
+        (|| -> anyhow::Result<()> {
+            let claim_name = match disclosure.claim_name() {
+                Some(val) => val,
+                None => bail!("A disclosure of an array element cannot be added to the _sd claim."),
+            };
+            if self.claims.contains_key(claim_name) {
+                bail!(
+                    "The JWT {} payload claim cannot be both disclosed and selectively disclosed.",
+                    claim_name
+                );
+            }
+            match self.claims.get("_sd_alg") {
+                Some(Value::String(val)) if val == SD_ALG_SHA256 => {}
+                Some(val) => bail!("The JWT _sd_alg payload claim is not supported: {}", val),
+                None => {
+                    self.claims.insert(
+                        "_sd_alg".to_string(),
+                        Value::String(SD_ALG_SHA256.to_string()),
+                    );
+                }
+            }
+
+            let digest = disclosure.digest()?;
+            let vals = match self
+                .claims
+                .entry("_sd")
+                .or_insert_with(|| Value::Array(Vec::new()))
+            {
+                Value::Array(vals) => vals,
+                _ => bail!("The JWT _sd payload claim must be an array."),
+            };
+            let pos = match vals.binary_search_by(|val| val.as_str().cmp(&Some(digest.as_str()))) {
+                Ok(_) => bail!("The disclosure is already added: {}", digest),
+                Err(pos) => pos,
+            };
+            vals.insert(pos, Value::String(digest));
+
+            Ok(())
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwtFormat(err),
+        })
+    }
+
+    /// Return the digests of SD-JWT disclosures in the _sd claim.
+    #[cfg(feature = "sd_jwt")]
+    pub fn disclosure_digests(&self) -> Vec<&str> {
+        match self.claims.get("_sd") {
+            Some(Value::Array(vals)) => vals.iter().filter_map(|val| val.as_str()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
     /// Set a value for payload claim of a specified key.
     ///
     /// # Arguments
@@ -233,6 +348,41 @@ impl JwtPayload {
         .map_err(|err| JoseError::InvalidJwtFormat(err))
     }
 
+    /// Insert the claims of the other payload. Overlapping claims are overwritten.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other payload.
+    pub fn merge(&mut self, other: &JwtPayload) {
+        for (key, value) in &other.claims {
+            self.claims.insert(key.clone(), value.clone());
+        }
+    }
+
+    /// Insert the claims of a JSON object. Overlapping claims are overwritten.
+    ///
+    /// No claim is inserted when the value is not an object or a claim is invalid.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a JSON object of payload claims
+    pub fn extend_from_value(&mut self, value: Value) -> Result<(), JoseError> {
+        let map = match value {
+            Value::Object(val) => val,
+            _ => {
+                return Err(JoseError::InvalidJwtFormat(anyhow::anyhow!(
+                    "The JWT payload must be an object."
+                )))
+            }
+        };
+        for (key, value) in &map {
+            Self::check_claim(key, value)?;
+        }
+
+        self.claims.extend(map);
+        Ok(())
+    }
+
     /// Return a value for payload claim of a specified key.
     ///
     /// # Arguments
@@ -300,6 +450,19 @@ impl Into<Map<String, Value>> for JwtPayload {
     }
 }
 
+impl Serialize for JwtPayload {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.claims.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for JwtPayload {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let map = Map::<String, Value>::deserialize(deserializer)?;
+        Self::from_map(map).map_err(de::Error::custom)
+    }
+}
+
 impl Display for JwtPayload {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         let val = serde_json::to_string(&self.claims).map_err(|_e| std::fmt::Error {})?;
@@ -315,6 +478,98 @@ mod tests {
     use serde_json::json;
 
     use super::JwtPayload;
+    use crate::Map;
+
+    #[test]
+    fn test_merge_payload() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_issuer("https://issuer.example.com");
+        payload.set_subject("base");
+        payload.set_claim("scope", Some(json!("read")))?;
+
+        let mut other = JwtPayload::new();
+        other.set_subject("request");
+        other.set_jwt_id("jti-1");
+
+        payload.merge(&other);
+        assert_eq!(payload.issuer(), Some("https://issuer.example.com"));
+        assert_eq!(payload.subject(), Some("request"));
+        assert_eq!(payload.jwt_id(), Some("jti-1"));
+        assert_eq!(payload.claim("scope"), Some(&json!("read")));
+
+        payload.extend_from_value(json!({"scope": "write", "aud": ["a", "b"]}))?;
+        assert_eq!(payload.claim("scope"), Some(&json!("write")));
+        assert_eq!(payload.audience(), Some(vec!["a", "b"]));
+
+        assert!(payload
+            .extend_from_value(json!({"scope": "admin", "exp": "tomorrow"}))
+            .is_err());
+        assert!(payload.extend_from_value(json!(["scope"])).is_err());
+        assert_eq!(payload.claim("scope"), Some(&json!("write")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_serde_payload() -> Result<()> {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Record {
+            name: String,
+            payload: JwtPayload,
+        }
+
+        let mut payload = JwtPayload::new();
+        payload.set_issuer("iss");
+        payload.set_claim("custom", Some(json!({ "a": [1, 2] })))?;
+
+        let value = serde_json::to_value(&payload)?;
+        assert_eq!(value, json!({ "iss": "iss", "custom": { "a": [1, 2] } }));
+        let decoded: JwtPayload = serde_json::from_value(value)?;
+        assert_eq!(decoded, payload);
+
+        let record = Record {
+            name: "test".to_string(),
+            payload: payload.clone(),
+        };
+        let value = serde_json::to_value(&record)?;
+        let decoded: Record = serde_json::from_value(value)?;
+        assert_eq!(decoded.name, "test");
+        assert_eq!(decoded.payload, payload);
+
+        assert!(serde_json::from_value::<JwtPayload>(json!("iss")).is_err());
+        assert!(serde_json::from_value::<JwtPayload>(json!([1])).is_err());
+        assert!(serde_json::from_value::<JwtPayload>(json!({ "iss": 1 })).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_audiences() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        assert!(payload.audiences().is_empty());
+
+        payload.set_audience(vec!["aud0"]);
+        assert_eq!(payload.claim("aud"), Some(&json!("aud0")));
+        assert_eq!(payload.audiences(), vec!["aud0".to_string()]);
+
+        payload.set_audience(vec!["aud0", "aud1"]);
+        assert_eq!(payload.claim("aud"), Some(&json!(["aud0", "aud1"])));
+        assert_eq!(
+            payload.audiences(),
+            vec!["aud0".to_string(), "aud1".to_string()]
+        );
+
+        // A one element array on the wire is kept as is, but is set back as a string.
+        let mut map = Map::new();
+        map.insert("aud".to_string(), json!(["aud0"]));
+        let mut payload = JwtPayload::from_map(map)?;
+        assert_eq!(payload.audiences(), vec!["aud0".to_string()]);
+        assert_eq!(payload.to_string(), r#"{"aud":["aud0"]}"#);
+
+        payload.set_audience(payload.audiences());
+        assert_eq!(payload.to_string(), r#"{"aud":"aud0"}"#);
+
+        Ok(())
+    }
 
     #[test]
     fn test_new_payload() -> Result<()> {
@@ -326,6 +581,7 @@ mod tests {
         payload.set_not_before(&SystemTime::UNIX_EPOCH);
         payload.set_issued_at(&SystemTime::UNIX_EPOCH);
         payload.set_jwt_id("jti");
+        payload.set_jkt("jkt");
         payload.set_claim("payload_claim", Some(json!("payload_claim")))?;
 
         assert!(matches!(payload.issuer(), Some("iss")));
@@ -337,6 +593,8 @@ mod tests {
         assert!(matches!(payload.not_before(), Some(ref val) if val == &SystemTime::UNIX_EPOCH));
         assert!(matches!(payload.issued_at(), Some(ref val) if val == &SystemTime::UNIX_EPOCH));
         assert!(matches!(payload.jwt_id(), Some("jti")));
+        assert!(matches!(payload.jkt(), Some("jkt")));
+        assert_eq!(payload.claim("cnf"), Some(&json!({"jkt": "jkt"})));
         assert!(
             matches!(payload.claim("payload_claim"), Some(val) if val == &json!("payload_claim"))
         );
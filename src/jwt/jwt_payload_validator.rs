@@ -1,18 +1,30 @@
+use std::collections::BTreeSet;
 use std::convert::Into;
-use std::time::SystemTime;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
-use anyhow::bail;
+use anyhow::anyhow;
 
+use crate::jwk::Jwk;
 use crate::jwt::JwtPayload;
 use crate::{JoseError, Map, Value};
 
+type AudienceMatcher = dyn Fn(&str, &str) -> bool + Send + Sync;
+
 /// Represents JWT payload validator.
-#[derive(Debug, Eq, PartialEq)]
 pub struct JwtPayloadValidator {
     base_time: Option<SystemTime>,
     min_issued_time: Option<SystemTime>,
     max_issued_time: Option<SystemTime>,
+    leeway: Duration,
+    exp_leeway: Option<Duration>,
+    nbf_leeway: Option<Duration>,
+    iat_leeway: Option<Duration>,
     audience: Option<String>,
+    audience_matcher: Option<Arc<AudienceMatcher>>,
+    jkt: Option<String>,
+    required_claims: BTreeSet<String>,
     claims: Map<String, Value>,
 }
 
@@ -23,7 +35,14 @@ impl JwtPayloadValidator {
             base_time: None,
             min_issued_time: None,
             max_issued_time: None,
+            leeway: Duration::ZERO,
+            exp_leeway: None,
+            nbf_leeway: None,
+            iat_leeway: None,
             audience: None,
+            audience_matcher: None,
+            jkt: None,
+            required_claims: BTreeSet::new(),
             claims: Map::new(),
         }
     }
@@ -70,6 +89,66 @@ impl JwtPayloadValidator {
         self.max_issued_time.as_ref()
     }
 
+    /// Set a leeway for time related claims (exp, nbf, iat) validation.
+    ///
+    /// It can be overridden for each claim. The default is zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - an acceptable clock skew
+    pub fn set_leeway(&mut self, value: Duration) {
+        self.leeway = value;
+    }
+
+    /// Return the leeway for time related claims (exp, nbf, iat) validation.
+    pub fn leeway(&self) -> Duration {
+        self.leeway
+    }
+
+    /// Set a leeway for expiration time payload claim (exp) validation.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - an acceptable time after the expiration time
+    pub fn set_exp_leeway(&mut self, value: Duration) {
+        self.exp_leeway = Some(value);
+    }
+
+    /// Return the leeway for expiration time payload claim (exp) validation.
+    pub fn exp_leeway(&self) -> Duration {
+        self.exp_leeway.unwrap_or(self.leeway)
+    }
+
+    /// Set a leeway for not before payload claim (nbf) validation.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - an acceptable time before the not before time
+    pub fn set_nbf_leeway(&mut self, value: Duration) {
+        self.nbf_leeway = Some(value);
+    }
+
+    /// Return the leeway for not before payload claim (nbf) validation.
+    pub fn nbf_leeway(&self) -> Duration {
+        self.nbf_leeway.unwrap_or(self.leeway)
+    }
+
+    /// Set a leeway for issued at payload claim (iat) validation.
+    ///
+    /// It allows the issued time to be later than the maximum issued time by the leeway.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - an acceptable time after the maximum issued time
+    pub fn set_iat_leeway(&mut self, value: Duration) {
+        self.iat_leeway = Some(value);
+    }
+
+    /// Return the leeway for issued at payload claim (iat) validation.
+    pub fn iat_leeway(&self) -> Duration {
+        self.iat_leeway.unwrap_or(self.leeway)
+    }
+
     /// Set a value for issuer payload claim (iss) validation.
     ///
     /// # Arguments
@@ -124,6 +203,26 @@ impl JwtPayloadValidator {
         }
     }
 
+    /// Set a function that compares the expected audience with a value of aud payload claim.
+    ///
+    /// The default comparison is the exact string match. A function can be used
+    /// to normalize audiences that are URIs (e.g. the case of the scheme and the host).
+    ///
+    /// # Arguments
+    ///
+    /// * `matcher` - a function that receives the expected audience and a value of aud
+    pub fn set_audience_matcher<F>(&mut self, matcher: F)
+    where
+        F: Fn(&str, &str) -> bool + Send + Sync + 'static,
+    {
+        self.audience_matcher = Some(Arc::new(matcher));
+    }
+
+    /// Remove the function for audience comparison to use the exact string match.
+    pub fn remove_audience_matcher(&mut self) {
+        self.audience_matcher = None;
+    }
+
     /// Set a value for JWT ID payload claim (jti) validation.
     ///
     /// # Arguments
@@ -142,6 +241,62 @@ impl JwtPayloadValidator {
         }
     }
 
+    /// Set a JWK whose SHA-256 thumbprint is expected in the confirmation claim (cnf.jkt).
+    ///
+    /// # Arguments
+    ///
+    /// * `jwk` - a JWK that the token is bound to
+    pub fn set_expected_jkt(&mut self, jwk: &Jwk) -> Result<(), JoseError> {
+        let jkt = jwk.thumbprint_sha256()?;
+        self.jkt = Some(jkt);
+        Ok(())
+    }
+
+    /// Return the expected value for confirmation claim (cnf.jkt) validation.
+    pub fn expected_jkt(&self) -> Option<&str> {
+        match self.jkt {
+            Some(ref val) => Some(val),
+            _ => None,
+        }
+    }
+
+    /// Require payload claims to be present regardless of their values.
+    ///
+    /// Without this, a absent claim is not validated at all (e.g. a JWT without
+    /// exp never expires).
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - key names of payload claims
+    pub fn require_claims(&mut self, keys: &[&str]) {
+        for key in keys {
+            self.required_claims.insert(key.to_string());
+        }
+    }
+
+    /// Require the issuer payload claim (iss) to be present.
+    pub fn require_issuer(&mut self) {
+        self.require_claims(&["iss"]);
+    }
+
+    /// Require the subject payload claim (sub) to be present.
+    pub fn require_subject(&mut self) {
+        self.require_claims(&["sub"]);
+    }
+
+    /// Require the expiration time payload claim (exp) to be present.
+    pub fn require_expiration(&mut self) {
+        self.require_claims(&["exp"]);
+    }
+
+    /// Return the key names of payload claims that must be present.
+    pub fn required_claims(&self) -> Vec<&str> {
+        self.required_claims
+            .iter()
+            .map(|key| key.as_str())
+            .collect()
+    }
+
     /// Set a value for payload claim of a specified key.
     ///
     /// # Arguments
@@ -167,81 +322,163 @@ impl JwtPayloadValidator {
     ///
     /// * `payload` - a decoded JWT payload.
     pub fn validate(&self, payload: &JwtPayload) -> Result<(), JoseError> {
-        (|| -> anyhow::Result<()> {
-            let now = SystemTime::now();
-            let current_time = self.base_time().unwrap_or(&now);
-            let min_issued_time = self.min_issued_time().unwrap_or(&SystemTime::UNIX_EPOCH);
-            let max_issued_time = self.max_issued_time().unwrap_or(&now);
-
-            if let Some(not_before) = payload.not_before() {
-                if &not_before > current_time {
-                    bail!(
-                        "The token is not yet valid: {}",
-                        time::OffsetDateTime::from(not_before),
-                    );
-                }
+        self.validate_collect(payload)
+            .map_err(|mut errors| errors.remove(0))
+    }
+
+    /// Validate a decoded JWT payload and return all failing constraints.
+    ///
+    /// Unlike `validate`, this method doesn't stop at the first failure.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - a decoded JWT payload.
+    pub fn validate_collect(&self, payload: &JwtPayload) -> Result<(), Vec<JoseError>> {
+        let mut errors = Vec::new();
+        let mut push = |err: anyhow::Error| errors.push(JoseError::InvalidClaim(err));
+
+        let now = SystemTime::now();
+        let current_time = self.base_time().unwrap_or(&now);
+        let min_issued_time = self.min_issued_time().unwrap_or(&SystemTime::UNIX_EPOCH);
+        let max_issued_time = self.max_issued_time().unwrap_or(&now);
+        // A limit that overflows by the leeway accepts any time.
+        let nbf_limit = current_time.checked_add(self.nbf_leeway());
+        let exp_limit = current_time.checked_sub(self.exp_leeway());
+        let iat_limit = max_issued_time.checked_add(self.iat_leeway());
+
+        for key in &self.required_claims {
+            if payload.claim(key).is_none() {
+                push(anyhow!("Key {} is missing.", key));
             }
+        }
 
-            if let Some(expires_at) = payload.expires_at() {
-                if &expires_at <= current_time {
-                    bail!(
-                        "The token has expired: {}",
-                        time::OffsetDateTime::from(expires_at),
-                    );
-                }
+        if let Some(not_before) = payload.not_before() {
+            if nbf_limit.is_some_and(|val| not_before > val) {
+                push(anyhow!(
+                    "The token is not yet valid: {}",
+                    time::OffsetDateTime::from(not_before),
+                ));
             }
+        }
 
-            if let Some(issued_at) = payload.issued_at() {
-                if &issued_at < min_issued_time {
-                    bail!(
-                        "The issued time is too old: {}",
-                        time::OffsetDateTime::from(issued_at),
-                    );
-                }
+        if let Some(expires_at) = payload.expires_at() {
+            if exp_limit.is_some_and(|val| expires_at <= val) {
+                push(anyhow!(
+                    "The token has expired: {}",
+                    time::OffsetDateTime::from(expires_at),
+                ));
+            }
+        }
 
-                if &issued_at > max_issued_time {
-                    bail!(
-                        "The issued time is too new: {}",
-                        time::OffsetDateTime::from(issued_at),
-                    );
-                }
+        if let Some(issued_at) = payload.issued_at() {
+            if &issued_at < min_issued_time {
+                push(anyhow!(
+                    "The issued time is too old: {}",
+                    time::OffsetDateTime::from(issued_at),
+                ));
             }
 
-            if let Some(audience) = &self.audience {
-                if let Some(audiences) = payload.audience() {
-                    if !audiences.contains(&audience.as_str()) {
-                        bail!("Key aud is invalid: {}", audiences.join(", "));
-                    }
+            if iat_limit.is_some_and(|val| issued_at > val) {
+                push(anyhow!(
+                    "The issued time is too new: {}",
+                    time::OffsetDateTime::from(issued_at),
+                ));
+            }
+        }
+
+        if let Some(audience) = &self.audience {
+            if let Some(audiences) = payload.audience() {
+                let matched = match &self.audience_matcher {
+                    Some(matcher) => audiences.iter().any(|val| matcher(audience, val)),
+                    None => audiences.contains(&audience.as_str()),
+                };
+                if !matched {
+                    push(anyhow!("Key aud is invalid: {}", audiences.join(", ")));
                 }
             }
+        }
+
+        if let Some(expected) = &self.jkt {
+            match payload.jkt() {
+                Some(val) if val == expected => {}
+                Some(val) => push(anyhow!("Key cnf.jkt is invalid: {}", val)),
+                None => push(anyhow!("Key cnf.jkt is missing.")),
+            }
+        }
 
-            for (key, value1) in &self.claims {
-                if let Some(value2) = payload.claim(key) {
-                    if value1 != value2 {
-                        bail!("Key {} is invalid: {}", key, value2);
-                    }
-                } else {
-                    bail!("Key {} is missing.", key);
+        for (key, value1) in &self.claims {
+            if let Some(value2) = payload.claim(key) {
+                if value1 != value2 {
+                    push(anyhow!("Key {} is invalid: {}", key, value2));
                 }
+            } else {
+                push(anyhow!("Key {} is missing.", key));
             }
+        }
 
+        if errors.is_empty() {
             Ok(())
-        })()
-        .map_err(|err| match err.downcast::<JoseError>() {
-            Ok(err) => err,
-            Err(err) => JoseError::InvalidClaim(err),
-        })
+        } else {
+            Err(errors)
+        }
     }
 }
 
+impl Debug for JwtPayloadValidator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtPayloadValidator")
+            .field("base_time", &self.base_time)
+            .field("min_issued_time", &self.min_issued_time)
+            .field("max_issued_time", &self.max_issued_time)
+            .field("leeway", &self.leeway)
+            .field("exp_leeway", &self.exp_leeway)
+            .field("nbf_leeway", &self.nbf_leeway)
+            .field("iat_leeway", &self.iat_leeway)
+            .field("audience", &self.audience)
+            .field("audience_matcher", &self.audience_matcher.is_some())
+            .field("jkt", &self.jkt)
+            .field("required_claims", &self.required_claims)
+            .field("claims", &self.claims)
+            .finish()
+    }
+}
+
+impl PartialEq for JwtPayloadValidator {
+    fn eq(&self, other: &Self) -> bool {
+        let same_matcher = match (&self.audience_matcher, &other.audience_matcher) {
+            (Some(val1), Some(val2)) => Arc::ptr_eq(val1, val2),
+            (None, None) => true,
+            _ => false,
+        };
+        self.base_time == other.base_time
+            && self.min_issued_time == other.min_issued_time
+            && self.max_issued_time == other.max_issued_time
+            && self.leeway == other.leeway
+            && self.exp_leeway == other.exp_leeway
+            && self.nbf_leeway == other.nbf_leeway
+            && self.iat_leeway == other.iat_leeway
+            && self.audience == other.audience
+            && same_matcher
+            && self.jkt == other.jkt
+            && self.required_claims == other.required_claims
+            && self.claims == other.claims
+    }
+}
+
+impl Eq for JwtPayloadValidator {}
+
 #[cfg(test)]
 mod tests {
+    use std::fs;
+    use std::path::PathBuf;
     use std::time::{Duration, SystemTime};
 
     use anyhow::Result;
     use serde_json::json;
 
+    use crate::jwk::Jwk;
     use crate::jwt::{JwtPayload, JwtPayloadValidator};
+    use crate::JoseError;
 
     #[test]
     fn test_jwt_payload_validate() -> Result<()> {
@@ -264,4 +501,183 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_jwt_payload_validate_with_leeway() -> Result<()> {
+        let base_time = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let mut payload = JwtPayload::new();
+        payload.set_not_before(&(base_time + Duration::from_secs(30)));
+        payload.set_expires_at(&(base_time - Duration::from_secs(5)));
+        payload.set_issued_at(&(base_time + Duration::from_secs(30)));
+
+        let mut validator = JwtPayloadValidator::new();
+        validator.set_base_time(base_time);
+        validator.set_max_issued_time(base_time);
+        assert_eq!(validator.validate_collect(&payload).unwrap_err().len(), 3);
+
+        // Lenient not-before, strict expiry.
+        validator.set_leeway(Duration::from_secs(60));
+        validator.set_exp_leeway(Duration::ZERO);
+        assert_eq!(validator.nbf_leeway(), Duration::from_secs(60));
+        assert_eq!(validator.iat_leeway(), Duration::from_secs(60));
+        let errors = validator.validate_collect(&payload).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("expired"));
+
+        validator.set_exp_leeway(Duration::from_secs(10));
+        validator.validate(&payload)?;
+
+        validator.set_nbf_leeway(Duration::from_secs(29));
+        let errors = validator.validate_collect(&payload).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("not yet valid"));
+
+        validator.set_nbf_leeway(Duration::from_secs(30));
+        validator.set_iat_leeway(Duration::from_secs(29));
+        let errors = validator.validate_collect(&payload).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("too new"));
+
+        validator.set_leeway(Duration::MAX);
+        validator.set_iat_leeway(Duration::MAX);
+        validator.set_exp_leeway(Duration::MAX);
+        validator.validate(&payload)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_validate_with_audience_matcher() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_audience(vec!["HTTPS://API.Example.com/v1/"]);
+
+        let mut validator = JwtPayloadValidator::new();
+        validator.set_audience("https://api.example.com/v1");
+        assert!(validator.validate(&payload).is_err());
+
+        // The scheme and the host are case-insensitive, and a trailing slash is ignored.
+        fn normalize(uri: &str) -> String {
+            let uri = uri.trim_end_matches('/');
+            match uri.find("://") {
+                Some(pos) => {
+                    let end = uri[(pos + 3)..]
+                        .find('/')
+                        .map_or(uri.len(), |val| pos + 3 + val);
+                    format!("{}{}", uri[..end].to_ascii_lowercase(), &uri[end..])
+                }
+                None => uri.to_string(),
+            }
+        }
+        validator.set_audience_matcher(|expected, actual| normalize(expected) == normalize(actual));
+        validator.validate(&payload)?;
+
+        // The path is still case-sensitive.
+        payload.set_audience(vec!["https://api.example.com/V1"]);
+        assert!(validator.validate(&payload).is_err());
+
+        validator.remove_audience_matcher();
+        payload.set_audience(vec!["https://api.example.com/v1"]);
+        validator.validate(&payload)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_validate_collect() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_issuer("iss");
+        payload.set_audience(vec!["aud0"]);
+        payload.set_expires_at(&(SystemTime::UNIX_EPOCH + Duration::from_secs(60)));
+
+        let mut validator = JwtPayloadValidator::new();
+        validator.set_base_time(SystemTime::UNIX_EPOCH + Duration::from_secs(90));
+        validator.set_issuer("other");
+        validator.set_audience("aud1");
+
+        let errors = validator.validate_collect(&payload).unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert!(errors
+            .iter()
+            .all(|err| matches!(err, JoseError::InvalidClaim(_))));
+        let messages: Vec<String> = errors.iter().map(|err| err.to_string()).collect();
+        assert!(messages[0].contains("The token has expired"));
+        assert!(messages[1].contains("Key aud is invalid"));
+        assert!(messages[2].contains("Key iss is invalid"));
+
+        // The fail-fast validation reports the first failure only.
+        let err = validator.validate(&payload).unwrap_err();
+        assert_eq!(err.to_string(), messages[0]);
+
+        validator.set_base_time(SystemTime::UNIX_EPOCH + Duration::from_secs(30));
+        validator.set_issuer("iss");
+        validator.set_audience("aud0");
+        assert!(validator.validate_collect(&payload).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_validate_required_claims() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_subject("sub");
+
+        // A JWT without exp passes the expiry check unless exp is required.
+        let mut validator = JwtPayloadValidator::new();
+        validator.validate(&payload)?;
+
+        validator.require_subject();
+        validator.require_expiration();
+        validator.require_claims(&["scope"]);
+        assert_eq!(validator.required_claims(), vec!["exp", "scope", "sub"]);
+
+        let errors = validator.validate_collect(&payload).unwrap_err();
+        let messages: Vec<String> = errors.iter().map(|err| err.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Invalid claim: Key exp is missing.",
+                "Invalid claim: Key scope is missing.",
+            ]
+        );
+
+        payload.set_expires_at(&(SystemTime::now() + Duration::from_secs(60)));
+        payload.set_claim("scope", Some(json!("read")))?;
+        validator.validate(&payload)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_validate_jkt() -> Result<()> {
+        let jwk = Jwk::from_bytes(load_file("jwk/EC_P-256_public.jwk")?)?;
+        let other = Jwk::from_bytes(load_file("jwk/EC_P-384_public.jwk")?)?;
+
+        let mut payload = JwtPayload::new();
+        payload.set_jkt(jwk.thumbprint_sha256()?);
+
+        let mut validator = JwtPayloadValidator::new();
+        validator.set_expected_jkt(&jwk)?;
+        validator.validate(&payload)?;
+
+        validator.set_expected_jkt(&other)?;
+        assert!(matches!(
+            validator.validate(&payload),
+            Err(JoseError::InvalidClaim(_))
+        ));
+        assert!(matches!(
+            validator.validate(&JwtPayload::new()),
+            Err(JoseError::InvalidClaim(_))
+        ));
+
+        Ok(())
+    }
+
+    fn load_file(path: &str) -> Result<Vec<u8>> {
+        let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        pb.push("data");
+        pb.push(path);
+
+        let data = fs::read(&pb)?;
+        Ok(data)
+    }
 }
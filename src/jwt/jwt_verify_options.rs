@@ -0,0 +1,62 @@
+use crate::util;
+
+/// Represents expectations that a JWT header must satisfy before it is verified.
+///
+/// The acceptable algorithms and the token type must be declared up front,
+/// so that the alg header claim chosen by a token never selects the algorithm.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct JwtVerifyOptions {
+    allowed_algorithms: Vec<String>,
+    token_type: String,
+}
+
+impl JwtVerifyOptions {
+    /// Return a new options.
+    ///
+    /// # Arguments
+    ///
+    /// * `allowed_algorithms` - acceptable values of the alg header claim (e.g. "ES256")
+    /// * `token_type` - a expected value of the typ header claim (e.g. "JWT")
+    pub fn new(allowed_algorithms: &[&str], token_type: &str) -> Self {
+        Self {
+            allowed_algorithms: allowed_algorithms
+                .iter()
+                .map(|val| val.to_string())
+                .collect(),
+            token_type: token_type.to_string(),
+        }
+    }
+
+    /// Return acceptable values of the alg header claim.
+    pub fn allowed_algorithms(&self) -> Vec<&str> {
+        self.allowed_algorithms
+            .iter()
+            .map(|val| val.as_str())
+            .collect()
+    }
+
+    /// Test a value of the alg header claim is acceptable.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a value of alg header claim
+    pub fn is_allowed_algorithm(&self, value: &str) -> bool {
+        self.allowed_algorithms.iter().any(|val| val == value)
+    }
+
+    /// Return a expected value of the typ header claim.
+    pub fn token_type(&self) -> &str {
+        &self.token_type
+    }
+
+    /// Test a value of the typ header claim is the expected one.
+    ///
+    /// The "application/" prefix and the letter case are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a value of typ header claim
+    pub fn is_expected_type(&self, value: &str) -> bool {
+        util::normalize_media_type(value) == util::normalize_media_type(&self.token_type)
+    }
+}
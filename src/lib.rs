@@ -6,6 +6,8 @@ pub mod jwe;
 pub mod jwk;
 pub mod jws;
 pub mod jwt;
+#[cfg(feature = "sd_jwt")]
+pub mod sd_jwt;
 pub mod util;
 
 mod jose_error;
@@ -0,0 +1,275 @@
+//! Selective Disclosure for JWTs (SD-JWT) building blocks.
+//!
+//! This module provides disclosures and their digests. The digests are embedded
+//! into a payload by [`JwtPayload::add_disclosure`](crate::jwt::JwtPayload::add_disclosure),
+//! and the payload is signed as a usual JWT. The presentation format is not supported yet.
+
+use anyhow::bail;
+use openssl::hash::{hash, MessageDigest};
+
+use crate::util;
+use crate::{JoseError, Value};
+
+/// The name of the hash algorithm used for digests of disclosures (_sd_alg).
+pub const SD_ALG_SHA256: &str = "sha-256";
+
+/// Represents a disclosure of SD-JWT.
+///
+/// A disclosure is a base64url encoded JSON array of a salt, a claim name and
+/// a claim value. The claim name is omitted for an array element.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Disclosure {
+    salt: String,
+    claim_name: Option<String>,
+    claim_value: Value,
+    encoded: String,
+}
+
+impl Disclosure {
+    /// Return a new disclosure of an object property with a random salt.
+    ///
+    /// # Arguments
+    ///
+    /// * `claim_name` - a claim name
+    /// * `claim_value` - a claim value
+    pub fn new(claim_name: impl Into<String>, claim_value: Value) -> Result<Self, JoseError> {
+        let salt = util::encode_base64_urlsafe_nopad(util::random_bytes(16)?);
+        Self::with_salt(salt, Some(claim_name.into()), claim_value)
+    }
+
+    /// Return a new disclosure of an array element with a random salt.
+    ///
+    /// # Arguments
+    ///
+    /// * `claim_value` - a value of the array element
+    pub fn new_array_element(claim_value: Value) -> Result<Self, JoseError> {
+        let salt = util::encode_base64_urlsafe_nopad(util::random_bytes(16)?);
+        Self::with_salt(salt, None, claim_value)
+    }
+
+    /// Return a new disclosure with a specified salt.
+    ///
+    /// # Arguments
+    ///
+    /// * `salt` - a salt. It should have at least 128 bits of entropy.
+    /// * `claim_name` - a claim name, or None for an array element
+    /// * `claim_value` - a claim value
+    pub fn with_salt(
+        salt: impl Into<String>,
+        claim_name: Option<String>,
+        claim_value: Value,
+    ) -> Result<Self, JoseError> {
+        (|| -> anyhow::Result<Self> {
+            let salt: String = salt.into();
+            let mut array = vec![Value::String(salt.clone())];
+            if let Some(val) = &claim_name {
+                Self::check_claim_name(val)?;
+                array.push(Value::String(val.clone()));
+            }
+            array.push(claim_value.clone());
+
+            let json = serde_json::to_string(&array)?;
+            Ok(Self {
+                salt,
+                claim_name,
+                claim_value,
+                encoded: util::encode_base64_urlsafe_nopad(json),
+            })
+        })()
+        .map_err(JoseError::InvalidJwtFormat)
+    }
+
+    /// Return the disclosure parsed from a base64url encoded string.
+    ///
+    /// The encoded string is kept as it is, because a digest is calculated over it.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a base64url encoded disclosure
+    pub fn from_encoded(input: &str) -> Result<Self, JoseError> {
+        (|| -> anyhow::Result<Self> {
+            let json = util::decode_base64_urlsafe_no_pad(input)?;
+            let array: Vec<Value> = match serde_json::from_slice(&json)? {
+                Value::Array(vals) => vals,
+                _ => bail!("The disclosure must be an array."),
+            };
+
+            let mut iter = array.into_iter();
+            let (salt, claim_name, claim_value) = match (iter.next(), iter.next(), iter.next()) {
+                (Some(salt), Some(value), None) => (salt, None, value),
+                (Some(salt), Some(name), Some(value)) => match name {
+                    Value::String(val) => {
+                        Self::check_claim_name(&val)?;
+                        (salt, Some(val), value)
+                    }
+                    _ => bail!("The claim name of the disclosure must be a string."),
+                },
+                _ => bail!("The disclosure must have two or three elements."),
+            };
+            if iter.next().is_some() {
+                bail!("The disclosure must have two or three elements.");
+            }
+            let salt = match salt {
+                Value::String(val) => val,
+                _ => bail!("The salt of the disclosure must be a string."),
+            };
+
+            Ok(Self {
+                salt,
+                claim_name,
+                claim_value,
+                encoded: input.to_string(),
+            })
+        })()
+        .map_err(JoseError::InvalidJwtFormat)
+    }
+
+    /// Return the salt.
+    pub fn salt(&self) -> &str {
+        &self.salt
+    }
+
+    /// Return the claim name, or None for an array element.
+    pub fn claim_name(&self) -> Option<&str> {
+        self.claim_name.as_deref()
+    }
+
+    /// Return the claim value.
+    pub fn claim_value(&self) -> &Value {
+        &self.claim_value
+    }
+
+    /// Return the base64url encoded disclosure.
+    pub fn encoded(&self) -> &str {
+        &self.encoded
+    }
+
+    /// Return the base64url encoded SHA-256 digest of the disclosure.
+    ///
+    /// It is the value to be listed in the _sd claim, or in the "..." member
+    /// of an array element.
+    pub fn digest(&self) -> Result<String, JoseError> {
+        let digest = hash(MessageDigest::sha256(), self.encoded.as_bytes())
+            .map_err(|err| JoseError::InvalidJwtFormat(err.into()))?;
+        Ok(util::encode_base64_urlsafe_nopad(digest))
+    }
+
+    fn check_claim_name(name: &str) -> anyhow::Result<()> {
+        if matches!(name, "_sd" | "...") {
+            bail!("The claim name of the disclosure must not be {}.", name);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use serde_json::json;
+
+    use super::{Disclosure, SD_ALG_SHA256};
+    use crate::jws::{JwsHeader, HS256};
+    use crate::jwt::{self, JwtPayload};
+
+    #[test]
+    fn disclosure_digest_spec_examples() -> Result<()> {
+        let disclosure = Disclosure::from_encoded(
+            "WyJfMjZiYzRMVC1hYzZxMktJNmNCVzVlcyIsICJmYW1pbHlfbmFtZSIsICJNw7ZiaXVzIl0",
+        )?;
+        assert_eq!(disclosure.salt(), "_26bc4LT-ac6q2KI6cBW5es");
+        assert_eq!(disclosure.claim_name(), Some("family_name"));
+        assert_eq!(disclosure.claim_value(), &json!("Möbius"));
+        assert_eq!(
+            disclosure.digest()?,
+            "X9yH0Ajrdm1Oij4tWso9UzzKJvPoDxwmuEcO3XAdRC0"
+        );
+
+        let disclosure = Disclosure::from_encoded(
+            "WyIyR0xDNDJzS1F2ZUNmR2ZyeU5STjl3IiwgImdpdmVuX25hbWUiLCAiSm9obiJd",
+        )?;
+        assert_eq!(disclosure.claim_name(), Some("given_name"));
+        assert_eq!(
+            disclosure.digest()?,
+            "jsu9yVulwQQlhFlM_3JlzMaSFzglhQG0DpfayQwLUK4"
+        );
+
+        let disclosure = Disclosure::from_encoded("WyJsa2x4RjVqTVlsR1RQVW92TU5JdkNBIiwgIlVTIl0")?;
+        assert_eq!(disclosure.salt(), "lklxF5jMYlGTPUovMNIvCA");
+        assert_eq!(disclosure.claim_name(), None);
+        assert_eq!(disclosure.claim_value(), &json!("US"));
+        assert_eq!(
+            disclosure.digest()?,
+            "pFndjkZ_VCzmyTa6UjlZo3dh-ko8aIKQc9DlGzhaVYo"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn create_disclosure() -> Result<()> {
+        let disclosure = Disclosure::new("address", json!({"country": "JP"}))?;
+        assert_eq!(disclosure.salt().len(), 22);
+        assert_ne!(
+            disclosure.salt(),
+            Disclosure::new("address", json!({"country": "JP"}))?.salt()
+        );
+
+        let parsed = Disclosure::from_encoded(disclosure.encoded())?;
+        assert_eq!(parsed, disclosure);
+        assert_eq!(parsed.digest()?, disclosure.digest()?);
+
+        let disclosure = Disclosure::with_salt("salt", None, json!(1))?;
+        assert_eq!(disclosure.encoded(), "WyJzYWx0IiwxXQ");
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_invalid_disclosure() {
+        assert!(Disclosure::new("_sd", json!([])).is_err());
+        assert!(Disclosure::new("...", json!("x")).is_err());
+
+        // ["salt"], ["salt","a","b","c"], {"a":1}, ["salt",1,2], [1,"a"]
+        for input in [
+            "WyJzYWx0Il0",
+            "WyJzYWx0IiwiYSIsImIiLCJjIl0",
+            "eyJhIjoxfQ",
+            "WyJzYWx0IiwxLDJd",
+            "WzEsImEiXQ",
+            "not base64!",
+        ] {
+            assert!(Disclosure::from_encoded(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn sign_payload_with_disclosures() -> Result<()> {
+        let given_name = Disclosure::new("given_name", json!("John"))?;
+        let family_name = Disclosure::new("family_name", json!("Doe"))?;
+
+        let mut payload = JwtPayload::new();
+        payload.set_issuer("https://issuer.example.com");
+        payload.add_disclosure(&given_name)?;
+        payload.add_disclosure(&family_name)?;
+        assert!(payload.add_disclosure(&given_name).is_err());
+        assert!(payload
+            .add_disclosure(&Disclosure::new_array_element(json!("x"))?)
+            .is_err());
+
+        let mut digests = vec![given_name.digest()?, family_name.digest()?];
+        digests.sort();
+        assert_eq!(payload.disclosure_digests(), digests);
+        assert_eq!(payload.claim("_sd_alg"), Some(&json!(SD_ALG_SHA256)));
+        assert_eq!(payload.claim("given_name"), None);
+
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let signer = HS256.signer_from_bytes(key)?;
+        let jwt_string = jwt::encode_with_signer(&payload, &JwsHeader::new(), &signer)?;
+
+        let verifier = HS256.verifier_from_bytes(key)?;
+        let (decoded, _) = jwt::decode_with_verifier(&jwt_string, &verifier)?;
+        assert_eq!(decoded.disclosure_digests(), digests);
+
+        Ok(())
+    }
+}
@@ -1,32 +1,120 @@
 pub mod der;
 pub mod hash_algorithm;
 pub mod oid;
+pub mod random_source;
 
 use anyhow::bail;
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
 use base64::DecodeError;
 use base64::Engine as _;
 use once_cell::sync::Lazy;
 use openssl::bn::BigNumRef;
-use openssl::rand;
+use openssl::derive::Deriver;
+use openssl::pkey::{PKey, PKeyRef, Private, Public};
+use openssl::sign::Signer;
 use regex;
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use crate::{JoseError, Map, Number, Value};
 
 pub use crate::util::hash_algorithm::HashAlgorithm;
+pub use crate::util::random_source::{OpensslRand, RandomSource};
 
 pub use HashAlgorithm::Sha1 as SHA_1;
 pub use HashAlgorithm::Sha256 as SHA_256;
 pub use HashAlgorithm::Sha384 as SHA_384;
 pub use HashAlgorithm::Sha512 as SHA_512;
 
-pub fn random_bytes(len: usize) -> Vec<u8> {
+/// Return random bytes that are generated by the OpenSSL CSPRNG.
+///
+/// # Arguments
+///
+/// * `len` - a byte length
+pub fn random_bytes(len: usize) -> Result<Vec<u8>, JoseError> {
+    random_bytes_from(&OpensslRand, len)
+}
+
+/// Return a key that is derived by HKDF (RFC 5869).
+///
+/// # Arguments
+///
+/// * `hash_algorithm` - A hash algorithm for HMAC.
+/// * `ikm` - A input keying material.
+/// * `salt` - A salt. If it is empty, a string of zeros is used.
+/// * `info` - A context and application specific information.
+/// * `len` - A length of the output keying material.
+///
+/// # Errors
+///
+/// Fails if `len` is greater than 255 times of the output length of the hash algorithm.
+pub fn hkdf(
+    hash_algorithm: HashAlgorithm,
+    ikm: &[u8],
+    salt: &[u8],
+    info: &[u8],
+    len: usize,
+) -> Result<Vec<u8>, JoseError> {
+    (|| -> anyhow::Result<Vec<u8>> {
+        let hash_len = hash_algorithm.output_len();
+        if len > 255 * hash_len {
+            bail!("The length of HKDF is too large: {}", len);
+        }
+
+        let hmac = |key: &[u8], inputs: &[&[u8]]| -> anyhow::Result<Vec<u8>> {
+            let key = PKey::hmac(key)?;
+            let mut signer = Signer::new(hash_algorithm.message_digest(), &key)?;
+            for input in inputs {
+                signer.update(input)?;
+            }
+            Ok(signer.sign_to_vec()?)
+        };
+
+        // extract
+        let zeros = vec![0; hash_len];
+        let prk = hmac(if salt.is_empty() { &zeros } else { salt }, &[ikm])?;
+
+        // expand
+        let mut okm = Vec::with_capacity(len + hash_len);
+        let mut t = Vec::new();
+        let mut counter = 1u8;
+        while okm.len() < len {
+            t = hmac(&prk, &[&t, info, &[counter]])?;
+            okm.extend_from_slice(&t);
+            counter = counter.wrapping_add(1);
+        }
+        okm.truncate(len);
+        Ok(okm)
+    })()
+    .map_err(|err| match err.downcast::<JoseError>() {
+        Ok(err) => err,
+        Err(err) => JoseError::InvalidKeyFormat(err),
+    })
+}
+
+pub(crate) fn random_bytes_from(
+    source: &dyn RandomSource,
+    len: usize,
+) -> Result<Vec<u8>, JoseError> {
     let mut vec = vec![0; len];
-    rand::rand_bytes(&mut vec).unwrap();
-    vec
+    source.fill(&mut vec)?;
+    Ok(vec)
 }
 
 pub(crate) fn ceiling(len: usize, div: usize) -> usize {
     (len + (div - 1)) / div
 }
 
+/// Return the lowercase media type without the "application/" prefix (RFC 7515 Section 4.1.9).
+pub(crate) fn normalize_media_type(value: &str) -> String {
+    let value = value.to_ascii_lowercase();
+    match value.strip_prefix("application/") {
+        Some(val) if !val.contains('/') => val.to_string(),
+        _ => value,
+    }
+}
+
 pub(crate) fn is_base64_standard(input: &str) -> bool {
     static RE_BASE64_STANDARD: Lazy<regex::Regex> = Lazy::new(|| {
         regex::Regex::new(
@@ -71,6 +159,17 @@ pub(crate) fn decode_base64_urlsafe_no_pad(
     base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(input)
 }
 
+/// Decode base64url that may have the "=" padding, which is not allowed by JOSE.
+pub(crate) fn decode_base64_urlsafe_padding_tolerant(
+    input: impl AsRef<[u8]>,
+) -> Result<Vec<u8>, DecodeError> {
+    static URL_SAFE_INDIFFERENT: GeneralPurpose = GeneralPurpose::new(
+        &alphabet::URL_SAFE,
+        GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+    );
+    URL_SAFE_INDIFFERENT.decode(input)
+}
+
 pub(crate) fn parse_pem(input: &[u8]) -> anyhow::Result<(String, Vec<u8>)> {
     static RE_PEM: Lazy<regex::bytes::Regex> = Lazy::new(|| {
         regex::bytes::Regex::new(concat!(
@@ -103,6 +202,202 @@ pub(crate) fn parse_pem(input: &[u8]) -> anyhow::Result<(String, Vec<u8>)> {
     Ok(result)
 }
 
+pub(crate) fn decrypt_pkcs8_pem(input: &[u8], passphrase: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let (alg, data) = parse_pem(input)?;
+    if alg != "ENCRYPTED PRIVATE KEY" {
+        bail!("Inappropriate algorithm: {}", alg);
+    }
+
+    let private_key = match PKey::private_key_from_pkcs8_passphrase(&data, passphrase) {
+        Ok(val) => val,
+        Err(_) => bail!("Failed to decrypt the private key. The passphrase may be wrong."),
+    };
+    Ok(private_key.private_key_to_pkcs8()?)
+}
+
+/// Return the raw shared secret of a key agreement without any KDF.
+pub(crate) fn derive_shared_secret(
+    private_key: &PKeyRef<Private>,
+    public_key: &PKeyRef<Public>,
+) -> anyhow::Result<Vec<u8>> {
+    let mut deriver = Deriver::new(private_key)?;
+    deriver.set_peer(public_key)?;
+    Ok(deriver.derive_to_vec()?)
+}
+
+/// Return a JSON object that is parsed from a input.
+///
+/// Unlike serde_json, which silently takes the last value, duplicate member names
+/// are rejected for objects at any depth.
+pub(crate) fn parse_json_object_strict(input: &[u8]) -> anyhow::Result<Map<String, Value>> {
+    struct StrictValue(Value);
+
+    impl<'de> Deserialize<'de> for StrictValue {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_any(StrictValueVisitor)
+        }
+    }
+
+    struct StrictValueVisitor;
+
+    impl<'de> Visitor<'de> for StrictValueVisitor {
+        type Value = StrictValue;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("any valid JSON value")
+        }
+
+        fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+            Ok(StrictValue(Value::Bool(value)))
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+            Ok(StrictValue(Value::Number(value.into())))
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+            Ok(StrictValue(Value::Number(value.into())))
+        }
+
+        fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+            match Number::from_f64(value) {
+                Some(val) => Ok(StrictValue(Value::Number(val))),
+                None => Err(de::Error::custom("A number must be finite.")),
+            }
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+            Ok(StrictValue(Value::String(value.to_string())))
+        }
+
+        fn visit_string<E: de::Error>(self, value: String) -> Result<Self::Value, E> {
+            Ok(StrictValue(Value::String(value)))
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(StrictValue(Value::Null))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut vec = Vec::new();
+            while let Some(StrictValue(val)) = seq.next_element()? {
+                vec.push(val);
+            }
+            Ok(StrictValue(Value::Array(vec)))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+            let mut map = Map::new();
+            while let Some(key) = access.next_key::<String>()? {
+                if map.contains_key(&key) {
+                    return Err(de::Error::custom(format!(
+                        "A duplicate member name exists: {}",
+                        key
+                    )));
+                }
+                let StrictValue(val) = access.next_value()?;
+                map.insert(key, val);
+            }
+            Ok(StrictValue(Value::Object(map)))
+        }
+    }
+
+    let StrictValue(value) = serde_json::from_slice(input)?;
+    match value {
+        Value::Object(val) => Ok(val),
+        _ => bail!("A JSON value must be an object."),
+    }
+}
+
+/// Return a JSON text that is serialized by JSON Canonicalization Scheme (RFC 8785).
+///
+/// Object members are sorted by the UTF-16 code units of the names, and numbers are
+/// formatted as ECMAScript does for IEEE 754 double precision values.
+pub(crate) fn to_canonical_json(value: &Value) -> String {
+    fn write_value(value: &Value, out: &mut String) {
+        match value {
+            Value::Null => out.push_str("null"),
+            Value::Bool(val) => out.push_str(if *val { "true" } else { "false" }),
+            Value::Number(val) => match val.as_f64() {
+                Some(val) => write_number(val, out),
+                None => out.push_str(&val.to_string()),
+            },
+            Value::String(val) => out.push_str(&serde_json::to_string(val).unwrap()),
+            Value::Array(vals) => {
+                out.push('[');
+                for (i, val) in vals.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    write_value(val, out);
+                }
+                out.push(']');
+            }
+            Value::Object(map) => {
+                let mut entries: Vec<(Vec<u16>, &String, &Value)> = map
+                    .iter()
+                    .map(|(key, val)| (key.encode_utf16().collect(), key, val))
+                    .collect();
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+                out.push('{');
+                for (i, (_, key, val)) in entries.into_iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    out.push_str(&serde_json::to_string(key).unwrap());
+                    out.push(':');
+                    write_value(val, out);
+                }
+                out.push('}');
+            }
+        }
+    }
+
+    fn write_number(value: f64, out: &mut String) {
+        if value == 0.0 {
+            out.push('0');
+            return;
+        }
+        if value < 0.0 {
+            out.push('-');
+        }
+
+        // The shortest digits that round trip, and the exponent of the first digit.
+        let exp_form = format!("{:e}", value.abs());
+        let (mantissa, exp) = exp_form.split_once('e').unwrap();
+        let digits = mantissa.replace('.', "");
+        let k = digits.len() as i32;
+        let n = exp.parse::<i32>().unwrap() + 1;
+
+        if k <= n && n <= 21 {
+            out.push_str(&digits);
+            out.push_str(&"0".repeat((n - k) as usize));
+        } else if 0 < n && n <= 21 {
+            out.push_str(&digits[..n as usize]);
+            out.push('.');
+            out.push_str(&digits[n as usize..]);
+        } else if -6 < n && n <= 0 {
+            out.push_str("0.");
+            out.push_str(&"0".repeat(-n as usize));
+            out.push_str(&digits);
+        } else {
+            out.push_str(&digits[..1]);
+            if k > 1 {
+                out.push('.');
+                out.push_str(&digits[1..]);
+            }
+            out.push('e');
+            out.push(if n - 1 < 0 { '-' } else { '+' });
+            out.push_str(&(n - 1).abs().to_string());
+        }
+    }
+
+    let mut out = String::new();
+    write_value(value, &mut out);
+    out
+}
+
 pub(crate) fn num_to_vec(num: &BigNumRef, len: usize) -> Vec<u8> {
     let vec = num.to_vec();
     if vec.len() < len {
@@ -117,10 +412,127 @@ pub(crate) fn num_to_vec(num: &BigNumRef, len: usize) -> Vec<u8> {
     }
 }
 
+/// Decode a hex string of a test vector.
+#[cfg(test)]
+pub(crate) fn from_hex(input: &str) -> Vec<u8> {
+    (0..input.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&input[i..(i + 2)], 16).unwrap())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_random_bytes() -> anyhow::Result<()> {
+        for len in [0, 1, 12, 16, 32, 64, 1024] {
+            let bytes1 = random_bytes(len)?;
+            let bytes2 = random_bytes(len)?;
+            assert_eq!(bytes1.len(), len);
+            assert_eq!(bytes2.len(), len);
+            if len >= 16 {
+                assert_ne!(bytes1, bytes2);
+            }
+        }
+
+        #[derive(Debug)]
+        struct BrokenRand;
+
+        impl RandomSource for BrokenRand {
+            fn fill(&self, _buf: &mut [u8]) -> Result<(), JoseError> {
+                Err(JoseError::InvalidKeyFormat(anyhow::anyhow!("broken")))
+            }
+
+            fn box_clone(&self) -> Box<dyn RandomSource> {
+                Box::new(BrokenRand)
+            }
+        }
+
+        assert!(random_bytes_from(&BrokenRand, 16).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hkdf() -> anyhow::Result<()> {
+        // RFC 5869 Appendix A.1
+        let okm = hkdf(
+            HashAlgorithm::Sha256,
+            &[0x0b; 22],
+            &from_hex("000102030405060708090a0b0c"),
+            &from_hex("f0f1f2f3f4f5f6f7f8f9"),
+            42,
+        )?;
+        assert_eq!(
+            okm,
+            from_hex(concat!(
+                "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf",
+                "34007208d5b887185865"
+            ))
+        );
+
+        // RFC 5869 Appendix A.2
+        let ikm: Vec<u8> = (0x00..=0x4f).collect();
+        let salt: Vec<u8> = (0x60..=0xaf).collect();
+        let info: Vec<u8> = (0xb0..=0xff).collect();
+        let okm = hkdf(HashAlgorithm::Sha256, &ikm, &salt, &info, 82)?;
+        assert_eq!(
+            okm,
+            from_hex(concat!(
+                "b11e398dc80327a1c8e7f78c596a49344f012eda2d4efad8a050cc4c19afa97c",
+                "59045a99cac7827271cb41c65e590e09da3275600c2f09b8367793a9aca3db71",
+                "cc30c58179ec3e87c14c01d5c1f3434f1d87"
+            ))
+        );
+
+        // RFC 5869 Appendix A.3
+        let okm = hkdf(HashAlgorithm::Sha256, &[0x0b; 22], &[], &[], 42)?;
+        assert_eq!(
+            okm,
+            from_hex(concat!(
+                "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d",
+                "9d201395faa4b61a96c8"
+            ))
+        );
+
+        // RFC 5869 Appendix A.4
+        let okm = hkdf(
+            HashAlgorithm::Sha1,
+            &[0x0b; 11],
+            &from_hex("000102030405060708090a0b0c"),
+            &from_hex("f0f1f2f3f4f5f6f7f8f9"),
+            42,
+        )?;
+        assert_eq!(
+            okm,
+            from_hex(concat!(
+                "085a01ea1b10f36933068b56efa5ad81a4f14b822f5b091568a9cdd4f155fda2",
+                "c22e422478d305f3f896"
+            ))
+        );
+
+        assert!(hkdf(HashAlgorithm::Sha256, &[0x0b; 22], &[], &[], 255 * 32 + 1).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_json_object_strict() -> anyhow::Result<()> {
+        let map = parse_json_object_strict(br#"{"a":1,"b":[true,null,1.5,"x"],"c":{"d":-1}}"#)?;
+        assert_eq!(
+            Value::Object(map),
+            serde_json::json!({"a":1,"b":[true,null,1.5,"x"],"c":{"d":-1}})
+        );
+
+        assert!(parse_json_object_strict(br#"{"alg":"HS256","alg":"none"}"#).is_err());
+        assert!(parse_json_object_strict(br#"{"a":{"b":1,"b":2}}"#).is_err());
+        assert!(parse_json_object_strict(br#"[1,2]"#).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_is_base64_standard() {
         assert_eq!(
@@ -200,4 +612,60 @@ mod tests {
             decode_base64_urlsafe_no_pad("AB<>").is_ok()
         );
     }
+
+    #[test]
+    fn test_decode_base64_urlsafe_padding_tolerant() {
+        for input in ["MDEyMzQ1Njc4OQ", "MDEyMzQ1Njc4OQ=="] {
+            assert_eq!(
+                decode_base64_urlsafe_padding_tolerant(input).unwrap(),
+                b"0123456789"
+            );
+        }
+        assert!(decode_base64_urlsafe_no_pad("MDEyMzQ1Njc4OQ==").is_err());
+        assert!(decode_base64_urlsafe_padding_tolerant("+/+/").is_err());
+    }
+
+    #[test]
+    fn test_to_canonical_json() -> anyhow::Result<()> {
+        // RFC 8785 Section 3.2.2
+        let input = r#"{
+            "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+            "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+            "literals": [null, true, false]
+        }"#;
+        let mut value: Value = serde_json::from_str(input)?;
+        // serde_json doesn't parse every float exactly without the float_roundtrip feature.
+        value["numbers"][0] = serde_json::json!(333333333.3333333_f64);
+        assert_eq!(
+            to_canonical_json(&value),
+            r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#
+        );
+
+        // RFC 8785 Appendix B
+        for (input, expected) in [
+            (0.0, "0"),
+            (-0.0, "0"),
+            (1.0, "1"),
+            (-1.5, "-1.5"),
+            (9007199254740992.0, "9007199254740992"),
+            (295147905179352830000.0, "295147905179352830000"),
+            (1e21, "1e+21"),
+            (0.000001, "0.000001"),
+            (1e-7, "1e-7"),
+            (5e-324, "5e-324"),
+            (1.7976931348623157e308, "1.7976931348623157e+308"),
+        ] {
+            let value = serde_json::json!(input);
+            assert_eq!(to_canonical_json(&value), expected, "{}", input);
+        }
+
+        // Names are sorted by UTF-16 code units.
+        let value: Value = serde_json::from_str(r#"{"😀":1,"דּ":2,"b":3,"a":4}"#)?;
+        assert_eq!(
+            to_canonical_json(&value),
+            "{\"a\":4,\"b\":3,\"\u{1f600}\":1,\"\u{fb33}\":2}"
+        );
+
+        Ok(())
+    }
 }
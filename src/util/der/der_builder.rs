@@ -101,6 +101,21 @@ impl DerBuilder {
         self.append(DerType::OctetString, None, contents);
     }
 
+    /// Append a bit string that has no unused bits (e.g. a subjectPublicKey of SPKI).
+    ///
+    /// # Arguments
+    ///
+    /// * `contents` - The bytes of the bit string.
+    pub fn append_bit_string_from_slice(&mut self, contents: &[u8]) {
+        self.append_bit_string_from_bytes(contents, 0);
+    }
+
+    /// Append a bit string.
+    ///
+    /// # Arguments
+    ///
+    /// * `contents` - The bytes of the bit string.
+    /// * `trailing_len` - The number of unused bits in the last byte (0 to 7).
     pub fn append_bit_string_from_bytes(&mut self, contents: &[u8], trailing_len: u8) {
         if trailing_len >= 8 {
             unreachable!();
@@ -187,6 +202,61 @@ impl DerBuilder {
 mod tests {
     use super::*;
     use anyhow::Result;
+    use openssl::ec::{EcGroup, EcKey, PointConversionForm};
+    use openssl::nid::Nid;
+    use openssl::pkey::PKey;
+
+    use crate::util::oid::{OID_ED25519, OID_ID_EC_PUBLIC_KEY, OID_PRIME256V1, OID_X25519};
+
+    #[test]
+    fn write_spki_of_ec_key() -> Result<()> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        let ec_key = EcKey::generate(&group)?;
+        let mut ctx = openssl::bn::BigNumContext::new()?;
+        let point =
+            ec_key
+                .public_key()
+                .to_bytes(&group, PointConversionForm::UNCOMPRESSED, &mut ctx)?;
+
+        let mut builder = DerBuilder::new();
+        builder.begin(DerType::Sequence);
+        {
+            builder.begin(DerType::Sequence);
+            {
+                builder.append_object_identifier(&OID_ID_EC_PUBLIC_KEY);
+                builder.append_object_identifier(&OID_PRIME256V1);
+            }
+            builder.end();
+            builder.append_bit_string_from_slice(&point);
+        }
+        builder.end();
+
+        assert_eq!(builder.build(), ec_key.public_key_to_der()?);
+        Ok(())
+    }
+
+    #[test]
+    fn write_spki_of_okp_key() -> Result<()> {
+        for (key, oid) in [
+            (PKey::generate_ed25519()?, &*OID_ED25519),
+            (PKey::generate_x25519()?, &*OID_X25519),
+        ] {
+            let mut builder = DerBuilder::new();
+            builder.begin(DerType::Sequence);
+            {
+                builder.begin(DerType::Sequence);
+                {
+                    builder.append_object_identifier(oid);
+                }
+                builder.end();
+                builder.append_bit_string_from_slice(&key.raw_public_key()?);
+            }
+            builder.end();
+
+            assert_eq!(builder.build(), key.public_key_to_der()?);
+        }
+        Ok(())
+    }
 
     #[test]
     fn write_integer() -> Result<()> {
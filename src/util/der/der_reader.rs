@@ -108,8 +108,15 @@ impl<R: Read> DerReader<R> {
 
                 let olength = self.get_length()?;
                 let offset = self.read_count - start_read_count;
+                let olength = match olength {
+                    Some(val) => match val.checked_add(offset) {
+                        Some(val) => Some(val),
+                        None => return Err(DerError::Overflow),
+                    },
+                    None => None,
+                };
                 self.stack.push(DerStackItem {
-                    len: olength.map(|val| val + offset),
+                    len: olength,
                     parsed_len: offset,
                 });
 
@@ -134,7 +141,8 @@ impl<R: Read> DerReader<R> {
                     }
                 };
 
-                let mut contents = Vec::with_capacity(length);
+                // The length is not trusted until the contents are actually read.
+                let mut contents = Vec::with_capacity(length.min(4096));
                 for _ in 0..length {
                     match self.get()? {
                         Some(val) => contents.push(val),
@@ -616,6 +624,22 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn parse_der_with_huge_length() -> Result<()> {
+        // A length that is larger than the input must not be allocated in advance.
+        let input = [
+            0x04, 0x88, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let mut parser = DerReader::from_bytes(&input);
+        assert!(matches!(parser.next(), Err(DerError::UnexpectedEndOfInput)));
+
+        let input = [0x30, 0x88, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+        let mut parser = DerReader::from_bytes(&input);
+        assert!(matches!(parser.next(), Err(DerError::Overflow)));
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<File> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
@@ -0,0 +1,64 @@
+use std::fmt::Debug;
+
+use openssl::rand;
+
+use crate::JoseError;
+
+/// Represent a source of random bytes used for generating keys, IVs and salts.
+///
+/// The default source is [`OpensslRand`]. Other sources are mainly useful for
+/// producing deterministic test vectors or for routing entropy through a
+/// controlled generator.
+///
+/// A random source is not compared when the contexts and encrypters that hold it are
+/// compared, so a clone of a context is equal to the original.
+pub trait RandomSource: Debug + Send + Sync {
+    /// Fill the buffer with random bytes.
+    ///
+    /// An error is returned when the source fails to generate random bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - The buffer to be filled.
+    fn fill(&self, buf: &mut [u8]) -> Result<(), JoseError>;
+
+    fn box_clone(&self) -> Box<dyn RandomSource>;
+}
+
+impl Clone for Box<dyn RandomSource> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+/// The random source backed by the OpenSSL CSPRNG.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Default)]
+pub struct OpensslRand;
+
+impl RandomSource for OpensslRand {
+    fn fill(&self, buf: &mut [u8]) -> Result<(), JoseError> {
+        rand::rand_bytes(buf).map_err(|err| {
+            JoseError::InvalidKeyFormat(anyhow::anyhow!("Failed to generate random bytes: {}", err))
+        })
+    }
+
+    fn box_clone(&self) -> Box<dyn RandomSource> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OpensslRand, RandomSource};
+
+    #[test]
+    fn test_openssl_rand() {
+        let source: Box<dyn RandomSource> = Box::new(OpensslRand);
+
+        let mut buf1 = [0; 32];
+        let mut buf2 = [0; 32];
+        source.fill(&mut buf1).unwrap();
+        source.fill(&mut buf2).unwrap();
+        assert_ne!(buf1, buf2);
+    }
+}
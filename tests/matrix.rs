@@ -0,0 +1,143 @@
+//! Tests every combination of key management algorithms and content encryption algorithms.
+
+use anyhow::Result;
+use openssl::rand;
+
+use josekit::jwe::enc::{A128CBC_HS256, A128GCM, A192CBC_HS384, A192GCM, A256CBC_HS512, A256GCM};
+use josekit::jwe::{self, JweContentEncryption, JweHeader};
+use josekit::jwk::alg::ec::EcCurve;
+use josekit::jwk::alg::ecx::EcxCurve;
+use josekit::jwk::Jwk;
+
+fn content_encryptions() -> Vec<Box<dyn JweContentEncryption>> {
+    vec![
+        Box::new(A128CBC_HS256),
+        Box::new(A192CBC_HS384),
+        Box::new(A256CBC_HS512),
+        Box::new(A128GCM),
+        Box::new(A192GCM),
+        Box::new(A256GCM),
+    ]
+}
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut buf = vec![0; len];
+    rand::rand_bytes(&mut buf).unwrap();
+    buf
+}
+
+/// Check the content encryption key through a key management algorithm.
+///
+/// The generated key must have the length of the content encryption algorithm,
+/// and the decrypter must return exactly the same key.
+fn check_cek_length(
+    alg_name: &str,
+    enc: &dyn JweContentEncryption,
+    encryption_key: &Jwk,
+    decryption_key: &Jwk,
+) -> Result<()> {
+    let alg = jwe::key_management_algorithm_from_name(alg_name).unwrap();
+    let encrypter = alg.encrypter_from_jwk(encryption_key)?;
+    let decrypter = alg.decrypter_from_jwk(decryption_key)?;
+
+    let mut header = JweHeader::new();
+    header.set_content_encryption(enc.name());
+
+    let mut out_header = header.clone();
+    let src_key = match encrypter.compute_content_encryption_key(enc, &header, &mut out_header)? {
+        Some(val) => val.into_owned(),
+        None => random_bytes(enc.key_len()),
+    };
+    assert_eq!(src_key.len(), enc.key_len(), "{} {}", alg_name, enc.name());
+
+    let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
+    if alg_name.ends_with("KW") {
+        // RFC 3394 adds a 8 bytes integrity check value.
+        let encrypted_key = encrypted_key.as_ref().unwrap();
+        assert_eq!(encrypted_key.len(), enc.key_len() + 8);
+    } else {
+        assert!(encrypted_key.is_none());
+    }
+
+    out_header.set_algorithm(alg_name);
+    let dst_key = decrypter.decrypt(encrypted_key.as_deref(), enc, &out_header)?;
+    assert_eq!(dst_key.len(), enc.key_len(), "{} {}", alg_name, enc.name());
+    assert_eq!(&src_key, dst_key.as_ref());
+
+    // A unwrapped key of the other length is rejected by the decrypter.
+    if alg_name.ends_with("KW") {
+        let other_key = random_bytes(enc.key_len() + 8);
+        let mut out_header = header.clone();
+        let encrypted_key = encrypter.encrypt(&other_key, &header, &mut out_header)?;
+        out_header.set_algorithm(alg_name);
+        let err = decrypter
+            .decrypt(encrypted_key.as_deref(), enc, &out_header)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("The length of the unwrapped key must be"));
+    }
+
+    // The whole JWE is processed with the same key.
+    let encrypter = alg.encrypter_from_jwk(encryption_key)?;
+    let jwe = jwe::serialize_compact(b"payload", &header, &*encrypter)?;
+    let (payload, dst_header) = jwe::deserialize_compact(&jwe, &*decrypter)?;
+    assert_eq!(payload, b"payload");
+    assert_eq!(dst_header.algorithm(), Some(alg_name));
+    assert_eq!(dst_header.content_encryption(), Some(enc.name()));
+
+    Ok(())
+}
+
+#[test]
+fn test_ecdh_es_cek_length_matrix() -> Result<()> {
+    let ec_key = Jwk::generate_ec_key(EcCurve::P256)?;
+    let x25519_key = Jwk::generate_ecx_key(EcxCurve::X25519)?;
+    // X448 has a 56 bytes shared secret, which is longer than a SHA-256 digest.
+    let x448_key = Jwk::generate_ecx_key(EcxCurve::X448)?;
+
+    for private_key in [&ec_key, &x25519_key, &x448_key] {
+        let public_key = private_key.to_public_key()?;
+        for alg_name in [
+            "ECDH-ES",
+            "ECDH-ES+A128KW",
+            "ECDH-ES+A192KW",
+            "ECDH-ES+A256KW",
+        ] {
+            for enc in content_encryptions() {
+                check_cek_length(alg_name, enc.as_ref(), &public_key, private_key)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_aeskw_cek_length_matrix() -> Result<()> {
+    for (alg_name, key_len) in [("A128KW", 16), ("A192KW", 24), ("A256KW", 32)] {
+        let key = Jwk::generate_oct_key(key_len)?;
+        for enc in content_encryptions() {
+            check_cek_length(alg_name, enc.as_ref(), &key, &key)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_direct_cek_length_matrix() -> Result<()> {
+    for enc in content_encryptions() {
+        let key = Jwk::generate_oct_key(enc.key_len() as u8)?;
+        check_cek_length("dir", enc.as_ref(), &key, &key)?;
+
+        // A direct key of the other length is rejected.
+        let other = Jwk::generate_oct_key(enc.key_len() as u8 + 8)?;
+        let encrypter = jwe::Dir.encrypter_from_jwk(&other)?;
+        let mut header = JweHeader::new();
+        header.set_content_encryption(enc.name());
+        assert!(jwe::serialize_compact(b"payload", &header, &encrypter).is_err());
+    }
+
+    Ok(())
+}